@@ -0,0 +1,423 @@
+use clap::{
+    builder::{OsStringValueParser, TypedValueParser},
+    Parser, Subcommand,
+};
+use service_manager::*;
+use std::{
+    ffi::{OsStr, OsString},
+    io,
+    path::PathBuf,
+};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    /// Name of the service manager to use, e.g. systemd or launchd, otherwise the native service
+    /// manager of the current operating system is used
+    #[clap(long, global = true)]
+    kind: Option<String>,
+
+    /// Work with user-level services instead of system-level services
+    #[clap(long, global = true)]
+    user: bool,
+
+    /// Print statuses and errors as JSON objects with the fields kind, label, state, pid,
+    /// exit_code, and message
+    #[clap(long, global = true)]
+    json: bool,
+
+    #[clap(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand)]
+enum Action {
+    /// Installs a service
+    Install {
+        /// Label of the service, e.g. com.example.my-service
+        label: ServiceLabel,
+
+        /// Path to the program run by the service
+        #[clap(long)]
+        program: PathBuf,
+
+        /// Arguments passed to the program, which consume the remaining command line
+        #[clap(long, num_args = 1.., allow_hyphen_values = true)]
+        args: Vec<OsString>,
+
+        /// Environment variable passed to the program as KEY=VALUE, which can be repeated
+        #[clap(long = "env", value_parser = OsStringValueParser::new().try_map(parse_env))]
+        environment: Vec<(OsString, OsString)>,
+
+        /// User to run the program as
+        #[clap(long)]
+        username: Option<String>,
+
+        /// Working directory of the program
+        #[clap(long)]
+        working_directory: Option<PathBuf>,
+
+        /// Start the service automatically when the system boots or the user logs in
+        #[clap(long)]
+        autostart: bool,
+
+        /// Write the files of the service beneath this directory without enabling or starting it,
+        /// such as when building a package
+        #[clap(long)]
+        install_root: Option<PathBuf>,
+    },
+
+    /// Uninstalls a service
+    Uninstall {
+        /// Label of the service, e.g. com.example.my-service
+        label: ServiceLabel,
+
+        /// Also remove logs and other files created for the service
+        #[clap(long)]
+        purge: bool,
+    },
+
+    /// Starts a service
+    Start {
+        /// Label of the service, e.g. com.example.my-service
+        label: ServiceLabel,
+    },
+
+    /// Stops a service
+    Stop {
+        /// Label of the service, e.g. com.example.my-service
+        label: ServiceLabel,
+    },
+
+    /// Stops a service if it is running and starts it again
+    Restart {
+        /// Label of the service, e.g. com.example.my-service
+        label: ServiceLabel,
+    },
+
+    /// Prints the status of a service
+    Status {
+        /// Label of the service, e.g. com.example.my-service
+        label: ServiceLabel,
+    },
+
+    /// Lists the service managers that can be selected with --kind and whether each is available
+    List,
+}
+
+impl Action {
+    /// Label of the service targeted by the action, if any
+    fn label(&self) -> Option<&ServiceLabel> {
+        match self {
+            Self::Install { label, .. }
+            | Self::Uninstall { label, .. }
+            | Self::Start { label }
+            | Self::Stop { label }
+            | Self::Restart { label }
+            | Self::Status { label } => Some(label),
+            Self::List => None,
+        }
+    }
+}
+
+impl Cli {
+    /// Name of the service manager in use, looking up the native one if none was specified
+    fn kind_name(&self) -> Option<String> {
+        self.kind.clone().or_else(|| {
+            let kind = ServiceManagerKind::native_for_level(self.level()).ok()?;
+            Some(kind.to_string())
+        })
+    }
+
+    /// Level of the services to work with
+    fn level(&self) -> ServiceLevel {
+        if self.user {
+            ServiceLevel::User
+        } else {
+            ServiceLevel::System
+        }
+    }
+
+    /// Runs CLI to completion
+    fn run(self) -> io::Result<()> {
+        if let Action::List = self.action {
+            return list(self.json);
+        }
+
+        let kind_name = if self.json { self.kind_name() } else { None };
+
+        let mut manager = match self.kind.as_deref() {
+            Some(name) => <dyn ServiceManager>::target_name_or_native(Some(name))?,
+            None => <dyn ServiceManager>::native_for_level(self.level())?,
+        };
+        if self.user {
+            manager.set_level(ServiceLevel::User)?;
+        }
+
+        match self.action {
+            Action::Install {
+                label,
+                program,
+                args,
+                environment,
+                username,
+                working_directory,
+                autostart,
+                install_root,
+            } => manager.install(ServiceInstallCtx {
+                label,
+                program,
+                args,
+                contents: None,
+                username,
+                working_directory,
+                environment: if environment.is_empty() {
+                    None
+                } else {
+                    Some(environment)
+                },
+                autostart,
+                restart_policy: None,
+                stop_command: None,
+                stop_signal: None,
+                start_timeout: None,
+                stop_timeout: None,
+                kind: ServiceKind::Simple,
+                health_check: None,
+                skip_program_check: false,
+                install_binary_to: None,
+                priority: None,
+                oom_score_adjust: None,
+                capabilities: Vec::new(),
+                display_name: None,
+                description: None,
+                start_limit: None,
+                log_to_syslog: None,
+                definition_permissions: None,
+                definition_owner: None,
+                backup_definition: false,
+                install_root,
+            }),
+            Action::Uninstall { label, purge } => manager.uninstall(ServiceUninstallCtx {
+                label,
+                install_binary_to: None,
+                purge,
+            }),
+            Action::Start { label } => manager.start(ServiceStartCtx {
+                label,
+                wait: WaitMode::None,
+            }),
+            Action::Stop { label } => manager.stop(ServiceStopCtx { label }),
+            Action::Restart { label } => {
+                let status = manager.status(ServiceStatusCtx {
+                    label: label.clone(),
+                    level: None,
+                })?;
+                if status == ServiceStatus::Running {
+                    manager.stop(ServiceStopCtx {
+                        label: label.clone(),
+                    })?;
+                }
+                manager.start(ServiceStartCtx {
+                    label,
+                    wait: WaitMode::None,
+                })
+            }
+            Action::Status { label } => {
+                let details = manager.status_details(ServiceStatusCtx {
+                    label: label.clone(),
+                    level: None,
+                })?;
+                if self.json {
+                    let report = ServiceReport::from_status(kind_name, &label, details);
+                    println!("{}", to_json(&report)?);
+                    return Ok(());
+                }
+
+                let status = match details.status {
+                    ServiceStatus::NotInstalled => String::from("not installed"),
+                    ServiceStatus::Running => String::from("running"),
+                    ServiceStatus::Paused => String::from("paused"),
+                    ServiceStatus::Stopped(None) => String::from("stopped"),
+                    ServiceStatus::Stopped(Some(reason)) => format!("stopped ({reason})"),
+                };
+                match details.pid {
+                    Some(pid) => println!("{status} (pid {pid})"),
+                    None => println!("{status}"),
+                }
+                Ok(())
+            }
+            Action::List => unreachable!("list is handled before selecting a service manager"),
+        }
+    }
+}
+
+/// Prints the names of built-in and registered service managers alongside their availability
+fn list(json: bool) -> io::Result<()> {
+    let mut names: Vec<String> = ServiceManagerKind::all()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    names.extend(ServiceManagerRegistry::names());
+    names.sort();
+    names.dedup();
+
+    let entries = names.into_iter().map(|name| {
+        let available = match ServiceManagerRegistry::resolve(&name) {
+            Some(manager) => manager.available().unwrap_or(false),
+            None => false,
+        };
+        (name, available)
+    });
+
+    if json {
+        let entries: Vec<serde_json::Value> = entries
+            .map(|(name, available)| serde_json::json!({ "name": name, "available": available }))
+            .collect();
+        println!("{}", to_json(&entries)?);
+        return Ok(());
+    }
+
+    for (name, available) in entries {
+        println!(
+            "{name}\t{}",
+            if available {
+                "available"
+            } else {
+                "unavailable"
+            }
+        );
+    }
+
+    Ok(())
+}
+
+/// Parses KEY=VALUE, where only the key needs to be valid UTF-8 so that values such as paths are
+/// passed along unchanged
+fn parse_env(s: OsString) -> Result<(OsString, OsString), String> {
+    let text = s.to_string_lossy();
+    match text.split_once('=') {
+        Some((key, _)) if !key.is_empty() && !key.contains(char::REPLACEMENT_CHARACTER) => {
+            Ok((OsString::from(key), env_value(&s, key)))
+        }
+        _ => Err(format!("Expected KEY=VALUE, but got {text}")),
+    }
+}
+
+/// Returns the part of `s` following `key` and the `=` after it
+#[cfg(unix)]
+fn env_value(s: &OsStr, key: &str) -> OsString {
+    use std::os::unix::ffi::OsStrExt;
+    OsStr::from_bytes(&s.as_bytes()[key.len() + 1..]).to_os_string()
+}
+
+/// Returns the part of `s` following `key` and the `=` after it
+#[cfg(windows)]
+fn env_value(s: &OsStr, key: &str) -> OsString {
+    use std::os::windows::ffi::{OsStrExt, OsStringExt};
+    let wide = s.encode_wide().collect::<Vec<_>>();
+    OsString::from_wide(&wide[key.encode_utf16().count() + 1..])
+}
+
+fn to_json(value: &impl serde::Serialize) -> io::Result<String> {
+    serde_json::to_string(value).map_err(|x| io::Error::new(io::ErrorKind::Other, x))
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    // Capture what is needed to report an error before running consumes the CLI
+    let error_report = match cli.action.label() {
+        Some(label) if cli.json => Some((cli.kind_name(), label.clone())),
+        _ => None,
+    };
+
+    if let Err(x) = cli.run() {
+        match error_report {
+            Some((kind_name, label)) => {
+                let report = ServiceReport::from_error(kind_name, &label, &x);
+                match to_json(&report) {
+                    Ok(json) => println!("{json}"),
+                    Err(_) => eprintln!("{x}"),
+                }
+            }
+            None => eprintln!("{x}"),
+        }
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_env() {
+        assert_eq!(
+            parse_env("RUST_LOG=info,tower=warn".into()),
+            Ok((
+                OsString::from("RUST_LOG"),
+                OsString::from("info,tower=warn")
+            ))
+        );
+        assert_eq!(
+            parse_env("EMPTY=".into()),
+            Ok((OsString::from("EMPTY"), OsString::new()))
+        );
+        assert!(parse_env("=value".into()).is_err());
+        assert!(parse_env("MISSING".into()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_env_keeps_non_utf8_values() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let value = OsString::from_vec(vec![b'/', b't', b'm', b'p', b'/', 0xff]);
+        let mut arg = OsString::from("DATA_DIR=");
+        arg.push(&value);
+        assert_eq!(parse_env(arg), Ok((OsString::from("DATA_DIR"), value)));
+    }
+
+    #[test]
+    fn test_cli_parses_install() {
+        let cli = Cli::try_parse_from([
+            "service-manager",
+            "--kind",
+            "systemd",
+            "install",
+            "com.example.my-service",
+            "--program",
+            "/usr/bin/my-service",
+            "--env",
+            "PORT=8080",
+            "--autostart",
+            "--args",
+            "--port",
+            "8080",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.kind.as_deref(), Some("systemd"));
+        match cli.action {
+            Action::Install {
+                label,
+                program,
+                args,
+                environment,
+                autostart,
+                ..
+            } => {
+                assert_eq!(label.to_qualified_name(), "com.example.my-service");
+                assert_eq!(program, PathBuf::from("/usr/bin/my-service"));
+                assert_eq!(args, [OsString::from("--port"), OsString::from("8080")]);
+                assert_eq!(
+                    environment,
+                    [(OsString::from("PORT"), OsString::from("8080"))]
+                );
+                assert!(autostart);
+            }
+            _ => panic!("Expected install action"),
+        }
+    }
+}
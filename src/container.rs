@@ -0,0 +1,484 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx, ServiceManager,
+    ServiceManagerKind, ServicePauseCtx, ServiceResumeCtx, ServiceStartCtx, ServiceStopCtx,
+    ServiceUninstallCtx, SharedCommandRunner,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fmt, io,
+    process::{Command, Output, Stdio},
+};
+
+/// Format passed to `inspect`, producing the state, main pid, and exit code of a container
+const INSPECT_FORMAT: &str = "{{.State.Status}} {{.State.Pid}} {{.State.ExitCode}}";
+
+/// Engine used to create and control containers
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum ContainerEngine {
+    /// Use `docker`
+    Docker,
+
+    /// Use `podman`
+    Podman,
+}
+
+impl Default for ContainerEngine {
+    fn default() -> Self {
+        Self::Docker
+    }
+}
+
+impl ContainerEngine {
+    /// Name of the binary of the engine
+    pub fn program(&self) -> &'static str {
+        match self {
+            Self::Docker => "docker",
+            Self::Podman => "podman",
+        }
+    }
+}
+
+impl fmt::Display for ContainerEngine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.program())
+    }
+}
+
+/// Configuration settings tied to containers
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContainerConfig {
+    pub install: ContainerInstallConfig,
+
+    /// Engine used to manage containers, defaulting to docker
+    pub engine: ContainerEngine,
+}
+
+/// Configuration settings used when creating containers
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContainerInstallConfig {
+    /// Image the container is created from, which must be set to install a service
+    ///
+    /// The program and arguments of the service are run as the command of the container, so the
+    /// program must exist within the image.
+    pub image: Option<String>,
+
+    /// Additional arguments passed to `create` before the image, such as `--network=host` or
+    /// `--volume=/data:/data`
+    pub create_args: Vec<String>,
+}
+
+/// Implementation of [`ServiceManager`] that runs services as containers using
+/// [docker](https://www.docker.com/) or [podman](https://podman.io/)
+///
+/// Installing a service creates a container named after the service's label, which is started,
+/// stopped, paused, and resumed through the engine. The restart policy of the service becomes the
+/// restart policy of the container; engines manage their own backoff between restarts, so delays
+/// are ignored. Podman only restarts containers after a reboot when `podman-restart.service` is
+/// enabled.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContainerServiceManager {
+    /// Configuration settings tied to containers
+    pub config: ContainerConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl ContainerServiceManager {
+    /// Creates a new manager instance working with system services
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: ContainerConfig) -> Self {
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    fn engine<I, S>(&self, args: I) -> io::Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new(self.config.engine.program());
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .args(args);
+
+        self.runner.run(&mut command, None)
+    }
+}
+
+impl ServiceManager for ContainerServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        match which::which(self.config.engine.program()) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        crate::utils::validate_environment(&ctx, true)?;
+        crate::utils::validate_capabilities(&ctx)?;
+        crate::utils::backup_unsupported(&ctx, self.config.engine.program())?;
+        crate::utils::staging_unsupported(&ctx, self.config.engine.program())?;
+        let args = make_create_args(&self.config.install, &ctx)?;
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Docker, self.level(), &ctx);
+        wrap_output(self.engine(args)?)?;
+        Ok(())
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        crate::utils::ensure_installed(self, &ctx.label)?;
+
+        // Anonymous volumes of the container hold its state, so they are only removed when purging
+        let name = ctx.label.to_qualified_name();
+        if ctx.purge {
+            wrap_output(self.engine(["rm", "--force", "--volumes", &name])?)?;
+        } else {
+            wrap_output(self.engine(["rm", "--force", &name])?)?;
+        }
+        manifest::remove(ServiceManagerKind::Docker, self.level(), &ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Docker, self.level(), label)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        let name = ctx.label.to_qualified_name();
+        wrap_output(self.engine(["start", &name])?)?;
+        crate::utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        let name = ctx.label.to_qualified_name();
+        wrap_output(self.engine(["stop", &name])?)?;
+        Ok(())
+    }
+
+    fn level(&self) -> ServiceLevel {
+        ServiceLevel::System
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => Ok(()),
+            ServiceLevel::User => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Container engines do not support user-level services",
+            )),
+        }
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let name = ctx.label.to_qualified_name();
+        let output = match ctx.lines {
+            Some(lines) => self.engine(["logs", "--tail", &lines.to_string(), &name])?,
+            None => self.engine(["logs", &name])?,
+        };
+        let output = wrap_output(output)?;
+
+        // The engine replays the output of the container on the same streams it was written to
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+        Ok(text)
+    }
+
+    fn pause(&self, ctx: ServicePauseCtx) -> io::Result<()> {
+        let name = ctx.label.to_qualified_name();
+        wrap_output(self.engine(["pause", &name])?)?;
+        Ok(())
+    }
+
+    fn resume(&self, ctx: ServiceResumeCtx) -> io::Result<()> {
+        let name = ctx.label.to_qualified_name();
+        wrap_output(self.engine(["unpause", &name])?)?;
+        Ok(())
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let name = ctx.label.to_qualified_name();
+        let output = self.engine([
+            "inspect",
+            "--type",
+            "container",
+            "--format",
+            INSPECT_FORMAT,
+            &name,
+        ])?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.to_lowercase().contains("no such") {
+                return Ok(crate::ServiceStatus::NotInstalled.into());
+            }
+        }
+
+        let output = wrap_output(output)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_inspect(&stdout).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "Failed to get status of container {name}: {}",
+                    stdout.trim()
+                ),
+            )
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        let name = ctx.label.to_qualified_name();
+        wrap_output(self.engine(["kill", &format!("--signal={}", ctx.signal), &name])?)?;
+        Ok(())
+    }
+}
+
+/// Parses the output of `inspect` using [`INSPECT_FORMAT`], which looks like `running 123 0`
+fn parse_inspect(output: &str) -> Option<crate::ServiceStatusDetails> {
+    let mut fields = output.split_whitespace();
+    let state = fields.next()?;
+    let pid = fields.next()?.parse::<u32>().ok().filter(|pid| *pid != 0);
+    let exit_code = fields.next()?.parse::<i32>().ok()?;
+
+    let (status, transition) = match state {
+        "running" => (crate::ServiceStatus::Running, None),
+        "paused" => (crate::ServiceStatus::Paused, None),
+        "restarting" => (
+            crate::ServiceStatus::Stopped(None),
+            Some(crate::ServiceTransition::Starting),
+        ),
+        "stopping" => (
+            crate::ServiceStatus::Running,
+            Some(crate::ServiceTransition::Stopping),
+        ),
+        "created" | "configured" | "initialized" => (crate::ServiceStatus::Stopped(None), None),
+        "exited" | "stopped" | "dead" | "removing" if exit_code != 0 => (
+            crate::ServiceStatus::Stopped(Some(format!("Exited with code {exit_code}"))),
+            None,
+        ),
+        "exited" | "stopped" | "dead" | "removing" => (crate::ServiceStatus::Stopped(None), None),
+        _ => return None,
+    };
+
+    let pid = match status {
+        crate::ServiceStatus::Running | crate::ServiceStatus::Paused => pid,
+        _ => None,
+    };
+
+    Some(crate::ServiceStatusDetails {
+        status,
+        pid,
+        transition,
+    })
+}
+
+/// Produces the arguments of the `create` command of the container for a service
+fn make_create_args(
+    config: &ContainerInstallConfig,
+    ctx: &ServiceInstallCtx,
+) -> io::Result<Vec<OsString>> {
+    // Containers are only started at boot by a restart policy that also restarts them after they
+    // exit, so there is no way to run one just once per boot
+    if ctx.kind == crate::ServiceKind::Oneshot {
+        return Err(crate::utils::oneshot_unsupported("Containers"));
+    }
+
+    let image = config.image.as_deref().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "An image is required to install a service as a container",
+        )
+    })?;
+
+    let restart = match ctx.restart_policy {
+        Some(RestartPolicy::Never) => "no",
+        Some(RestartPolicy::Always { .. }) => "always",
+        Some(RestartPolicy::OnFailure { .. }) => "on-failure",
+        None if ctx.autostart => "unless-stopped",
+        None => "no",
+    };
+
+    let mut args: Vec<OsString> = vec![
+        "create".into(),
+        "--name".into(),
+        ctx.label.to_qualified_name().into(),
+        format!("--restart={restart}").into(),
+    ];
+
+    if let Some(signal) = ctx.stop_signal {
+        args.push(format!("--stop-signal={signal}").into());
+    }
+
+    if let Some(timeout) = ctx.stop_timeout {
+        args.push(format!("--stop-timeout={}", timeout.as_secs()).into());
+    }
+
+    // The health command runs through a shell within the container
+    if let Some(crate::HealthCheck::Exec {
+        program,
+        args: check_args,
+    }) = &ctx.health_check
+    {
+        let cmd = crate::utils::shell_join(
+            std::iter::once(program.as_os_str()).chain(check_args.iter().map(OsString::as_os_str)),
+        );
+        args.push(format!("--health-cmd={cmd}").into());
+    }
+
+    // Capability names are given to the engine without their CAP_ prefix
+    for cap in &ctx.capabilities {
+        let cap = cap.strip_prefix("CAP_").unwrap_or(cap);
+        args.push(format!("--cap-add={cap}").into());
+    }
+
+    if let Some(username) = &ctx.username {
+        args.push(format!("--user={username}").into());
+    }
+
+    if let Some(dir) = &ctx.working_directory {
+        let mut arg = OsString::from("--workdir=");
+        arg.push(dir);
+        args.push(arg);
+    }
+
+    if let Some(env_vars) = &ctx.environment {
+        for (var, val) in env_vars {
+            let mut arg = OsString::from("--env=");
+            arg.push(var);
+            arg.push("=");
+            arg.push(val);
+            args.push(arg);
+        }
+    }
+
+    args.extend(config.create_args.iter().map(OsString::from));
+    args.push(image.into());
+    args.extend(ctx.cmd_iter().map(OsString::from));
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn install_ctx(restart_policy: Option<RestartPolicy>) -> ServiceInstallCtx {
+        ServiceInstallCtx {
+            args: vec![OsString::from("--port"), OsString::from("8080")],
+            username: Some(String::from("app")),
+            working_directory: Some(PathBuf::from("/srv")),
+            environment: Some(vec![(OsString::from("RUST_LOG"), OsString::from("info"))]),
+            autostart: true,
+            restart_policy,
+            ..crate::utils::tests::install_ctx(PathBuf::from("/usr/bin/my_service"))
+        }
+    }
+
+    #[test]
+    fn test_make_create_args() {
+        let config = ContainerInstallConfig {
+            image: Some(String::from("example/my_service:latest")),
+            create_args: vec![String::from("--network=host")],
+        };
+
+        let args = make_create_args(
+            &config,
+            &install_ctx(Some(RestartPolicy::OnFailure {
+                delay_secs: Some(5),
+            })),
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            [
+                "create",
+                "--name",
+                "org.example.my_service",
+                "--restart=on-failure",
+                "--user=app",
+                "--workdir=/srv",
+                "--env=RUST_LOG=info",
+                "--network=host",
+                "example/my_service:latest",
+                "/usr/bin/my_service",
+                "--port",
+                "8080",
+            ]
+            .map(OsString::from)
+        );
+    }
+
+    #[test]
+    fn test_make_create_args_with_health_check() {
+        let config = ContainerInstallConfig {
+            image: Some(String::from("example/my_service:latest")),
+            create_args: Vec::new(),
+        };
+        let mut ctx = install_ctx(None);
+        ctx.health_check = Some(crate::HealthCheck::Exec {
+            program: PathBuf::from("curl"),
+            args: vec![OsString::from("-f"), OsString::from("http://localhost/")],
+        });
+
+        let args = make_create_args(&config, &ctx).unwrap();
+        assert!(args.contains(&OsString::from("--health-cmd=curl -f http://localhost/")));
+    }
+
+    #[test]
+    fn test_make_create_args_requires_image() {
+        let err =
+            make_create_args(&ContainerInstallConfig::default(), &install_ctx(None)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_parse_inspect() {
+        assert_eq!(
+            parse_inspect("running 1234 0\n"),
+            Some(crate::ServiceStatusDetails {
+                status: crate::ServiceStatus::Running,
+                pid: Some(1234),
+                transition: None,
+            })
+        );
+        assert_eq!(
+            parse_inspect("exited 0 1\n"),
+            Some(crate::ServiceStatus::Stopped(Some(String::from("Exited with code 1"))).into())
+        );
+        assert_eq!(
+            parse_inspect("created 0 0\n"),
+            Some(crate::ServiceStatus::Stopped(None).into())
+        );
+        assert_eq!(parse_inspect(""), None);
+    }
+}
@@ -0,0 +1,564 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, utils, CommandRunner, ServiceInstallCtx, ServiceLabel, ServiceLevel, ServiceManager,
+    ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx, SharedCommandRunner,
+};
+use std::{
+    io,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+static CRONTAB: &str = "crontab";
+const CRON_FILE_PERMISSIONS: u32 = 0o644;
+
+/// Prefix of the comments that delimit the entry of a service within a crontab
+const MARKER: &str = "# service-manager";
+
+/// Configuration settings tied to cron jobs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronConfig {
+    pub install: CronInstallConfig,
+
+    /// Directory where system-level jobs are written, defaulting to `/etc/cron.d`
+    pub cron_d_dir_path: PathBuf,
+}
+
+impl Default for CronConfig {
+    fn default() -> Self {
+        Self {
+            install: CronInstallConfig::default(),
+            cron_d_dir_path: PathBuf::from("/etc/cron.d"),
+        }
+    }
+}
+
+/// Configuration settings used when installing cron jobs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronInstallConfig {
+    /// Schedule of the job such as `*/5 * * * *` or `@hourly`, defaulting to `@reboot`
+    pub schedule: String,
+}
+
+impl Default for CronInstallConfig {
+    fn default() -> Self {
+        Self {
+            schedule: String::from("@reboot"),
+        }
+    }
+}
+
+/// Implementation of [`ServiceManager`] for [cron](https://en.wikipedia.org/wiki/Cron), meant for
+/// jobs that only need to run periodically
+///
+/// User-level jobs are added to the crontab of the current user, while system-level jobs are
+/// written to their own file within `/etc/cron.d`. As cron does not supervise processes, starting
+/// and stopping a job enables and disables its entry, and a job is reported as running while its
+/// entry is enabled.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CronServiceManager {
+    /// Whether or not this manager is operating at the user-level
+    pub user: bool,
+
+    /// Configuration settings tied to cron jobs
+    pub config: CronConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl CronServiceManager {
+    /// Creates a new manager instance working with system-level jobs
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new manager instance working with user-level jobs
+    pub fn user() -> Self {
+        Self::default().into_user()
+    }
+
+    /// Change manager to work with system-level jobs
+    pub fn into_system(self) -> Self {
+        Self {
+            config: self.config,
+            user: false,
+            runner: self.runner,
+        }
+    }
+
+    /// Change manager to work with user-level jobs
+    pub fn into_user(self) -> Self {
+        Self {
+            config: self.config,
+            user: true,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: CronConfig) -> Self {
+        Self {
+            config,
+            user: self.user,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Path of the file within `/etc/cron.d` holding a system-level job
+    ///
+    /// cron skips files whose names contain dots, so they are replaced with underscores.
+    fn cron_d_path(&self, label: &ServiceLabel) -> PathBuf {
+        self.config
+            .cron_d_dir_path
+            .join(label.to_script_name().replace('.', "_"))
+    }
+
+    fn read(&self, label: &ServiceLabel) -> io::Result<String> {
+        if self.user {
+            let output = crontab(&*self.runner, ["-l"], None)?;
+
+            // crontab fails when the user has no crontab yet, which is the same as an empty one.
+            // Any other failure is returned, as writing back only this entry would wipe the rest.
+            if !output.status.success()
+                && String::from_utf8_lossy(&output.stderr).contains("no crontab for")
+            {
+                return Ok(String::new());
+            }
+
+            let output = wrap_output(output)?;
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            match std::fs::read_to_string(self.cron_d_path(label)) {
+                Ok(text) => Ok(text),
+                Err(x) if x.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+                Err(x) => Err(x),
+            }
+        }
+    }
+
+    fn write(&self, label: &ServiceLabel, text: &str) -> io::Result<()> {
+        if self.user {
+            wrap_output(crontab(&*self.runner, ["-"], Some(text))?)?;
+            return Ok(());
+        }
+
+        let path = self.cron_d_path(label);
+        if text.trim().is_empty() {
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+            return Ok(());
+        }
+
+        std::fs::create_dir_all(&self.config.cron_d_dir_path)?;
+        utils::write_file(&path, text.as_bytes(), CRON_FILE_PERMISSIONS)
+    }
+
+    fn set_enabled(&self, label: &ServiceLabel, enabled: bool) -> io::Result<()> {
+        let text = self.read(label)?;
+        let name = label.to_qualified_name();
+        if find_entry(&text, &name).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No cron job found for {name}"),
+            ));
+        }
+
+        self.write(label, &set_entry_enabled(&text, &name, enabled))
+    }
+}
+
+impl ServiceManager for CronServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        if !self.user {
+            return Ok(self.config.cron_d_dir_path.is_dir());
+        }
+
+        match which::which(CRONTAB) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        utils::validate_environment(&ctx, false)?;
+        utils::require_utf8(&ctx, "cron")?;
+        utils::capabilities_unsupported(&ctx, "cron")?;
+        utils::backup_unsupported(&ctx, "cron")?;
+        if self.user {
+            utils::staging_unsupported(&ctx, "cron at the user level")?;
+        }
+        utils::check_program(&ctx)?;
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "cron")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Cron, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+        let entry = match &ctx.contents {
+            Some(contents) => contents.trim().to_string(),
+            None => make_entry(&self.config.install, !self.user, &ctx),
+        };
+
+        // Staged entries are written to a cron.d file beneath the install root instead
+        let mut manager = self.clone();
+        manager.config.cron_d_dir_path = utils::staged_path(&ctx, &self.config.cron_d_dir_path);
+
+        let name = ctx.label.to_qualified_name();
+        let text = manager.read(&ctx.label)?;
+        let text = upsert_entry(&text, &name, &entry);
+        let text = set_entry_enabled(&text, &name, ctx.autostart);
+        manager.write(&ctx.label, &text)
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "cron")?;
+
+        let text = self.read(&ctx.label)?;
+        let text = remove_entry(&text, &ctx.label.to_qualified_name());
+        self.write(&ctx.label, &text)?;
+        manifest::remove(ServiceManagerKind::Cron, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Cron, self.level(), label)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        self.set_enabled(&ctx.label, true)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        self.set_enabled(&ctx.label, false)
+    }
+
+    fn level(&self) -> ServiceLevel {
+        if self.user {
+            ServiceLevel::User
+        } else {
+            ServiceLevel::System
+        }
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => self.user = false,
+            ServiceLevel::User => self.user = true,
+        }
+
+        Ok(())
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        let text = self.read(&ctx.label)?;
+        Ok(match find_entry(&text, &ctx.label.to_qualified_name()) {
+            None => crate::ServiceStatus::NotInstalled,
+            Some(true) => crate::ServiceStatus::Running,
+            Some(false) => crate::ServiceStatus::Stopped(None),
+        })
+    }
+}
+
+fn crontab<'a>(
+    runner: &dyn CommandRunner,
+    args: impl IntoIterator<Item = &'a str>,
+    input: Option<&str>,
+) -> io::Result<Output> {
+    let mut command = Command::new(CRONTAB);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .args(args);
+
+    runner.run(&mut command, input.map(str::as_bytes))
+}
+
+#[inline]
+fn begin_marker(name: &str) -> String {
+    format!("{MARKER} begin {name}")
+}
+
+#[inline]
+fn end_marker(name: &str) -> String {
+    format!("{MARKER} end {name}")
+}
+
+/// Returns whether the entry of a service is enabled, or `None` if the crontab has no entry for it
+fn find_entry(text: &str, name: &str) -> Option<bool> {
+    let begin = begin_marker(name);
+    let end = end_marker(name);
+    let mut lines = text.lines().skip_while(|line| line.trim() != begin);
+    lines.next()?;
+
+    let mut enabled = false;
+    for line in lines {
+        let line = line.trim();
+        if line == end {
+            return Some(enabled);
+        }
+        if !line.is_empty() && !line.starts_with('#') {
+            enabled = true;
+        }
+    }
+
+    None
+}
+
+/// Replaces the entry of a service within a crontab, appending it if the crontab has no entry
+fn upsert_entry(text: &str, name: &str, entry: &str) -> String {
+    let mut text = remove_entry(text, name);
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+
+    text.push_str(&begin_marker(name));
+    text.push('\n');
+    text.push_str(entry);
+    text.push('\n');
+    text.push_str(&end_marker(name));
+    text.push('\n');
+    text
+}
+
+/// Removes the entry of a service from a crontab, leaving other lines untouched
+fn remove_entry(text: &str, name: &str) -> String {
+    let begin = begin_marker(name);
+    let end = end_marker(name);
+
+    let mut within = false;
+    let mut result = String::new();
+    for line in text.lines() {
+        if line.trim() == begin {
+            within = true;
+        } else if within && line.trim() == end {
+            within = false;
+        } else if !within {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+
+    result
+}
+
+/// Comments out or restores the lines of a service's entry within a crontab
+fn set_entry_enabled(text: &str, name: &str, enabled: bool) -> String {
+    const DISABLED: &str = "#disabled# ";
+
+    let begin = begin_marker(name);
+    let end = end_marker(name);
+
+    let mut within = false;
+    let mut result = String::new();
+    for line in text.lines() {
+        if line.trim() == begin {
+            within = true;
+        } else if within && line.trim() == end {
+            within = false;
+        } else if within && !line.trim().is_empty() {
+            let line = line.strip_prefix(DISABLED).unwrap_or(line);
+            if !enabled {
+                result.push_str(DISABLED);
+            }
+            result.push_str(line);
+            result.push('\n');
+            continue;
+        }
+
+        result.push_str(line);
+        result.push('\n');
+    }
+
+    result
+}
+
+/// Produces the crontab line of a job, which includes the user to run the job as when written to
+/// `/etc/cron.d`
+fn make_entry(config: &CronInstallConfig, system: bool, ctx: &ServiceInstallCtx) -> String {
+    let mut entry = config.schedule.clone();
+
+    if system {
+        entry.push(' ');
+        entry.push_str(ctx.username.as_deref().unwrap_or("root"));
+    }
+
+    entry.push(' ');
+    if let Some(dir) = &ctx.working_directory {
+        let dir = utils::escape_shell_double_quoted(&dir.to_string_lossy()).replace('%', "\\%");
+        entry.push_str(&format!("cd \"{dir}\" && "));
+    }
+
+    if let Some(env_vars) = &ctx.environment {
+        for (var, val) in env_vars {
+            let val = utils::escape_shell_double_quoted(&val.to_string_lossy()).replace('%', "\\%");
+            entry.push_str(&format!("{}=\"{val}\" ", var.to_string_lossy()));
+        }
+    }
+
+    // Percent signs end the command of a crontab line unless escaped
+    let cmd = utils::shell_join(ctx.cmd_iter()).replace('%', "\\%");
+    entry.push_str(&cmd);
+
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::tests::{RecordingRunner, ScriptedRunner};
+    use indoc::indoc;
+    use std::{ffi::OsString, sync::Arc};
+
+    #[test]
+    fn test_install_keeps_crontab_when_listing_fails() {
+        let runner = Arc::new(ScriptedRunner::failing(
+            "crontab: cannot open /var/spool/cron: Permission denied",
+        ));
+        let manager =
+            CronServiceManager::user().with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        let err = manager
+            .install(ServiceInstallCtx {
+                skip_program_check: true,
+                ..crate::utils::tests::install_ctx(PathBuf::from("/usr/local/bin/my_job"))
+            })
+            .unwrap_err();
+        assert!(err.to_string().contains("Permission denied"));
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [vec![String::from("crontab"), String::from("-l")]]
+        );
+    }
+
+    #[test]
+    fn test_uninstall_missing_entry_is_not_found() {
+        let runner = Arc::new(RecordingRunner::default());
+        let manager =
+            CronServiceManager::user().with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        let err = manager
+            .uninstall(ServiceUninstallCtx {
+                label: "org.example.my_job".parse().unwrap(),
+                install_binary_to: None,
+                purge: false,
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        // Only the existing crontab is read, leaving it untouched
+        assert_eq!(*runner.commands.lock().unwrap(), [["crontab", "-l"]]);
+    }
+
+    #[test]
+    fn test_make_entry() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_job".parse::<ServiceLabel>().unwrap(),
+            program: PathBuf::from("/usr/bin/date"),
+            args: vec![OsString::from("+%Y")],
+            contents: None,
+            username: None,
+            working_directory: Some(PathBuf::from("/tmp")),
+            environment: Some(vec![(OsString::from("TZ"), OsString::from("UTC"))]),
+            autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+        let config = CronInstallConfig {
+            schedule: String::from("*/5 * * * *"),
+        };
+
+        assert_eq!(
+            make_entry(&config, true, &ctx),
+            r#"*/5 * * * * root cd "/tmp" && TZ="UTC" /usr/bin/date +\%Y"#
+        );
+        assert_eq!(
+            make_entry(&config, false, &ctx),
+            r#"*/5 * * * * cd "/tmp" && TZ="UTC" /usr/bin/date +\%Y"#
+        );
+
+        let ctx = ServiceInstallCtx {
+            working_directory: Some(PathBuf::from("/tmp/$HOME/100%")),
+            ..ctx
+        };
+        assert_eq!(
+            make_entry(&config, false, &ctx),
+            r#"*/5 * * * * cd "/tmp/\$HOME/100\%" && TZ="UTC" /usr/bin/date +\%Y"#
+        );
+    }
+
+    #[test]
+    fn test_upsert_and_remove_entry() {
+        let text = "MAILTO=\"\"\n0 * * * * /usr/bin/other\n";
+        let text = upsert_entry(text, "org.example.my_job", "@reboot /usr/bin/my_job");
+        assert_eq!(
+            text,
+            indoc! {r#"
+                MAILTO=""
+                0 * * * * /usr/bin/other
+                # service-manager begin org.example.my_job
+                @reboot /usr/bin/my_job
+                # service-manager end org.example.my_job
+            "#}
+        );
+        assert_eq!(find_entry(&text, "org.example.my_job"), Some(true));
+        assert_eq!(find_entry(&text, "org.example.other_job"), None);
+
+        let text = upsert_entry(&text, "org.example.my_job", "@hourly /usr/bin/my_job");
+        assert_eq!(text.matches("begin org.example.my_job").count(), 1);
+        assert!(text.contains("@hourly /usr/bin/my_job\n"));
+
+        assert_eq!(
+            remove_entry(&text, "org.example.my_job"),
+            "MAILTO=\"\"\n0 * * * * /usr/bin/other\n"
+        );
+    }
+
+    #[test]
+    fn test_set_entry_enabled() {
+        let text = upsert_entry("", "org.example.my_job", "@reboot /usr/bin/my_job");
+
+        let disabled = set_entry_enabled(&text, "org.example.my_job", false);
+        assert!(disabled.contains("#disabled# @reboot /usr/bin/my_job\n"));
+        assert_eq!(find_entry(&disabled, "org.example.my_job"), Some(false));
+
+        let enabled = set_entry_enabled(&disabled, "org.example.my_job", true);
+        assert_eq!(enabled, text);
+        assert_eq!(find_entry(&enabled, "org.example.my_job"), Some(true));
+    }
+}
@@ -0,0 +1,374 @@
+use super::{RestartPolicy, ServiceInstallCtx, ServiceKind, ServiceLabel, ServiceManagerKind};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    io,
+    path::PathBuf,
+};
+
+/// Declarative description of a service, typically loaded from a TOML or YAML file, that is
+/// converted into a [`ServiceInstallCtx`] for a specific [`ServiceManagerKind`]
+///
+/// Settings are layered when converting: the base definition is updated by the section in
+/// `platforms` matching the family of the current operating system (`unix` or `windows`), then by
+/// the section matching the operating system itself (e.g. `linux` or `macos`), and finally by the
+/// section in `overrides` matching the kind of service manager.
+///
+/// ```toml
+/// label = "com.example.my-service"
+/// program = "/usr/local/bin/my-service"
+/// args = ["--port", "8080"]
+/// restart_policy = { policy = "on-failure", delay_secs = 5 }
+///
+/// [environment]
+/// RUST_LOG = "info"
+///
+/// [platforms.windows]
+/// program = 'C:\Program Files\my-service\my-service.exe'
+///
+/// [overrides.launchd]
+/// args = ["--port", "8080", "--foreground"]
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ServiceDefinition {
+    /// Label of the service in the form of `{qualifier}.{organization}.{application}`
+    pub label: String,
+
+    /// Path to the program to run
+    pub program: PathBuf,
+
+    /// Arguments to pass to the program
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub args: Vec<String>,
+
+    /// Service-manager-specific content used in place of the generated service definition
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub contents: Option<String>,
+
+    /// User to run the program as
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub username: Option<String>,
+
+    /// Working directory of the program
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub working_directory: Option<PathBuf>,
+
+    /// Environment variables to supply to the program
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub environment: BTreeMap<String, String>,
+
+    /// Whether the service starts automatically upon OS reboot, defaulting to true
+    #[cfg_attr(feature = "serde", serde(default = "default_autostart"))]
+    pub autostart: bool,
+
+    /// Policy for restarting the service after its process exits
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub restart_policy: Option<RestartPolicy>,
+
+    /// Settings applied on specific platforms, keyed by operating system family such as `unix`
+    /// or by operating system such as `linux`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub platforms: HashMap<String, ServiceDefinitionOverride>,
+
+    /// Settings applied when installing with a specific kind of service manager
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub overrides: HashMap<ServiceManagerKind, ServiceDefinitionOverride>,
+}
+
+#[cfg(feature = "serde")]
+#[inline]
+fn default_autostart() -> bool {
+    true
+}
+
+/// Settings of a [`ServiceDefinition`] replaced for a platform or kind of service manager, where
+/// environment variables are merged with those already defined
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ServiceDefinitionOverride {
+    pub program: Option<PathBuf>,
+    pub args: Option<Vec<String>>,
+    pub contents: Option<String>,
+    pub username: Option<String>,
+    pub working_directory: Option<PathBuf>,
+    pub environment: BTreeMap<String, String>,
+    pub autostart: Option<bool>,
+    pub restart_policy: Option<RestartPolicy>,
+}
+
+impl ServiceDefinition {
+    /// Creates a definition for `label` running `program` with every other setting left at its
+    /// default
+    pub fn new(label: impl Into<String>, program: impl Into<PathBuf>) -> Self {
+        Self {
+            label: label.into(),
+            program: program.into(),
+            args: Vec::new(),
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: BTreeMap::new(),
+            autostart: true,
+            restart_policy: None,
+            platforms: HashMap::new(),
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Parses a definition from TOML
+    #[cfg(feature = "toml")]
+    pub fn from_toml_str(s: &str) -> io::Result<Self> {
+        ::toml::from_str(s).map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))
+    }
+
+    /// Parses a definition from YAML
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml_str(s: &str) -> io::Result<Self> {
+        ::serde_yaml::from_str(s).map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))
+    }
+
+    /// Reads a definition from a file, parsed as YAML when the extension is `yaml` or `yml` and
+    /// as TOML otherwise
+    #[cfg(any(feature = "toml", feature = "yaml"))]
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let is_yaml = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yaml") | Some("yml")
+        );
+
+        if is_yaml {
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "yaml")] {
+                    Self::from_yaml_str(&text)
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "Loading YAML service definitions requires the yaml feature",
+                    ))
+                }
+            }
+        } else {
+            cfg_if::cfg_if! {
+                if #[cfg(feature = "toml")] {
+                    Self::from_toml_str(&text)
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::Unsupported,
+                        "Loading TOML service definitions requires the toml feature",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Produces the context used to install the service with the given kind of service manager,
+    /// applying the sections for the current platform and for `kind`
+    pub fn into_install_ctx(self, kind: ServiceManagerKind) -> io::Result<ServiceInstallCtx> {
+        self.into_install_ctx_for(kind, std::env::consts::FAMILY, std::env::consts::OS)
+    }
+
+    fn into_install_ctx_for(
+        mut self,
+        kind: ServiceManagerKind,
+        family: &str,
+        os: &str,
+    ) -> io::Result<ServiceInstallCtx> {
+        let label: ServiceLabel = self.label.parse()?;
+
+        let mut sections = Vec::new();
+        sections.extend(self.platforms.remove(family));
+        sections.extend(self.platforms.remove(os));
+        sections.extend(self.overrides.remove(&kind));
+        for section in sections {
+            self.apply(section);
+        }
+
+        Ok(ServiceInstallCtx {
+            label,
+            program: self.program,
+            args: self.args.into_iter().map(OsString::from).collect(),
+            contents: self.contents,
+            username: self.username,
+            working_directory: self.working_directory,
+            environment: if self.environment.is_empty() {
+                None
+            } else {
+                Some(
+                    self.environment
+                        .into_iter()
+                        .map(|(var, val)| (var.into(), val.into()))
+                        .collect(),
+                )
+            },
+            autostart: self.autostart,
+            restart_policy: self.restart_policy,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        })
+    }
+
+    fn apply(&mut self, section: ServiceDefinitionOverride) {
+        if let Some(program) = section.program {
+            self.program = program;
+        }
+        if let Some(args) = section.args {
+            self.args = args;
+        }
+        if section.contents.is_some() {
+            self.contents = section.contents;
+        }
+        if section.username.is_some() {
+            self.username = section.username;
+        }
+        if section.working_directory.is_some() {
+            self.working_directory = section.working_directory;
+        }
+        self.environment.extend(section.environment);
+        if let Some(autostart) = section.autostart {
+            self.autostart = autostart;
+        }
+        if section.restart_policy.is_some() {
+            self.restart_policy = section.restart_policy;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_into_install_ctx_layers_sections() {
+        let mut definition =
+            ServiceDefinition::new("com.example.my-service", "/usr/bin/my-service");
+        definition.args = vec![String::from("--port"), String::from("8080")];
+        definition
+            .environment
+            .insert(String::from("RUST_LOG"), String::from("info"));
+        definition.platforms.insert(
+            String::from("unix"),
+            ServiceDefinitionOverride {
+                username: Some(String::from("nobody")),
+                ..Default::default()
+            },
+        );
+        definition.platforms.insert(
+            String::from("linux"),
+            ServiceDefinitionOverride {
+                program: Some(PathBuf::from("/opt/my-service/bin/my-service")),
+                environment: [(String::from("PORT"), String::from("8080"))].into(),
+                ..Default::default()
+            },
+        );
+        definition.overrides.insert(
+            ServiceManagerKind::Systemd,
+            ServiceDefinitionOverride {
+                restart_policy: Some(RestartPolicy::OnFailure { delay_secs: None }),
+                ..Default::default()
+            },
+        );
+
+        let ctx = definition
+            .clone()
+            .into_install_ctx_for(ServiceManagerKind::Systemd, "unix", "linux")
+            .unwrap();
+        assert_eq!(ctx.label.to_qualified_name(), "com.example.my-service");
+        assert_eq!(ctx.program, PathBuf::from("/opt/my-service/bin/my-service"));
+        assert_eq!(ctx.args, [OsString::from("--port"), OsString::from("8080")]);
+        assert_eq!(ctx.username.as_deref(), Some("nobody"));
+        assert_eq!(
+            ctx.environment,
+            Some(vec![
+                (OsString::from("PORT"), OsString::from("8080")),
+                (OsString::from("RUST_LOG"), OsString::from("info")),
+            ])
+        );
+        assert!(ctx.autostart);
+        assert_eq!(
+            ctx.restart_policy,
+            Some(RestartPolicy::OnFailure { delay_secs: None })
+        );
+
+        let ctx = definition
+            .into_install_ctx_for(ServiceManagerKind::Sc, "windows", "windows")
+            .unwrap();
+        assert_eq!(ctx.program, PathBuf::from("/usr/bin/my-service"));
+        assert_eq!(ctx.username, None);
+        assert_eq!(ctx.restart_policy, None);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_from_toml_str() {
+        let definition = ServiceDefinition::from_toml_str(
+            r#"
+            label = "com.example.my-service"
+            program = "/usr/bin/my-service"
+            args = ["--port", "8080"]
+            restart_policy = { policy = "on-failure", delay_secs = 5 }
+
+            [environment]
+            RUST_LOG = "info"
+
+            [overrides.openrc]
+            autostart = false
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(definition.args, ["--port", "8080"]);
+        assert_eq!(
+            definition.restart_policy,
+            Some(RestartPolicy::OnFailure {
+                delay_secs: Some(5)
+            })
+        );
+        assert_eq!(
+            definition.overrides[&ServiceManagerKind::OpenRc].autostart,
+            Some(false)
+        );
+        assert!(definition.autostart);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_from_yaml_str() {
+        let definition = ServiceDefinition::from_yaml_str(
+            "label: com.example.my-service\n\
+             program: /usr/bin/my-service\n\
+             restart_policy:\n  policy: always\n\
+             platforms:\n  windows:\n    program: C:\\my-service.exe\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            definition.restart_policy,
+            Some(RestartPolicy::Always { delay_secs: None })
+        );
+        assert_eq!(
+            definition.platforms["windows"].program,
+            Some(PathBuf::from("C:\\my-service.exe"))
+        );
+    }
+}
@@ -0,0 +1,544 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, utils, CommandRunner, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner,
+};
+use std::{
+    fmt::Write as _,
+    io,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+static DINITCTL: &str = "dinitctl";
+const SERVICE_FILE_PERMISSIONS: u32 = 0o644;
+
+/// Configuration settings tied to dinit services
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DinitConfig {
+    pub install: DinitInstallConfig,
+
+    /// Directory that service descriptions are written to, overriding [`dinit_global_dir_path`]
+    /// for system services and [`dinit_user_dir_path`] for user services
+    pub service_dir_path: Option<PathBuf>,
+}
+
+/// Configuration settings used when installing dinit services
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DinitInstallConfig {
+    /// Services that must be started before this service, written as `depends-on`
+    pub depends_on: Vec<String>,
+
+    /// Services that are started before this service without requiring them to succeed, written
+    /// as `waits-for`
+    pub waits_for: Vec<String>,
+
+    /// File that the output of the service is written to, written as `logfile`
+    pub logfile: Option<PathBuf>,
+}
+
+/// Implementation of [`ServiceManager`] for [dinit](https://davmac.org/projects/dinit/)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DinitServiceManager {
+    /// Whether or not this manager is operating at the user-level
+    pub user: bool,
+
+    /// Configuration settings tied to dinit services
+    pub config: DinitConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl DinitServiceManager {
+    /// Creates a new manager instance working with system services
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new manager instance working with user services
+    pub fn user() -> Self {
+        Self::default().into_user()
+    }
+
+    /// Change manager to work with system services
+    pub fn into_system(self) -> Self {
+        Self {
+            config: self.config,
+            user: false,
+            runner: self.runner,
+        }
+    }
+
+    /// Change manager to work with user services
+    pub fn into_user(self) -> Self {
+        Self {
+            config: self.config,
+            user: true,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: DinitConfig) -> Self {
+        Self {
+            config,
+            user: self.user,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Update manager to write service descriptions to the specified directory rather than the
+    /// default one of its level, such as a staging directory used to build a package
+    pub fn with_service_dir(mut self, dir_path: impl Into<PathBuf>) -> Self {
+        self.config.service_dir_path = Some(dir_path.into());
+        self
+    }
+
+    /// Returns the directory that service descriptions are written to
+    pub fn service_dir(&self) -> io::Result<PathBuf> {
+        match &self.config.service_dir_path {
+            Some(dir_path) => Ok(dir_path.clone()),
+            None if self.user => dinit_user_dir_path(),
+            None => Ok(dinit_global_dir_path()),
+        }
+    }
+}
+
+impl ServiceManager for DinitServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        match which::which(DINITCTL) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        utils::validate_environment(&ctx, false)?;
+        utils::require_utf8(&ctx, "dinit")?;
+        utils::capabilities_unsupported(&ctx, "dinit")?;
+        utils::backup_unsupported(&ctx, "dinit")?;
+        utils::check_program(&ctx)?;
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "dinit")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Dinit, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+        let dir_path = self.service_dir()?;
+        let staged_dir_path = utils::staged_path(&ctx, &dir_path);
+        std::fs::create_dir_all(&staged_dir_path)?;
+
+        let service = ctx.label.to_script_name();
+        let env_file_path = dir_path.join(format!("{service}.env"));
+        let staged_env_file_path = staged_dir_path.join(format!("{service}.env"));
+
+        // Environment variables are kept in a separate file loaded via env-file, as the service
+        // description has no setting for individual variables
+        let env_file = match &ctx.environment {
+            Some(env_vars) => {
+                let env = env_vars
+                    .iter()
+                    .map(|(var, val)| {
+                        format!("{}={}\n", var.to_string_lossy(), val.to_string_lossy())
+                    })
+                    .collect::<String>();
+                utils::write_definition(
+                    &ctx,
+                    &staged_env_file_path,
+                    env.as_bytes(),
+                    SERVICE_FILE_PERMISSIONS,
+                )?;
+                Some(env_file_path)
+            }
+            None => {
+                if staged_env_file_path.exists() {
+                    std::fs::remove_file(&staged_env_file_path)?;
+                }
+                None
+            }
+        };
+
+        let description = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_service(&self.config.install, env_file.as_ref(), &ctx),
+        };
+
+        utils::write_definition(
+            &ctx,
+            &staged_dir_path.join(&service),
+            description.as_bytes(),
+            SERVICE_FILE_PERMISSIONS,
+        )?;
+
+        if ctx.autostart && ctx.install_root.is_none() {
+            wrap_output(dinitctl(&*self.runner, "enable", &service, self.user)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "dinit")?;
+
+        let dir_path = self.service_dir()?;
+        let service = ctx.label.to_script_name();
+
+        // The service may be neither enabled, running, nor loaded, so failures are ignored until
+        // its files are removed
+        let _ = dinitctl(&*self.runner, "disable", &service, self.user);
+        let _ = dinitctl(&*self.runner, "stop", &service, self.user);
+        let _ = dinitctl(&*self.runner, "unload", &service, self.user);
+
+        for path in [
+            dir_path.join(&service),
+            dir_path.join(format!("{service}.env")),
+        ] {
+            if path.exists() {
+                std::fs::remove_file(path)?;
+            }
+        }
+
+        utils::purge(&ctx, self.config.install.logfile.clone())?;
+        manifest::remove(ServiceManagerKind::Dinit, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Dinit, self.level(), label)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        wrap_output(dinitctl(
+            &*self.runner,
+            "start",
+            &ctx.label.to_script_name(),
+            self.user,
+        )?)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        wrap_output(dinitctl(
+            &*self.runner,
+            "stop",
+            &ctx.label.to_script_name(),
+            self.user,
+        )?)?;
+        Ok(())
+    }
+
+    fn level(&self) -> ServiceLevel {
+        if self.user {
+            ServiceLevel::User
+        } else {
+            ServiceLevel::System
+        }
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => self.user = false,
+            ServiceLevel::User => self.user = true,
+        }
+
+        Ok(())
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        match &self.config.install.logfile {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)?;
+                Ok(utils::tail(&text, ctx.lines))
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Output of dinit services is only logged when a logfile is configured",
+            )),
+        }
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let service = ctx.label.to_script_name();
+        if !self.service_dir()?.join(&service).exists() {
+            return Ok(crate::ServiceStatus::NotInstalled.into());
+        }
+
+        let output = dinitctl(&*self.runner, "status", &service, self.user)?;
+
+        // Services that have not been loaded yet are reported as errors, although dinit loads
+        // them on demand from their description
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("not loaded") {
+                return Ok(crate::ServiceStatus::Stopped(None).into());
+            }
+        }
+
+        let output = wrap_output(output)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_status(&stdout).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to get status of service {service}: {stdout}"),
+            )
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        let mut command = Command::new(DINITCTL);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .arg(if self.user { "--user" } else { "--system" })
+            .arg("signal")
+            .arg(ctx.signal.name())
+            .arg(ctx.label.to_script_name());
+        wrap_output(self.runner.run(&mut command, None)?)?;
+        Ok(())
+    }
+}
+
+fn dinitctl(
+    runner: &dyn CommandRunner,
+    cmd: &str,
+    service: &str,
+    user: bool,
+) -> io::Result<Output> {
+    let mut command = Command::new(DINITCTL);
+
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if user {
+        command.arg("--user");
+    } else {
+        command.arg("--system");
+    }
+
+    command.arg(cmd).arg(service);
+
+    runner.run(&mut command, None)
+}
+
+#[inline]
+pub fn dinit_global_dir_path() -> PathBuf {
+    PathBuf::from("/etc/dinit.d")
+}
+
+pub fn dinit_user_dir_path() -> io::Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Unable to locate home directory"))?
+        .join("dinit.d"))
+}
+
+/// Parses the output of `dinitctl status`, which lists properties such as `State: STARTED` and
+/// `Process ID: 123` on separate lines
+fn parse_status(output: &str) -> Option<crate::ServiceStatusDetails> {
+    use crate::{ServiceStatus, ServiceTransition};
+
+    let mut state = None;
+    let mut pid = None;
+    for line in output.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(x) => x,
+            None => continue,
+        };
+
+        match key.trim() {
+            "State" => state = value.split_whitespace().next(),
+            "Process ID" => pid = value.trim().parse().ok(),
+            _ => {}
+        }
+    }
+
+    let (status, transition) = match state? {
+        "STARTED" => (ServiceStatus::Running, None),
+        "STARTING" => (
+            ServiceStatus::Stopped(None),
+            Some(ServiceTransition::Starting),
+        ),
+        "STOPPING" => (ServiceStatus::Running, Some(ServiceTransition::Stopping)),
+        "STOPPED" => (ServiceStatus::Stopped(None), None),
+        _ => return None,
+    };
+
+    Some(crate::ServiceStatusDetails {
+        status,
+        pid,
+        transition,
+    })
+}
+
+fn make_service(
+    config: &DinitInstallConfig,
+    env_file: Option<&PathBuf>,
+    ctx: &ServiceInstallCtx,
+) -> String {
+    let cmd = ctx
+        .cmd_iter()
+        .map(|a| utils::double_quote(&a.to_string_lossy()).into_owned())
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    // A scripted service is considered started once its command exits successfully
+    let service_type = match ctx.kind {
+        crate::ServiceKind::Simple => "process",
+        crate::ServiceKind::Oneshot => "scripted",
+    };
+    let mut service = format!(
+        "# Service for {}\ntype = {service_type}\ncommand = {cmd}\n",
+        ctx.label.to_qualified_name()
+    );
+
+    if let Some(dir) = &ctx.working_directory {
+        let _ = writeln!(service, "working-dir = {}", dir.to_string_lossy());
+    }
+
+    if let Some(username) = &ctx.username {
+        let _ = writeln!(service, "run-as = {username}");
+    }
+
+    if let Some(env_file) = env_file {
+        let _ = writeln!(service, "env-file = {}", env_file.to_string_lossy());
+    }
+
+    let (restart, delay_secs) = match ctx.restart_policy {
+        _ if ctx.kind == crate::ServiceKind::Oneshot => (None, None),
+        None => (None, None),
+        Some(RestartPolicy::Never) => (Some("false"), None),
+        Some(RestartPolicy::Always { delay_secs }) => (Some("true"), delay_secs),
+        Some(RestartPolicy::OnFailure { delay_secs }) => (Some("on-failure"), delay_secs),
+    };
+    if let Some(restart) = restart {
+        let _ = writeln!(service, "restart = {restart}");
+    }
+    if let Some(delay) = delay_secs {
+        let _ = writeln!(service, "restart-delay = {delay}");
+    }
+
+    for dependency in &config.depends_on {
+        let _ = writeln!(service, "depends-on = {dependency}");
+    }
+
+    for dependency in &config.waits_for {
+        let _ = writeln!(service, "waits-for = {dependency}");
+    }
+
+    if let Some(logfile) = &config.logfile {
+        let _ = writeln!(service, "logfile = {}", logfile.to_string_lossy());
+    }
+
+    service.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServiceLabel;
+    use indoc::indoc;
+    use std::ffi::OsString;
+
+    #[test]
+    fn test_make_service() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse::<ServiceLabel>().unwrap(),
+            program: PathBuf::from("/usr/bin/my_service"),
+            args: vec![OsString::from("--port"), OsString::from("8080")],
+            contents: None,
+            username: Some(String::from("nobody")),
+            working_directory: Some(PathBuf::from("/var/lib/my_service")),
+            environment: None,
+            autostart: true,
+            restart_policy: Some(RestartPolicy::OnFailure {
+                delay_secs: Some(3),
+            }),
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+        let config = DinitInstallConfig {
+            depends_on: vec![String::from("network")],
+            logfile: Some(PathBuf::from("/var/log/my_service.log")),
+            ..Default::default()
+        };
+        let env_file = PathBuf::from("/etc/dinit.d/org.example.my_service.env");
+
+        assert_eq!(
+            make_service(&config, Some(&env_file), &ctx),
+            indoc! {r#"
+                # Service for org.example.my_service
+                type = process
+                command = /usr/bin/my_service --port 8080
+                working-dir = /var/lib/my_service
+                run-as = nobody
+                env-file = /etc/dinit.d/org.example.my_service.env
+                restart = on-failure
+                restart-delay = 3
+                depends-on = network
+                logfile = /var/log/my_service.log"#}
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let details = parse_status(indoc! {r#"
+            Service: org.example.my_service
+                State: STARTED
+                Activation: explicitly started
+                Process ID: 1234
+        "#})
+        .unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Running);
+        assert_eq!(details.pid, Some(1234));
+
+        let details = parse_status(indoc! {r#"
+            Service: org.example.my_service
+                State: STOPPED (terminated; exited - status 1)
+        "#})
+        .unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Stopped(None));
+        assert_eq!(details.pid, None);
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+
+/// Setting of a service that can differ between its installed definition and a
+/// [`ServiceInstallCtx`](crate::ServiceInstallCtx)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum DriftField {
+    Program,
+    Args,
+    Environment,
+    Username,
+    RestartPolicy,
+
+    /// Any other setting of the definition, such as its description or working directory
+    Other,
+}
+
+/// Outcome of comparing the installed definition of a service to the definition that would be
+/// installed for a [`ServiceInstallCtx`](crate::ServiceInstallCtx)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct DriftReport {
+    /// Whether the service is installed at all
+    pub installed: bool,
+
+    /// Settings that differ, in the order of [`DriftField`] and without duplicates
+    pub differences: Vec<DriftField>,
+
+    /// Files recorded in the [`InstallManifest`](crate::InstallManifest) of the service that no
+    /// longer exist
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub missing_files: Vec<PathBuf>,
+}
+
+impl DriftReport {
+    /// Report for a service that is not installed
+    pub fn not_installed() -> Self {
+        Self::default()
+    }
+
+    /// Compares the settings of the installed definition to the expected ones, where each setting
+    /// is paired with the field it belongs to and the order of settings does not matter
+    pub fn compare(
+        installed: impl IntoIterator<Item = (DriftField, String)>,
+        expected: impl IntoIterator<Item = (DriftField, String)>,
+    ) -> Self {
+        let installed = installed.into_iter().collect::<Vec<_>>();
+        let expected = expected.into_iter().collect::<Vec<_>>();
+
+        let mut differences = installed
+            .iter()
+            .filter(|x| !expected.contains(x))
+            .chain(expected.iter().filter(|x| !installed.contains(x)))
+            .map(|(field, _)| *field)
+            .collect::<Vec<_>>();
+        differences.sort();
+        differences.dedup();
+
+        Self {
+            installed: true,
+            differences,
+            missing_files: Vec::new(),
+        }
+    }
+
+    /// Update report to list the files of the service that no longer exist
+    pub fn with_missing_files(self, missing_files: Vec<PathBuf>) -> Self {
+        Self {
+            missing_files,
+            ..self
+        }
+    }
+
+    /// Returns true if the service is installed, its definition matches, and none of its files are
+    /// missing
+    pub fn is_up_to_date(&self) -> bool {
+        self.installed && self.differences.is_empty() && self.missing_files.is_empty()
+    }
+}
+
+/// Splits the settings of a line-based definition such as a systemd unit, ignoring blank lines and
+/// comments, and pairing each with its field using `classify`, which may split a setting in two
+pub(crate) fn line_entries(
+    text: &str,
+    classify: impl Fn(&str) -> Vec<(DriftField, String)>,
+) -> Vec<(DriftField, String)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+        .flat_map(classify)
+        .collect()
+}
+
+/// Splits a command line into its program and the rest of its arguments, where the program may be
+/// wrapped in single or double quotes
+pub(crate) fn split_command(cmd: &str) -> (String, String) {
+    let cmd = cmd.trim();
+    let end = match cmd.chars().next() {
+        Some(quote @ ('"' | '\'')) => cmd[1..].find(quote).map(|i| i + 2).unwrap_or(cmd.len()),
+        _ => cmd.find(char::is_whitespace).unwrap_or(cmd.len()),
+    };
+
+    (cmd[..end].to_string(), cmd[end..].trim_start().to_string())
+}
+
+/// Pairs the program and arguments of a command line with their fields
+pub(crate) fn command_entries(cmd: &str) -> Vec<(DriftField, String)> {
+    let (program, args) = split_command(cmd);
+    vec![(DriftField::Program, program), (DriftField::Args, args)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command("/usr/bin/my_service --port 8080"),
+            (
+                String::from("/usr/bin/my_service"),
+                String::from("--port 8080")
+            )
+        );
+        assert_eq!(
+            split_command(r#""/opt/My App/my_service" --verbose"#),
+            (
+                String::from(r#""/opt/My App/my_service""#),
+                String::from("--verbose")
+            )
+        );
+        assert_eq!(
+            split_command("/usr/bin/my_service"),
+            (String::from("/usr/bin/my_service"), String::new())
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let entries = |program: &str, env: &[&str]| {
+            let mut entries = vec![(DriftField::Program, program.to_string())];
+            entries.extend(env.iter().map(|x| (DriftField::Environment, x.to_string())));
+            entries
+        };
+
+        let report = DriftReport::compare(
+            entries("/usr/bin/a", &["A=1", "B=2"]),
+            entries("/usr/bin/a", &["B=2", "A=1"]),
+        );
+        assert!(report.is_up_to_date());
+
+        let report = DriftReport::compare(
+            entries("/usr/bin/a", &["A=1"]),
+            entries("/usr/bin/b", &["A=2"]),
+        );
+        assert_eq!(
+            report.differences,
+            [DriftField::Program, DriftField::Environment]
+        );
+        assert!(!DriftReport::not_installed().is_up_to_date());
+    }
+}
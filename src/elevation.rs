@@ -0,0 +1,272 @@
+use super::{CommandRunner, ServiceLevel, SharedCommandRunner};
+use once_cell::sync::OnceCell;
+use std::{
+    fmt, io,
+    process::{Command, Output, Stdio},
+};
+
+/// How commands that need administrator privileges are run from a process that lacks them
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ElevationStrategy {
+    /// Run commands as the current user, failing when they need privileges it lacks
+    None,
+
+    /// Run commands through `sudo`, which may prompt for a password on the terminal
+    Sudo,
+
+    /// Run commands through `doas`, which may prompt for a password on the terminal
+    Doas,
+
+    /// Run commands through `pkexec`, which asks for authorization through polkit
+    Pkexec,
+
+    /// Windows has no way to elevate a single command while capturing its output, so commands
+    /// are run as the current user and [`ElevationStrategy::check`] explains how to relaunch the
+    /// program from an elevated prompt instead
+    RunAs,
+}
+
+impl Default for ElevationStrategy {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+impl ElevationStrategy {
+    /// Returns the program that commands are run through to elevate them, if any
+    pub fn program(&self) -> Option<&'static str> {
+        match self {
+            Self::None | Self::RunAs => None,
+            Self::Sudo => Some("sudo"),
+            Self::Doas => Some("doas"),
+            Self::Pkexec => Some("pkexec"),
+        }
+    }
+
+    /// Fails with [`io::ErrorKind::PermissionDenied`] if working with services at `level` needs
+    /// privileges that the current process lacks and this strategy cannot provide, explaining how
+    /// to gain them
+    pub fn check(&self, level: ServiceLevel) -> io::Result<()> {
+        if self.program().is_some() || !needs_elevation(level)? {
+            return Ok(());
+        }
+
+        let msg = match self {
+            Self::RunAs => {
+                "System services can only be managed by an administrator; relaunch this program \
+                from an elevated prompt, such as by choosing \"Run as administrator\" or using \
+                `runas /user:Administrator`"
+            }
+            _ => {
+                "System services can only be managed by an administrator; run this program as \
+                root or elevate its commands with sudo, doas, or pkexec"
+            }
+        };
+        Err(io::Error::new(io::ErrorKind::PermissionDenied, msg))
+    }
+}
+
+impl fmt::Display for ElevationStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Sudo => write!(f, "sudo"),
+            Self::Doas => write!(f, "doas"),
+            Self::Pkexec => write!(f, "pkexec"),
+            Self::RunAs => write!(f, "runas"),
+        }
+    }
+}
+
+/// Returns true if the current process runs with administrator privileges, meaning as root on
+/// Unix or with an elevated token on Windows
+pub fn is_elevated() -> io::Result<bool> {
+    cfg_if::cfg_if! {
+        if #[cfg(windows)] {
+            // Listing sessions requires an elevated token, making it a common way to check for one
+            let status = Command::new("net")
+                .arg("session")
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()?;
+            Ok(status.success())
+        } else {
+            let output = Command::new("id").arg("-u").stdin(Stdio::null()).output()?;
+            Ok(String::from_utf8_lossy(&output.stdout).trim() == "0")
+        }
+    }
+}
+
+/// Returns true if working with services at `level` needs administrator privileges that the
+/// current process lacks
+///
+/// User services never need elevation, while system services need it unless the process is
+/// already elevated.
+pub fn needs_elevation(level: ServiceLevel) -> io::Result<bool> {
+    match level {
+        ServiceLevel::User => Ok(false),
+        ServiceLevel::System => Ok(!is_elevated()?),
+    }
+}
+
+/// Implementation of [`CommandRunner`] that runs commands through `sudo`, `doas`, or `pkexec`
+/// when the current process is not already elevated, passing them to another runner
+///
+/// Only provide this to a manager working with system services, as elevating the commands of a
+/// user-level manager would have them work with the services of the administrator instead.
+/// Definition files such as unit files are still written by the current process, so managers that
+/// write them fail early with [`io::ErrorKind::PermissionDenied`] when installing or uninstalling
+/// a system service from a process that is not elevated. Commands such as starting, stopping, and
+/// querying services are elevated for every manager, as is installing them with managers that
+/// only run commands, such as containers.
+///
+/// Since the stdio settings of a command cannot be carried over to the elevated command, elevated
+/// commands without input are run using [`CommandRunner::run_until_exit`] of the wrapped runner.
+#[derive(Clone, Debug)]
+pub struct ElevatingCommandRunner {
+    strategy: ElevationStrategy,
+    runner: SharedCommandRunner,
+    elevated: OnceCell<bool>,
+}
+
+impl ElevatingCommandRunner {
+    /// Creates a runner that elevates commands using `strategy` and spawns them as processes
+    pub fn new(strategy: ElevationStrategy) -> Self {
+        Self {
+            strategy,
+            runner: SharedCommandRunner::default(),
+            elevated: OnceCell::new(),
+        }
+    }
+
+    /// Update runner to pass commands to the specified runner once elevated
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Returns the strategy used to elevate commands
+    pub fn strategy(&self) -> ElevationStrategy {
+        self.strategy
+    }
+
+    /// Returns the command to run in place of `command`, or `None` if it should run as is
+    fn elevate(&self, command: &Command) -> Option<Command> {
+        let program = self.strategy.program()?;
+
+        // Failing to tell whether the process is elevated is treated as not being elevated
+        if *self.elevated.get_or_init(|| is_elevated().unwrap_or(false)) {
+            return None;
+        }
+
+        let mut elevated = Command::new(program);
+        elevated
+            .stdin(Stdio::null())
+            .arg(command.get_program())
+            .args(command.get_args());
+        if let Some(dir) = command.get_current_dir() {
+            elevated.current_dir(dir);
+        }
+        for (key, value) in command.get_envs() {
+            match value {
+                Some(value) => elevated.env(key, value),
+                None => elevated.env_remove(key),
+            };
+        }
+
+        Some(elevated)
+    }
+}
+
+impl CommandRunner for ElevatingCommandRunner {
+    fn run(&self, command: &mut Command, input: Option<&[u8]>) -> io::Result<Output> {
+        match (self.elevate(command), input) {
+            (Some(mut elevated), Some(input)) => self.runner.run(&mut elevated, Some(input)),
+            (Some(mut elevated), None) => self.runner.run_until_exit(&mut elevated),
+            (None, input) => self.runner.run(command, input),
+        }
+    }
+
+    fn run_until_exit(&self, command: &mut Command) -> io::Result<Output> {
+        match self.elevate(command) {
+            Some(mut elevated) => self.runner.run_until_exit(&mut elevated),
+            None => self.runner.run_until_exit(command),
+        }
+    }
+
+    fn elevation(&self) -> ElevationStrategy {
+        self.strategy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::tests::RecordingRunner;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_elevates_commands() {
+        let runner = Arc::new(RecordingRunner::default());
+        let elevating = ElevatingCommandRunner::new(ElevationStrategy::Sudo)
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+        elevating.elevated.set(false).unwrap();
+
+        let mut command = Command::new("systemctl");
+        command.arg("start").arg("example-my-service");
+        elevating.run(&mut command, None).unwrap();
+
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [["sudo", "systemctl", "start", "example-my-service"]]
+        );
+    }
+
+    #[test]
+    fn test_runs_commands_as_is_when_elevated() {
+        let runner = Arc::new(RecordingRunner::default());
+        let elevating = ElevatingCommandRunner::new(ElevationStrategy::Doas)
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+        elevating.elevated.set(true).unwrap();
+
+        let mut command = Command::new("rc-service");
+        command.arg("my-service").arg("start");
+        elevating.run(&mut command, None).unwrap();
+
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [["rc-service", "my-service", "start"]]
+        );
+    }
+
+    #[test]
+    fn test_elevation_is_reported_through_shared_runner() {
+        let runner =
+            SharedCommandRunner::new(ElevatingCommandRunner::new(ElevationStrategy::Pkexec));
+        assert_eq!(runner.elevation(), ElevationStrategy::Pkexec);
+        assert_eq!(
+            SharedCommandRunner::default().elevation(),
+            ElevationStrategy::None
+        );
+
+        // Runners that do not elevate never keep definitions from being written
+        assert!(crate::utils::check_file_access(
+            &*SharedCommandRunner::default(),
+            ServiceLevel::System,
+            "systemd"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_check_user_level() {
+        for strategy in [ElevationStrategy::None, ElevationStrategy::RunAs] {
+            assert!(strategy.check(ServiceLevel::User).is_ok());
+        }
+    }
+}
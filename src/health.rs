@@ -0,0 +1,287 @@
+use super::ServiceLabel;
+use std::{
+    ffi::OsString,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::PathBuf,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time given to a health check when none is specified
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time between checks of whether a probing command has exited
+const EXEC_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Probe confirming that a service is actually serving rather than merely running according to
+/// its service manager
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HealthCheck {
+    /// Healthy when a TCP connection can be established to the address, e.g. `127.0.0.1:8080`
+    Tcp { address: String },
+
+    /// Healthy when a GET request to the plain `http://` url responds with the expected status
+    Http { url: String, status: u16 },
+
+    /// Healthy when the program exits successfully
+    Exec {
+        program: PathBuf,
+        args: Vec<OsString>,
+    },
+}
+
+impl HealthCheck {
+    /// Runs the probe, failing with the reason the service is unhealthy if it does not succeed
+    /// within `timeout`
+    pub fn probe(&self, timeout: Duration) -> io::Result<()> {
+        match self {
+            Self::Tcp { address } => probe_tcp(address, timeout).map(drop),
+            Self::Http { url, status } => probe_http(url, *status, timeout),
+            Self::Exec { program, args } => probe_exec(program, args, timeout),
+        }
+    }
+}
+
+/// Health of a service as determined by a [`HealthCheck`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ServiceHealth {
+    /// The service is running and passed its health check
+    Healthy,
+
+    /// The service is not running according to its service manager, so it was not probed
+    NotRunning,
+
+    /// The service is running but failed its health check for the given reason
+    Unhealthy(String),
+}
+
+/// Context provided to the health function of [`ServiceManager`](crate::ServiceManager)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceHealthCtx {
+    /// Label associated with the service
+    ///
+    /// E.g. `rocks.distant.manager`
+    pub label: ServiceLabel,
+
+    /// Probe used to check the health of the service
+    pub check: HealthCheck,
+
+    /// Time given to the probe, defaulting to [`DEFAULT_HEALTH_CHECK_TIMEOUT`]
+    pub timeout: Option<Duration>,
+}
+
+fn probe_tcp(address: &str, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(x) => last_err = Some(x),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Address {address} did not resolve"),
+        )
+    }))
+}
+
+fn probe_http(url: &str, expected: u16, timeout: Duration) -> io::Result<()> {
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Only http:// urls can be probed: {url}"),
+        )
+    })?;
+    let (host, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let address = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:80")
+    };
+
+    let mut stream = probe_tcp(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    write!(
+        stream,
+        "GET {path} HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n"
+    )?;
+
+    // Only the status line is needed, so the rest of the response is never read
+    let mut response = Vec::new();
+    let mut buf = [0; 256];
+    while !response.contains(&b'\n') {
+        match stream.read(&mut buf)? {
+            0 => break,
+            n => response.extend_from_slice(&buf[..n]),
+        }
+    }
+
+    let response = String::from_utf8_lossy(&response);
+    let status = parse_status_line(&response).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid response from {url}"),
+        )
+    })?;
+
+    if status == expected {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("Expected status {expected} from {url}, got {status}"),
+        ))
+    }
+}
+
+/// Parses the status code out of an HTTP status line such as `HTTP/1.1 200 OK`
+fn parse_status_line(response: &str) -> Option<u16> {
+    let mut parts = response.lines().next()?.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+
+    parts.next()?.parse().ok()
+}
+
+fn probe_exec(program: &PathBuf, args: &[OsString], timeout: Duration) -> io::Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return if status.success() {
+                Ok(())
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("{} failed with {status}", program.display()),
+                ))
+            };
+        }
+
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} did not exit within {timeout:?}", program.display()),
+            ));
+        }
+
+        thread::sleep(EXEC_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn test_health_of_running_service() {
+        use crate::{MockServiceManager, ServiceManager, ServiceStatus};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let label: ServiceLabel = "com.example.my-service".parse().unwrap();
+        let ctx = ServiceHealthCtx {
+            label: label.clone(),
+            check: HealthCheck::Tcp {
+                address: listener.local_addr().unwrap().to_string(),
+            },
+            timeout: None,
+        };
+
+        let mock = MockServiceManager::new();
+        assert_eq!(mock.health(ctx.clone()).unwrap(), ServiceHealth::NotRunning);
+
+        mock.set_status(&label, ServiceStatus::Running);
+        assert_eq!(mock.health(ctx.clone()).unwrap(), ServiceHealth::Healthy);
+
+        drop(listener);
+        assert!(matches!(
+            mock.health(ctx).unwrap(),
+            ServiceHealth::Unhealthy(_)
+        ));
+    }
+
+    #[test]
+    fn test_probe_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let check = HealthCheck::Tcp { address };
+        assert!(check.probe(DEFAULT_HEALTH_CHECK_TIMEOUT).is_ok());
+
+        drop(listener);
+        assert!(check.probe(DEFAULT_HEALTH_CHECK_TIMEOUT).is_err());
+    }
+
+    #[test]
+    fn test_probe_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/health", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 1024];
+                let _ = stream.read(&mut buf).unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n")
+                    .unwrap();
+            }
+        });
+
+        let check = HealthCheck::Http {
+            url: url.clone(),
+            status: 204,
+        };
+        assert!(check.probe(DEFAULT_HEALTH_CHECK_TIMEOUT).is_ok());
+
+        let check = HealthCheck::Http { url, status: 200 };
+        assert!(check.probe(DEFAULT_HEALTH_CHECK_TIMEOUT).is_err());
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_parse_status_line() {
+        assert_eq!(parse_status_line("HTTP/1.1 200 OK\r\n"), Some(200));
+        assert_eq!(parse_status_line("HTTP/1.0 503\r\n"), Some(503));
+        assert_eq!(parse_status_line("SSH-2.0-OpenSSH_9.6\r\n"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_probe_exec() {
+        let check = |program: &str| HealthCheck::Exec {
+            program: PathBuf::from(program),
+            args: Vec::new(),
+        };
+        assert!(check("true").probe(DEFAULT_HEALTH_CHECK_TIMEOUT).is_ok());
+        assert!(check("false").probe(DEFAULT_HEALTH_CHECK_TIMEOUT).is_err());
+
+        let check = HealthCheck::Exec {
+            program: PathBuf::from("sleep"),
+            args: vec![OsString::from("10")],
+        };
+        assert_eq!(
+            check.probe(Duration::from_millis(100)).unwrap_err().kind(),
+            io::ErrorKind::TimedOut
+        );
+    }
+}
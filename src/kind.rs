@@ -1,5 +1,6 @@
+use super::ServiceLevel;
 use cfg_if::cfg_if;
-use std::io;
+use std::{fmt, io, str::FromStr};
 
 /// Represents the kind of service manager
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
@@ -8,42 +9,225 @@ use std::io;
 #[cfg_attr(feature = "clap", clap(rename_all = "lowercase"))]
 #[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum ServiceManagerKind {
+    /// Use cron to manage the service
+    Cron,
+
+    /// Use dinit to manage the service
+    Dinit,
+
+    /// Use docker to manage the service
+    Docker,
+
     /// Use launchd to manage the service
     Launchd,
 
+    /// Use NSSM to manage the service
+    Nssm,
+
     /// Use OpenRC to manage the service
     OpenRc,
 
+    /// Use procd to manage the service
+    Procd,
+
     /// Use rc.d to manage the service
     Rcd,
 
+    /// Use runit to manage the service
+    Runit,
+
+    /// Use s6 to manage the service
+    S6,
+
+    /// Use Windows Task Scheduler to manage the service
+    Schtasks,
+
     /// Use Windows service controller to manage the service
     Sc,
 
+    /// Use supervisord to manage the service
+    Supervisord,
+
     /// Use systemd to manage the service
     Systemd,
 
+    /// Use SysV init to manage the service
+    SysV,
+
+    /// Use upstart to manage the service
+    Upstart,
+
     /// Use WinSW to manage the service
     WinSw,
 }
 
 impl ServiceManagerKind {
+    /// Order in which [`ServiceManagerKind::native`] considers service managers on Linux
+    pub const LINUX_PREFERENCE: &'static [ServiceManagerKind] = &[
+        ServiceManagerKind::Systemd,
+        ServiceManagerKind::OpenRc,
+        ServiceManagerKind::Runit,
+        ServiceManagerKind::S6,
+        ServiceManagerKind::Dinit,
+        ServiceManagerKind::Procd,
+        ServiceManagerKind::Upstart,
+        ServiceManagerKind::SysV,
+    ];
+
+    /// Order in which [`ServiceManagerKind::native`] considers service managers on Windows
+    ///
+    /// WinSW comes before sc.exe, because if it's present, it's likely been explicitly installed as
+    /// an alternative to sc.exe.
+    pub const WINDOWS_PREFERENCE: &'static [ServiceManagerKind] =
+        &[ServiceManagerKind::WinSw, ServiceManagerKind::Sc];
+
+    /// Returns every kind of service manager, including those whose feature is disabled, which
+    /// are never [available](super::ServiceManager::available)
+    pub fn all() -> &'static [ServiceManagerKind] {
+        &[
+            ServiceManagerKind::Cron,
+            ServiceManagerKind::Dinit,
+            ServiceManagerKind::Docker,
+            ServiceManagerKind::Launchd,
+            ServiceManagerKind::Nssm,
+            ServiceManagerKind::OpenRc,
+            ServiceManagerKind::Procd,
+            ServiceManagerKind::Rcd,
+            ServiceManagerKind::Runit,
+            ServiceManagerKind::S6,
+            ServiceManagerKind::Schtasks,
+            ServiceManagerKind::Sc,
+            ServiceManagerKind::Supervisord,
+            ServiceManagerKind::Systemd,
+            ServiceManagerKind::SysV,
+            ServiceManagerKind::Upstart,
+            ServiceManagerKind::WinSw,
+        ]
+    }
+
+    /// Returns every kind of service manager that is available on the current machine, in the
+    /// same order as [`ServiceManagerKind::all`]
+    pub fn detect_all() -> Vec<ServiceManagerKind> {
+        use super::{ServiceManager, TypedServiceManager};
+
+        Self::all()
+            .iter()
+            .copied()
+            .filter(|kind| matches!(TypedServiceManager::target(*kind).available(), Ok(true)))
+            .collect()
+    }
+
+    /// Returns the lowercase name of this kind, e.g. `systemd` or `winsw`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Cron => "cron",
+            Self::Dinit => "dinit",
+            Self::Docker => "docker",
+            Self::Launchd => "launchd",
+            Self::Nssm => "nssm",
+            Self::OpenRc => "openrc",
+            Self::Procd => "procd",
+            Self::Rcd => "rcd",
+            Self::Runit => "runit",
+            Self::S6 => "s6",
+            Self::Sc => "sc",
+            Self::Schtasks => "schtasks",
+            Self::Supervisord => "supervisord",
+            Self::Systemd => "systemd",
+            Self::SysV => "sysv",
+            Self::Upstart => "upstart",
+            Self::WinSw => "winsw",
+        }
+    }
+
+    /// Looks up a kind by its lowercase name, e.g. `systemd` or `winsw`
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Self::all().iter().copied().find(|kind| kind.name() == name)
+    }
+
+    /// Selects the first of `preference` whose init system is running, falling back to the first
+    /// whose service manager is available
+    ///
+    /// On Linux, the init system in use is recognized from the name of PID 1 and the paths each
+    /// init system creates under `/run` while running, such as `/run/systemd/system` or
+    /// `/run/openrc/softlevel`, rather than files that are merely installed. This prevents
+    /// picking a service manager whose tools happen to be installed alongside another init
+    /// system.
+    pub fn native_with_preference(preference: &[ServiceManagerKind]) -> io::Result<Self> {
+        use super::{ServiceManager, TypedServiceManager};
+
+        if let Some(kind) = preference.iter().find(|kind| kind.is_init_in_use()) {
+            return Ok(*kind);
+        }
+
+        for kind in preference {
+            let manager = TypedServiceManager::target(*kind);
+            if let Ok(true) = manager.available() {
+                return Ok(*kind);
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "None of the preferred service managers are available",
+        ))
+    }
+
+    /// Returns true if this kind is the init system running on the current machine
+    fn is_init_in_use(&self) -> bool {
+        cfg_if! {
+            if #[cfg(target_os = "linux")] {
+                use std::path::Path;
+
+                let comm = std::fs::read_to_string("/proc/1/comm").unwrap_or_default();
+                let pid1 = comm.trim();
+
+                match self {
+                    Self::Systemd => pid1 == "systemd" || Path::new("/run/systemd/system").is_dir(),
+                    Self::OpenRc => {
+                        pid1 == "openrc-init" || Path::new("/run/openrc/softlevel").exists()
+                    }
+                    Self::Runit => pid1 == "runit" || Path::new("/run/runit").is_dir(),
+                    Self::S6 => pid1 == "s6-svscan" || Path::new("/run/s6").is_dir(),
+                    Self::Dinit => pid1 == "dinit",
+                    Self::Procd => pid1 == "procd",
+                    _ => false,
+                }
+            } else {
+                false
+            }
+        }
+    }
+
+    /// Looks up the kind of service management platform native to the operating system that
+    /// supports services at `level`
+    ///
+    /// This matches [`ServiceManagerKind::native`], except on Windows where user-level services
+    /// are managed by Task Scheduler, as neither sc.exe nor WinSW can install a service for only the
+    /// current user.
+    pub fn native_for_level(level: ServiceLevel) -> io::Result<ServiceManagerKind> {
+        cfg_if! {
+            if #[cfg(target_os = "windows")] {
+                match level {
+                    ServiceLevel::System => Self::native(),
+                    ServiceLevel::User => Ok(ServiceManagerKind::Schtasks),
+                }
+            } else {
+                let _ = level;
+                Self::native()
+            }
+        }
+    }
+
     /// Looks up the kind of service management platform native to the operating system
     pub fn native() -> io::Result<ServiceManagerKind> {
         cfg_if! {
             if #[cfg(target_os = "macos")] {
                 Ok(ServiceManagerKind::Launchd)
             } else if #[cfg(target_os = "windows")] {
-                use super::{ServiceManager, TypedServiceManager};
-
-                // Prefer WinSW over sc.exe, because if it's present, it's likely been explicitly
-                // installed as an alternative to sc.exe.
-                let manager = TypedServiceManager::target(ServiceManagerKind::WinSw);
-                if let Ok(true) = manager.available() {
-                    return Ok(ServiceManagerKind::WinSw);
-                }
-
-                Ok(ServiceManagerKind::Sc)
+                // sc.exe ships with Windows, so it is used even when it cannot be reached
+                Self::native_with_preference(Self::WINDOWS_PREFERENCE)
+                    .or(Ok(ServiceManagerKind::Sc))
             } else if #[cfg(any(
                 target_os = "freebsd",
                 target_os = "dragonfly",
@@ -52,22 +236,19 @@ impl ServiceManagerKind {
             ))] {
                 Ok(ServiceManagerKind::Rcd)
             } else if #[cfg(target_os = "linux")] {
-                use super::{ServiceManager, TypedServiceManager};
-
-                let manager = TypedServiceManager::target(ServiceManagerKind::Systemd);
-                if let Ok(true) = manager.available() {
-                    return Ok(ServiceManagerKind::Systemd);
-                }
-
-                let manager = TypedServiceManager::target(ServiceManagerKind::OpenRc);
-                if let Ok(true) = manager.available() {
-                    return Ok(ServiceManagerKind::OpenRc);
-                }
-
-                Err(io::Error::new(
-                    io::ErrorKind::Unsupported,
-                    "Only systemd and openrc are supported on Linux",
-                ))
+                Self::native_with_preference(Self::LINUX_PREFERENCE).map_err(|_| {
+                    if super::utils::is_wsl() {
+                        io::Error::new(
+                            io::ErrorKind::Unsupported,
+                            "No service manager is usable within WSL, which requires systemd to be enabled via [boot] systemd=true in /etc/wsl.conf",
+                        )
+                    } else {
+                        io::Error::new(
+                            io::ErrorKind::Unsupported,
+                            "Only systemd, openrc, runit, s6, dinit, procd, upstart, and SysV init are supported on Linux",
+                        )
+                    }
+                })
             } else {
                 Err(io::Error::new(
                     io::ErrorKind::Unsupported,
@@ -77,3 +258,43 @@ impl ServiceManagerKind {
         }
     }
 }
+
+impl fmt::Display for ServiceManagerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for ServiceManagerKind {
+    type Err = io::Error;
+
+    /// Parses the lowercase name of a kind, e.g. `systemd` or `winsw`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown service manager: {s}"),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_round_trip() {
+        for kind in ServiceManagerKind::all() {
+            assert_eq!(
+                kind.to_string().parse::<ServiceManagerKind>().unwrap(),
+                *kind
+            );
+        }
+
+        assert_eq!(
+            "SystemD".parse::<ServiceManagerKind>().unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
@@ -1,14 +1,14 @@
 use crate::utils::wrap_output;
 
 use super::{
-    utils, ServiceInstallCtx, ServiceLevel, ServiceManager, ServiceStartCtx, ServiceStopCtx,
-    ServiceUninstallCtx,
+    manifest, utils, AvailabilityDetails, CommandRunner, ServiceInstallCtx, ServiceLevel,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner,
 };
 use plist::{Dictionary, Value};
 use std::{
-    borrow::Cow,
     ffi::OsStr,
-    io,
+    fmt, io,
     path::PathBuf,
     process::{Command, Output, Stdio},
 };
@@ -20,6 +20,19 @@ const PLIST_FILE_PERMISSIONS: u32 = 0o644;
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct LaunchdConfig {
     pub install: LaunchdInstallConfig,
+
+    /// Path of the `launchctl` program, which is searched for on the `PATH` if not provided
+    pub launchctl_path: Option<PathBuf>,
+
+    /// Directory that plists are written to, overriding [`LaunchdDomain::dir_path`] for the
+    /// domain of the manager
+    pub plist_dir_path: Option<PathBuf>,
+}
+
+impl LaunchdConfig {
+    fn launchctl(&self) -> &OsStr {
+        utils::program_or(self.launchctl_path.as_deref(), LAUNCHCTL)
+    }
 }
 
 /// Configuration settings tied to launchd services during installation
@@ -27,22 +40,104 @@ pub struct LaunchdConfig {
 pub struct LaunchdInstallConfig {
     /// If true, will include `KeepAlive` flag set to true
     pub keep_alive: bool,
+
+    /// If specified, will include `ProcessType` to tell the system how to apply resource limits
+    /// such as CPU and memory pressure to the service
+    pub process_type: Option<LaunchdProcessType>,
+
+    /// If specified, will include `JetsamProperties` with this `JetsamPriority`, where a higher
+    /// priority is killed later when the system runs low on memory
+    pub jetsam_priority: Option<i64>,
 }
 
 impl Default for LaunchdInstallConfig {
     fn default() -> Self {
-        Self { keep_alive: true }
+        Self {
+            keep_alive: true,
+            process_type: None,
+            jetsam_priority: None,
+        }
+    }
+}
+
+/// Value of `ProcessType` describing the intended purpose of a launchd service
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LaunchdProcessType {
+    /// Limited in the resources it may use, such as for work that the user did not ask for
+    Background,
+
+    /// Default limits of a service
+    Standard,
+
+    /// Limits moved between those of background and interactive processes based on XPC traffic
+    Adaptive,
+
+    /// Same limits as applications, which should be reserved for services critical to the user
+    Interactive,
+}
+
+impl fmt::Display for LaunchdProcessType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Background => write!(f, "Background"),
+            Self::Standard => write!(f, "Standard"),
+            Self::Adaptive => write!(f, "Adaptive"),
+            Self::Interactive => write!(f, "Interactive"),
+        }
+    }
+}
+
+/// Set of launchd services that a manager works with, each kept in its own directory
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LaunchdDomain {
+    /// Daemons in `/Library/LaunchDaemons`, which run as root outside of any login session
+    System,
+
+    /// Agents in `~/Library/LaunchAgents`, which run within the GUI session of the current user
+    User,
+
+    /// Agents in `/Library/LaunchAgents`, which are installed by an administrator and run within
+    /// the GUI session of every user that logs in
+    AllUsers,
+}
+
+impl Default for LaunchdDomain {
+    fn default() -> Self {
+        Self::System
+    }
+}
+
+impl LaunchdDomain {
+    /// Returns the directory containing the plists of services within this domain
+    pub fn dir_path(&self) -> io::Result<PathBuf> {
+        match self {
+            Self::System => Ok(global_daemon_dir_path()),
+            Self::User => user_agent_dir_path(),
+            Self::AllUsers => Ok(global_agent_dir_path()),
+        }
+    }
+
+    /// Returns true if services within this domain are agents run within a GUI session
+    pub fn is_agent(&self) -> bool {
+        matches!(self, Self::User | Self::AllUsers)
     }
 }
 
 /// Implementation of [`ServiceManager`] for MacOS's [Launchd](https://en.wikipedia.org/wiki/Launchd)
+///
+/// Agents of [`LaunchdDomain::AllUsers`] are loaded by launchd into the session of each user when
+/// they log in, so installing them does not load them even when `autostart` is set. Starting,
+/// stopping, and querying them works with the session of the user running this process.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct LaunchdServiceManager {
-    /// Whether or not this manager is operating at the user-level
-    pub user: bool,
+    /// Set of services this manager is operating on
+    pub domain: LaunchdDomain,
 
     /// Configuration settings tied to launchd services
     pub config: LaunchdConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
 }
 
 impl LaunchdServiceManager {
@@ -56,195 +151,458 @@ impl LaunchdServiceManager {
         Self::default().into_user()
     }
 
+    /// Creates a new manager instance working with agents installed for all users
+    pub fn all_users() -> Self {
+        Self::default().with_domain(LaunchdDomain::AllUsers)
+    }
+
     /// Change manager to work with system services
     pub fn into_system(self) -> Self {
-        Self {
-            config: self.config,
-            user: false,
-        }
+        self.with_domain(LaunchdDomain::System)
     }
 
     /// Change manager to work with user services
     pub fn into_user(self) -> Self {
-        Self {
-            config: self.config,
-            user: true,
-        }
+        self.with_domain(LaunchdDomain::User)
+    }
+
+    /// Change manager to work with services of the specified domain
+    pub fn with_domain(self, domain: LaunchdDomain) -> Self {
+        Self { domain, ..self }
     }
 
     /// Update manager to use the specified config
     pub fn with_config(self, config: LaunchdConfig) -> Self {
+        Self { config, ..self }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
         Self {
-            config,
-            user: self.user,
+            runner: runner.into(),
+            ..self
         }
     }
 
-    fn get_plist_path(&self, qualified_name: String) -> PathBuf {
-        let dir_path = if self.user {
-            user_agent_dir_path().unwrap()
+    /// Domain target of `launchctl` containing the services of the given domain, which is the GUI
+    /// session of the current user for agents
+    fn domain_target(&self, domain: LaunchdDomain) -> io::Result<String> {
+        if domain.is_agent() {
+            Ok(format!("gui/{}", current_uid(&*self.runner)?))
         } else {
-            global_daemon_dir_path()
-        };
+            Ok(String::from("system"))
+        }
+    }
+
+    /// Update manager to write plists to the specified directory rather than the one of its
+    /// domain, such as a staging directory used to build a package
+    pub fn with_plist_dir(mut self, dir_path: impl Into<PathBuf>) -> Self {
+        self.config.plist_dir_path = Some(dir_path.into());
+        self
+    }
 
-        dir_path.join(format!("{}.plist", qualified_name))
+    /// Returns the directory that plists are written to
+    pub fn plist_dir(&self) -> io::Result<PathBuf> {
+        self.domain_dir(self.domain)
+    }
+
+    /// Returns the directory containing the plists of the given domain, which is only overridden
+    /// for the domain of the manager
+    fn domain_dir(&self, domain: LaunchdDomain) -> io::Result<PathBuf> {
+        match &self.config.plist_dir_path {
+            Some(dir_path) if domain == self.domain => Ok(dir_path.clone()),
+            _ => domain.dir_path(),
+        }
+    }
+
+    fn get_plist_path(&self, qualified_name: String) -> io::Result<PathBuf> {
+        Ok(self.plist_dir()?.join(format!("{}.plist", qualified_name)))
     }
 }
 
 impl ServiceManager for LaunchdServiceManager {
     fn available(&self) -> io::Result<bool> {
-        match which::which(LAUNCHCTL) {
-            Ok(_) => Ok(true),
-            Err(which::Error::CannotFindBinaryPath) => Ok(false),
-            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        Ok(self.availability_details()?.available)
+    }
+
+    fn availability_details(&self) -> io::Result<AvailabilityDetails> {
+        match which::which(self.config.launchctl()) {
+            Ok(_) => {}
+            Err(which::Error::CannotFindBinaryPath) => {
+                return Ok(AvailabilityDetails::unavailable("launchctl was not found"))
+            }
+            Err(x) => return Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+
+        let output = launchctl(&*self.runner, self.config.launchctl(), "print", "system")?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+            return Ok(AvailabilityDetails::unavailable(format!(
+                "Unable to reach launchd: {stderr}"
+            )));
         }
+
+        Ok(AvailabilityDetails::available())
     }
 
     fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
-        let dir_path = if self.user {
-            user_agent_dir_path()?
-        } else {
-            global_daemon_dir_path()
-        };
+        utils::validate_environment(&ctx, true)?;
+        utils::require_utf8(&ctx, "launchd")?;
+        utils::capabilities_unsupported(&ctx, "launchd")?;
+        utils::check_program(&ctx)?;
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "launchd")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Launchd, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+        let dir_path = utils::staged_path(&ctx, &self.plist_dir()?);
 
         std::fs::create_dir_all(&dir_path)?;
 
         let qualified_name = ctx.label.to_qualified_name();
         let plist_path = dir_path.join(format!("{}.plist", qualified_name));
-        let plist = match ctx.contents {
-            Some(contents) => contents,
-            _ => make_plist(
-                &self.config.install,
-                &qualified_name,
-                ctx.cmd_iter(),
-                ctx.username.clone(),
-                ctx.working_directory.clone(),
-                ctx.environment.clone(),
-                ctx.autostart,
-            ),
+        let plist = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_plist(&self.config.install, &qualified_name, &ctx),
         };
 
-        utils::write_file(
+        utils::write_definition(
+            &ctx,
             plist_path.as_path(),
             plist.as_bytes(),
             PLIST_FILE_PERMISSIONS,
         )?;
 
-        if ctx.autostart {
-            wrap_output(launchctl("load", plist_path.to_string_lossy().as_ref())?)?;
+        // Packages load their jobs themselves once installed
+        if ctx.autostart && self.domain != LaunchdDomain::AllUsers && ctx.install_root.is_none() {
+            wrap_output(launchctl(
+                &*self.runner,
+                self.config.launchctl(),
+                "load",
+                plist_path.to_string_lossy().as_ref(),
+            )?)?;
         }
 
         Ok(())
     }
 
     fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
-        let plist_path = self.get_plist_path(ctx.label.to_qualified_name());
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "launchd")?;
+
+        let plist_path = self.get_plist_path(ctx.label.to_qualified_name())?;
+
+        wrap_output(launchctl(
+            &*self.runner,
+            self.config.launchctl(),
+            "unload",
+            plist_path.to_string_lossy().as_ref(),
+        )?)?;
+        std::fs::remove_file(&plist_path)?;
+        utils::remove_definition_backup(&plist_path)?;
+        manifest::remove(ServiceManagerKind::Launchd, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
 
-        wrap_output(launchctl("unload", plist_path.to_string_lossy().as_ref())?)?;
-        std::fs::remove_file(plist_path)
+    fn rollback(&self, label: &crate::ServiceLabel) -> io::Result<()> {
+        utils::restore_definition(&self.get_plist_path(label.to_qualified_name())?)?;
+        manifest::restore(ServiceManagerKind::Launchd, self.level(), label)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Launchd, self.level(), label)
+    }
+
+    fn uninstall_matching(
+        &self,
+        prefix: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::ServiceLabel>> {
+        let dir_path = self.plist_dir()?;
+        let labels = utils::labels_matching(
+            &dir_path,
+            ".plist",
+            prefix,
+            crate::ServiceLabel::to_qualified_name,
+        )?;
+        utils::uninstall_all(self, labels)
     }
 
     fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
-        let plist_path = self.get_plist_path(ctx.label.to_qualified_name());
-        wrap_output(launchctl("load", plist_path.to_string_lossy().as_ref())?)?;
-        Ok(())
+        let plist_path = self.get_plist_path(ctx.label.to_qualified_name())?;
+        wrap_output(launchctl(
+            &*self.runner,
+            self.config.launchctl(),
+            "load",
+            plist_path.to_string_lossy().as_ref(),
+        )?)?;
+        utils::wait_until_active(self, &ctx)
     }
 
     fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
-        let plist_path = self.get_plist_path(ctx.label.to_qualified_name());
-        wrap_output(launchctl("unload", plist_path.to_string_lossy().as_ref())?)?;
+        let plist_path = self.get_plist_path(ctx.label.to_qualified_name())?;
+        wrap_output(launchctl(
+            &*self.runner,
+            self.config.launchctl(),
+            "unload",
+            plist_path.to_string_lossy().as_ref(),
+        )?)?;
         Ok(())
     }
 
+    /// Agents installed for all users are reported as system-level, since installing them needs
+    /// the privileges of an administrator
     fn level(&self) -> ServiceLevel {
-        if self.user {
-            ServiceLevel::User
-        } else {
-            ServiceLevel::System
+        match self.domain {
+            LaunchdDomain::System | LaunchdDomain::AllUsers => ServiceLevel::System,
+            LaunchdDomain::User => ServiceLevel::User,
         }
     }
 
     fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
         match level {
-            ServiceLevel::System => self.user = false,
-            ServiceLevel::User => self.user = true,
+            ServiceLevel::System => self.domain = LaunchdDomain::System,
+            ServiceLevel::User => self.domain = LaunchdDomain::User,
         }
 
         Ok(())
     }
 
     fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
-        let mut service_name = ctx.label.to_qualified_name();
-        // Due to we could not get the status of a service via a service label, so we have to run this command twice
-        // in first time, if there is a service exists, the output will advice us a full service label with a prefix.
-        // Or it will return nothing, it means the service is not installed(not exists).
-        let mut out: Cow<str> = Cow::Borrowed("");
-        for i in 0..2 {
-            let output = launchctl("print", &service_name)?;
-            if !output.status.success() {
-                if output.status.code() == Some(64) {
-                    // 64 is the exit code for a service not found
-                    out = Cow::Owned(String::from_utf8_lossy(&output.stderr).to_string());
-                    if out.trim().is_empty() {
-                        out = Cow::Owned(String::from_utf8_lossy(&output.stdout).to_string());
-                    }
-                    if i == 0 {
-                        let label = out.lines().find(|line| line.contains(&service_name));
-                        match label {
-                            Some(label) => {
-                                service_name = label.trim().to_string();
-                                continue;
-                            }
-                            None => return Ok(crate::ServiceStatus::NotInstalled),
-                        }
-                    } else {
-                        // We have access to the full service label, so it impossible to get the failed status, or it must be input error.
-                        return Err(io::Error::new(
-                            io::ErrorKind::Other,
-                            format!(
-                                "Command failed with exit code {}: {}",
-                                output.status.code().unwrap_or(-1),
-                                out
-                            ),
-                        ));
-                    }
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let domain = match ctx.level {
+            Some(level) if level != self.level() => match level {
+                ServiceLevel::System => LaunchdDomain::System,
+                ServiceLevel::User => LaunchdDomain::User,
+            },
+            _ => self.domain,
+        };
+
+        // Services are looked up within the domain of the requested level so that a service with
+        // the same label at the other level is never reported instead
+        let qualified_name = ctx.label.to_qualified_name();
+        let target = self.domain_target(domain)?;
+        let output = launchctl(
+            &*self.runner,
+            self.config.launchctl(),
+            "print",
+            &format!("{target}/{qualified_name}"),
+        )?;
+
+        if !output.status.success() {
+            // 113 is the exit code for a service that is not loaded within the domain, which is
+            // the case for installed services that were stopped by unloading them
+            if output.status.code() == Some(113) {
+                let plist_path = self
+                    .domain_dir(domain)?
+                    .join(format!("{qualified_name}.plist"));
+                return if plist_path.exists() {
+                    Ok(crate::ServiceStatus::Stopped(None).into())
                 } else {
-                    return Err(io::Error::new(
-                        io::ErrorKind::Other,
-                        format!(
-                            "Command failed with exit code {}: {}",
-                            output.status.code().unwrap_or(-1),
-                            String::from_utf8_lossy(&output.stderr)
-                        ),
-                    ));
-                }
+                    Ok(crate::ServiceStatus::NotInstalled.into())
+                };
             }
-            out = Cow::Owned(String::from_utf8_lossy(&output.stdout).to_string());
+
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "Command failed with exit code {}: {}",
+                    output.status.code().unwrap_or(-1),
+                    String::from_utf8_lossy(&output.stderr)
+                ),
+            ));
         }
-        let lines = out
-            .lines()
-            .map(|s| s.trim())
-            .filter(|s| s.contains("state"))
-            .collect::<Vec<&str>>();
-        if lines
-            .into_iter()
-            .any(|s| !s.contains("not running") && s.contains("running"))
-        {
-            Ok(crate::ServiceStatus::Running)
+
+        let job = LaunchdJob::parse(&String::from_utf8_lossy(&output.stdout));
+        Ok(if job.is_running() {
+            crate::ServiceStatusDetails {
+                status: crate::ServiceStatus::Running,
+                pid: job.pid,
+                transition: None,
+            }
         } else {
-            Ok(crate::ServiceStatus::Stopped(None))
+            crate::ServiceStatus::Stopped(None).into()
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        let target = format!(
+            "{}/{}",
+            self.domain_target(self.domain)?,
+            ctx.label.to_qualified_name()
+        );
+        let mut command = Command::new(self.config.launchctl());
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .arg("kill")
+            .arg(ctx.signal.to_string())
+            .arg(target);
+        wrap_output(self.runner.run(&mut command, None)?)?;
+        Ok(())
+    }
+
+    fn is_up_to_date(&self, ctx: &ServiceInstallCtx) -> io::Result<crate::DriftReport> {
+        utils::require_utf8(ctx, "launchd")?;
+        let qualified_name = ctx.label.to_qualified_name();
+        let dir_path = self.plist_dir()?;
+        let installed =
+            match std::fs::read_to_string(dir_path.join(format!("{qualified_name}.plist"))) {
+                Ok(x) => x,
+                Err(x) if x.kind() == io::ErrorKind::NotFound => {
+                    return Ok(crate::DriftReport::not_installed())
+                }
+                Err(x) => return Err(x),
+            };
+
+        let ctx = utils::installed_ctx(ctx)?;
+        let expected = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            None => make_plist(&self.config.install, &qualified_name, &ctx),
+        };
+
+        Ok(
+            crate::DriftReport::compare(plist_entries(&installed)?, plist_entries(&expected)?)
+                .with_missing_files(manifest::missing_files(
+                    ServiceManagerKind::Launchd,
+                    self.level(),
+                    &ctx.label,
+                )),
+        )
+    }
+
+    fn export(&self, ctx: crate::ServiceExportCtx) -> io::Result<crate::ServiceSnapshot> {
+        let qualified_name = ctx.label.to_qualified_name();
+        let dir_path = self.plist_dir()?;
+        let file = crate::snapshot::read_file(
+            &dir_path,
+            &format!("{qualified_name}.plist"),
+            &ctx.label,
+            PLIST_FILE_PERMISSIONS,
+        )?;
+
+        // Services are only loaded upon install when they start automatically
+        let autostart = Value::from_reader(io::Cursor::new(file.contents.as_bytes()))
+            .map_err(|x| io::Error::new(io::ErrorKind::Other, x))?
+            .as_dictionary()
+            .and_then(|dict| dict.get("RunAtLoad"))
+            .and_then(Value::as_boolean)
+            .unwrap_or(false);
+
+        Ok(crate::ServiceSnapshot {
+            kind: ServiceManagerKind::Launchd,
+            label: qualified_name,
+            autostart,
+            files: vec![file],
+        })
+    }
+
+    fn import(&self, snapshot: crate::ServiceSnapshot) -> io::Result<()> {
+        snapshot.check_kind(ServiceManagerKind::Launchd)?;
+        let dir_path = self.plist_dir()?;
+
+        let label = snapshot.service_label()?;
+        crate::snapshot::write_files(
+            &dir_path,
+            &snapshot,
+            &[format!("{}.plist", label.to_qualified_name())],
+        )?;
+
+        if snapshot.autostart {
+            for file in &snapshot.files {
+                wrap_output(launchctl(
+                    &*self.runner,
+                    self.config.launchctl(),
+                    "load",
+                    dir_path.join(&file.name).to_string_lossy().as_ref(),
+                )?)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the id of the current user, which identifies the domain of its user agents
+fn current_uid(runner: &dyn CommandRunner) -> io::Result<String> {
+    let mut command = Command::new("id");
+    command.arg("-u");
+    let output = wrap_output(runner.run(&mut command, None)?)?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Properties of a job as reported by `launchctl print {domain}/{label}`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct LaunchdJob {
+    /// State of the job, such as `running`, `not running`, or `spawn scheduled`
+    state: Option<String>,
+
+    /// Id of the job's process while it is running
+    pid: Option<u32>,
+}
+
+impl LaunchdJob {
+    /// Parses the output of `launchctl print`, which lists the properties of the job as
+    /// `key = value` within braces, along with nested dictionaries such as `endpoints` or
+    /// `event triggers` whose properties are skipped as they may reuse names such as `state`
+    fn parse(stdout: &str) -> Self {
+        let mut job = Self::default();
+        let mut depth = 0usize;
+
+        for line in stdout.lines().map(str::trim) {
+            if line == "}" {
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+
+            let entry = line.split_once(" = ");
+            if line.ends_with('{') {
+                depth += 1;
+                continue;
+            } else if depth != 1 {
+                continue;
+            }
+
+            match entry {
+                Some(("state", value)) => job.state = Some(value.to_string()),
+                Some(("pid", value)) => job.pid = value.parse().ok(),
+                _ => {}
+            }
         }
+
+        job
+    }
+
+    fn is_running(&self) -> bool {
+        self.state.as_deref() == Some("running")
     }
 }
 
-fn launchctl(cmd: &str, label: &str) -> io::Result<Output> {
-    Command::new(LAUNCHCTL)
+fn launchctl(
+    runner: &dyn CommandRunner,
+    program: &OsStr,
+    cmd: &str,
+    label: &str,
+) -> io::Result<Output> {
+    let mut command = Command::new(program);
+    command
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .arg(cmd)
-        .arg(label)
-        .output()
+        .arg(label);
+
+    runner.run(&mut command, None)
 }
 
 #[inline]
@@ -252,6 +610,10 @@ fn global_daemon_dir_path() -> PathBuf {
     PathBuf::from("/Library/LaunchDaemons")
 }
 
+fn global_agent_dir_path() -> PathBuf {
+    PathBuf::from("/Library/LaunchAgents")
+}
+
 fn user_agent_dir_path() -> io::Result<PathBuf> {
     Ok(dirs::home_dir()
         .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Unable to locate home directory"))?
@@ -259,20 +621,49 @@ fn user_agent_dir_path() -> io::Result<PathBuf> {
         .join("LaunchAgents"))
 }
 
-fn make_plist<'a>(
-    config: &LaunchdInstallConfig,
-    label: &str,
-    args: impl Iterator<Item = &'a OsStr>,
-    username: Option<String>,
-    working_directory: Option<PathBuf>,
-    environment: Option<Vec<(String, String)>>,
-    autostart: bool,
-) -> String {
+/// Pairs each key of a plist with the setting it belongs to when checking for drift
+fn plist_entries(text: &str) -> io::Result<Vec<(crate::DriftField, String)>> {
+    let dict = Value::from_reader(io::Cursor::new(text.as_bytes()))
+        .map_err(|x| io::Error::new(io::ErrorKind::Other, x))?
+        .into_dictionary()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Plist is not a dictionary"))?;
+
+    let mut entries = Vec::new();
+    for (key, value) in dict {
+        match key.as_str() {
+            "ProgramArguments" => {
+                let mut args = value
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|arg| arg.as_string().unwrap_or_default().to_string());
+                let program = args.next().unwrap_or_default();
+                entries.push((crate::DriftField::Program, program));
+                entries.push((crate::DriftField::Args, args.collect::<Vec<_>>().join(" ")));
+            }
+            "EnvironmentVariables" => {
+                for (var, val) in value.as_dictionary().into_iter().flatten() {
+                    entries.push((crate::DriftField::Environment, format!("{var}={val:?}")));
+                }
+            }
+            "UserName" => entries.push((crate::DriftField::Username, format!("{value:?}"))),
+            "KeepAlive" | "ThrottleInterval" => {
+                entries.push((crate::DriftField::RestartPolicy, format!("{value:?}")))
+            }
+            _ => entries.push((crate::DriftField::Other, format!("{key}={value:?}"))),
+        }
+    }
+
+    Ok(entries)
+}
+
+fn make_plist(config: &LaunchdInstallConfig, label: &str, ctx: &ServiceInstallCtx) -> String {
     let mut dict = Dictionary::new();
 
     dict.insert("Label".to_string(), Value::String(label.to_string()));
 
-    let program_arguments: Vec<Value> = args
+    let program_arguments: Vec<Value> = ctx
+        .cmd_iter()
         .map(|arg| Value::String(arg.to_string_lossy().into_owned()))
         .collect();
     dict.insert(
@@ -280,23 +671,63 @@ fn make_plist<'a>(
         Value::Array(program_arguments),
     );
 
-    dict.insert("KeepAlive".to_string(), Value::Boolean(config.keep_alive));
+    if ctx.kind == crate::ServiceKind::Oneshot {
+        // Run the program only once until the next boot instead of keeping it alive
+        dict.insert("KeepAlive".to_string(), Value::Boolean(false));
+        dict.insert("LaunchOnlyOnce".to_string(), Value::Boolean(true));
+    } else {
+        // The restart policy of the context takes precedence over the config, where restarting
+        // only on failure keeps the service alive until it exits successfully
+        let (keep_alive, delay_secs) = match ctx.restart_policy {
+            Some(crate::RestartPolicy::Never) => (Value::Boolean(false), None),
+            Some(crate::RestartPolicy::Always { delay_secs }) => (Value::Boolean(true), delay_secs),
+            Some(crate::RestartPolicy::OnFailure { delay_secs }) => {
+                let mut keep_alive = Dictionary::new();
+                keep_alive.insert("SuccessfulExit".to_string(), Value::Boolean(false));
+                (Value::Dictionary(keep_alive), delay_secs)
+            }
+            None => (Value::Boolean(config.keep_alive), None),
+        };
+        dict.insert("KeepAlive".to_string(), keep_alive);
 
-    if let Some(username) = username {
-        dict.insert("UserName".to_string(), Value::String(username));
+        // launchd has no limit on starts, so launches are instead spaced out evenly across the
+        // interval of the start limit
+        let throttle_secs = ctx.start_limit.map(|limit| {
+            let burst = u64::from(limit.burst.max(1));
+            let secs = limit.interval.as_secs().saturating_add(burst - 1) / burst;
+            u32::try_from(secs).unwrap_or(u32::MAX)
+        });
+        let delay_secs = delay_secs.max(throttle_secs);
+
+        // launchd waits at least this long between launches of the service
+        if let Some(delay_secs) = delay_secs {
+            dict.insert(
+                "ThrottleInterval".to_string(),
+                Value::Integer(delay_secs.into()),
+            );
+        }
     }
 
-    if let Some(working_dir) = working_directory {
+    if let Some(username) = &ctx.username {
+        dict.insert("UserName".to_string(), Value::String(username.clone()));
+    }
+
+    if let Some(working_dir) = &ctx.working_directory {
         dict.insert(
             "WorkingDirectory".to_string(),
             Value::String(working_dir.to_string_lossy().to_string()),
         );
     }
 
-    if let Some(env_vars) = environment {
+    if let Some(env_vars) = &ctx.environment {
         let env_dict: Dictionary = env_vars
-            .into_iter()
-            .map(|(k, v)| (k, Value::String(v)))
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.to_string_lossy().into_owned(),
+                    Value::String(v.to_string_lossy().into_owned()),
+                )
+            })
             .collect();
         dict.insert(
             "EnvironmentVariables".to_string(),
@@ -304,7 +735,45 @@ fn make_plist<'a>(
         );
     }
 
-    if autostart {
+    // launchd waits this many seconds after sending SIGTERM before sending SIGKILL
+    if let Some(timeout) = ctx.stop_timeout {
+        dict.insert(
+            "ExitTimeOut".to_string(),
+            Value::Integer(timeout.as_secs().max(1).into()),
+        );
+    }
+
+    if let Some(process_type) = config.process_type {
+        dict.insert(
+            "ProcessType".to_string(),
+            Value::String(process_type.to_string()),
+        );
+    }
+
+    if let Some(priority) = config.jetsam_priority {
+        let mut jetsam = Dictionary::new();
+        jetsam.insert(
+            "JetsamPriority".to_string(),
+            Value::Integer(priority.into()),
+        );
+        dict.insert("JetsamProperties".to_string(), Value::Dictionary(jetsam));
+    }
+
+    if let Some(priority) = &ctx.priority {
+        if let Some(nice) = priority.nice {
+            dict.insert("Nice".to_string(), Value::Integer(nice.into()));
+        }
+
+        // launchd only distinguishes throttled IO from the rest
+        if let Some(class) = priority.io_class {
+            dict.insert(
+                "LowPriorityIO".to_string(),
+                Value::Boolean(class == crate::IoSchedulingClass::Idle),
+            );
+        }
+    }
+
+    if ctx.autostart {
         dict.insert("RunAtLoad".to_string(), Value::Boolean(true));
     } else {
         dict.insert("RunAtLoad".to_string(), Value::Boolean(false));
@@ -316,3 +785,241 @@ fn make_plist<'a>(
     plist.to_writer_xml(&mut buffer).unwrap();
     String::from_utf8(buffer).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::tests::RecordingRunner;
+    use indoc::indoc;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_parse_job() {
+        // macOS 14
+        let job = LaunchdJob::parse(indoc! {r#"
+            gui/501/com.example.my-service = {
+            	active count = 1
+            	path = /Users/me/Library/LaunchAgents/com.example.my-service.plist
+            	type = LaunchAgent
+            	state = running
+
+            	program = /usr/local/bin/my_service
+            	arguments = {
+            		/usr/local/bin/my_service
+            		--port
+            		8080
+            	}
+
+            	stdout path = /tmp/my-service.log
+            	inherited environment = {
+            		SSH_AUTH_SOCK => /private/tmp/com.apple.launchd.abc/Listeners
+            	}
+
+            	domain = gui/501 [100005]
+            	runs = 1
+            	pid = 4321
+            	immediate reason = speculative
+            	forks = 0
+            	execs = 1
+            	last exit code = (never exited)
+
+            	event triggers = {
+            		com.example.trigger => {
+            			keepalive = 0
+            			service = com.example.my-service
+            			stream = com.apple.notifyd.matching
+            			state = running
+            		}
+            	}
+            }
+        "#});
+        assert_eq!(
+            job,
+            LaunchdJob {
+                state: Some(String::from("running")),
+                pid: Some(4321),
+            }
+        );
+        assert!(job.is_running());
+
+        // macOS 11, where a nested dictionary reports a state after that of the job
+        let job = LaunchdJob::parse(indoc! {r#"
+            system/com.example.my-service = {
+            	active count = 0
+            	path = /Library/LaunchDaemons/com.example.my-service.plist
+            	state = not running
+
+            	program = /usr/local/bin/my_service
+            	runs = 2
+            	last exit code = 78: EX_CONFIG
+
+            	endpoints = {
+            		"com.example.my-service" = {
+            			port = 0x1a03
+            			active = 0
+            			managed = 1
+            			state = running
+            		}
+            	}
+            }
+        "#});
+        assert_eq!(
+            job,
+            LaunchdJob {
+                state: Some(String::from("not running")),
+                pid: None,
+            }
+        );
+        assert!(!job.is_running());
+
+        let job = LaunchdJob::parse(indoc! {r#"
+            gui/501/com.example.my-service = {
+            	state = spawn scheduled
+            	last exit code = 1
+            }
+        "#});
+        assert!(!job.is_running());
+    }
+
+    #[test]
+    fn test_status_of_other_level() {
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = LaunchdServiceManager::user()
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        manager
+            .status(crate::ServiceStatusCtx {
+                label: "com.example.my-service".parse().unwrap(),
+                level: Some(ServiceLevel::System),
+            })
+            .unwrap();
+
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [["launchctl", "print", "system/com.example.my-service"]]
+        );
+    }
+
+    #[test]
+    fn test_all_users_domain() {
+        let manager = LaunchdServiceManager::all_users();
+        assert_eq!(
+            manager
+                .get_plist_path(String::from("com.example.my-service"))
+                .unwrap(),
+            PathBuf::from("/Library/LaunchAgents/com.example.my-service.plist")
+        );
+        assert_eq!(manager.level(), ServiceLevel::System);
+
+        let mut manager = manager;
+        manager.set_level(ServiceLevel::User).unwrap();
+        assert_eq!(manager.domain, LaunchdDomain::User);
+    }
+
+    #[test]
+    fn test_plist_dir() {
+        let manager = LaunchdServiceManager::system().with_plist_dir("/tmp/staging");
+        assert_eq!(
+            manager
+                .get_plist_path(String::from("com.example.my-service"))
+                .unwrap(),
+            PathBuf::from("/tmp/staging/com.example.my-service.plist")
+        );
+
+        // Only the domain of the manager is affected by the override
+        assert_eq!(
+            manager.domain_dir(LaunchdDomain::AllUsers).unwrap(),
+            PathBuf::from("/Library/LaunchAgents")
+        );
+    }
+
+    fn install_ctx() -> ServiceInstallCtx {
+        ServiceInstallCtx {
+            label: "com.example.my-service".parse().unwrap(),
+            program: PathBuf::from("/usr/local/bin/my-service"),
+            args: Vec::new(),
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: None,
+            autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        }
+    }
+
+    fn parse_plist(plist: &str) -> Dictionary {
+        Value::from_reader(io::Cursor::new(plist.as_bytes()))
+            .unwrap()
+            .into_dictionary()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_make_plist_with_memory_pressure_settings() {
+        let config = LaunchdInstallConfig {
+            process_type: Some(LaunchdProcessType::Interactive),
+            jetsam_priority: Some(40),
+            ..Default::default()
+        };
+        let ctx = install_ctx();
+
+        let dict = parse_plist(&make_plist(&config, "com.example.my-service", &ctx));
+        assert_eq!(
+            dict.get("ProcessType"),
+            Some(&Value::String(String::from("Interactive")))
+        );
+        assert_eq!(
+            dict.get("JetsamProperties")
+                .and_then(Value::as_dictionary)
+                .and_then(|x| x.get("JetsamPriority"))
+                .and_then(Value::as_signed_integer),
+            Some(40)
+        );
+    }
+
+    #[test]
+    fn test_make_plist_with_restart_policy() {
+        let config = LaunchdInstallConfig::default();
+        let mut ctx = install_ctx();
+
+        ctx.restart_policy = Some(crate::RestartPolicy::OnFailure {
+            delay_secs: Some(30),
+        });
+        let dict = parse_plist(&make_plist(&config, "com.example.my-service", &ctx));
+        assert_eq!(
+            dict.get("KeepAlive")
+                .and_then(Value::as_dictionary)
+                .and_then(|x| x.get("SuccessfulExit")),
+            Some(&Value::Boolean(false))
+        );
+        assert_eq!(
+            dict.get("ThrottleInterval")
+                .and_then(Value::as_signed_integer),
+            Some(30)
+        );
+
+        ctx.restart_policy = Some(crate::RestartPolicy::Never);
+        let dict = parse_plist(&make_plist(&config, "com.example.my-service", &ctx));
+        assert_eq!(dict.get("KeepAlive"), Some(&Value::Boolean(false)));
+        assert_eq!(dict.get("ThrottleInterval"), None);
+    }
+}
@@ -9,25 +9,80 @@ use std::{
     fmt, io,
     path::PathBuf,
     str::FromStr,
+    time::Duration,
 };
 
+mod container;
+mod cron;
+mod definition;
+mod dinit;
+mod drift;
+mod elevation;
+mod health;
 mod kind;
+#[cfg(feature = "launchd")]
 mod launchd;
+mod manifest;
+#[cfg(feature = "test-util")]
+mod mock;
+mod nssm;
+mod observer;
 mod openrc;
+mod procd;
 mod rcd;
+mod registry;
+mod report;
+mod retry;
+mod runit;
+mod runner;
+mod s6;
 mod sc;
+#[cfg(feature = "schtasks")]
+mod schtasks;
+mod snapshot;
+mod supervisord;
 mod systemd;
+mod sysv;
 mod typed;
+mod upstart;
 mod utils;
+#[cfg(feature = "winsw")]
 mod winsw;
 
+pub use container::*;
+pub use cron::*;
+pub use definition::*;
+pub use dinit::*;
+pub use drift::*;
+pub use elevation::*;
+pub use health::*;
 pub use kind::*;
+#[cfg(feature = "launchd")]
 pub use launchd::*;
+pub use manifest::*;
+#[cfg(feature = "test-util")]
+pub use mock::*;
+pub use nssm::*;
+pub use observer::*;
 pub use openrc::*;
+pub use procd::*;
 pub use rcd::*;
+pub use registry::*;
+pub use report::*;
+pub use retry::*;
+pub use runit::*;
+pub use runner::*;
+pub use s6::*;
 pub use sc::*;
+#[cfg(feature = "schtasks")]
+pub use schtasks::*;
+pub use snapshot::*;
+pub use supervisord::*;
 pub use systemd::*;
+pub use sysv::*;
 pub use typed::*;
+pub use upstart::*;
+#[cfg(feature = "winsw")]
 pub use winsw::*;
 
 /// Interface for a service manager
@@ -36,12 +91,40 @@ pub trait ServiceManager {
     /// can be used
     fn available(&self) -> io::Result<bool>;
 
+    /// Determines if the service manager can be used, explaining why when it cannot
+    ///
+    /// By default, this wraps [`ServiceManager::available`], giving a generic reason when the
+    /// manager is unavailable.
+    fn availability_details(&self) -> io::Result<AvailabilityDetails> {
+        Ok(if self.available()? {
+            AvailabilityDetails::available()
+        } else {
+            AvailabilityDetails::unavailable("Service manager is not installed")
+        })
+    }
+
     /// Installs a new service using the manager
     fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()>;
 
     /// Uninstalls an existing service using the manager
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] when the service is not installed.
     fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()>;
 
+    /// Uninstalls every service whose label is `prefix` or extends its application after a `.`,
+    /// such as `com.example.app.api` and `com.example.app.worker` for `com.example.app`,
+    /// returning the labels of the services uninstalled
+    ///
+    /// Copies of programs made by [`install_binary_to`](ServiceInstallCtx::install_binary_to) are
+    /// left in place. By default, this returns an error of kind [`io::ErrorKind::Unsupported`].
+    fn uninstall_matching(&self, prefix: &ServiceLabel) -> io::Result<Vec<ServiceLabel>> {
+        let _ = prefix;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not support discovering services to uninstall",
+        ))
+    }
+
     /// Starts a service using the manager
     fn start(&self, ctx: ServiceStartCtx) -> io::Result<()>;
 
@@ -56,6 +139,173 @@ pub trait ServiceManager {
 
     /// Return the service status info
     fn status(&self, ctx: ServiceStatusCtx) -> io::Result<ServiceStatus>;
+
+    /// Return detailed service status info such as the id of the running process
+    ///
+    /// By default, this wraps [`ServiceManager::status`] without any additional details.
+    fn status_details(&self, ctx: ServiceStatusCtx) -> io::Result<ServiceStatusDetails> {
+        Ok(ServiceStatusDetails::from(self.status(ctx)?))
+    }
+
+    /// Return the output logged by a service, if the manager knows where to find it
+    ///
+    /// By default, this returns an error of kind [`io::ErrorKind::Unsupported`].
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let _ = ctx;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not support retrieving logs",
+        ))
+    }
+
+    /// Collects the status, last exit code, and recent logs of a service into one report, such as
+    /// to explain to end users why it failed to start
+    ///
+    /// By default, the report includes the logs of the service whenever [`ServiceManager::logs`]
+    /// can retrieve them, but no exit code.
+    fn diagnose(&self, ctx: ServiceDiagnoseCtx) -> io::Result<StartFailureReport> {
+        let status = self.status_details(ServiceStatusCtx {
+            label: ctx.label.clone(),
+            level: None,
+        })?;
+        let logs = self
+            .logs(ServiceLogsCtx {
+                label: ctx.label.clone(),
+                lines: Some(ctx.lines.unwrap_or(DEFAULT_DIAGNOSE_LOG_LINES)),
+            })
+            .ok();
+
+        Ok(StartFailureReport {
+            label: ctx.label.to_qualified_name(),
+            status,
+            exit_code: None,
+            logs,
+        })
+    }
+
+    /// Pauses a running service using the manager
+    ///
+    /// By default, this returns an error of kind [`io::ErrorKind::Unsupported`].
+    fn pause(&self, ctx: ServicePauseCtx) -> io::Result<()> {
+        let _ = ctx;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not support pausing services",
+        ))
+    }
+
+    /// Resumes a paused service using the manager
+    ///
+    /// By default, this returns an error of kind [`io::ErrorKind::Unsupported`].
+    fn resume(&self, ctx: ServiceResumeCtx) -> io::Result<()> {
+        let _ = ctx;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not support resuming services",
+        ))
+    }
+
+    /// Compares the installed definition of a service to the one that would be installed for `ctx`,
+    /// reporting which settings differ so that the service is only reinstalled when needed
+    ///
+    /// This is supported by systemd, launchd, and supervisord. Other service managers, including
+    /// those defined by scripts such as OpenRC, rc.d, and SysV init or by XML such as WinSW,
+    /// return an error of kind [`io::ErrorKind::Unsupported`] by default.
+    fn is_up_to_date(&self, ctx: &ServiceInstallCtx) -> io::Result<DriftReport> {
+        let _ = ctx;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not support comparing installed services",
+        ))
+    }
+
+    /// Checks whether a service is actually serving by running the probe of the context
+    ///
+    /// By default, the service is probed whenever [`ServiceManager::status`] reports it as running.
+    fn health(&self, ctx: ServiceHealthCtx) -> io::Result<ServiceHealth> {
+        let status = self.status(ServiceStatusCtx {
+            label: ctx.label,
+            level: None,
+        })?;
+        if status != ServiceStatus::Running {
+            return Ok(ServiceHealth::NotRunning);
+        }
+
+        let timeout = ctx.timeout.unwrap_or(DEFAULT_HEALTH_CHECK_TIMEOUT);
+        Ok(match ctx.check.probe(timeout) {
+            Ok(()) => ServiceHealth::Healthy,
+            Err(x) => ServiceHealth::Unhealthy(x.to_string()),
+        })
+    }
+
+    /// Sends a signal to the main process of a running service, such as [`Signal::Hup`] to have it
+    /// reload its configuration
+    ///
+    /// By default, this returns an error of kind [`io::ErrorKind::Unsupported`].
+    fn signal(&self, ctx: ServiceSignalCtx) -> io::Result<()> {
+        let _ = ctx;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not support signaling services",
+        ))
+    }
+
+    /// Captures the complete definition of an installed service, such as its unit file or plist,
+    /// so that it can later be restored using [`ServiceManager::import`]
+    ///
+    /// This is supported by systemd, launchd, and supervisord. Other service managers, including
+    /// those defined by scripts such as OpenRC, rc.d, and SysV init or by registry values such as
+    /// sc.exe, return an error of kind [`io::ErrorKind::Unsupported`] by default.
+    fn export(&self, ctx: ServiceExportCtx) -> io::Result<ServiceSnapshot> {
+        let _ = ctx;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not support exporting services",
+        ))
+    }
+
+    /// Installs a service from a snapshot produced by [`ServiceManager::export`] of the same kind
+    /// of service manager, replacing any existing definition of it
+    ///
+    /// Only the files named after the label of the snapshot are written, failing with an error of
+    /// kind [`io::ErrorKind::InvalidInput`] for any other file. This is supported by the same
+    /// service managers as [`ServiceManager::export`], and returns an error of kind
+    /// [`io::ErrorKind::Unsupported`] by default.
+    fn import(&self, snapshot: ServiceSnapshot) -> io::Result<()> {
+        let _ = snapshot;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not support importing services",
+        ))
+    }
+
+    /// Restores the definition of the service labeled `label` that was replaced by its last
+    /// install with [`backup_definition`](ServiceInstallCtx::backup_definition) set
+    ///
+    /// The backup is consumed, so a definition can only be rolled back once per install. A
+    /// running service keeps using the replaced definition until it is restarted. By default, this
+    /// returns an error of kind [`io::ErrorKind::Unsupported`].
+    fn rollback(&self, label: &ServiceLabel) -> io::Result<()> {
+        let _ = label;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not support rolling back definitions",
+        ))
+    }
+
+    /// Returns everything that installing the service labeled `label` created or changed on the
+    /// machine, as recorded in its [`InstallManifest`], such as the files written and commands run
+    ///
+    /// Fails with [`io::ErrorKind::NotFound`] if no manifest was recorded, as is the case for
+    /// services installed by earlier versions of this crate. By default, this returns an error of
+    /// kind [`io::ErrorKind::Unsupported`].
+    fn installed_artifacts(&self, label: &ServiceLabel) -> io::Result<Vec<InstalledArtifact>> {
+        let _ = label;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Service manager does not record install manifests",
+        ))
+    }
 }
 
 impl dyn ServiceManager {
@@ -67,21 +317,48 @@ impl dyn ServiceManager {
         Ok(TypedServiceManager::target_or_native(kind)?.into_box())
     }
 
+    /// Creates a new service manager by name, which may be a built-in kind or a service manager
+    /// added to the [`ServiceManagerRegistry`], falling back to selecting based on native service
+    /// manager for the current operating system if no name provided
+    pub fn target_name_or_native(name: Option<&str>) -> io::Result<Box<dyn ServiceManager>> {
+        ServiceManagerRegistry::resolve_or_native(name)
+    }
+
     /// Creates a new service manager targeting the specific service manager kind using the
     /// default service manager instance
     pub fn target(kind: ServiceManagerKind) -> Box<dyn ServiceManager> {
         TypedServiceManager::target(kind).into_box()
     }
 
+    /// Creates a new service manager targeting the specific service manager kind using the
+    /// configuration of that kind from `config`
+    pub fn target_with(
+        kind: ServiceManagerKind,
+        config: ServiceManagerConfig,
+    ) -> Box<dyn ServiceManager> {
+        TypedServiceManager::target_with(kind, config).into_box()
+    }
+
     /// Attempts to select a native service manager for the current operating system
     ///
     /// * For MacOS, this will use [`LaunchdServiceManager`]
     /// * For Windows, this will use [`ScServiceManager`]
     /// * For BSD variants, this will use [`RcdServiceManager`]
-    /// * For Linux variants, this will use either [`SystemdServiceManager`] or [`OpenRcServiceManager`]
+    /// * For Linux variants, this will use [`SystemdServiceManager`], [`OpenRcServiceManager`],
+    ///   [`RunitServiceManager`], [`S6ServiceManager`], [`DinitServiceManager`],
+    ///   [`ProcdServiceManager`], [`UpstartServiceManager`], or [`SysVServiceManager`]
     pub fn native() -> io::Result<Box<dyn ServiceManager>> {
         native_service_manager()
     }
+
+    /// Attempts to select a native service manager for the current operating system that
+    /// supports services at `level`, working with services at that level
+    ///
+    /// This matches [`native`](#method.native), except on Windows where user-level services use
+    /// [`SchtasksServiceManager`].
+    pub fn native_for_level(level: ServiceLevel) -> io::Result<Box<dyn ServiceManager>> {
+        Ok(TypedServiceManager::native_for_level(level)?.into_box())
+    }
 }
 
 /// Attempts to select a native service manager for the current operating system1
@@ -89,7 +366,9 @@ impl dyn ServiceManager {
 /// * For MacOS, this will use [`LaunchdServiceManager`]
 /// * For Windows, this will use [`ScServiceManager`]
 /// * For BSD variants, this will use [`RcdServiceManager`]
-/// * For Linux variants, this will use either [`SystemdServiceManager`] or [`OpenRcServiceManager`]
+/// * For Linux variants, this will use [`SystemdServiceManager`], [`OpenRcServiceManager`],
+///   [`RunitServiceManager`], [`S6ServiceManager`], [`DinitServiceManager`],
+///   [`ProcdServiceManager`], [`UpstartServiceManager`], or [`SysVServiceManager`]
 #[inline]
 pub fn native_service_manager() -> io::Result<Box<dyn ServiceManager>> {
     Ok(TypedServiceManager::native()?.into_box())
@@ -106,6 +385,8 @@ where
 
 /// Represents whether a service is system-wide or user-level
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum ServiceLevel {
     System,
     User,
@@ -113,12 +394,80 @@ pub enum ServiceLevel {
 
 /// Represents the status of a service
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ServiceStatus {
     NotInstalled,
     Running,
+    Paused,
     Stopped(Option<String>), // Provide a reason if possible
 }
 
+/// Represents the status of a service alongside details that a service manager was able to find
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ServiceStatusDetails {
+    /// Status of the service
+    pub status: ServiceStatus,
+
+    /// Id of the service's main process, if running and known
+    pub pid: Option<u32>,
+
+    /// Transition the service is in the middle of, if any and known
+    pub transition: Option<ServiceTransition>,
+}
+
+impl From<ServiceStatus> for ServiceStatusDetails {
+    fn from(status: ServiceStatus) -> Self {
+        Self {
+            status,
+            pid: None,
+            transition: None,
+        }
+    }
+}
+
+/// Represents whether a service manager can be used on the current machine
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct AvailabilityDetails {
+    /// Whether the service manager can be used
+    pub available: bool,
+
+    /// Reason the service manager cannot be used, such as its init system not running or being
+    /// unable to reach it
+    pub reason: Option<String>,
+}
+
+impl AvailabilityDetails {
+    /// Creates details of a service manager that can be used
+    pub fn available() -> Self {
+        Self {
+            available: true,
+            reason: None,
+        }
+    }
+
+    /// Creates details of a service manager that cannot be used for the given reason
+    pub fn unavailable(reason: impl Into<String>) -> Self {
+        Self {
+            available: false,
+            reason: Some(reason.into()),
+        }
+    }
+}
+
+/// Represents a change of status that a service is in the middle of
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ServiceTransition {
+    Starting,
+    Stopping,
+    Pausing,
+    Resuming,
+}
+
 /// Label describing the service (e.g. `org.example.my_application`
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ServiceLabel {
@@ -240,13 +589,186 @@ pub struct ServiceInstallCtx {
 
     /// Optionally specify a list of environment variables to be passed to the process launched by
     /// the service
-    pub environment: Option<Vec<(String, String)>>,
+    ///
+    /// Like the program and its arguments, names and values are passed along unchanged by service
+    /// managers that run commands, such as sc.exe or container engines. Service managers whose
+    /// definitions can only hold text, such as systemd or launchd, fail to install a service whose
+    /// settings are not valid UTF-8 rather than altering them.
+    pub environment: Option<Vec<(OsString, OsString)>>,
 
     /// Specify whether the service should automatically start on reboot
     pub autostart: bool,
+
+    /// Optionally specify a policy for restarting the service after its process exits
+    ///
+    /// If not specified, the service manager's own configuration determines whether the service is
+    /// restarted. Otherwise, the policy takes precedence over that configuration, and its delay maps
+    /// to `RestartSec=` on systemd, `ThrottleInterval` on launchd, `<onfailure delay=>` on WinSW,
+    /// and the delay of the restart failure action on sc.exe.
+    pub restart_policy: Option<RestartPolicy>,
+
+    /// Optionally specify a program and its arguments to run in order to stop the service instead
+    /// of signalling its process
+    ///
+    /// E.g. `/usr/local/bin/my-program-ctl` with `stop`
+    ///
+    /// This is supported by systemd, OpenRC, rc.d, and WinSW, and ignored by other service
+    /// managers.
+    pub stop_command: Option<(PathBuf, Vec<OsString>)>,
+
+    /// Optionally specify the signal sent to the service's process in order to stop it
+    ///
+    /// If not specified, the service manager's default is used, which is typically `SIGTERM`.
+    /// This is supported by systemd, OpenRC, rc.d, s6, supervisord, and containers, and ignored by
+    /// other service managers.
+    pub stop_signal: Option<Signal>,
+
+    /// Optionally specify how long the service may take to start before it is considered failed
+    ///
+    /// This is supported by systemd and ignored by other service managers.
+    pub start_timeout: Option<Duration>,
+
+    /// Optionally specify how long the service may take to stop before its process is killed
+    ///
+    /// This is supported by systemd, launchd, OpenRC, s6, supervisord, WinSW, and containers. It
+    /// is ignored by other service managers, including sc.exe, where the service control manager
+    /// decides how long to wait.
+    pub stop_timeout: Option<Duration>,
+
+    /// Specify whether the service runs continuously or once each time the system boots
+    pub kind: ServiceKind,
+
+    /// Optionally specify a probe confirming that the service is actually serving, which can be
+    /// checked afterwards through [`ServiceInstallCtx::health_ctx`]
+    ///
+    /// [`HealthCheck::Exec`] is also installed as the health check of containers, while other
+    /// service managers have no notion of health and ignore it.
+    pub health_check: Option<HealthCheck>,
+
+    /// Skip checking that the program exists and is executable when installing the service
+    ///
+    /// This is needed when the program is only available to the service once it runs, such as
+    /// when installing into an image or chroot. Containers never check the program, as it lives
+    /// within the image, and neither does sc.exe when managing a remote computer.
+    pub skip_program_check: bool,
+
+    /// Optionally specify a directory to copy the program into when installing the service, so
+    /// that the service keeps working after the original program is removed
+    ///
+    /// The program is copied into a subdirectory named after the service's label, e.g.
+    /// `/usr/local/libexec/org.example.my_service/my-program`, and the service runs that copy.
+    /// Pass the same directory to [`ServiceUninstallCtx::install_binary_to`] to remove the copy
+    /// when uninstalling. Containers ignore this, as their program lives within the image.
+    pub install_binary_to: Option<PathBuf>,
+
+    /// Optionally specify the CPU and IO scheduling priority of the program, which service
+    /// managers without a way to set it ignore
+    ///
+    /// This maps to `Nice=` and `IOSchedulingClass=` on systemd, `Nice` and `LowPriorityIO` on
+    /// launchd, `<priority>` on WinSW, `SSD_NICELEVEL` and `SSD_IONICELEVEL` on OpenRC, and
+    /// running the program with `nice -n` through `${name}_nice` on rc.d.
+    pub priority: Option<ProcessPriority>,
+
+    /// Optionally specify how much more or less likely the program is to be killed when the
+    /// system runs out of memory, from `-1000` (never) to `1000` (first)
+    ///
+    /// This maps to `OOMScoreAdjust=` on systemd, while other service managers ignore it. On
+    /// launchd, [`LaunchdInstallConfig`] instead sets `ProcessType` and `JetsamProperties`.
+    pub oom_score_adjust: Option<i32>,
+
+    /// Linux capabilities granted to the program, such as `CAP_NET_BIND_SERVICE` to bind ports
+    /// below 1024 without running as root
+    ///
+    /// This maps to `AmbientCapabilities=` and `CapabilityBoundingSet=` on systemd and `--cap-add`
+    /// on containers. Other service managers fail to install a service with capabilities with
+    /// [`std::io::ErrorKind::Unsupported`], as the capabilities must instead be granted to the
+    /// program file itself using `setcap`.
+    pub capabilities: Vec<String>,
+
+    /// Optionally specify a human-readable name for the service, separate from its label
+    ///
+    /// This maps to `Description=` on systemd, `displayname=` on sc.exe, `<name>` on WinSW, and
+    /// `DisplayName` on NSSM, each falling back to the name derived from the label. launchd has no
+    /// such setting and ignores it.
+    pub display_name: Option<String>,
+
+    /// Optionally specify a description of what the service does
+    ///
+    /// This maps to `Description=` on systemd in place of the display name, `desc=` on rc.d,
+    /// `description=` on OpenRC, `description` on Upstart, `sc description` on sc.exe,
+    /// `<description>` on WinSW, `Description` on NSSM, and the description of the registration
+    /// info on Task Scheduler. Service manager configs that have their own description take
+    /// precedence over it.
+    pub description: Option<String>,
+
+    /// Optionally limit how often the service is started, so that a service stuck in a loop of
+    /// crashing and restarting is eventually given up on
+    ///
+    /// This maps to `StartLimitIntervalSec=`, `StartLimitBurst=`, and `StartLimitAction=` on
+    /// systemd, the number of restart failure actions and their reset period on sc.exe and WinSW,
+    /// and `startretries` on supervisord, taking precedence over the configs of those service
+    /// managers. launchd has no such limit, so it instead spaces out launches of the service with
+    /// `ThrottleInterval` to allow no more than `burst` launches within `interval`.
+    pub start_limit: Option<StartLimit>,
+
+    /// Optionally send the output of the service to syslog, tagged with the given identifier
+    ///
+    /// This maps to `SyslogIdentifier=` on systemd, `daemon -S -T` on rc.d, and piping output
+    /// through `logger -t` on OpenRC. On sc.exe, the service is instead registered as a source of
+    /// the Application event log under its service name, which it reports its events with. Other
+    /// service managers ignore it.
+    pub log_to_syslog: Option<String>,
+
+    /// Optionally set the permissions of the files defining the service, such as its unit file
+    /// or init script, in place of the defaults of the service manager (typically `0o644`, or
+    /// `0o755` for scripts)
+    ///
+    /// Definitions can embed secrets through `environment`, so this allows restricting them to
+    /// `0o600` or `0o640`. Scripts that the service manager executes must keep their execute bits,
+    /// e.g. `0o700`. The permissions are applied before the definition is written and only on unix
+    /// systems. The crontabs of cron and the definitions kept by Windows service managers are not
+    /// affected.
+    pub definition_permissions: Option<u32>,
+
+    /// Optionally change the owner of the files defining the service, given as `user`,
+    /// `user:group`, or `:group` in the form understood by `chown`
+    ///
+    /// This pairs with `definition_permissions` to make definitions readable by a group, such as
+    /// `0o640` owned by `root:adm`. The owner is applied before the definition is written and
+    /// only on unix systems.
+    pub definition_owner: Option<String>,
+
+    /// If true, the definition being replaced by this install is kept alongside the new one with
+    /// `.bak` appended to its name, so that [`ServiceManager::rollback`] can restore it
+    ///
+    /// This is supported on systemd, launchd, supervisord, and Upstart. Other service managers
+    /// fail to install the service, as a stray copy of a definition could be picked up as a
+    /// service of its own, such as an executable script within `/etc/init.d` for OpenRC, procd,
+    /// and SysV init.
+    pub backup_definition: bool,
+
+    /// Optionally write the files of the service beneath this directory rather than the root of
+    /// the filesystem, such as a staging directory used to build a `.deb` or `.rpm` package
+    ///
+    /// Definitions are written where they would otherwise be installed, but beneath this root,
+    /// e.g. `<root>/etc/systemd/system/{name}.service`, while paths within them still refer to the
+    /// final locations. Commands that enable, load, or start the service are skipped and left to
+    /// the package, and the program is not checked to exist. Service managers that register
+    /// services through commands rather than files, such as sc.exe or Task Scheduler, fail to
+    /// install the service.
+    pub install_root: Option<PathBuf>,
 }
 
 impl ServiceInstallCtx {
+    /// Context to check the health of the service using its health check, if one is specified
+    pub fn health_ctx(&self) -> Option<ServiceHealthCtx> {
+        Some(ServiceHealthCtx {
+            label: self.label.clone(),
+            check: self.health_check.clone()?,
+            timeout: None,
+        })
+    }
+
     /// Iterator over the program and its arguments
     pub fn cmd_iter(&self) -> impl Iterator<Item = &OsStr> {
         std::iter::once(self.program.as_os_str()).chain(self.args_iter())
@@ -256,6 +778,148 @@ impl ServiceInstallCtx {
     pub fn args_iter(&self) -> impl Iterator<Item = &OsStr> {
         self.args.iter().map(OsString::as_os_str)
     }
+
+    /// Iterator over the program and arguments of the stop command, if one is specified
+    pub fn stop_cmd_iter(&self) -> impl Iterator<Item = &OsStr> {
+        self.stop_command.iter().flat_map(|(program, args)| {
+            std::iter::once(program.as_os_str()).chain(args.iter().map(OsString::as_os_str))
+        })
+    }
+}
+
+/// Policy for restarting a service after its process exits
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "policy", rename_all = "kebab-case"))]
+pub enum RestartPolicy {
+    /// Never restart the service
+    Never,
+
+    /// Always restart the service after it exits, optionally waiting `delay_secs` beforehand
+    Always { delay_secs: Option<u32> },
+
+    /// Restart the service only after it exits with a failure, optionally waiting `delay_secs`
+    /// beforehand
+    OnFailure { delay_secs: Option<u32> },
+}
+
+/// Limit on how often a service is started, beyond which it is no longer restarted
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct StartLimit {
+    /// Period within which starts of the service are counted
+    pub interval: Duration,
+
+    /// Number of starts allowed within `interval`
+    pub burst: u32,
+
+    /// Action taken once the service hits the limit
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub action: StartLimitAction,
+}
+
+/// Action taken once a service hits its [`StartLimit`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum StartLimitAction {
+    /// Leave the service stopped
+    None,
+
+    /// Reboot the machine
+    Reboot,
+}
+
+impl Default for StartLimitAction {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// CPU and IO scheduling priority of the process run by a service
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ProcessPriority {
+    /// Niceness of the process from `-20` (scheduled most favorably) to `19` (least favorably)
+    pub nice: Option<i8>,
+
+    /// Class used to schedule the disk IO of the process
+    pub io_class: Option<IoSchedulingClass>,
+}
+
+/// Class used to schedule the disk IO of a process
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum IoSchedulingClass {
+    /// Served before any other class
+    Realtime,
+
+    /// Served fairly alongside other processes, which is the default for most processes
+    BestEffort,
+
+    /// Only served when no other process needs the disk
+    Idle,
+}
+
+/// Kind of process run by a service
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum ServiceKind {
+    /// Long-running process supervised by the service manager
+    Simple,
+
+    /// Task that runs once each time the system boots and then exits without being restarted
+    ///
+    /// This maps to `Type=oneshot` on systemd, `LaunchOnlyOnce` on launchd, `type = scripted` on
+    /// dinit, and a `task` on Upstart. OpenRC runs the program in the foreground, supervisord never
+    /// restarts it, and cron and Task Scheduler already run it once at boot.
+    /// Service managers that only supervise long-running processes fail to install it with
+    /// [`std::io::ErrorKind::Unsupported`].
+    Oneshot,
+}
+
+impl Default for ServiceKind {
+    fn default() -> Self {
+        Self::Simple
+    }
+}
+
+/// Signal sent to the process of a service, such as to stop it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
+pub enum Signal {
+    Hup,
+    Int,
+    Quit,
+    Kill,
+    Usr1,
+    Usr2,
+    Term,
+}
+
+impl Signal {
+    /// Name of the signal without the `SIG` prefix, e.g. `TERM`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Hup => "HUP",
+            Self::Int => "INT",
+            Self::Quit => "QUIT",
+            Self::Kill => "KILL",
+            Self::Usr1 => "USR1",
+            Self::Usr2 => "USR2",
+            Self::Term => "TERM",
+        }
+    }
+}
+
+impl fmt::Display for Signal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SIG{}", self.name())
+    }
 }
 
 /// Context provided to the uninstall function of [`ServiceManager`]
@@ -265,6 +929,17 @@ pub struct ServiceUninstallCtx {
     ///
     /// E.g. `rocks.distant.manager`
     pub label: ServiceLabel,
+
+    /// Directory the program was copied into by [`ServiceInstallCtx::install_binary_to`], whose
+    /// copy of the program is removed alongside the service
+    pub install_binary_to: Option<PathBuf>,
+
+    /// If true, also removes what was created for the service besides its definition, such as
+    /// its log files, pidfile, and the files it was configured with
+    ///
+    /// Paths that depend on the configuration of the service manager, such as its log file, are
+    /// looked up using the configuration of the manager uninstalling the service.
+    pub purge: bool,
 }
 
 /// Context provided to the start function of [`ServiceManager`]
@@ -274,6 +949,27 @@ pub struct ServiceStartCtx {
     ///
     /// E.g. `rocks.distant.manager`
     pub label: ServiceLabel,
+
+    /// Whether starting returns once the start is requested or waits for the service to run
+    pub wait: WaitMode,
+}
+
+/// How long starting a service waits before returning
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WaitMode {
+    /// Return once the service manager has accepted the request to start the service, which may
+    /// still fail to run afterwards
+    None,
+
+    /// Return once the service manager reports the service as running, failing with an error of
+    /// kind [`io::ErrorKind::TimedOut`] if it is not running within the given time
+    UntilActive(Duration),
+}
+
+impl Default for WaitMode {
+    fn default() -> Self {
+        Self::None
+    }
 }
 
 /// Context provided to the stop function of [`ServiceManager`]
@@ -285,6 +981,36 @@ pub struct ServiceStopCtx {
     pub label: ServiceLabel,
 }
 
+/// Context provided to the pause function of [`ServiceManager`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServicePauseCtx {
+    /// Label associated with the service
+    ///
+    /// E.g. `rocks.distant.manager`
+    pub label: ServiceLabel,
+}
+
+/// Context provided to the resume function of [`ServiceManager`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceResumeCtx {
+    /// Label associated with the service
+    ///
+    /// E.g. `rocks.distant.manager`
+    pub label: ServiceLabel,
+}
+
+/// Context provided to the signal function of [`ServiceManager`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceSignalCtx {
+    /// Label associated with the service
+    ///
+    /// E.g. `rocks.distant.manager`
+    pub label: ServiceLabel,
+
+    /// Signal to send to the main process of the service
+    pub signal: Signal,
+}
+
 /// Context provided to the status function of [`ServiceManager`]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ServiceStatusCtx {
@@ -292,6 +1018,24 @@ pub struct ServiceStatusCtx {
     ///
     /// E.g. `rocks.distant.manager`
     pub label: ServiceLabel,
+
+    /// Level of the service to look up, defaulting to the level of the manager when not provided
+    ///
+    /// This is only supported by launchd and systemd, which keep user and system services apart.
+    /// Other service managers ignore it.
+    pub level: Option<ServiceLevel>,
+}
+
+/// Context provided to the logs function of [`ServiceManager`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceLogsCtx {
+    /// Label associated with the service
+    ///
+    /// E.g. `rocks.distant.manager`
+    pub label: ServiceLabel,
+
+    /// Optionally limit the logs to the most recent number of lines
+    pub lines: Option<usize>,
 }
 
 #[cfg(test)]
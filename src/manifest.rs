@@ -0,0 +1,526 @@
+use super::{
+    CommandRunner, ServiceInstallCtx, ServiceLabel, ServiceLevel, ServiceManagerKind,
+    ServiceUninstallCtx,
+};
+use crate::utils;
+use std::{
+    cell::RefCell,
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    sync::Arc,
+};
+
+/// Something that a service manager created or changed on the machine while installing a service
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(rename_all = "snake_case", tag = "type", content = "value")
+)]
+pub enum InstalledArtifact {
+    /// File that was written, such as a unit file or an init script
+    File(PathBuf),
+
+    /// External command that was run, given as its program followed by its arguments, with
+    /// passwords replaced by `<redacted>`
+    Command(Vec<String>),
+
+    /// Windows registry key that was created or changed
+    RegistryKey(String),
+}
+
+impl fmt::Display for InstalledArtifact {
+    /// Writes the artifact as a line of an install manifest
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "file\t{}", escape(&path.to_string_lossy())),
+            Self::Command(args) => {
+                write!(f, "command")?;
+                for arg in args {
+                    write!(f, "\t{}", escape(arg))?;
+                }
+                Ok(())
+            }
+            Self::RegistryKey(key) => write!(f, "registry\t{}", escape(key)),
+        }
+    }
+}
+
+/// Record of everything a service manager created or changed on the machine while installing a
+/// service, kept in a state directory so that the service can later be purged, rolled back, or
+/// checked for drift
+///
+/// Manifests are plain text with one entry per line, such as `file` followed by the path of a
+/// file or `command` followed by a program and its arguments, where the fields of an entry are
+/// separated by tabs and any tab, newline, or backslash within a field is escaped with a backslash.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct InstallManifest {
+    /// Qualified name of the label associated with the service
+    ///
+    /// E.g. `rocks.distant.manager`
+    pub label: String,
+
+    /// Artifacts in the order that they were created
+    pub artifacts: Vec<InstalledArtifact>,
+}
+
+impl InstallManifest {
+    /// Returns the path of the manifest of the service with `label` installed by the service
+    /// manager of `kind` at `level`
+    ///
+    /// Manifests of system services are kept in `/var/lib/service-manager/manifests` on unix and
+    /// `%ProgramData%\service-manager\manifests` on Windows, while manifests of user services are
+    /// kept in `service-manager/manifests` within the local data directory of the user.
+    pub fn path(
+        kind: ServiceManagerKind,
+        level: ServiceLevel,
+        label: &ServiceLabel,
+    ) -> io::Result<PathBuf> {
+        let dir = match level {
+            ServiceLevel::System => system_state_dir_path(),
+            ServiceLevel::User => dirs::data_local_dir().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "Unable to locate the local data directory",
+                )
+            })?,
+        };
+
+        Ok(dir
+            .join("service-manager")
+            .join("manifests")
+            .join(kind.name())
+            .join(format!("{}.manifest", label.to_qualified_name())))
+    }
+
+    /// Loads the manifest of the service with `label` installed by the service manager of `kind`
+    /// at `level`, failing with [`io::ErrorKind::NotFound`] if none was recorded
+    pub fn load(
+        kind: ServiceManagerKind,
+        level: ServiceLevel,
+        label: &ServiceLabel,
+    ) -> io::Result<Self> {
+        Self::read(&Self::path(kind, level, label)?)
+    }
+
+    /// Reads the manifest at `path`
+    pub fn read(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses the text of a manifest
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let mut manifest = Self::default();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            let mut fields = line.split('\t').map(unescape);
+            let kind = fields.next().unwrap_or_default();
+            let mut fields = fields.collect::<Vec<_>>();
+            match (kind.as_str(), fields.len()) {
+                ("label", 1) => manifest.label = fields.remove(0),
+                ("file", 1) => manifest
+                    .artifacts
+                    .push(InstalledArtifact::File(PathBuf::from(fields.remove(0)))),
+                ("command", _) => manifest.artifacts.push(InstalledArtifact::Command(fields)),
+                ("registry", 1) => manifest
+                    .artifacts
+                    .push(InstalledArtifact::RegistryKey(fields.remove(0))),
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid manifest entry: {line}"),
+                    ))
+                }
+            }
+        }
+
+        Ok(manifest)
+    }
+
+    /// Returns the paths of the files that were written, without duplicates
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for artifact in &self.artifacts {
+            if let InstalledArtifact::File(path) = artifact {
+                if !files.contains(path) {
+                    files.push(path.clone());
+                }
+            }
+        }
+        files
+    }
+
+    /// Returns the paths of the files that were written but no longer exist
+    pub fn missing_files(&self) -> Vec<PathBuf> {
+        self.files()
+            .into_iter()
+            .filter(|path| path.symlink_metadata().is_err())
+            .collect()
+    }
+}
+
+impl fmt::Display for InstallManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "label\t{}", escape(&self.label))?;
+        for artifact in &self.artifacts {
+            writeln!(f, "{artifact}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the artifacts recorded in the manifest of the service with `label`, failing with
+/// [`io::ErrorKind::NotFound`] if none was recorded
+pub(crate) fn installed_artifacts(
+    kind: ServiceManagerKind,
+    level: ServiceLevel,
+    label: &ServiceLabel,
+) -> io::Result<Vec<InstalledArtifact>> {
+    match InstallManifest::load(kind, level, label) {
+        Ok(manifest) => Ok(manifest.artifacts),
+        Err(x) if x.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No install manifest was recorded for {label}"),
+        )),
+        Err(x) => Err(x),
+    }
+}
+
+/// Returns the files recorded in the manifest of the service with `label` that no longer exist,
+/// which is empty if no manifest was recorded
+pub(crate) fn missing_files(
+    kind: ServiceManagerKind,
+    level: ServiceLevel,
+    label: &ServiceLabel,
+) -> Vec<PathBuf> {
+    InstallManifest::load(kind, level, label)
+        .map(|manifest| manifest.missing_files())
+        .unwrap_or_default()
+}
+
+/// Removes the manifest of the service of `ctx` along with its backup, first removing every file
+/// it lists when `ctx` asks to purge the service
+pub(crate) fn remove(
+    kind: ServiceManagerKind,
+    level: ServiceLevel,
+    ctx: &ServiceUninstallCtx,
+) -> io::Result<()> {
+    match InstallManifest::path(kind, level, &ctx.label) {
+        Ok(path) => remove_at(&path, ctx),
+        Err(_) => Ok(()),
+    }
+}
+
+fn remove_at(path: &Path, ctx: &ServiceUninstallCtx) -> io::Result<()> {
+    if ctx.purge {
+        match InstallManifest::read(path) {
+            Ok(manifest) => utils::purge(ctx, manifest.files())?,
+            Err(x) if x.kind() == io::ErrorKind::NotFound => {}
+            Err(x) => return Err(x),
+        }
+    }
+
+    for path in [path.to_path_buf(), utils::backup_path(path)] {
+        match fs::remove_file(path) {
+            Err(x) if x.kind() == io::ErrorKind::NotFound => {}
+            x => x?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Puts back the manifest that the last install of the service with `label` replaced, if any
+pub(crate) fn restore(
+    kind: ServiceManagerKind,
+    level: ServiceLevel,
+    label: &ServiceLabel,
+) -> io::Result<()> {
+    match InstallManifest::path(kind, level, label) {
+        Ok(path) => restore_at(&path),
+        Err(_) => Ok(()),
+    }
+}
+
+fn restore_at(path: &Path) -> io::Result<()> {
+    match utils::restore_definition(path) {
+        Err(x) if x.kind() == io::ErrorKind::NotFound => Ok(()),
+        x => x,
+    }
+}
+
+thread_local! {
+    /// Manifest that artifacts created on this thread are appended to, if an install is underway
+    static RECORDING: RefCell<Option<Recording>> = const { RefCell::new(None) };
+}
+
+struct Recording {
+    file: fs::File,
+
+    /// Whether recording is paused while a recorded command runs, so that runners wrapping other
+    /// runners only record the command once
+    paused: bool,
+
+    /// Install root that files are written beneath, which is left out of the recorded paths so
+    /// that they refer to the final locations of the files
+    root: Option<PathBuf>,
+}
+
+/// Guard that records the artifacts created on the current thread into the manifest of a service
+/// until dropped
+///
+/// Entries are appended as they are created so that an install that fails partway still leaves a
+/// record of what it did. Recording is best-effort: an install never fails because its manifest
+/// cannot be written, and an install nested within another is recorded into the outer manifest.
+pub(crate) struct ManifestRecorder {
+    active: bool,
+}
+
+impl ManifestRecorder {
+    /// Starts recording the install of the service of `ctx` by the service manager of `kind` at
+    /// `level`, replacing any previous manifest or keeping it as a backup when `ctx` asks for one
+    pub(crate) fn start(
+        kind: ServiceManagerKind,
+        level: ServiceLevel,
+        ctx: &ServiceInstallCtx,
+    ) -> Self {
+        match InstallManifest::path(kind, level, &ctx.label) {
+            // Staged installs ship their manifest beneath the install root like the rest
+            Ok(path) => Self::start_at(&utils::staged_path(ctx, &path), ctx),
+            Err(_) => Self { active: false },
+        }
+    }
+
+    fn start_at(path: &Path, ctx: &ServiceInstallCtx) -> Self {
+        if RECORDING.with(|x| x.borrow().is_some()) {
+            return Self { active: false };
+        }
+
+        let file = (|| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            if ctx.backup_definition && path.exists() {
+                fs::rename(path, utils::backup_path(path))?;
+            }
+
+            // Recorded commands may include secrets such as environment variables
+            let mut opts = fs::OpenOptions::new();
+            opts.create(true).write(true).truncate(true);
+
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                opts.mode(0o600);
+            }
+
+            let mut file = opts.open(path)?;
+            writeln!(file, "label\t{}", escape(&ctx.label.to_qualified_name()))?;
+            io::Result::Ok(file)
+        })();
+
+        match file {
+            Ok(file) => {
+                RECORDING.with(|x| {
+                    *x.borrow_mut() = Some(Recording {
+                        file,
+                        paused: false,
+                        root: ctx.install_root.clone(),
+                    })
+                });
+                Self { active: true }
+            }
+            Err(_) => Self { active: false },
+        }
+    }
+}
+
+impl Drop for ManifestRecorder {
+    fn drop(&mut self) {
+        if self.active {
+            RECORDING.with(|x| x.borrow_mut().take());
+        }
+    }
+}
+
+/// Appends `artifact` to the manifest of the install underway on the current thread, if any
+pub(crate) fn record(artifact: InstalledArtifact) {
+    RECORDING.with(|x| {
+        if let Some(recording) = x.borrow_mut().as_mut().filter(|x| !x.paused) {
+            let artifact = match (artifact, recording.root.as_deref()) {
+                (InstalledArtifact::File(path), Some(root)) => match path.strip_prefix(root) {
+                    Ok(path) => InstalledArtifact::File(Path::new("/").join(path)),
+                    Err(_) => InstalledArtifact::File(path),
+                },
+                (artifact, _) => artifact,
+            };
+            let _ = writeln!(recording.file, "{artifact}");
+        }
+    });
+}
+
+/// Implementation of [`CommandRunner`] that records each command into the manifest of the install
+/// underway on the current thread before passing it to another runner
+#[derive(Clone, Debug)]
+pub(crate) struct ManifestCommandRunner(pub(crate) Arc<dyn CommandRunner>);
+
+impl ManifestCommandRunner {
+    fn record<T>(&self, command: &mut Command, run: impl FnOnce(&mut Command) -> T) -> T {
+        // Manifests outlive the install and are not protected by file modes on every platform
+        record(InstalledArtifact::Command(crate::runner::redacted_args(
+            command,
+        )));
+
+        let paused = set_paused(true);
+        let result = run(command);
+        set_paused(paused);
+        result
+    }
+}
+
+impl CommandRunner for ManifestCommandRunner {
+    fn run(&self, command: &mut Command, input: Option<&[u8]>) -> io::Result<Output> {
+        self.record(command, |command| self.0.run(command, input))
+    }
+
+    fn run_until_exit(&self, command: &mut Command) -> io::Result<Output> {
+        self.record(command, |command| self.0.run_until_exit(command))
+    }
+
+    fn elevation(&self) -> crate::ElevationStrategy {
+        self.0.elevation()
+    }
+}
+
+/// Pauses or resumes recording, returning whether it was paused before
+fn set_paused(paused: bool) -> bool {
+    RECORDING.with(|x| match x.borrow_mut().as_mut() {
+        Some(recording) => std::mem::replace(&mut recording.paused, paused),
+        None => false,
+    })
+}
+
+#[cfg(windows)]
+fn system_state_dir_path() -> PathBuf {
+    std::env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
+}
+
+#[cfg(not(windows))]
+fn system_state_dir_path() -> PathBuf {
+    PathBuf::from("/var/lib")
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{runner::tests::RecordingRunner, ElevatingCommandRunner, ElevationStrategy};
+
+    #[test]
+    fn test_parse_round_trip() {
+        let manifest = InstallManifest {
+            label: String::from("org.example.my_service"),
+            artifacts: vec![
+                InstalledArtifact::File(PathBuf::from("/etc/init.d/my_service")),
+                InstalledArtifact::Command(vec![
+                    String::from("my_service"),
+                    String::from("--message=a\tb\nc\\d"),
+                    String::new(),
+                ]),
+                InstalledArtifact::RegistryKey(String::from(
+                    r"HKLM\SYSTEM\CurrentControlSet\Services\my_service",
+                )),
+            ],
+        };
+
+        assert_eq!(
+            InstallManifest::parse(&manifest.to_string()).unwrap(),
+            manifest
+        );
+        assert_eq!(
+            InstallManifest::parse("unknown\tvalue").unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_records_install() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("my_service.manifest");
+        let service_path = temp.path().join("my_service.service");
+        let ctx = utils::tests::install_ctx("/usr/local/bin/my_service".into());
+
+        // Commands passed through another runner are only recorded once
+        let runner = Arc::new(RecordingRunner::default());
+        let runner = crate::SharedCommandRunner::new(
+            ElevatingCommandRunner::new(ElevationStrategy::None)
+                .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>),
+        );
+
+        let recorder = ManifestRecorder::start_at(&path, &ctx);
+        assert!(!ManifestRecorder::start_at(&temp.path().join("nested.manifest"), &ctx).active);
+        utils::write_file(&service_path, b"[Unit]\n", 0o644).unwrap();
+        runner
+            .run(Command::new("systemctl").arg("daemon-reload"), None)
+            .unwrap();
+        drop(recorder);
+        runner
+            .run(Command::new("systemctl").arg("start"), None)
+            .unwrap();
+
+        assert_eq!(
+            InstallManifest::read(&path).unwrap(),
+            InstallManifest {
+                label: String::from("org.example.my_service"),
+                artifacts: vec![
+                    InstalledArtifact::File(service_path.clone()),
+                    InstalledArtifact::Command(vec![
+                        String::from("systemctl"),
+                        String::from("daemon-reload"),
+                    ]),
+                ],
+            }
+        );
+
+        let ctx = ServiceUninstallCtx {
+            label: ctx.label,
+            install_binary_to: None,
+            purge: true,
+        };
+        remove_at(&path, &ctx).unwrap();
+        assert!(!path.exists());
+        assert!(!service_path.exists());
+    }
+}
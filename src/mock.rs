@@ -0,0 +1,435 @@
+use super::{
+    ServiceInstallCtx, ServiceLabel, ServiceLevel, ServiceLogsCtx, ServiceManager, ServicePauseCtx,
+    ServiceResumeCtx, ServiceSignalCtx, ServiceStartCtx, ServiceStatus, ServiceStatusCtx,
+    ServiceStatusDetails, ServiceStopCtx, ServiceUninstallCtx,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Operation of a [`ServiceManager`], used to script failures of a [`MockServiceManager`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MockOperation {
+    Available,
+    Install,
+    Uninstall,
+    Start,
+    Stop,
+    SetLevel,
+    Status,
+    Logs,
+    Pause,
+    Resume,
+    Signal,
+}
+
+/// Call received by a [`MockServiceManager`] alongside the context it was given
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MockCall {
+    Available,
+    Install(ServiceInstallCtx),
+    Uninstall(ServiceUninstallCtx),
+    Start(ServiceStartCtx),
+    Stop(ServiceStopCtx),
+    SetLevel(ServiceLevel),
+    Status(ServiceStatusCtx),
+    Logs(ServiceLogsCtx),
+    Pause(ServicePauseCtx),
+    Resume(ServiceResumeCtx),
+    Signal(ServiceSignalCtx),
+}
+
+impl MockCall {
+    /// Operation that was called
+    pub fn operation(&self) -> MockOperation {
+        match self {
+            Self::Available => MockOperation::Available,
+            Self::Install(_) => MockOperation::Install,
+            Self::Uninstall(_) => MockOperation::Uninstall,
+            Self::Start(_) => MockOperation::Start,
+            Self::Stop(_) => MockOperation::Stop,
+            Self::SetLevel(_) => MockOperation::SetLevel,
+            Self::Status(_) => MockOperation::Status,
+            Self::Logs(_) => MockOperation::Logs,
+            Self::Pause(_) => MockOperation::Pause,
+            Self::Resume(_) => MockOperation::Resume,
+            Self::Signal(_) => MockOperation::Signal,
+        }
+    }
+
+    /// Label of the service the call targeted, if any
+    pub fn label(&self) -> Option<&ServiceLabel> {
+        match self {
+            Self::Available | Self::SetLevel(_) => None,
+            Self::Install(ctx) => Some(&ctx.label),
+            Self::Uninstall(ctx) => Some(&ctx.label),
+            Self::Start(ctx) => Some(&ctx.label),
+            Self::Stop(ctx) => Some(&ctx.label),
+            Self::Status(ctx) => Some(&ctx.label),
+            Self::Logs(ctx) => Some(&ctx.label),
+            Self::Pause(ctx) => Some(&ctx.label),
+            Self::Resume(ctx) => Some(&ctx.label),
+            Self::Signal(ctx) => Some(&ctx.label),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MockState {
+    available: bool,
+    level: ServiceLevel,
+    calls: Vec<MockCall>,
+    statuses: HashMap<String, ServiceStatusDetails>,
+    scripted_statuses: HashMap<String, VecDeque<ServiceStatusDetails>>,
+    logs: HashMap<String, String>,
+    failures: HashMap<MockOperation, VecDeque<(io::ErrorKind, String)>>,
+}
+
+/// Implementation of [`ServiceManager`] for tests that records every call it receives
+///
+/// The mock tracks the status of each service as calls are made, e.g. a service is stopped after
+/// being installed and running after being started, and this can be replaced by scripting the
+/// statuses or failures that calls return. Clones share the same state, so a clone can be kept to
+/// make assertions after the mock is handed to the code under test.
+///
+/// ```
+/// use service_manager::*;
+///
+/// let mock = MockServiceManager::new();
+/// let manager: Box<dyn ServiceManager> = Box::new(mock.clone());
+///
+/// let label: ServiceLabel = "com.example.my-service".parse().unwrap();
+/// manager
+///     .start(ServiceStartCtx {
+///         label: label.clone(),
+///         wait: WaitMode::None,
+///     })
+///     .unwrap();
+///
+/// mock.assert_called(MockOperation::Start, &label);
+/// assert_eq!(
+///     manager.status(ServiceStatusCtx { label, level: None }).unwrap(),
+///     ServiceStatus::Running,
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct MockServiceManager {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl Default for MockServiceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockServiceManager {
+    /// Creates a new mock that is available, works with system-level services, and has no
+    /// services installed
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MockState {
+                available: true,
+                level: ServiceLevel::System,
+                calls: Vec::new(),
+                statuses: HashMap::new(),
+                scripted_statuses: HashMap::new(),
+                logs: HashMap::new(),
+                failures: HashMap::new(),
+            })),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.state.lock().unwrap_or_else(|x| x.into_inner())
+    }
+
+    /// Sets whether the mock reports itself as available
+    pub fn set_available(&self, available: bool) {
+        self.state().available = available;
+    }
+
+    /// Sets the status of a service, replacing the status tracked from previous calls
+    pub fn set_status(&self, label: &ServiceLabel, details: impl Into<ServiceStatusDetails>) {
+        self.state()
+            .statuses
+            .insert(label.to_qualified_name(), details.into());
+    }
+
+    /// Queues a status to return from the next status call for a service that has not had its
+    /// queued statuses used up, after which the tracked status is returned again
+    pub fn push_status(&self, label: &ServiceLabel, details: impl Into<ServiceStatusDetails>) {
+        self.state()
+            .scripted_statuses
+            .entry(label.to_qualified_name())
+            .or_default()
+            .push_back(details.into());
+    }
+
+    /// Sets the logs returned for a service
+    pub fn set_logs(&self, label: &ServiceLabel, logs: impl Into<String>) {
+        self.state()
+            .logs
+            .insert(label.to_qualified_name(), logs.into());
+    }
+
+    /// Makes the next call of `operation` fail with an error of the given kind and message
+    ///
+    /// Failures queue up, so scripting the same operation twice makes its next two calls fail.
+    pub fn fail_next(
+        &self,
+        operation: MockOperation,
+        kind: io::ErrorKind,
+        message: impl Into<String>,
+    ) {
+        self.state()
+            .failures
+            .entry(operation)
+            .or_default()
+            .push_back((kind, message.into()));
+    }
+
+    /// Returns every call received so far in order
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state().calls.clone()
+    }
+
+    /// Returns the contexts of every install call received so far in order
+    pub fn installs(&self) -> Vec<ServiceInstallCtx> {
+        self.state()
+            .calls
+            .iter()
+            .filter_map(|call| match call {
+                MockCall::Install(ctx) => Some(ctx.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the number of calls of `operation` received for a service
+    pub fn call_count(&self, operation: MockOperation, label: &ServiceLabel) -> usize {
+        self.state()
+            .calls
+            .iter()
+            .filter(|call| call.operation() == operation && call.label() == Some(label))
+            .count()
+    }
+
+    /// Panics unless `operation` was called at least once for a service
+    #[track_caller]
+    pub fn assert_called(&self, operation: MockOperation, label: &ServiceLabel) {
+        assert!(
+            self.call_count(operation, label) > 0,
+            "Expected {operation:?} to be called for {}, but received {:#?}",
+            label.to_qualified_name(),
+            self.calls()
+        );
+    }
+
+    /// Panics if `operation` was called for a service
+    #[track_caller]
+    pub fn assert_not_called(&self, operation: MockOperation, label: &ServiceLabel) {
+        assert_eq!(
+            self.call_count(operation, label),
+            0,
+            "Expected {operation:?} not to be called for {}",
+            label.to_qualified_name(),
+        );
+    }
+
+    /// Records a call, returning the failure scripted for its operation if there is one
+    fn record(&self, call: MockCall) -> io::Result<MutexGuard<'_, MockState>> {
+        let mut state = self.state();
+        let operation = call.operation();
+        state.calls.push(call);
+
+        match state
+            .failures
+            .get_mut(&operation)
+            .and_then(VecDeque::pop_front)
+        {
+            Some((kind, message)) => Err(io::Error::new(kind, message)),
+            None => Ok(state),
+        }
+    }
+
+    fn track(&self, call: MockCall, status: ServiceStatus) -> io::Result<()> {
+        let name = call.label().map(ServiceLabel::to_qualified_name);
+        let mut state = self.record(call)?;
+        if let Some(name) = name {
+            state.statuses.insert(name, status.into());
+        }
+        Ok(())
+    }
+}
+
+impl ServiceManager for MockServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        Ok(self.record(MockCall::Available)?.available)
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        self.track(MockCall::Install(ctx), ServiceStatus::Stopped(None))
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        self.track(MockCall::Uninstall(ctx), ServiceStatus::NotInstalled)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        self.track(MockCall::Start(ctx), ServiceStatus::Running)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        self.track(MockCall::Stop(ctx), ServiceStatus::Stopped(None))
+    }
+
+    fn level(&self) -> ServiceLevel {
+        self.state().level
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        self.record(MockCall::SetLevel(level))?.level = level;
+        Ok(())
+    }
+
+    fn status(&self, ctx: ServiceStatusCtx) -> io::Result<ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(&self, ctx: ServiceStatusCtx) -> io::Result<ServiceStatusDetails> {
+        let name = ctx.label.to_qualified_name();
+        let mut state = self.record(MockCall::Status(ctx))?;
+
+        if let Some(details) = state
+            .scripted_statuses
+            .get_mut(&name)
+            .and_then(VecDeque::pop_front)
+        {
+            return Ok(details);
+        }
+
+        Ok(state
+            .statuses
+            .get(&name)
+            .cloned()
+            .unwrap_or_else(|| ServiceStatus::NotInstalled.into()))
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let name = ctx.label.to_qualified_name();
+        let lines = ctx.lines;
+        let state = self.record(MockCall::Logs(ctx))?;
+        let text = state.logs.get(&name).cloned().unwrap_or_default();
+        Ok(crate::utils::tail(&text, lines))
+    }
+
+    fn pause(&self, ctx: ServicePauseCtx) -> io::Result<()> {
+        self.track(MockCall::Pause(ctx), ServiceStatus::Paused)
+    }
+
+    fn resume(&self, ctx: ServiceResumeCtx) -> io::Result<()> {
+        self.track(MockCall::Resume(ctx), ServiceStatus::Running)
+    }
+
+    fn signal(&self, ctx: ServiceSignalCtx) -> io::Result<()> {
+        drop(self.record(MockCall::Signal(ctx))?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn label() -> ServiceLabel {
+        "com.example.my-service".parse().unwrap()
+    }
+
+    #[test]
+    fn test_tracks_status_from_calls() {
+        let mock = MockServiceManager::new();
+        let status = || {
+            mock.status(ServiceStatusCtx {
+                label: label(),
+                level: None,
+            })
+            .unwrap()
+        };
+
+        assert_eq!(status(), ServiceStatus::NotInstalled);
+        mock.start(ServiceStartCtx {
+            label: label(),
+            wait: crate::WaitMode::None,
+        })
+        .unwrap();
+        assert_eq!(status(), ServiceStatus::Running);
+        mock.stop(ServiceStopCtx { label: label() }).unwrap();
+        assert_eq!(status(), ServiceStatus::Stopped(None));
+        mock.uninstall(ServiceUninstallCtx {
+            label: label(),
+            install_binary_to: None,
+            purge: false,
+        })
+        .unwrap();
+        assert_eq!(status(), ServiceStatus::NotInstalled);
+
+        assert_eq!(mock.call_count(MockOperation::Status, &label()), 4);
+        mock.assert_called(MockOperation::Uninstall, &label());
+        mock.assert_not_called(MockOperation::Install, &label());
+    }
+
+    #[test]
+    fn test_scripted_statuses_and_failures() {
+        let mock = MockServiceManager::new();
+        mock.push_status(&label(), ServiceStatus::Paused);
+        mock.fail_next(
+            MockOperation::Start,
+            io::ErrorKind::PermissionDenied,
+            "denied",
+        );
+
+        assert_eq!(
+            mock.status(ServiceStatusCtx {
+                label: label(),
+                level: None
+            })
+            .unwrap(),
+            ServiceStatus::Paused
+        );
+        assert_eq!(
+            mock.status(ServiceStatusCtx {
+                label: label(),
+                level: None
+            })
+            .unwrap(),
+            ServiceStatus::NotInstalled
+        );
+
+        let err = mock
+            .start(ServiceStartCtx {
+                label: label(),
+                wait: crate::WaitMode::None,
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            mock.status(ServiceStatusCtx {
+                label: label(),
+                level: None
+            })
+            .unwrap(),
+            ServiceStatus::NotInstalled
+        );
+
+        mock.start(ServiceStartCtx {
+            label: label(),
+            wait: crate::WaitMode::None,
+        })
+        .unwrap();
+        assert_eq!(mock.call_count(MockOperation::Start, &label()), 2);
+    }
+}
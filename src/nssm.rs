@@ -0,0 +1,518 @@
+use crate::utils::{self, wrap_output};
+
+use super::{
+    manifest, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx, ServiceManager,
+    ServiceManagerKind, ServicePauseCtx, ServiceResumeCtx, ServiceStartCtx, ServiceStopCtx,
+    ServiceUninstallCtx, SharedCommandRunner, WindowsAccount,
+};
+use std::{
+    ffi::{OsStr, OsString},
+    fmt, io,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+static NSSM_EXE: &str = "nssm.exe";
+
+/// Configuration settings tied to NSSM services
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NssmConfig {
+    pub install: NssmInstallConfig,
+
+    /// Path to `nssm.exe`, otherwise it is expected to be found on the `PATH`
+    pub nssm_path: Option<PathBuf>,
+}
+
+/// Configuration settings used when installing NSSM services
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NssmInstallConfig {
+    /// Name of the service as displayed by the service control manager, set as `DisplayName` in
+    /// place of the display name of the install context
+    pub display_name: Option<String>,
+
+    /// Description of the service, set as `Description`
+    pub description: Option<String>,
+
+    /// File that the standard output of the program is redirected to, set as `AppStdout`
+    pub stdout_path: Option<PathBuf>,
+
+    /// File that the standard error of the program is redirected to, set as `AppStderr`
+    pub stderr_path: Option<PathBuf>,
+
+    /// If true, the redirected output files are rotated when the service starts, set as
+    /// `AppRotateFiles`
+    pub rotate_files: bool,
+
+    /// Action taken when the program exits, set as `AppExit Default`
+    ///
+    /// If not specified, the action is derived from the `restart_policy` of
+    /// [`ServiceInstallCtx`].
+    pub exit_action: Option<NssmExitAction>,
+
+    /// Account used to run the service, set as `ObjectName`
+    ///
+    /// If not specified, the `username` of [`ServiceInstallCtx`] is used when provided.
+    pub account: Option<WindowsAccount>,
+}
+
+/// Action NSSM takes when the program of a service exits
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NssmExitAction {
+    /// Restart the program after the restart delay
+    Restart,
+
+    /// Leave the service running without restarting the program
+    Ignore,
+
+    /// Stop the service
+    Exit,
+
+    /// Stop the service without informing the service control manager, which treats it as a
+    /// failure and applies the recovery actions of the service
+    Suicide,
+}
+
+impl fmt::Display for NssmExitAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Restart => write!(f, "Restart"),
+            Self::Ignore => write!(f, "Ignore"),
+            Self::Exit => write!(f, "Exit"),
+            Self::Suicide => write!(f, "Suicide"),
+        }
+    }
+}
+
+/// Implementation of [`ServiceManager`] for the
+/// [Non-Sucking Service Manager](https://nssm.cc/)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NssmServiceManager {
+    /// Configuration settings tied to NSSM services
+    pub config: NssmConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl NssmServiceManager {
+    /// Creates a new manager instance working with system services
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: NssmConfig) -> Self {
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    fn nssm_exe<'a>(
+        &self,
+        cmd: &str,
+        service_name: &str,
+        args: impl IntoIterator<Item = &'a OsStr>,
+    ) -> io::Result<Output> {
+        let program = match &self.config.nssm_path {
+            Some(path) => path.as_os_str(),
+            None => OsStr::new(NSSM_EXE),
+        };
+
+        let mut command = Command::new(program);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .arg(cmd)
+            .arg(service_name);
+
+        for arg in args {
+            command.arg(arg);
+        }
+
+        let mut output = self.runner.run(&mut command, None)?;
+        output.stdout = decode_utf16(output.stdout);
+        output.stderr = decode_utf16(output.stderr);
+        Ok(output)
+    }
+
+    /// Sets a parameter of the service via `nssm set`
+    fn set<'a>(
+        &self,
+        service_name: &str,
+        parameter: &'a str,
+        values: impl IntoIterator<Item = &'a OsStr>,
+    ) -> io::Result<()> {
+        let args = std::iter::once(OsStr::new(parameter)).chain(values);
+        wrap_output(self.nssm_exe("set", service_name, args)?)?;
+        Ok(())
+    }
+}
+
+impl ServiceManager for NssmServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        if let Some(path) = &self.config.nssm_path {
+            return Ok(path.is_file());
+        }
+
+        match which::which(NSSM_EXE) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        crate::utils::validate_environment(&ctx, false)?;
+        crate::utils::capabilities_unsupported(&ctx, "NSSM")?;
+        crate::utils::backup_unsupported(&ctx, "NSSM")?;
+        crate::utils::staging_unsupported(&ctx, "NSSM")?;
+        crate::utils::check_program(&ctx)?;
+        if ctx.kind == crate::ServiceKind::Oneshot {
+            return Err(utils::oneshot_unsupported("NSSM"));
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Nssm, self.level(), &ctx);
+        let ctx = crate::utils::install_binary(ctx)?;
+
+        let config = &self.config.install;
+        let service_name = ctx.label.to_qualified_name();
+
+        wrap_output(self.nssm_exe("install", &service_name, ctx.cmd_iter())?)?;
+
+        if let Some(display_name) = config.display_name.as_ref().or(ctx.display_name.as_ref()) {
+            self.set(&service_name, "DisplayName", [OsStr::new(display_name)])?;
+        }
+
+        if let Some(description) = config.description.as_ref().or(ctx.description.as_ref()) {
+            self.set(&service_name, "Description", [OsStr::new(description)])?;
+        }
+
+        if let Some(dir) = &ctx.working_directory {
+            self.set(&service_name, "AppDirectory", [dir.as_os_str()])?;
+        }
+
+        if let Some(env_vars) = &ctx.environment {
+            let env_vars = env_vars
+                .iter()
+                .map(|(var, val)| {
+                    let mut env_var = var.clone();
+                    env_var.push("=");
+                    env_var.push(val);
+                    env_var
+                })
+                .collect::<Vec<OsString>>();
+            self.set(
+                &service_name,
+                "AppEnvironmentExtra",
+                env_vars.iter().map(OsString::as_os_str),
+            )?;
+        }
+
+        if let Some(path) = &config.stdout_path {
+            self.set(&service_name, "AppStdout", [path.as_os_str()])?;
+        }
+
+        if let Some(path) = &config.stderr_path {
+            self.set(&service_name, "AppStderr", [path.as_os_str()])?;
+        }
+
+        if config.rotate_files {
+            self.set(&service_name, "AppRotateFiles", [OsStr::new("1")])?;
+        }
+
+        for (code, action) in exit_actions(config.exit_action, ctx.restart_policy) {
+            self.set(
+                &service_name,
+                "AppExit",
+                [OsStr::new(code), OsStr::new(&action.to_string())],
+            )?;
+        }
+
+        if let Some(delay_secs) = restart_delay_secs(ctx.restart_policy) {
+            let delay_ms = (u64::from(delay_secs) * 1000).to_string();
+            self.set(&service_name, "AppRestartDelay", [OsStr::new(&delay_ms)])?;
+        }
+
+        let account = match (&config.account, &ctx.username) {
+            (Some(account), _) => Some(account.clone()),
+            (None, Some(username)) => Some(WindowsAccount::new(username)),
+            (None, None) => None,
+        };
+        if let Some(account) = account {
+            let mut values = vec![OsString::from(&account.name)];
+            if !account.is_managed() {
+                values.extend(account.password.map(OsString::from));
+            }
+            self.set(
+                &service_name,
+                "ObjectName",
+                values.iter().map(OsString::as_os_str),
+            )?;
+        }
+
+        let start = if ctx.autostart {
+            "SERVICE_AUTO_START"
+        } else {
+            "SERVICE_DEMAND_START"
+        };
+        self.set(&service_name, "Start", [OsStr::new(start)])
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        crate::utils::ensure_installed(self, &ctx.label)?;
+
+        let service_name = ctx.label.to_qualified_name();
+
+        // Stopping a service that is not running fails, so any failure is ignored before removing it
+        let _ = self.nssm_exe("stop", &service_name, []);
+        wrap_output(self.nssm_exe("remove", &service_name, [OsStr::new("confirm")])?)?;
+
+        let config = &self.config.install;
+        crate::utils::purge(
+            &ctx,
+            config
+                .stdout_path
+                .iter()
+                .chain(&config.stderr_path)
+                .cloned(),
+        )?;
+        manifest::remove(ServiceManagerKind::Nssm, self.level(), &ctx)?;
+        crate::utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Nssm, self.level(), label)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        wrap_output(self.nssm_exe("start", &ctx.label.to_qualified_name(), [])?)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        wrap_output(self.nssm_exe("stop", &ctx.label.to_qualified_name(), [])?)?;
+        Ok(())
+    }
+
+    fn level(&self) -> ServiceLevel {
+        ServiceLevel::System
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => Ok(()),
+            ServiceLevel::User => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "NSSM does not support user-level services, which are managed by SchtasksServiceManager instead",
+            )),
+        }
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let config = &self.config.install;
+        let mut paths = config.stdout_path.iter().collect::<Vec<&PathBuf>>();
+        if let Some(path) = &config.stderr_path {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+
+        if paths.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Output of NSSM services is only logged when redirected to a file",
+            ));
+        }
+
+        // A single file is returned as is, while separate files are combined in the same manner as
+        // `tail` does for multiple files
+        if let [path] = paths.as_slice() {
+            let text = std::fs::read_to_string(path)?;
+            return Ok(utils::tail(&text, ctx.lines));
+        }
+
+        let mut logs = String::new();
+        for path in paths {
+            let text = std::fs::read_to_string(path)?;
+            if !logs.is_empty() {
+                logs.push_str("\n\n");
+            }
+            logs.push_str(&format!("==> {} <==\n", path.display()));
+            logs.push_str(&utils::tail(&text, ctx.lines));
+        }
+
+        Ok(logs)
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let service_name = ctx.label.to_qualified_name();
+        let output = self.nssm_exe("status", &service_name, [])?;
+
+        // NSSM fails to open services that do not exist
+        if !output.status.success()
+            && String::from_utf8_lossy(&output.stderr).contains("Can't open service")
+        {
+            return Ok(crate::ServiceStatus::NotInstalled.into());
+        }
+
+        let output = wrap_output(output)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_status(&stdout).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to get status of service {service_name}: {stdout}"),
+            )
+        })
+    }
+
+    fn pause(&self, ctx: ServicePauseCtx) -> io::Result<()> {
+        wrap_output(self.nssm_exe("pause", &ctx.label.to_qualified_name(), [])?)?;
+        Ok(())
+    }
+
+    fn resume(&self, ctx: ServiceResumeCtx) -> io::Result<()> {
+        wrap_output(self.nssm_exe("continue", &ctx.label.to_qualified_name(), [])?)?;
+        Ok(())
+    }
+}
+
+/// Converts output written by NSSM as UTF-16LE into UTF-8, which NSSM uses when its output is
+/// redirected, leaving output that is not UTF-16LE untouched
+fn decode_utf16(bytes: Vec<u8>) -> Vec<u8> {
+    let bytes_without_bom = bytes.strip_prefix(&[0xFF, 0xFE]).unwrap_or(&bytes);
+    let pairs = bytes_without_bom.chunks_exact(2);
+
+    // NSSM only prints ASCII, so every other byte of UTF-16LE output is zero
+    let looks_utf16 = !bytes_without_bom.is_empty()
+        && pairs.remainder().is_empty()
+        && pairs.clone().all(|pair| pair[1] == 0);
+    if !looks_utf16 {
+        return bytes;
+    }
+
+    let units = pairs
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect::<Vec<u16>>();
+    String::from_utf16_lossy(&units).into_bytes()
+}
+
+/// Returns the `AppExit` actions keyed by exit code, where `Default` applies to any exit code
+/// without its own action
+fn exit_actions(
+    exit_action: Option<NssmExitAction>,
+    restart_policy: Option<RestartPolicy>,
+) -> Vec<(&'static str, NssmExitAction)> {
+    if let Some(action) = exit_action {
+        return vec![("Default", action)];
+    }
+
+    match restart_policy {
+        None => Vec::new(),
+        Some(RestartPolicy::Never) => vec![("Default", NssmExitAction::Exit)],
+        Some(RestartPolicy::Always { .. }) => vec![("Default", NssmExitAction::Restart)],
+        Some(RestartPolicy::OnFailure { .. }) => vec![
+            ("Default", NssmExitAction::Restart),
+            ("0", NssmExitAction::Exit),
+        ],
+    }
+}
+
+fn restart_delay_secs(restart_policy: Option<RestartPolicy>) -> Option<u32> {
+    match restart_policy? {
+        RestartPolicy::Never => None,
+        RestartPolicy::Always { delay_secs } | RestartPolicy::OnFailure { delay_secs } => {
+            delay_secs
+        }
+    }
+}
+
+/// Parses the output of `nssm status`, which is the name of the state such as `SERVICE_RUNNING`
+fn parse_status(output: &str) -> Option<crate::ServiceStatusDetails> {
+    use crate::{ServiceStatus, ServiceTransition};
+
+    let (status, transition) = match output.trim() {
+        "SERVICE_RUNNING" => (ServiceStatus::Running, None),
+        "SERVICE_STOPPED" => (ServiceStatus::Stopped(None), None),
+        "SERVICE_PAUSED" => (ServiceStatus::Paused, None),
+        "SERVICE_START_PENDING" => (
+            ServiceStatus::Stopped(None),
+            Some(ServiceTransition::Starting),
+        ),
+        "SERVICE_STOP_PENDING" => (ServiceStatus::Running, Some(ServiceTransition::Stopping)),
+        "SERVICE_PAUSE_PENDING" => (ServiceStatus::Running, Some(ServiceTransition::Pausing)),
+        "SERVICE_CONTINUE_PENDING" => (ServiceStatus::Paused, Some(ServiceTransition::Resuming)),
+        _ => return None,
+    };
+
+    Some(crate::ServiceStatusDetails {
+        status,
+        pid: None,
+        transition,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf16() {
+        let utf16 = "SERVICE_RUNNING\r\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<u8>>();
+        assert_eq!(decode_utf16(utf16), b"SERVICE_RUNNING\r\n");
+        assert_eq!(
+            decode_utf16(b"SERVICE_RUNNING\r\n".to_vec()),
+            b"SERVICE_RUNNING\r\n"
+        );
+    }
+
+    #[test]
+    fn test_exit_actions() {
+        assert_eq!(exit_actions(None, None), Vec::new());
+        assert_eq!(
+            exit_actions(None, Some(RestartPolicy::OnFailure { delay_secs: None })),
+            vec![
+                ("Default", NssmExitAction::Restart),
+                ("0", NssmExitAction::Exit)
+            ]
+        );
+        assert_eq!(
+            exit_actions(Some(NssmExitAction::Ignore), Some(RestartPolicy::Never)),
+            vec![("Default", NssmExitAction::Ignore)]
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let details = parse_status("SERVICE_RUNNING\r\n").unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Running);
+        assert_eq!(details.transition, None);
+
+        let details = parse_status("SERVICE_STOP_PENDING\r\n").unwrap();
+        assert_eq!(details.transition, Some(crate::ServiceTransition::Stopping));
+
+        assert_eq!(parse_status("SERVICE_UNKNOWN\r\n"), None);
+    }
+}
@@ -0,0 +1,280 @@
+use super::{
+    AvailabilityDetails, DriftReport, InstalledArtifact, ServiceDiagnoseCtx, ServiceExportCtx,
+    ServiceHealth, ServiceHealthCtx, ServiceInstallCtx, ServiceLabel, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServicePauseCtx, ServiceResumeCtx, ServiceSignalCtx, ServiceSnapshot,
+    ServiceStartCtx, ServiceStatus, ServiceStatusCtx, ServiceStatusDetails, ServiceStopCtx,
+    ServiceUninstallCtx, StartFailureReport,
+};
+use std::{
+    fmt, io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Lifecycle operation reported to a [`ServiceManagerObserver`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ServiceOperation {
+    Install,
+    Uninstall,
+    Start,
+    Stop,
+}
+
+/// Receives notifications around the lifecycle operations of an [`ObservedServiceManager`], such
+/// as to display progress or record how long operations take
+///
+/// Both callbacks do nothing by default.
+pub trait ServiceManagerObserver: Send + Sync {
+    /// Called before `operation` begins on the service labeled `label`
+    fn before(&self, operation: ServiceOperation, label: &ServiceLabel) {
+        let _ = (operation, label);
+    }
+
+    /// Called once `operation` on the service labeled `label` completes, with its result and the
+    /// time it took
+    fn after(
+        &self,
+        operation: ServiceOperation,
+        label: &ServiceLabel,
+        result: Result<(), &io::Error>,
+        elapsed: Duration,
+    ) {
+        let _ = (operation, label, result, elapsed);
+    }
+}
+
+/// [`ServiceManager`] that notifies its observers before and after installing, uninstalling,
+/// starting, or stopping a service with the manager it wraps
+///
+/// All other operations are passed through to the wrapped manager without notifying observers.
+#[derive(Clone)]
+pub struct ObservedServiceManager<S> {
+    manager: S,
+    observers: Vec<Arc<dyn ServiceManagerObserver>>,
+}
+
+impl<S: ServiceManager> ObservedServiceManager<S> {
+    /// Wraps `manager` without any observers
+    pub fn new(manager: S) -> Self {
+        Self {
+            manager,
+            observers: Vec::new(),
+        }
+    }
+
+    /// Adds an observer, which is notified after the observers added before it
+    pub fn with_observer(mut self, observer: impl ServiceManagerObserver + 'static) -> Self {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    /// Returns a reference to the wrapped manager
+    pub fn get_ref(&self) -> &S {
+        &self.manager
+    }
+
+    /// Returns the wrapped manager
+    pub fn into_inner(self) -> S {
+        self.manager
+    }
+
+    fn observe(
+        &self,
+        operation: ServiceOperation,
+        label: &ServiceLabel,
+        f: impl FnOnce() -> io::Result<()>,
+    ) -> io::Result<()> {
+        for observer in &self.observers {
+            observer.before(operation, label);
+        }
+
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+
+        for observer in &self.observers {
+            observer.after(operation, label, result.as_ref().map(|_| ()), elapsed);
+        }
+
+        result
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for ObservedServiceManager<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObservedServiceManager")
+            .field("manager", &self.manager)
+            .field("observers", &self.observers.len())
+            .finish()
+    }
+}
+
+impl<S: ServiceManager> ServiceManager for ObservedServiceManager<S> {
+    fn available(&self) -> io::Result<bool> {
+        self.manager.available()
+    }
+
+    fn availability_details(&self) -> io::Result<AvailabilityDetails> {
+        self.manager.availability_details()
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        let label = ctx.label.clone();
+        self.observe(ServiceOperation::Install, &label, || {
+            self.manager.install(ctx)
+        })
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        let label = ctx.label.clone();
+        self.observe(ServiceOperation::Uninstall, &label, || {
+            self.manager.uninstall(ctx)
+        })
+    }
+
+    fn uninstall_matching(&self, prefix: &ServiceLabel) -> io::Result<Vec<ServiceLabel>> {
+        self.manager.uninstall_matching(prefix)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        let label = ctx.label.clone();
+        self.observe(ServiceOperation::Start, &label, || self.manager.start(ctx))
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        let label = ctx.label.clone();
+        self.observe(ServiceOperation::Stop, &label, || self.manager.stop(ctx))
+    }
+
+    fn level(&self) -> ServiceLevel {
+        self.manager.level()
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        self.manager.set_level(level)
+    }
+
+    fn status(&self, ctx: ServiceStatusCtx) -> io::Result<ServiceStatus> {
+        self.manager.status(ctx)
+    }
+
+    fn status_details(&self, ctx: ServiceStatusCtx) -> io::Result<ServiceStatusDetails> {
+        self.manager.status_details(ctx)
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        self.manager.logs(ctx)
+    }
+
+    fn diagnose(&self, ctx: ServiceDiagnoseCtx) -> io::Result<StartFailureReport> {
+        self.manager.diagnose(ctx)
+    }
+
+    fn pause(&self, ctx: ServicePauseCtx) -> io::Result<()> {
+        self.manager.pause(ctx)
+    }
+
+    fn resume(&self, ctx: ServiceResumeCtx) -> io::Result<()> {
+        self.manager.resume(ctx)
+    }
+
+    fn is_up_to_date(&self, ctx: &ServiceInstallCtx) -> io::Result<DriftReport> {
+        self.manager.is_up_to_date(ctx)
+    }
+
+    fn health(&self, ctx: ServiceHealthCtx) -> io::Result<ServiceHealth> {
+        self.manager.health(ctx)
+    }
+
+    fn signal(&self, ctx: ServiceSignalCtx) -> io::Result<()> {
+        self.manager.signal(ctx)
+    }
+
+    fn export(&self, ctx: ServiceExportCtx) -> io::Result<ServiceSnapshot> {
+        self.manager.export(ctx)
+    }
+
+    fn import(&self, snapshot: ServiceSnapshot) -> io::Result<()> {
+        self.manager.import(snapshot)
+    }
+
+    fn rollback(&self, label: &ServiceLabel) -> io::Result<()> {
+        self.manager.rollback(label)
+    }
+
+    fn installed_artifacts(&self, label: &ServiceLabel) -> io::Result<Vec<InstalledArtifact>> {
+        self.manager.installed_artifacts(label)
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use super::*;
+    use crate::{MockOperation, MockServiceManager};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        events: Mutex<Vec<String>>,
+    }
+
+    impl ServiceManagerObserver for Arc<RecordingObserver> {
+        fn before(&self, operation: ServiceOperation, label: &ServiceLabel) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("before {operation:?} {label}"));
+        }
+
+        fn after(
+            &self,
+            operation: ServiceOperation,
+            label: &ServiceLabel,
+            result: Result<(), &io::Error>,
+            _elapsed: Duration,
+        ) {
+            self.events.lock().unwrap().push(format!(
+                "after {operation:?} {label} {}",
+                if result.is_ok() { "ok" } else { "err" }
+            ));
+        }
+    }
+
+    #[test]
+    fn test_observes_lifecycle_operations() {
+        let observer = Arc::new(RecordingObserver::default());
+        let mock = MockServiceManager::new();
+        mock.fail_next(
+            MockOperation::Stop,
+            io::ErrorKind::PermissionDenied,
+            "denied",
+        );
+        let manager = ObservedServiceManager::new(mock).with_observer(Arc::clone(&observer));
+
+        let label: ServiceLabel = "com.example.my-service".parse().unwrap();
+        manager
+            .start(ServiceStartCtx {
+                label: label.clone(),
+                wait: crate::WaitMode::None,
+            })
+            .unwrap();
+        manager
+            .stop(ServiceStopCtx {
+                label: label.clone(),
+            })
+            .unwrap_err();
+        manager
+            .status(ServiceStatusCtx { label, level: None })
+            .unwrap();
+
+        assert_eq!(
+            *observer.events.lock().unwrap(),
+            [
+                "before Start com.example.my-service",
+                "after Start com.example.my-service ok",
+                "before Stop com.example.my-service",
+                "after Stop com.example.my-service err",
+            ]
+        );
+    }
+}
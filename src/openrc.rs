@@ -1,31 +1,61 @@
 use crate::utils::wrap_output;
 
 use super::{
-    utils, ServiceInstallCtx, ServiceLevel, ServiceManager, ServiceStartCtx, ServiceStopCtx,
-    ServiceUninstallCtx,
+    manifest, utils, CommandRunner, ServiceInstallCtx, ServiceLevel, ServiceManager,
+    ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx, SharedCommandRunner,
 };
 use std::{
-    ffi::{OsStr, OsString},
+    ffi::OsStr,
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, Output, Stdio},
 };
 
 static RC_SERVICE: &str = "rc-service";
 static RC_UPDATE: &str = "rc-update";
+static START_STOP_DAEMON: &str = "start-stop-daemon";
 
 // NOTE: On Alpine Linux, /etc/init.d/{script} has permissions of rwxr-xr-x (755)
 const SCRIPT_FILE_PERMISSIONS: u32 = 0o755;
 
 /// Configuration settings tied to OpenRC services
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
-pub struct OpenRcConfig {}
+pub struct OpenRcConfig {
+    /// Whether OpenRC is running inside of a container (e.g. Alpine Linux within Docker)
+    ///
+    /// When in a container, stopping a service falls back to signaling the process referenced by
+    /// the service's pidfile if `rc-service` fails to stop it. If not specified, this is detected
+    /// automatically.
+    pub container: Option<bool>,
+
+    /// Path of the `rc-service` program, which is searched for on the `PATH` if not provided
+    pub rc_service_path: Option<PathBuf>,
+
+    /// Path of the `rc-update` program, which is searched for on the `PATH` if not provided
+    pub rc_update_path: Option<PathBuf>,
+
+    /// Directory that init scripts are written to, defaulting to `/etc/init.d`
+    pub init_dir_path: Option<PathBuf>,
+}
+
+impl OpenRcConfig {
+    fn rc_service(&self) -> &OsStr {
+        utils::program_or(self.rc_service_path.as_deref(), RC_SERVICE)
+    }
+
+    fn rc_update(&self) -> &OsStr {
+        utils::program_or(self.rc_update_path.as_deref(), RC_UPDATE)
+    }
+}
 
 /// Implementation of [`ServiceManager`] for Linux's [OpenRC](https://en.wikipedia.org/wiki/OpenRC)
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct OpenRcServiceManager {
     /// Configuration settings tied to OpenRC services
     pub config: OpenRcConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
 }
 
 impl OpenRcServiceManager {
@@ -36,13 +66,47 @@ impl OpenRcServiceManager {
 
     /// Update manager to use the specified config
     pub fn with_config(self, config: OpenRcConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Returns true if the manager is operating within a container, either because it was
+    /// configured to do so or because a container environment was detected
+    /// Update manager to write init scripts to the specified directory rather than `/etc/init.d`,
+    /// such as a staging directory used to build a package
+    pub fn with_init_dir(mut self, dir_path: impl Into<PathBuf>) -> Self {
+        self.config.init_dir_path = Some(dir_path.into());
+        self
+    }
+
+    /// Returns the directory that init scripts are written to
+    pub fn init_dir(&self) -> PathBuf {
+        self.config
+            .init_dir_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("/etc/init.d"))
+    }
+
+    pub fn is_container(&self) -> bool {
+        self.config
+            .container
+            .unwrap_or_else(is_running_in_container)
     }
 }
 
 impl ServiceManager for OpenRcServiceManager {
     fn available(&self) -> io::Result<bool> {
-        match which::which(RC_SERVICE) {
+        match which::which(self.config.rc_service()) {
             Ok(_) => Ok(true),
             Err(which::Error::CannotFindBinaryPath) => Ok(false),
             Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
@@ -50,53 +114,121 @@ impl ServiceManager for OpenRcServiceManager {
     }
 
     fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
-        let dir_path = service_dir_path();
+        utils::capabilities_unsupported(&ctx, "OpenRC")?;
+        utils::backup_unsupported(&ctx, "OpenRC")?;
+        utils::require_utf8(&ctx, "OpenRC")?;
+        utils::check_program(&ctx)?;
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "OpenRC")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::OpenRc, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+        let dir_path = utils::staged_path(&ctx, &self.init_dir());
         std::fs::create_dir_all(&dir_path)?;
 
         let script_name = ctx.label.to_script_name();
         let script_path = dir_path.join(&script_name);
 
-        let script = match ctx.contents {
-            Some(contents) => contents,
-            _ => make_script(
-                &script_name,
-                &script_name,
-                ctx.program.as_os_str(),
-                ctx.args,
-            ),
+        let script = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_script(&script_name, &script_name, &ctx),
         };
 
-        utils::write_file(
+        utils::write_definition(
+            &ctx,
             script_path.as_path(),
             script.as_bytes(),
             SCRIPT_FILE_PERMISSIONS,
         )?;
 
-        if ctx.autostart {
+        if ctx.autostart && ctx.install_root.is_none() {
             // Add with default run level explicitly defined to prevent weird systems
             // like alpine's docker container with openrc from setting a different
             // run level than default
-            rc_update("add", &script_name, [OsStr::new("default")])?;
+            rc_update(
+                &*self.runner,
+                self.config.rc_update(),
+                "add",
+                &script_name,
+                [OsStr::new("default")],
+            )?;
         }
 
         Ok(())
     }
 
     fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "OpenRC")?;
+
         rc_update(
+            &*self.runner,
+            self.config.rc_update(),
             "delete",
             &ctx.label.to_script_name(),
             [OsStr::new("default")],
-        )
+        )?;
+
+        let script_name = ctx.label.to_script_name();
+        utils::purge(
+            &ctx,
+            [
+                self.init_dir().join(&script_name),
+                PathBuf::from("/etc/conf.d").join(&script_name),
+                pidfile_path(&script_name),
+            ],
+        )?;
+        manifest::remove(ServiceManagerKind::OpenRc, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::OpenRc, self.level(), label)
     }
 
     fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
-        wrap_output(rc_service("start", &ctx.label.to_script_name(), [])?)?;
-        Ok(())
+        wrap_output(rc_service(
+            &*self.runner,
+            self.config.rc_service(),
+            "start",
+            &ctx.label.to_script_name(),
+            [],
+        )?)?;
+        utils::wait_until_active(self, &ctx)
     }
 
     fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
-        wrap_output(rc_service("stop", &ctx.label.to_script_name(), [])?)?;
+        let script_name = ctx.label.to_script_name();
+        let output = rc_service(
+            &*self.runner,
+            self.config.rc_service(),
+            "stop",
+            &script_name,
+            [],
+        )?;
+        if output.status.success() || !self.is_container() {
+            wrap_output(output)?;
+            return Ok(());
+        }
+
+        // Within containers, OpenRC frequently fails to stop services that it started, so we stop
+        // the process directly using the pidfile from our script and then reset OpenRC's record
+        // of the service so that it is reported as stopped
+        wrap_output(start_stop_daemon_stop(
+            &*self.runner,
+            &pidfile_path(&script_name),
+        )?)?;
+        wrap_output(rc_service(
+            &*self.runner,
+            self.config.rc_service(),
+            "zap",
+            &script_name,
+            [],
+        )?)?;
         Ok(())
     }
 
@@ -115,7 +247,13 @@ impl ServiceManager for OpenRcServiceManager {
     }
 
     fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
-        let output = rc_service("status", &ctx.label.to_script_name(), [])?;
+        let output = rc_service(
+            &*self.runner,
+            self.config.rc_service(),
+            "status",
+            &ctx.label.to_script_name(),
+            [],
+        )?;
         match output.status.code() {
             Some(1) => {
                 let mut stdio = String::from_utf8_lossy(&output.stderr);
@@ -150,11 +288,13 @@ impl ServiceManager for OpenRcServiceManager {
 }
 
 fn rc_service<'a>(
+    runner: &dyn CommandRunner,
+    program: &OsStr,
     cmd: &str,
     service: &str,
     args: impl IntoIterator<Item = &'a OsStr>,
 ) -> io::Result<Output> {
-    let mut command = Command::new(RC_SERVICE);
+    let mut command = Command::new(program);
     command
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
@@ -164,15 +304,17 @@ fn rc_service<'a>(
     for arg in args {
         command.arg(arg);
     }
-    command.output()
+    runner.run(&mut command, None)
 }
 
 fn rc_update<'a>(
+    runner: &dyn CommandRunner,
+    program: &OsStr,
     cmd: &str,
     service: &str,
     args: impl IntoIterator<Item = &'a OsStr>,
 ) -> io::Result<()> {
-    let mut command = Command::new(RC_UPDATE);
+    let mut command = Command::new(program);
     command
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
@@ -184,7 +326,7 @@ fn rc_update<'a>(
         command.arg(arg);
     }
 
-    let output = command.output()?;
+    let output = runner.run(&mut command, None)?;
 
     if output.status.success() {
         Ok(())
@@ -203,33 +345,253 @@ fn rc_update<'a>(
     }
 }
 
+fn start_stop_daemon_stop(runner: &dyn CommandRunner, pidfile: &Path) -> io::Result<Output> {
+    let mut command = Command::new(START_STOP_DAEMON);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg("--stop")
+        .arg("--pidfile")
+        .arg(pidfile);
+
+    runner.run(&mut command, None)
+}
+
+/// Path to the pidfile used by scripts generated by [`make_script`]
 #[inline]
-fn service_dir_path() -> PathBuf {
-    PathBuf::from("/etc/init.d")
+fn pidfile_path(script_name: &str) -> PathBuf {
+    PathBuf::from("/run").join(format!("{script_name}.pid"))
+}
+
+/// Detects whether we are running inside of a container such as Docker or Podman
+fn is_running_in_container() -> bool {
+    if Path::new("/.dockerenv").exists() || Path::new("/run/.containerenv").exists() {
+        return true;
+    }
+
+    // OpenRC records the booted runlevel in this file, which is missing when OpenRC was not used
+    // to boot the system as is the case with most containers
+    if !Path::new("/run/openrc/softlevel").exists() {
+        return true;
+    }
+
+    std::fs::read_to_string("/proc/1/cgroup")
+        .map(|cgroup| cgroup_is_container(&cgroup))
+        .unwrap_or(false)
+}
+
+/// Inspects the contents of `/proc/1/cgroup` for signs of a container runtime
+fn cgroup_is_container(cgroup: &str) -> bool {
+    const RUNTIMES: &[&str] = &["docker", "lxc", "kubepods", "containerd", "libpod"];
+    cgroup
+        .lines()
+        .any(|line| RUNTIMES.iter().any(|runtime| line.contains(runtime)))
 }
 
-fn make_script(description: &str, provide: &str, program: &OsStr, args: Vec<OsString>) -> String {
-    let program = program.to_string_lossy();
-    let args = args
-        .into_iter()
-        .map(|a| a.to_string_lossy().to_string())
-        .collect::<Vec<String>>()
-        .join(" ");
+fn make_script(description: &str, provide: &str, ctx: &ServiceInstallCtx) -> String {
+    let description =
+        utils::escape_shell_double_quoted(ctx.description.as_deref().unwrap_or(description));
+    let program = ctx.program.to_string_lossy();
+    // command_args is evaluated by the shell when starting the service
+    let args = utils::escape_shell_double_quoted(&utils::shell_join(ctx.args_iter()));
+
+    // A oneshot program runs in the foreground so that the service is started once it exits
+    let background = match ctx.kind {
+        crate::ServiceKind::Simple => {
+            "\npidfile=\"/run/${RC_SVCNAME}.pid\"\ncommand_background=true"
+        }
+        crate::ServiceKind::Oneshot => "",
+    };
+
+    let stopsig = match ctx.stop_signal {
+        Some(signal) => format!("\nstopsig=\"{}\"", signal.name()),
+        None => String::new(),
+    };
+
+    // start-stop-daemon pipes the output of the program through these loggers
+    let logger = match &ctx.log_to_syslog {
+        Some(tag) => {
+            let tag = utils::escape_shell_double_quoted(tag);
+            format!(
+                "\noutput_logger=\"logger -t {tag} -p daemon.info\"\nerror_logger=\"logger -t {tag} -p daemon.err\""
+            )
+        }
+        None => String::new(),
+    };
+
+    // start-stop-daemon applies these to the program when starting it
+    let mut nice = String::new();
+    if let Some(priority) = &ctx.priority {
+        if let Some(level) = priority.nice {
+            nice.push_str(&format!("\nSSD_NICELEVEL=\"{level}\""));
+        }
+
+        if let Some(class) = priority.io_class {
+            let class = match class {
+                crate::IoSchedulingClass::Realtime => 1,
+                crate::IoSchedulingClass::BestEffort => 2,
+                crate::IoSchedulingClass::Idle => 3,
+            };
+            nice.push_str(&format!("\nSSD_IONICELEVEL=\"{class}\""));
+        }
+    }
+
+    // A lone timeout retries with the stop signal before escalating to SIGKILL
+    let retry = match ctx.stop_timeout {
+        Some(timeout) => format!("\nretry=\"{}\"", timeout.as_secs().max(1)),
+        None => String::new(),
+    };
+
+    // openrc-run signals the pidfile's process to stop the service unless stop() is overridden
+    let stop = if ctx.stop_command.is_some() {
+        let cmd = utils::shell_join(ctx.stop_cmd_iter());
+        format!(
+            "\n\nstop() {{\n    ebegin \"Stopping ${{RC_SVCNAME}}\"\n    {cmd}\n    eend $?\n}}"
+        )
+    } else {
+        String::new()
+    };
     format!(
         r#"
 #!/sbin/openrc-run
 
 description="{description}"
 command="{program}"
-command_args="{args}"
-pidfile="/run/${{RC_SVCNAME}}.pid"
-command_background=true
+command_args="{args}"{background}{stopsig}{retry}{nice}{logger}
 
 depend() {{
     provide {provide}
-}}
+}}{stop}
     "#
     )
     .trim()
     .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::{ffi::OsString, path::PathBuf};
+
+    #[test]
+    fn test_make_script_with_stop_options() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse().unwrap(),
+            program: PathBuf::from("/usr/local/bin/my_service"),
+            args: vec![OsString::from("--arg"), OsString::from("value")],
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: None,
+            autostart: true,
+            restart_policy: None,
+            stop_command: Some((
+                PathBuf::from("/usr/local/bin/my_service_ctl"),
+                vec![OsString::from("stop")],
+            )),
+            stop_signal: Some(crate::Signal::Int),
+            start_timeout: None,
+            stop_timeout: Some(std::time::Duration::from_secs(30)),
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+
+        assert_eq!(
+            make_script("example-my_service", "example-my_service", &ctx),
+            indoc! {r#"
+                #!/sbin/openrc-run
+
+                description="example-my_service"
+                command="/usr/local/bin/my_service"
+                command_args="--arg value"
+                pidfile="/run/${RC_SVCNAME}.pid"
+                command_background=true
+                stopsig="INT"
+                retry="30"
+
+                depend() {
+                    provide example-my_service
+                }
+
+                stop() {
+                    ebegin "Stopping ${RC_SVCNAME}"
+                    /usr/local/bin/my_service_ctl stop
+                    eend $?
+                }"#}
+        );
+    }
+
+    #[test]
+    fn test_make_script_with_priority_and_description() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse().unwrap(),
+            program: PathBuf::from("/usr/local/bin/my_service"),
+            args: Vec::new(),
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: None,
+            autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: Some(crate::ProcessPriority {
+                nice: Some(-5),
+                io_class: Some(crate::IoSchedulingClass::Idle),
+            }),
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: Some(String::from("Serves \"examples\" for $USER")),
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+
+        let script = make_script("example-my_service", "example-my_service", &ctx);
+        assert!(script.contains("\ndescription=\"Serves \\\"examples\\\" for \\$USER\"\n"));
+        assert!(script.contains("\nSSD_NICELEVEL=\"-5\"\nSSD_IONICELEVEL=\"3\"\n"));
+    }
+
+    #[test]
+    fn test_cgroup_is_container_for_docker() {
+        let cgroup = "12:pids:/docker/3f2a9c\n11:memory:/docker/3f2a9c\n0::/docker/3f2a9c\n";
+        assert!(cgroup_is_container(cgroup));
+    }
+
+    #[test]
+    fn test_cgroup_is_container_for_kubernetes() {
+        let cgroup = "0::/kubepods/besteffort/pod1234/abcd\n";
+        assert!(cgroup_is_container(cgroup));
+    }
+
+    #[test]
+    fn test_cgroup_is_container_for_host() {
+        let cgroup = "12:pids:/init.scope\n0::/init.scope\n";
+        assert!(!cgroup_is_container(cgroup));
+    }
+}
@@ -0,0 +1,430 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, utils, CommandRunner, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceManager,
+    ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx, SharedCommandRunner,
+};
+use std::{
+    fmt::Write as _,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+static PROCD: &str = "procd";
+static UBUS: &str = "ubus";
+
+// NOTE: On OpenWrt, /etc/init.d/{script} has permissions of rwxr-xr-x (755)
+const SCRIPT_FILE_PERMISSIONS: u32 = 0o755;
+
+/// Configuration settings tied to procd services
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcdConfig {
+    pub install: ProcdInstallConfig,
+
+    /// Directory where init scripts are written, defaulting to `/etc/init.d`
+    pub script_dir_path: PathBuf,
+}
+
+impl Default for ProcdConfig {
+    fn default() -> Self {
+        Self {
+            install: ProcdInstallConfig::default(),
+            script_dir_path: PathBuf::from("/etc/init.d"),
+        }
+    }
+}
+
+/// Configuration settings used when installing procd services
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProcdInstallConfig {
+    /// Order in which the service is started at boot, written as `START`
+    pub start_priority: u8,
+
+    /// Order in which the service is stopped at shutdown, written as `STOP`
+    pub stop_priority: u8,
+
+    /// Window in seconds within which crashes are counted towards `respawn_retry`
+    pub respawn_threshold_secs: u32,
+
+    /// Number of crashes within `respawn_threshold_secs` before procd gives up on the service,
+    /// where `0` retries forever
+    pub respawn_retry: u32,
+}
+
+impl Default for ProcdInstallConfig {
+    fn default() -> Self {
+        // Matches the defaults of procd_set_param respawn within /lib/functions/procd.sh
+        Self {
+            start_priority: 95,
+            stop_priority: 10,
+            respawn_threshold_secs: 3600,
+            respawn_retry: 5,
+        }
+    }
+}
+
+/// Implementation of [`ServiceManager`] for OpenWrt's
+/// [procd](https://openwrt.org/docs/guide-developer/procd-init-scripts)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcdServiceManager {
+    /// Configuration settings tied to procd services
+    pub config: ProcdConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl ProcdServiceManager {
+    /// Creates a new manager instance working with system services
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: ProcdConfig) -> Self {
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+}
+
+impl ServiceManager for ProcdServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        match which::which(PROCD) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        utils::validate_environment(&ctx, true)?;
+        utils::require_utf8(&ctx, "procd")?;
+        utils::capabilities_unsupported(&ctx, "procd")?;
+        utils::backup_unsupported(&ctx, "procd")?;
+        utils::check_program(&ctx)?;
+        if ctx.kind == crate::ServiceKind::Oneshot {
+            return Err(utils::oneshot_unsupported("procd"));
+        }
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "procd")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Procd, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+
+        let dir_path = utils::staged_path(&ctx, &self.config.script_dir_path);
+        std::fs::create_dir_all(&dir_path)?;
+
+        let script_path = dir_path.join(ctx.label.to_script_name());
+        let script = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_script(&self.config.install, &ctx),
+        };
+
+        utils::write_definition(
+            &ctx,
+            &script_path,
+            script.as_bytes(),
+            SCRIPT_FILE_PERMISSIONS,
+        )?;
+
+        if ctx.autostart && ctx.install_root.is_none() {
+            wrap_output(init_script(&*self.runner, &script_path, "enable")?)?;
+        }
+
+        Ok(())
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "procd")?;
+
+        let script_path = self.config.script_dir_path.join(ctx.label.to_script_name());
+        if !script_path.exists() {
+            return Ok(());
+        }
+
+        // Removes the links within /etc/rc.d, which succeeds even when the service was not enabled
+        wrap_output(init_script(&*self.runner, &script_path, "disable")?)?;
+        std::fs::remove_file(&script_path)?;
+        manifest::remove(ServiceManagerKind::Procd, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Procd, self.level(), label)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        let script_path = self.config.script_dir_path.join(ctx.label.to_script_name());
+        wrap_output(init_script(&*self.runner, &script_path, "start")?)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        let script_path = self.config.script_dir_path.join(ctx.label.to_script_name());
+        wrap_output(init_script(&*self.runner, &script_path, "stop")?)?;
+        Ok(())
+    }
+
+    fn level(&self) -> ServiceLevel {
+        ServiceLevel::System
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => Ok(()),
+            ServiceLevel::User => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "procd does not support user-level services",
+            )),
+        }
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let script_name = ctx.label.to_script_name();
+        if !self.config.script_dir_path.join(&script_name).exists() {
+            return Ok(crate::ServiceStatus::NotInstalled.into());
+        }
+
+        let output = wrap_output(ubus_service_list(&*self.runner, &script_name)?)?;
+        Ok(parse_service_list(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        utils::kill_main_process(&*self.runner, self, &ctx.label, ctx.signal)
+    }
+}
+
+fn init_script(runner: &dyn CommandRunner, script_path: &Path, cmd: &str) -> io::Result<Output> {
+    let mut command = Command::new(script_path);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg(cmd);
+
+    runner.run(&mut command, None)
+}
+
+fn ubus_service_list(runner: &dyn CommandRunner, script_name: &str) -> io::Result<Output> {
+    let mut command = Command::new(UBUS);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .args(["call", "service", "list"])
+        .arg(format!("{{\"name\":\"{script_name}\"}}"));
+
+    runner.run(&mut command, None)
+}
+
+/// Parses the output of `ubus call service list '{"name":"{name}"}'`, which is an empty object
+/// when procd is not tracking the service and otherwise describes each of its instances
+fn parse_service_list(output: &str) -> crate::ServiceStatusDetails {
+    let running = output
+        .lines()
+        .any(|line| line.trim().trim_end_matches(',') == "\"running\": true");
+    if !running {
+        return crate::ServiceStatus::Stopped(None).into();
+    }
+
+    let pid = output.lines().find_map(|line| {
+        line.trim()
+            .trim_end_matches(',')
+            .strip_prefix("\"pid\":")
+            .and_then(|pid| pid.trim().parse().ok())
+    });
+
+    crate::ServiceStatusDetails {
+        status: crate::ServiceStatus::Running,
+        pid,
+        transition: None,
+    }
+}
+
+fn make_script(config: &ProcdInstallConfig, ctx: &ServiceInstallCtx) -> String {
+    let start = config.start_priority;
+    let stop = config.stop_priority;
+
+    let cmd = utils::shell_join(ctx.cmd_iter());
+
+    // procd has no parameter for the working directory, so the command is wrapped in a shell that
+    // changes into it before replacing itself with the program
+    let command = match &ctx.working_directory {
+        Some(dir) => format!(
+            "/bin/sh -c \"cd '{}' && exec {}\"",
+            dir.to_string_lossy(),
+            utils::escape_shell_double_quoted(&cmd)
+        ),
+        None => cmd,
+    };
+
+    let mut params = format!("    procd_set_param command {command}");
+
+    if let Some(env_vars) = &ctx.environment {
+        let env = env_vars
+            .iter()
+            .map(|(var, val)| {
+                format!(
+                    "{}=\"{}\"",
+                    var.to_string_lossy(),
+                    utils::escape_shell_double_quoted(&val.to_string_lossy())
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        let _ = write!(params, "\n    procd_set_param env {env}");
+    }
+
+    if let Some(username) = &ctx.username {
+        let _ = write!(params, "\n    procd_set_param user {username}");
+    }
+
+    // procd respawns a service whenever it exits regardless of its exit code, so both policies
+    // respawn the service
+    let delay = match ctx.restart_policy {
+        None | Some(RestartPolicy::Never) => None,
+        Some(RestartPolicy::Always { delay_secs })
+        | Some(RestartPolicy::OnFailure { delay_secs }) => Some(delay_secs.unwrap_or(5)),
+    };
+    if let Some(delay) = delay {
+        let _ = write!(
+            params,
+            "\n    procd_set_param respawn {} {delay} {}",
+            config.respawn_threshold_secs, config.respawn_retry
+        );
+    }
+
+    format!(
+        r#"
+#!/bin/sh /etc/rc.common
+
+START={start}
+STOP={stop}
+USE_PROCD=1
+
+start_service() {{
+    procd_open_instance
+{params}
+    procd_set_param stdout 1
+    procd_set_param stderr 1
+    procd_close_instance
+}}
+    "#
+    )
+    .trim()
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServiceLabel;
+    use indoc::indoc;
+    use std::ffi::OsString;
+
+    #[test]
+    fn test_make_script() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse::<ServiceLabel>().unwrap(),
+            program: PathBuf::from("/usr/bin/my_service"),
+            args: vec![OsString::from("--port"), OsString::from("8080")],
+            contents: None,
+            username: Some(String::from("nobody")),
+            working_directory: None,
+            environment: Some(vec![(OsString::from("RUST_LOG"), OsString::from("info"))]),
+            autostart: true,
+            restart_policy: Some(RestartPolicy::OnFailure {
+                delay_secs: Some(10),
+            }),
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+
+        assert_eq!(
+            make_script(&ProcdInstallConfig::default(), &ctx),
+            indoc! {r#"
+                #!/bin/sh /etc/rc.common
+
+                START=95
+                STOP=10
+                USE_PROCD=1
+
+                start_service() {
+                    procd_open_instance
+                    procd_set_param command /usr/bin/my_service --port 8080
+                    procd_set_param env RUST_LOG="info"
+                    procd_set_param user nobody
+                    procd_set_param respawn 3600 10 5
+                    procd_set_param stdout 1
+                    procd_set_param stderr 1
+                    procd_close_instance
+                }"#}
+        );
+    }
+
+    #[test]
+    fn test_parse_service_list() {
+        let output = indoc! {r#"
+            {
+                "org.example.my_service": {
+                    "instances": {
+                        "instance1": {
+                            "running": true,
+                            "pid": 1234,
+                            "command": [
+                                "/usr/bin/my_service"
+                            ]
+                        }
+                    }
+                }
+            }
+        "#};
+        let details = parse_service_list(output);
+        assert_eq!(details.status, crate::ServiceStatus::Running);
+        assert_eq!(details.pid, Some(1234));
+
+        let details = parse_service_list("{\n\n}\n");
+        assert_eq!(details.status, crate::ServiceStatus::Stopped(None));
+        assert_eq!(details.pid, None);
+    }
+}
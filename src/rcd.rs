@@ -1,28 +1,170 @@
+use crate::utils::wrap_output;
+use cfg_if::cfg_if;
+
 use super::{
-    utils, ServiceInstallCtx, ServiceLevel, ServiceManager, ServiceStartCtx, ServiceStopCtx,
-    ServiceUninstallCtx,
+    manifest, utils, CommandRunner, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner,
 };
 use std::{
-    ffi::{OsStr, OsString},
+    ffi::OsStr,
+    fmt::{self, Write as _},
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, ExitStatus, Stdio},
 };
 
 static SERVICE: &str = "service";
+static JEXEC: &str = "jexec";
+static JLS: &str = "jls";
+
+static SYSLOG_FILE: &str = "/var/log/messages";
 
 // NOTE: On FreeBSD, /usr/local/etc/rc.d/{script} has permissions of rwxr-xr-x (755)
 const SCRIPT_FILE_PERMISSIONS: u32 = 0o755;
 
+// NOTE: On FreeBSD, /etc/rc.conf.d/{name} has permissions of rw-r--r-- (644)
+const RC_CONF_FILE_PERMISSIONS: u32 = 0o644;
+
 /// Configuration settings tied to rc.d services
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
-pub struct RcdConfig {}
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RcdConfig {
+    pub install: RcdInstallConfig,
+
+    /// Directory containing the rc.d scripts of services
+    ///
+    /// Defaults to the directory used for third-party services on the current operating system,
+    /// which is `/etc/rc.d` on NetBSD and OpenBSD and `/usr/local/etc/rc.d` elsewhere.
+    /// Use [`RcdConfig::BASE_SCRIPT_DIR`] or [`RcdConfig::LOCAL_SCRIPT_DIR`] to pick one
+    /// explicitly.
+    pub script_dir_path: PathBuf,
+
+    /// Path of the `service` program, which is searched for on the `PATH` if not provided
+    ///
+    /// When working with a jail, this is the path of the program within the jail.
+    pub service_path: Option<PathBuf>,
+}
+
+impl RcdConfig {
+    fn service(&self) -> &OsStr {
+        utils::program_or(self.service_path.as_deref(), SERVICE)
+    }
+
+    /// Directory containing the rc.d scripts of the base system
+    pub const BASE_SCRIPT_DIR: &'static str = "/etc/rc.d";
+
+    /// Directory containing the rc.d scripts of ports and packages on FreeBSD and DragonFly BSD
+    pub const LOCAL_SCRIPT_DIR: &'static str = "/usr/local/etc/rc.d";
+}
+
+impl Default for RcdConfig {
+    fn default() -> Self {
+        Self {
+            install: RcdInstallConfig::default(),
+            script_dir_path: default_script_dir_path(),
+            service_path: None,
+        }
+    }
+}
+
+/// Configuration settings tied to rc.d services during installation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RcdInstallConfig {
+    /// Services that must be started before this one, written as `# REQUIRE:`
+    pub require: Vec<String>,
+
+    /// Services that must be started after this one, written as `# BEFORE:`
+    pub before: Vec<String>,
+
+    /// Keywords used by rcorder(8) to filter the script, written as `# KEYWORD:`
+    pub keywords: Vec<RcdKeyword>,
+
+    /// If specified, the service is enabled by writing `{name}_enable="YES"` to a file named after
+    /// the service within this directory (e.g. `/etc/rc.conf.d` or `/usr/local/etc/rc.conf.d`)
+    /// instead of modifying rc.conf via `service {name} enable`. The file is removed on uninstall.
+    pub rc_conf_d_dir: Option<PathBuf>,
+
+    /// Additional variables written to the rc.conf.d file as `{name}_{key}="{value}"`, overriding
+    /// the defaults of the generated script (e.g. `("options", "--verbose")`)
+    ///
+    /// Only used when `rc_conf_d_dir` is specified.
+    pub rc_conf_vars: Vec<(String, String)>,
+
+    /// Where daemon(8) sends the stdout and stderr of the service
+    pub log_target: RcdLogTarget,
+}
+
+impl Default for RcdInstallConfig {
+    fn default() -> Self {
+        Self {
+            require: vec![String::from("LOGIN"), String::from("FILESYSTEMS")],
+            before: Vec::new(),
+            keywords: vec![RcdKeyword::Shutdown],
+            rc_conf_d_dir: None,
+            rc_conf_vars: Vec::new(),
+            log_target: RcdLogTarget::default(),
+        }
+    }
+}
+
+/// Keyword used by rcorder(8) to select which rc.d scripts to run
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RcdKeyword {
+    /// Script is run when the system shuts down, stopping the service
+    Shutdown,
+
+    /// Script is skipped when the system is running within a jail
+    NoJail,
+
+    /// Script is skipped during system startup
+    NoStart,
+
+    /// Script is run only on the first boot of the system
+    FirstBoot,
+}
+
+impl fmt::Display for RcdKeyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Shutdown => write!(f, "shutdown"),
+            Self::NoJail => write!(f, "nojail"),
+            Self::NoStart => write!(f, "nostart"),
+            Self::FirstBoot => write!(f, "firstboot"),
+        }
+    }
+}
+
+/// Destination for the output of rc.d services as captured by daemon(8)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RcdLogTarget {
+    /// Send output to syslog using the given tag, defaulting to the name of the service
+    Syslog(Option<String>),
+
+    /// Append output to the given file
+    File(PathBuf),
+}
+
+impl Default for RcdLogTarget {
+    fn default() -> Self {
+        Self::Syslog(None)
+    }
+}
 
 /// Implementation of [`ServiceManager`] for FreeBSD's [rc.d](https://en.wikipedia.org/wiki/Init#Research_Unix-style/BSD-style)
+///
+/// The generated scripts rely on FreeBSD's `rc.subr` and `daemon(8)`, so installing on NetBSD or
+/// OpenBSD requires the script to be provided through [`ServiceInstallCtx::contents`].
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct RcdServiceManager {
     /// Configuration settings tied to rc.d services
     pub config: RcdConfig,
+
+    /// If specified, services are managed within the jail with this name or id rather than the
+    /// host, writing scripts beneath the jail's root path and running commands via `jexec`
+    pub jail: Option<String>,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
 }
 
 impl RcdServiceManager {
@@ -33,13 +175,42 @@ impl RcdServiceManager {
 
     /// Update manager to use the specified config
     pub fn with_config(self, config: RcdConfig) -> Self {
-        Self { config }
+        Self { config, ..self }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Update manager to work with services within the specified jail
+    pub fn in_jail(self, jail: impl Into<String>) -> Self {
+        Self {
+            jail: Some(jail.into()),
+            ..self
+        }
+    }
+
+    /// Returns the path of `path` as seen from the host, which is beneath the jail's root path
+    /// when working with a jail
+    fn host_path(&self, path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let path = path.as_ref();
+        match self.jail.as_deref() {
+            Some(jail) => {
+                let root = jail_root_path(&*self.runner, jail)?;
+                Ok(root.join(path.strip_prefix("/").unwrap_or(path)))
+            }
+            None => Ok(path.to_path_buf()),
+        }
     }
 }
 
 impl ServiceManager for RcdServiceManager {
     fn available(&self) -> io::Result<bool> {
-        match std::fs::metadata(service_dir_path()) {
+        match std::fs::metadata(self.host_path(&self.config.script_dir_path)?) {
             Ok(_) => Ok(true),
             Err(x) if x.kind() == io::ErrorKind::NotFound => Ok(false),
             Err(x) => Err(x),
@@ -47,44 +218,169 @@ impl ServiceManager for RcdServiceManager {
     }
 
     fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        utils::validate_environment(&ctx, false)?;
+        utils::require_utf8(&ctx, "rc.d")?;
+        utils::capabilities_unsupported(&ctx, "rc.d")?;
+        utils::backup_unsupported(&ctx, "rc.d")?;
+        utils::check_program(&ctx)?;
+
+        // The variables of ${name}_env are separated by whitespace, which cannot be escaped
+        for (var, val) in ctx.environment.iter().flatten() {
+            if val.to_string_lossy().contains(char::is_whitespace) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "rc.d does not support whitespace in environment variable {}",
+                        var.to_string_lossy()
+                    ),
+                ));
+            }
+        }
+
+        if ctx.kind == crate::ServiceKind::Oneshot {
+            return Err(utils::oneshot_unsupported("rc.d"));
+        }
+
+        // The rc.subr of NetBSD and OpenBSD differs from FreeBSD's and neither has the daemon(8)
+        // that supervises the program
+        if cfg!(any(target_os = "netbsd", target_os = "openbsd")) && ctx.contents.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Generated rc.d scripts are not supported on NetBSD or OpenBSD, provide the script as contents",
+            ));
+        }
+
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "rc.d")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Rcd, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+
         let service = ctx.label.to_script_name();
-        let script = match ctx.contents {
-            Some(contents) => contents,
-            _ => make_script(&service, &service, ctx.program.as_os_str(), ctx.args),
+        let script = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_script(&self.config.install, &service, &service, &ctx),
+        };
+
+        // Files staged beneath an install root are placed there instead of within the jail
+        let target_path = |path: &Path| match ctx.install_root {
+            Some(_) => Ok(utils::staged_path(&ctx, path)),
+            None => self.host_path(path),
         };
 
-        utils::write_file(
-            &rc_d_script_path(&service),
+        let script_dir_path = target_path(&self.config.script_dir_path)?;
+        std::fs::create_dir_all(&script_dir_path)?;
+        utils::write_definition(
+            &ctx,
+            &script_dir_path.join(&service),
             script.as_bytes(),
             SCRIPT_FILE_PERMISSIONS,
         )?;
 
-        if ctx.autostart {
-            rc_d_script("enable", &service, true)?;
+        match &self.config.install.rc_conf_d_dir {
+            Some(dir_path) => {
+                let dir_path = target_path(dir_path)?;
+                std::fs::create_dir_all(&dir_path)?;
+                let rc_conf = make_rc_conf(
+                    &rc_name(&service),
+                    ctx.autostart,
+                    &self.config.install.rc_conf_vars,
+                );
+                utils::write_file(
+                    &dir_path.join(rc_name(&service)),
+                    rc_conf.as_bytes(),
+                    RC_CONF_FILE_PERMISSIONS,
+                )?;
+            }
+            None if ctx.autostart && ctx.install_root.is_none() => {
+                rc_d_script(
+                    &*self.runner,
+                    self.config.service(),
+                    self.jail.as_deref(),
+                    "enable",
+                    &service,
+                    true,
+                )?;
+            }
+            None => {}
         }
 
         Ok(())
     }
 
     fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "rc.d")?;
+
         let service = ctx.label.to_script_name();
 
-        // Remove the service from rc.conf
-        rc_d_script("delete", &service, true)?;
+        match &self.config.install.rc_conf_d_dir {
+            // Remove the service's rc.conf.d file
+            Some(dir_path) => {
+                let rc_conf_path = self.host_path(dir_path)?.join(rc_name(&service));
+                match std::fs::remove_file(rc_conf_path) {
+                    Ok(()) => {}
+                    Err(x) if x.kind() == io::ErrorKind::NotFound => {}
+                    Err(x) => return Err(x),
+                }
+            }
+
+            // Remove the service from rc.conf
+            None => {
+                rc_d_script(
+                    &*self.runner,
+                    self.config.service(),
+                    self.jail.as_deref(),
+                    "delete",
+                    &service,
+                    true,
+                )?;
+            }
+        }
 
         // Delete the actual service file
-        std::fs::remove_file(rc_d_script_path(&service))
+        std::fs::remove_file(self.host_path(self.config.script_dir_path.join(&service))?)?;
+
+        let mut paths = vec![self.host_path(format!("/var/run/{}.pid", rc_name(&service)))?];
+        if let RcdLogTarget::File(path) = &self.config.install.log_target {
+            paths.push(self.host_path(path)?);
+        }
+        utils::purge(&ctx, paths)?;
+        manifest::remove(ServiceManagerKind::Rcd, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Rcd, self.level(), label)
     }
 
     fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
         let service = ctx.label.to_script_name();
-        rc_d_script("start", &service, true)?;
-        Ok(())
+        rc_d_script(
+            &*self.runner,
+            self.config.service(),
+            self.jail.as_deref(),
+            "start",
+            &service,
+            true,
+        )?;
+        utils::wait_until_active(self, &ctx)
     }
 
     fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
         let service = ctx.label.to_script_name();
-        rc_d_script("stop", &service, true)?;
+        rc_d_script(
+            &*self.runner,
+            self.config.service(),
+            self.jail.as_deref(),
+            "stop",
+            &service,
+            true,
+        )?;
         Ok(())
     }
 
@@ -102,43 +398,141 @@ impl ServiceManager for RcdServiceManager {
         }
     }
 
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let service = ctx.label.to_script_name();
+        match &self.config.install.log_target {
+            RcdLogTarget::File(path) => {
+                let text = std::fs::read_to_string(self.host_path(path)?)?;
+                Ok(utils::tail(&text, ctx.lines))
+            }
+            RcdLogTarget::Syslog(tag) => {
+                let tag = tag.clone().unwrap_or_else(|| rc_name(&service));
+                let text = std::fs::read_to_string(self.host_path(SYSLOG_FILE)?)?;
+                let text = filter_syslog(&text, &tag);
+                Ok(utils::tail(&text, ctx.lines))
+            }
+        }
+    }
+
     fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
         let service = ctx.label.to_script_name();
-        let status = rc_d_script("status", &service, false)?;
-        match status.code() {
-            Some(0) => Ok(crate::ServiceStatus::Running),
-            Some(3) => Ok(crate::ServiceStatus::Stopped(None)),
-            Some(1) => Ok(crate::ServiceStatus::NotInstalled),
+        let (status, output) = rc_d_script_output(
+            &*self.runner,
+            self.config.service(),
+            self.jail.as_deref(),
+            "status",
+            &service,
+        )?;
+        let status = match status.code() {
+            Some(0) => crate::ServiceStatus::Running,
+            Some(3) => crate::ServiceStatus::Stopped(None),
+            Some(1) => crate::ServiceStatus::NotInstalled,
             _ => {
                 let code = status.code().unwrap_or(-1);
                 let msg = format!("Failed to get status of {service}, exit code: {code}");
-                Err(io::Error::new(io::ErrorKind::Other, msg))
+                return Err(io::Error::new(io::ErrorKind::Other, msg));
             }
-        }
+        };
+
+        let pid = match status {
+            crate::ServiceStatus::Running => parse_pid(&output),
+            _ => None,
+        };
+
+        Ok(crate::ServiceStatusDetails {
+            status,
+            pid,
+            transition: None,
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        utils::kill_main_process(&*self.runner, self, &ctx.label, ctx.signal)
     }
 }
 
+/// Name of the service as used within rc.subr variables, which cannot contain dashes
 #[inline]
-fn rc_d_script_path(name: &str) -> PathBuf {
-    service_dir_path().join(name)
+fn rc_name(service: &str) -> String {
+    service.replace('-', "_")
 }
 
 #[inline]
-fn service_dir_path() -> PathBuf {
-    PathBuf::from("/usr/local/etc/rc.d")
+fn default_script_dir_path() -> PathBuf {
+    cfg_if! {
+        if #[cfg(any(target_os = "netbsd", target_os = "openbsd"))] {
+            // NetBSD and OpenBSD keep packaged rc.d scripts alongside those of the base system
+            PathBuf::from(RcdConfig::BASE_SCRIPT_DIR)
+        } else {
+            PathBuf::from(RcdConfig::LOCAL_SCRIPT_DIR)
+        }
+    }
+}
+
+/// Looks up the root path of a jail on the host using `jls`
+fn jail_root_path(runner: &dyn CommandRunner, jail: &str) -> io::Result<PathBuf> {
+    let mut command = Command::new(JLS);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg("-j")
+        .arg(jail)
+        .arg("path");
+
+    let output = wrap_output(runner.run(&mut command, None)?)?;
+
+    let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if path.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Unable to locate root path of jail {jail}"),
+        ));
+    }
+
+    Ok(PathBuf::from(path))
 }
 
-fn rc_d_script(cmd: &str, service: &str, wrap: bool) -> io::Result<ExitStatus> {
+fn service_command(program: &OsStr, jail: Option<&str>) -> Command {
+    // When working with a jail, we run the service command within the jail itself
+    match jail {
+        Some(jail) => {
+            let mut command = Command::new(JEXEC);
+            command.arg(jail).arg(program);
+            command
+        }
+        None => Command::new(program),
+    }
+}
+
+fn rc_d_script(
+    runner: &dyn CommandRunner,
+    program: &OsStr,
+    jail: Option<&str>,
+    cmd: &str,
+    service: &str,
+    wrap: bool,
+) -> io::Result<ExitStatus> {
+    let mut command = service_command(program, jail);
+
     // NOTE: We MUST mark stdout/stderr as null, otherwise this hangs. Attempting to use output()
     //       does not work. The alternative is to spawn threads to read the stdout and stderr,
     //       but that seems overkill for the purpose of displaying an error message.
-    let status = Command::new(SERVICE)
+    command
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::null())
         .arg(service)
-        .arg(cmd)
-        .status()?;
+        .arg(cmd);
+
+    let status = runner.run(&mut command, None)?.status;
     if wrap {
         if status.success() {
             Ok(status)
@@ -151,21 +545,172 @@ fn rc_d_script(cmd: &str, service: &str, wrap: bool) -> io::Result<ExitStatus> {
     }
 }
 
-fn make_script(description: &str, provide: &str, program: &OsStr, args: Vec<OsString>) -> String {
-    let name = provide.replace('-', "_");
-    let program = program.to_string_lossy();
-    let args = args
-        .into_iter()
-        .map(|a| a.to_string_lossy().to_string())
-        .collect::<Vec<String>>()
-        .join(" ");
+/// Runs the rc.d script, capturing its combined stdout and stderr
+///
+/// Processes spawned by the script such as daemon(8) inherit stdout and stderr and keep them open,
+/// so this relies on [`CommandRunner::run_until_exit`] to return once the script itself exits.
+fn rc_d_script_output(
+    runner: &dyn CommandRunner,
+    program: &OsStr,
+    jail: Option<&str>,
+    cmd: &str,
+    service: &str,
+) -> io::Result<(ExitStatus, String)> {
+    let mut command = service_command(program, jail);
+    command.stdin(Stdio::null()).arg(service).arg(cmd);
+
+    let output = runner.run_until_exit(&mut command)?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+
+    Ok((output.status, text))
+}
+
+/// Parses the pid from the output of `service {name} status`, which is in the form of
+/// `{name} is running as pid {pid}.`
+fn parse_pid(output: &str) -> Option<u32> {
+    let (_, rest) = output.split_once("as pid ")?;
+    let pid = rest
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect::<String>();
+    pid.parse().ok()
+}
+
+/// Filters syslog output down to the lines logged with `tag`, which appear in the form of
+/// `{timestamp} {host} {tag}[{pid}]: {message}`
+fn filter_syslog(text: &str, tag: &str) -> String {
+    let with_pid = format!(" {tag}[");
+    let without_pid = format!(" {tag}:");
+    text.lines()
+        .filter(|line| line.contains(&with_pid) || line.contains(&without_pid))
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+fn make_rc_conf(name: &str, enable: bool, vars: &[(String, String)]) -> String {
+    let mut rc_conf = String::new();
+    if enable {
+        let _ = writeln!(rc_conf, "{name}_enable=\"YES\"");
+    }
+    for (key, value) in vars {
+        let _ = writeln!(rc_conf, "{name}_{key}=\"{value}\"");
+    }
+    rc_conf
+}
+
+fn make_script(
+    config: &RcdInstallConfig,
+    description: &str,
+    provide: &str,
+    ctx: &ServiceInstallCtx,
+) -> String {
+    let name = rc_name(provide);
+    let description =
+        utils::escape_shell_double_quoted(ctx.description.as_deref().unwrap_or(description));
+    let program = ctx.program.to_string_lossy();
+    // The options are evaluated by the shell when starting the service
+    let args = utils::escape_shell_double_quoted(&utils::shell_join(ctx.args_iter()));
+
+    // Defaults that can be overridden within rc.conf
+    let mut defaults = format!(": ${{{name}_options=\"{args}\"}}");
+    if let Some(username) = &ctx.username {
+        let username = utils::escape_shell_double_quoted(username);
+        let _ = write!(defaults, "\n: ${{{name}_user=\"{username}\"}}");
+    }
+    // rc.subr runs the command with nice(1) at this level, which daemon(8) passes to its child
+    if let Some(nice) = ctx.priority.and_then(|x| x.nice) {
+        let _ = write!(defaults, "\n: ${{{name}_nice=\"{nice}\"}}");
+    }
+    if let Some(env_vars) = &ctx.environment {
+        let env = env_vars
+            .iter()
+            .map(|(var, val)| {
+                format!(
+                    "{}={}",
+                    var.to_string_lossy(),
+                    utils::escape_shell_double_quoted(&val.to_string_lossy())
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        let _ = write!(defaults, "\n: ${{{name}_env=\"{env}\"}}");
+    }
+
+    // When a user is specified, daemon(8) drops privileges for the child process after writing
+    // the pidfile as root. The user is then cleared so that rc.subr does not also attempt to run
+    // daemon(8) itself via su, which would prevent it from writing the pidfile.
+    let (user_flag, clear_user) = if ctx.username.is_some() {
+        (
+            format!(" -u ${{{name}_user}}"),
+            format!("\n{name}_user=\"\""),
+        )
+    } else {
+        (String::new(), String::new())
+    };
+
+    // daemon(8) supervises the child process when asked to restart it, but it cannot distinguish
+    // between successful and failed exits, so both policies restart the child regardless
+    let restart_flag = match ctx.restart_policy {
+        None | Some(RestartPolicy::Never) => String::new(),
+        Some(RestartPolicy::Always { delay_secs: None })
+        | Some(RestartPolicy::OnFailure { delay_secs: None }) => String::from(" -r"),
+        Some(RestartPolicy::Always {
+            delay_secs: Some(delay),
+        })
+        | Some(RestartPolicy::OnFailure {
+            delay_secs: Some(delay),
+        }) => format!(" -R {delay}"),
+    };
+
+    // rcorder(8) headers describing the dependencies and keywords of the script
+    let mut headers = format!("# PROVIDE: {provide}");
+    if !config.require.is_empty() {
+        let _ = write!(headers, "\n# REQUIRE: {}", config.require.join(" "));
+    }
+    if !config.before.is_empty() {
+        let _ = write!(headers, "\n# BEFORE: {}", config.before.join(" "));
+    }
+    if !config.keywords.is_empty() {
+        let keywords = config
+            .keywords
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join(" ");
+        let _ = write!(headers, "\n# KEYWORD: {keywords}");
+    }
+
+    let sig_stop = match ctx.stop_signal {
+        Some(signal) => format!("\nsig_stop=\"{}\"", signal.name()),
+        None => String::new(),
+    };
+
+    // rc.subr signals the pidfile's process to stop the service unless given a command to run
+    let stop_cmd = if ctx.stop_command.is_some() {
+        let cmd = utils::shell_join(ctx.stop_cmd_iter());
+        format!("\nstop_cmd=\"{name}_stop\"\n\n{name}_stop()\n{{\n    {cmd}\n}}")
+    } else {
+        String::new()
+    };
+
+    // Sending output to syslog from the context takes precedence over the config
+    let log_flags = match (&ctx.log_to_syslog, &config.log_target) {
+        (Some(tag), _) | (None, RcdLogTarget::Syslog(Some(tag))) => format!("-S -T {tag}"),
+        (None, RcdLogTarget::Syslog(None)) => String::from("-S -T ${name}"),
+        // rc.subr evals the command arguments, so the path is quoted for that before being escaped
+        // to sit within the double quotes of `command_args`
+        (None, RcdLogTarget::File(path)) => format!(
+            "-o {}",
+            utils::escape_shell_double_quoted(&utils::shell_quote(&path.to_string_lossy()))
+        ),
+    };
+
     format!(
         r#"
 #!/bin/sh
 #
-# PROVIDE: {provide}
-# REQUIRE: LOGIN FILESYSTEMS
-# KEYWORD: shutdown
+{headers}
 
 . /etc/rc.subr
 
@@ -175,12 +720,12 @@ rcvar="{name}_enable"
 
 load_rc_config ${{name}}
 
-: ${{{name}_options="{args}"}}
+{defaults}
 
 pidfile="/var/run/{name}.pid"
 procname="{program}"
 command="/usr/sbin/daemon"
-command_args="-c -S -T ${{name}} -p ${{pidfile}} ${{procname}} ${{{name}_options}}"
+command_args="-c {log_flags}{restart_flag}{user_flag} -p ${{pidfile}} ${{procname}} ${{{name}_options}}"{clear_user}{sig_stop}{stop_cmd}
 
 run_rc_command "$1"
     "#
@@ -188,3 +733,272 @@ run_rc_command "$1"
     .trim()
     .to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::ffi::OsString;
+
+    fn install_ctx() -> ServiceInstallCtx {
+        ServiceInstallCtx {
+            label: "org.example.my_service".parse().unwrap(),
+            program: PathBuf::from("/usr/local/bin/my_service"),
+            args: vec![OsString::from("--arg"), OsString::from("value")],
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: None,
+            autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        }
+    }
+
+    #[test]
+    fn test_make_script_with_log_file() {
+        let config = RcdInstallConfig {
+            log_target: RcdLogTarget::File(PathBuf::from("/var/log/my_service.log")),
+            ..Default::default()
+        };
+        let script = make_script(
+            &config,
+            "example-my_service",
+            "example-my_service",
+            &install_ctx(),
+        );
+        assert!(script.contains(r#"command_args="-c -o /var/log/my_service.log -p"#));
+
+        let config = RcdInstallConfig {
+            log_target: RcdLogTarget::File(PathBuf::from("/var/log/my $service.log")),
+            ..Default::default()
+        };
+        let script = make_script(
+            &config,
+            "example-my_service",
+            "example-my_service",
+            &install_ctx(),
+        );
+        assert!(script.contains(r#"command_args="-c -o '/var/log/my \$service.log' -p"#));
+    }
+
+    #[test]
+    fn test_make_script_with_log_to_syslog() {
+        let config = RcdInstallConfig {
+            log_target: RcdLogTarget::File(PathBuf::from("/var/log/my_service.log")),
+            ..Default::default()
+        };
+        let ctx = ServiceInstallCtx {
+            log_to_syslog: Some(String::from("my_service")),
+            ..install_ctx()
+        };
+        let script = make_script(&config, "example-my_service", "example-my_service", &ctx);
+        assert!(script.contains(r#"command_args="-c -S -T my_service -p"#));
+    }
+
+    #[test]
+    fn test_make_script_with_ordering() {
+        let config = RcdInstallConfig {
+            require: vec![String::from("NETWORKING")],
+            before: vec![String::from("nginx")],
+            keywords: vec![RcdKeyword::NoJail, RcdKeyword::Shutdown],
+            ..Default::default()
+        };
+        let script = make_script(
+            &config,
+            "example-my_service",
+            "example-my_service",
+            &install_ctx(),
+        );
+        assert!(script.contains(indoc! {"
+            # PROVIDE: example-my_service
+            # REQUIRE: NETWORKING
+            # BEFORE: nginx
+            # KEYWORD: nojail shutdown
+        "}));
+    }
+
+    #[test]
+    fn test_parse_pid() {
+        assert_eq!(
+            parse_pid("example_my_service is running as pid 1234.\n"),
+            Some(1234)
+        );
+        assert_eq!(parse_pid("example_my_service is not running.\n"), None);
+    }
+
+    #[test]
+    fn test_filter_syslog() {
+        let text = indoc! {"
+            Jan  1 00:00:00 host example_my_service[123]: hello
+            Jan  1 00:00:01 host other_service[456]: ignored
+            Jan  1 00:00:02 host example_my_service[123]: world
+        "};
+        assert_eq!(
+            filter_syslog(text, "example_my_service"),
+            "Jan  1 00:00:00 host example_my_service[123]: hello\n\
+             Jan  1 00:00:02 host example_my_service[123]: world"
+        );
+    }
+
+    #[test]
+    fn test_make_rc_conf() {
+        let rc_conf = make_rc_conf(
+            "example_my_service",
+            true,
+            &[("options".to_string(), "--verbose".to_string())],
+        );
+        assert_eq!(
+            rc_conf,
+            indoc! {r#"
+                example_my_service_enable="YES"
+                example_my_service_options="--verbose"
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_make_script_with_defaults() {
+        let script = make_script(
+            &RcdInstallConfig::default(),
+            "example-my_service",
+            "example-my_service",
+            &install_ctx(),
+        );
+        assert_eq!(
+            script,
+            indoc! {r#"
+                #!/bin/sh
+                #
+                # PROVIDE: example-my_service
+                # REQUIRE: LOGIN FILESYSTEMS
+                # KEYWORD: shutdown
+
+                . /etc/rc.subr
+
+                name="example_my_service"
+                desc="example-my_service"
+                rcvar="example_my_service_enable"
+
+                load_rc_config ${name}
+
+                : ${example_my_service_options="--arg value"}
+
+                pidfile="/var/run/example_my_service.pid"
+                procname="/usr/local/bin/my_service"
+                command="/usr/sbin/daemon"
+                command_args="-c -S -T ${name} -p ${pidfile} ${procname} ${example_my_service_options}"
+
+                run_rc_command "$1""#}
+        );
+    }
+
+    #[test]
+    fn test_make_script_with_stop_command() {
+        let ctx = ServiceInstallCtx {
+            stop_command: Some((
+                PathBuf::from("/usr/local/bin/my_service_ctl"),
+                vec![OsString::from("stop")],
+            )),
+            ..install_ctx()
+        };
+        let script = make_script(
+            &RcdInstallConfig::default(),
+            "example-my_service",
+            "example-my_service",
+            &ctx,
+        );
+        assert!(script.contains(indoc! {r#"
+            stop_cmd="example_my_service_stop"
+
+            example_my_service_stop()
+            {
+                /usr/local/bin/my_service_ctl stop
+            }
+        "#}));
+    }
+
+    #[test]
+    fn test_make_script_with_username_and_environment() {
+        let ctx = ServiceInstallCtx {
+            username: Some("www".to_string()),
+            environment: Some(vec![
+                (OsString::from("ENV1"), OsString::from("val1")),
+                (OsString::from("ENV2"), OsString::from("val2")),
+            ]),
+            ..install_ctx()
+        };
+
+        let script = make_script(
+            &RcdInstallConfig::default(),
+            "example-my_service",
+            "example-my_service",
+            &ctx,
+        );
+        assert!(script.contains(r#": ${example_my_service_user="www"}"#));
+        assert!(script.contains(r#": ${example_my_service_env="ENV1=val1 ENV2=val2"}"#));
+        assert!(script.contains(
+            r#"command_args="-c -S -T ${name} -u ${example_my_service_user} -p ${pidfile} ${procname} ${example_my_service_options}""#
+        ));
+        assert!(script.contains("\nexample_my_service_user=\"\"\n"));
+    }
+
+    #[test]
+    fn test_make_script_with_restart_policy() {
+        let ctx = ServiceInstallCtx {
+            restart_policy: Some(RestartPolicy::OnFailure { delay_secs: None }),
+            ..install_ctx()
+        };
+        let script = make_script(
+            &RcdInstallConfig::default(),
+            "example-my_service",
+            "example-my_service",
+            &ctx,
+        );
+        assert!(script.contains(r#"command_args="-c -S -T ${name} -r -p"#));
+
+        let ctx = ServiceInstallCtx {
+            restart_policy: Some(RestartPolicy::Always {
+                delay_secs: Some(10),
+            }),
+            ..install_ctx()
+        };
+        let script = make_script(
+            &RcdInstallConfig::default(),
+            "example-my_service",
+            "example-my_service",
+            &ctx,
+        );
+        assert!(script.contains(r#"command_args="-c -S -T ${name} -R 10 -p"#));
+
+        let ctx = ServiceInstallCtx {
+            restart_policy: Some(RestartPolicy::Never),
+            ..install_ctx()
+        };
+        let script = make_script(
+            &RcdInstallConfig::default(),
+            "example-my_service",
+            "example-my_service",
+            &ctx,
+        );
+        assert!(script.contains(r#"command_args="-c -S -T ${name} -p"#));
+    }
+}
@@ -0,0 +1,145 @@
+use super::{ServiceManager, ServiceManagerKind, TypedServiceManager};
+use once_cell::sync::OnceCell;
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, RwLock},
+};
+
+/// Function producing a new instance of a registered service manager
+pub type ServiceManagerFactory = Arc<dyn Fn() -> Box<dyn ServiceManager> + Send + Sync>;
+
+static REGISTRY: OnceCell<RwLock<HashMap<String, ServiceManagerFactory>>> = OnceCell::new();
+
+fn registry() -> &'static RwLock<HashMap<String, ServiceManagerFactory>> {
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Process-wide registry of service managers implemented outside of this crate, which lets them
+/// be selected by name alongside the built-in kinds
+///
+/// ```
+/// use service_manager::*;
+///
+/// ServiceManagerRegistry::register("in-house", || {
+///     Box::new(SystemdServiceManager::system()) as Box<dyn ServiceManager>
+/// });
+///
+/// assert!(ServiceManagerRegistry::resolve("in-house").is_some());
+/// assert!(ServiceManagerRegistry::resolve("systemd").is_some());
+/// assert!(ServiceManagerRegistry::resolve("unknown").is_none());
+/// ```
+pub struct ServiceManagerRegistry;
+
+impl ServiceManagerRegistry {
+    /// Registers a factory of service managers under `name`, replacing and returning true if a
+    /// factory was already registered under the same name
+    ///
+    /// Registered names take precedence over the names of built-in kinds, so a built-in service
+    /// manager can be replaced by registering a factory under its name, e.g. `systemd`.
+    pub fn register<F>(name: impl Into<String>, factory: F) -> bool
+    where
+        F: Fn() -> Box<dyn ServiceManager> + Send + Sync + 'static,
+    {
+        registry()
+            .write()
+            .unwrap_or_else(|x| x.into_inner())
+            .insert(name.into(), Arc::new(factory))
+            .is_some()
+    }
+
+    /// Removes the factory registered under `name`, returning true if one was registered
+    pub fn unregister(name: &str) -> bool {
+        registry()
+            .write()
+            .unwrap_or_else(|x| x.into_inner())
+            .remove(name)
+            .is_some()
+    }
+
+    /// Returns true if a factory is registered under `name`
+    pub fn is_registered(name: &str) -> bool {
+        registry()
+            .read()
+            .unwrap_or_else(|x| x.into_inner())
+            .contains_key(name)
+    }
+
+    /// Returns the names of all registered factories in sorted order, excluding built-in kinds
+    pub fn names() -> Vec<String> {
+        let mut names: Vec<String> = registry()
+            .read()
+            .unwrap_or_else(|x| x.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Creates a new service manager using the factory registered under `name`, falling back to
+    /// the built-in kind with that name such as `systemd` or `launchd`
+    pub fn resolve(name: &str) -> Option<Box<dyn ServiceManager>> {
+        // Clone the factory so that the lock is not held while it runs, as it may use the registry
+        let factory = registry()
+            .read()
+            .unwrap_or_else(|x| x.into_inner())
+            .get(name)
+            .cloned();
+
+        match factory {
+            Some(factory) => Some(factory()),
+            None => ServiceManagerKind::from_name(name)
+                .map(|kind| TypedServiceManager::target(kind).into_box()),
+        }
+    }
+
+    /// Creates a new service manager by name using [`ServiceManagerRegistry::resolve`], falling
+    /// back to the native service manager for the current operating system if no name provided
+    pub fn resolve_or_native(name: Option<&str>) -> io::Result<Box<dyn ServiceManager>> {
+        match name {
+            Some(name) => Self::resolve(name).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No service manager is registered as {name}"),
+                )
+            }),
+            None => Ok(TypedServiceManager::native()?.into_box()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ServiceLevel, SupervisordServiceManager};
+
+    #[test]
+    fn test_register_and_resolve() {
+        let name = "test-register-and-resolve";
+        assert!(!ServiceManagerRegistry::register(name, || {
+            Box::new(SupervisordServiceManager::system())
+        }));
+        assert!(ServiceManagerRegistry::is_registered(name));
+        assert!(ServiceManagerRegistry::names().contains(&name.to_string()));
+
+        let manager = ServiceManagerRegistry::resolve(name).unwrap();
+        assert_eq!(manager.level(), ServiceLevel::System);
+
+        assert!(ServiceManagerRegistry::unregister(name));
+        assert!(!ServiceManagerRegistry::is_registered(name));
+        assert!(ServiceManagerRegistry::resolve(name).is_none());
+    }
+
+    #[test]
+    fn test_resolve_built_in_kinds() {
+        assert!(ServiceManagerRegistry::resolve("systemd").is_some());
+        assert!(ServiceManagerRegistry::resolve("sc").is_some());
+        assert!(ServiceManagerRegistry::resolve("not-a-service-manager").is_none());
+
+        let err = ServiceManagerRegistry::resolve_or_native(Some("not-a-service-manager"))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,159 @@
+use super::{ServiceLabel, ServiceStatus, ServiceStatusDetails};
+use std::io;
+
+/// Number of recent log lines collected by [`ServiceManager::diagnose`](crate::ServiceManager::diagnose)
+/// when none is specified
+pub const DEFAULT_DIAGNOSE_LOG_LINES: usize = 20;
+
+/// Context provided to the diagnose function of [`ServiceManager`](crate::ServiceManager)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceDiagnoseCtx {
+    /// Label associated with the service
+    ///
+    /// E.g. `rocks.distant.manager`
+    pub label: ServiceLabel,
+
+    /// Number of recent log lines to collect, defaulting to [`DEFAULT_DIAGNOSE_LOG_LINES`]
+    pub lines: Option<usize>,
+}
+
+/// Everything a service manager knows about why a service is not running, such as after it
+/// failed to start, gathered to be shown to end users
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct StartFailureReport {
+    /// Fully-qualified label of the service
+    pub label: String,
+
+    /// Status of the service at the time of the report
+    pub status: ServiceStatusDetails,
+
+    /// Exit code of the service's last process, if known
+    pub exit_code: Option<i32>,
+
+    /// Most recent lines logged by the service, if the service manager can retrieve them
+    pub logs: Option<String>,
+}
+
+/// State of a service within a [`ServiceReport`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+pub enum ServiceReportState {
+    NotInstalled,
+    Running,
+    Paused,
+    Stopped,
+
+    /// The operation on the service failed, with the reason given as the report's message
+    Error,
+}
+
+/// Outcome of an operation on a service meant for automation, such as scripts parsing the JSON
+/// output of a command-line tool
+///
+/// With the `serde` feature, this serializes to an object with the fields `kind`, `label`,
+/// `state`, `pid`, `exit_code`, and `message`, where unknown values are `null`. The names of these
+/// fields and of the states are kept stable across versions of this crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ServiceReport {
+    /// Name of the service manager used, e.g. `systemd`
+    pub kind: Option<String>,
+
+    /// Fully-qualified label of the service
+    pub label: String,
+
+    /// State of the service, or [`ServiceReportState::Error`] if the operation failed
+    pub state: ServiceReportState,
+
+    /// Id of the service's main process, if running and known
+    pub pid: Option<u32>,
+
+    /// Exit code of the service's last process, if stopped and known
+    pub exit_code: Option<i32>,
+
+    /// Reason the service stopped or the operation failed
+    pub message: Option<String>,
+}
+
+impl ServiceReport {
+    /// Creates a report from the status of a service
+    pub fn from_status(
+        kind: Option<String>,
+        label: &ServiceLabel,
+        details: ServiceStatusDetails,
+    ) -> Self {
+        let (state, message) = match details.status {
+            ServiceStatus::NotInstalled => (ServiceReportState::NotInstalled, None),
+            ServiceStatus::Running => (ServiceReportState::Running, None),
+            ServiceStatus::Paused => (ServiceReportState::Paused, None),
+            ServiceStatus::Stopped(reason) => (ServiceReportState::Stopped, reason),
+        };
+
+        Self {
+            kind,
+            label: label.to_qualified_name(),
+            state,
+            pid: details.pid,
+            exit_code: None,
+            message,
+        }
+    }
+
+    /// Creates a report from an error encountered while operating on a service
+    pub fn from_error(kind: Option<String>, label: &ServiceLabel, error: &io::Error) -> Self {
+        Self {
+            kind,
+            label: label.to_qualified_name(),
+            state: ServiceReportState::Error,
+            pid: None,
+            exit_code: None,
+            message: Some(error.to_string()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_schema() {
+        let label: ServiceLabel = "com.example.my-service".parse().unwrap();
+        let report = ServiceReport::from_status(
+            Some(String::from("systemd")),
+            &label,
+            ServiceStatusDetails {
+                status: ServiceStatus::Running,
+                pid: Some(1234),
+                transition: None,
+            },
+        );
+
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"kind":"systemd","label":"com.example.my-service","state":"running","pid":1234,"exit_code":null,"message":null}"#
+        );
+
+        let report = ServiceReport::from_error(
+            None,
+            &label,
+            &io::Error::new(io::ErrorKind::NotFound, "Unit not found"),
+        );
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            r#"{"kind":null,"label":"com.example.my-service","state":"error","pid":null,"exit_code":null,"message":"Unit not found"}"#
+        );
+
+        let report = ServiceReport::from_status(
+            None,
+            &label,
+            ServiceStatus::Stopped(Some(String::from("Exited too quickly"))).into(),
+        );
+        assert_eq!(
+            serde_json::to_value(&report).unwrap()["state"],
+            serde_json::json!("stopped")
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use super::{
+    AvailabilityDetails, DriftReport, InstalledArtifact, ServiceDiagnoseCtx, ServiceExportCtx,
+    ServiceHealth, ServiceHealthCtx, ServiceInstallCtx, ServiceLabel, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServicePauseCtx, ServiceResumeCtx, ServiceSignalCtx, ServiceSnapshot,
+    ServiceStartCtx, ServiceStatus, ServiceStatusCtx, ServiceStatusDetails, ServiceStopCtx,
+    ServiceUninstallCtx, StartFailureReport,
+};
+use std::{io, thread, time::Duration};
+
+/// How a [`RetryingServiceManager`] retries operations that fail
+#[derive(Copy, Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of times an operation is retried after its first attempt fails
+    pub max_retries: u32,
+
+    /// Time to wait before the first retry
+    pub initial_delay: Duration,
+
+    /// Longest time to wait between retries
+    pub max_delay: Duration,
+
+    /// Factor by which the time to wait grows after each retry
+    pub multiplier: u32,
+
+    /// Determines whether an error may go away by trying again, defaulting to
+    /// [`RetryPolicy::is_transient`]
+    pub is_retryable: fn(&io::Error) -> bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            multiplier: 2,
+            is_retryable: Self::is_transient,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns true for errors that may go away by trying again, which are those of a command that
+    /// failed, timed out, or was interrupted, rather than those that will fail the same way again
+    /// such as a service that is not installed or a lack of permissions
+    pub fn is_transient(error: &io::Error) -> bool {
+        matches!(
+            error.kind(),
+            io::ErrorKind::Other
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::Interrupted
+                | io::ErrorKind::WouldBlock
+        )
+    }
+
+    /// Time to wait before retry number `retry`, starting from zero
+    pub fn delay(&self, retry: u32) -> Duration {
+        self.multiplier
+            .checked_pow(retry)
+            .and_then(|factor| self.initial_delay.checked_mul(factor))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay))
+    }
+}
+
+/// [`ServiceManager`] that retries the operations of the manager it wraps when they fail with an
+/// error that its [`RetryPolicy`] considers retryable
+///
+/// Each operation is retried as a whole, so it must be safe to repeat after failing partway.
+/// Discovering services with [`ServiceManager::uninstall_matching`] and checking their health are
+/// passed through without retrying.
+#[derive(Clone, Debug)]
+pub struct RetryingServiceManager<S> {
+    manager: S,
+    policy: RetryPolicy,
+}
+
+impl<S: ServiceManager> RetryingServiceManager<S> {
+    /// Wraps `manager`, retrying using the default [`RetryPolicy`]
+    pub fn new(manager: S) -> Self {
+        Self {
+            manager,
+            policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Update manager to retry operations using the specified policy
+    pub fn with_policy(self, policy: RetryPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    /// Returns a reference to the wrapped manager
+    pub fn get_ref(&self) -> &S {
+        &self.manager
+    }
+
+    /// Returns the wrapped manager
+    pub fn into_inner(self) -> S {
+        self.manager
+    }
+
+    fn retry<T>(&self, mut f: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+        let mut retry = 0;
+        loop {
+            match f() {
+                Err(x) if retry < self.policy.max_retries && (self.policy.is_retryable)(&x) => {
+                    thread::sleep(self.policy.delay(retry));
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<S: ServiceManager> ServiceManager for RetryingServiceManager<S> {
+    fn available(&self) -> io::Result<bool> {
+        self.retry(|| self.manager.available())
+    }
+
+    fn availability_details(&self) -> io::Result<AvailabilityDetails> {
+        self.retry(|| self.manager.availability_details())
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        self.retry(|| self.manager.install(ctx.clone()))
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        self.retry(|| self.manager.uninstall(ctx.clone()))
+    }
+
+    fn uninstall_matching(&self, prefix: &ServiceLabel) -> io::Result<Vec<ServiceLabel>> {
+        self.manager.uninstall_matching(prefix)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        self.retry(|| self.manager.start(ctx.clone()))
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        self.retry(|| self.manager.stop(ctx.clone()))
+    }
+
+    fn level(&self) -> ServiceLevel {
+        self.manager.level()
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        self.manager.set_level(level)
+    }
+
+    fn status(&self, ctx: ServiceStatusCtx) -> io::Result<ServiceStatus> {
+        self.retry(|| self.manager.status(ctx.clone()))
+    }
+
+    fn status_details(&self, ctx: ServiceStatusCtx) -> io::Result<ServiceStatusDetails> {
+        self.retry(|| self.manager.status_details(ctx.clone()))
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        self.retry(|| self.manager.logs(ctx.clone()))
+    }
+
+    fn diagnose(&self, ctx: ServiceDiagnoseCtx) -> io::Result<StartFailureReport> {
+        self.retry(|| self.manager.diagnose(ctx.clone()))
+    }
+
+    fn pause(&self, ctx: ServicePauseCtx) -> io::Result<()> {
+        self.retry(|| self.manager.pause(ctx.clone()))
+    }
+
+    fn resume(&self, ctx: ServiceResumeCtx) -> io::Result<()> {
+        self.retry(|| self.manager.resume(ctx.clone()))
+    }
+
+    fn is_up_to_date(&self, ctx: &ServiceInstallCtx) -> io::Result<DriftReport> {
+        self.retry(|| self.manager.is_up_to_date(ctx))
+    }
+
+    fn health(&self, ctx: ServiceHealthCtx) -> io::Result<ServiceHealth> {
+        self.manager.health(ctx)
+    }
+
+    fn signal(&self, ctx: ServiceSignalCtx) -> io::Result<()> {
+        self.retry(|| self.manager.signal(ctx.clone()))
+    }
+
+    fn export(&self, ctx: ServiceExportCtx) -> io::Result<ServiceSnapshot> {
+        self.retry(|| self.manager.export(ctx.clone()))
+    }
+
+    fn import(&self, snapshot: ServiceSnapshot) -> io::Result<()> {
+        self.retry(|| self.manager.import(snapshot.clone()))
+    }
+
+    fn rollback(&self, label: &ServiceLabel) -> io::Result<()> {
+        self.retry(|| self.manager.rollback(label))
+    }
+
+    fn installed_artifacts(&self, label: &ServiceLabel) -> io::Result<Vec<InstalledArtifact>> {
+        self.retry(|| self.manager.installed_artifacts(label))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            multiplier: 2,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(100));
+        assert_eq!(policy.delay(1), Duration::from_millis(200));
+        assert_eq!(policy.delay(2), Duration::from_millis(400));
+        assert_eq!(policy.delay(3), Duration::from_millis(500));
+        assert_eq!(policy.delay(100), Duration::from_millis(500));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn test_retries_transient_errors() {
+        use crate::{MockOperation, MockServiceManager};
+
+        let mock = MockServiceManager::new();
+        let manager = RetryingServiceManager::new(mock.clone()).with_policy(RetryPolicy {
+            max_retries: 2,
+            initial_delay: Duration::ZERO,
+            ..Default::default()
+        });
+        let label: ServiceLabel = "com.example.my-service".parse().unwrap();
+        let ctx = ServiceStopCtx {
+            label: label.clone(),
+        };
+
+        mock.fail_next(MockOperation::Stop, io::ErrorKind::Other, "busy");
+        mock.fail_next(MockOperation::Stop, io::ErrorKind::Other, "busy");
+        manager.stop(ctx.clone()).unwrap();
+        assert_eq!(mock.call_count(MockOperation::Stop, &label), 3);
+
+        for _ in 0..3 {
+            mock.fail_next(MockOperation::Stop, io::ErrorKind::Other, "busy");
+        }
+        manager.stop(ctx.clone()).unwrap_err();
+        assert_eq!(mock.call_count(MockOperation::Stop, &label), 6);
+
+        mock.fail_next(
+            MockOperation::Stop,
+            io::ErrorKind::PermissionDenied,
+            "denied",
+        );
+        manager.stop(ctx).unwrap_err();
+        assert_eq!(mock.call_count(MockOperation::Stop, &label), 7);
+    }
+}
@@ -0,0 +1,498 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, utils, CommandRunner, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner,
+};
+use std::{
+    fmt::Write as _,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+static SV: &str = "sv";
+
+// NOTE: runsv(8) requires the run, finish, and log/run scripts to be executable
+const SCRIPT_FILE_PERMISSIONS: u32 = 0o755;
+
+/// Configuration settings tied to runit services
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunitConfig {
+    pub install: RunitInstallConfig,
+
+    /// Directory where service directories are created, defaulting to `/etc/sv`
+    pub sv_dir_path: PathBuf,
+
+    /// Directory scanned by runsvdir(8) where services are linked to be supervised
+    ///
+    /// If not specified, `/var/service` is used when it exists, otherwise `/etc/service`.
+    pub service_dir_path: Option<PathBuf>,
+}
+
+impl Default for RunitConfig {
+    fn default() -> Self {
+        Self {
+            install: RunitInstallConfig::default(),
+            sv_dir_path: PathBuf::from("/etc/sv"),
+            service_dir_path: None,
+        }
+    }
+}
+
+/// Configuration settings used when installing runit services
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunitInstallConfig {
+    /// Whether to write a `log/run` script that captures the output of the service using
+    /// svlogd(8) within `/var/log/{service}`
+    pub log: bool,
+}
+
+impl Default for RunitInstallConfig {
+    fn default() -> Self {
+        Self { log: true }
+    }
+}
+
+/// Implementation of [`ServiceManager`] for [runit](http://smarden.org/runit/)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunitServiceManager {
+    /// Configuration settings tied to runit services
+    pub config: RunitConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl RunitServiceManager {
+    /// Creates a new manager instance working with system services
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: RunitConfig) -> Self {
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Returns the directory scanned by runsvdir(8) for supervised services
+    pub fn service_dir_path(&self) -> PathBuf {
+        match &self.config.service_dir_path {
+            Some(path) => path.clone(),
+            None if Path::new("/var/service").is_dir() => PathBuf::from("/var/service"),
+            None => PathBuf::from("/etc/service"),
+        }
+    }
+}
+
+impl ServiceManager for RunitServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        match which::which(SV) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        utils::validate_environment(&ctx, true)?;
+        utils::require_utf8(&ctx, "runit")?;
+        utils::capabilities_unsupported(&ctx, "runit")?;
+        utils::backup_unsupported(&ctx, "runit")?;
+        utils::check_program(&ctx)?;
+        if ctx.kind == crate::ServiceKind::Oneshot {
+            return Err(utils::oneshot_unsupported("runit"));
+        }
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "runit")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Runit, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+
+        let service = ctx.label.to_script_name();
+        let final_dir_path = self.config.sv_dir_path.join(&service);
+        let dir_path = utils::staged_path(&ctx, &final_dir_path);
+        std::fs::create_dir_all(&dir_path)?;
+
+        let script = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_run_script(&ctx),
+        };
+        utils::write_definition(
+            &ctx,
+            &dir_path.join("run"),
+            script.as_bytes(),
+            SCRIPT_FILE_PERMISSIONS,
+        )?;
+
+        let finish_path = dir_path.join("finish");
+        match make_finish_script(ctx.restart_policy) {
+            Some(script) => {
+                utils::write_file(&finish_path, script.as_bytes(), SCRIPT_FILE_PERMISSIONS)?
+            }
+            None if finish_path.exists() => std::fs::remove_file(&finish_path)?,
+            None => {}
+        }
+
+        if self.config.install.log {
+            let log_dir_path = dir_path.join("log");
+            std::fs::create_dir_all(&log_dir_path)?;
+            utils::write_file(
+                &log_dir_path.join("run"),
+                make_log_script(&service).as_bytes(),
+                SCRIPT_FILE_PERMISSIONS,
+            )?;
+        }
+
+        // runsv(8) brings a service up as soon as it is linked unless a down file is present, so
+        // services without autostart are still linked to be controllable but are left down
+        let down_path = dir_path.join("down");
+        if ctx.autostart {
+            if down_path.exists() {
+                std::fs::remove_file(&down_path)?;
+            }
+        } else {
+            utils::write_file(&down_path, b"", 0o644)?;
+        }
+
+        // A staged link still points to where the service directory is finally installed
+        let link_path = utils::staged_path(&ctx, &self.service_dir_path().join(&service));
+        if link_path.symlink_metadata().is_err() {
+            if ctx.install_root.is_some() {
+                std::fs::create_dir_all(link_path.parent().unwrap_or(Path::new("/")))?;
+            }
+            utils::symlink(&final_dir_path, &link_path)?;
+        }
+
+        Ok(())
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "runit")?;
+
+        let service = ctx.label.to_script_name();
+
+        // Bring the service down before unlinking it, ignoring failures as runsv(8) may not be
+        // supervising the service
+        let _ = sv(
+            &*self.runner,
+            "down",
+            &self.service_dir_path().join(&service),
+        );
+
+        let link_path = self.service_dir_path().join(&service);
+        if link_path.symlink_metadata().is_ok() {
+            std::fs::remove_file(&link_path)?;
+        }
+
+        let dir_path = self.config.sv_dir_path.join(&service);
+        if dir_path.exists() {
+            std::fs::remove_dir_all(&dir_path)?;
+        }
+
+        utils::purge(&ctx, [log_dir_path(&service)])?;
+        manifest::remove(ServiceManagerKind::Runit, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Runit, self.level(), label)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        let path = self.service_dir_path().join(ctx.label.to_script_name());
+        wrap_output(sv(&*self.runner, "up", &path)?)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        let path = self.service_dir_path().join(ctx.label.to_script_name());
+        wrap_output(sv(&*self.runner, "down", &path)?)?;
+        Ok(())
+    }
+
+    fn level(&self) -> ServiceLevel {
+        ServiceLevel::System
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => Ok(()),
+            ServiceLevel::User => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "runit does not support user-level services",
+            )),
+        }
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        if !self.config.install.log {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Logging is not enabled for runit services",
+            ));
+        }
+
+        let text =
+            std::fs::read_to_string(log_dir_path(&ctx.label.to_script_name()).join("current"))?;
+        Ok(utils::tail(&text, ctx.lines))
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let service = ctx.label.to_script_name();
+        let link_path = self.service_dir_path().join(&service);
+        if !self.config.sv_dir_path.join(&service).exists() || link_path.symlink_metadata().is_err()
+        {
+            return Ok(crate::ServiceStatus::NotInstalled.into());
+        }
+
+        let output = wrap_output(sv(&*self.runner, "status", &link_path)?)?;
+        parse_status(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "Failed to get status of service {service}: {}",
+                    String::from_utf8_lossy(&output.stdout)
+                ),
+            )
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        let cmd = match ctx.signal {
+            crate::Signal::Hup => "hup",
+            crate::Signal::Int => "interrupt",
+            crate::Signal::Quit => "quit",
+            crate::Signal::Kill => "kill",
+            crate::Signal::Usr1 => "1",
+            crate::Signal::Usr2 => "2",
+            crate::Signal::Term => "term",
+        };
+        let path = self.service_dir_path().join(ctx.label.to_script_name());
+        wrap_output(sv(&*self.runner, cmd, &path)?)?;
+        Ok(())
+    }
+}
+
+fn sv(runner: &dyn CommandRunner, cmd: &str, service: &Path) -> io::Result<Output> {
+    let mut command = Command::new(SV);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg(cmd)
+        .arg(service);
+
+    runner.run(&mut command, None)
+}
+
+#[inline]
+fn log_dir_path(service: &str) -> PathBuf {
+    PathBuf::from("/var/log").join(service)
+}
+
+/// Parses the first line of `sv status`, which looks like
+/// `run: /var/service/name: (pid 123) 45s; run: log: (pid 122) 45s`
+fn parse_status(output: &str) -> Option<crate::ServiceStatusDetails> {
+    let line = output.lines().next()?;
+    let (state, rest) = line.split_once(':')?;
+
+    let (status, transition) = match state.trim() {
+        "run" => (crate::ServiceStatus::Running, None),
+        "down" => (crate::ServiceStatus::Stopped(None), None),
+        "finish" => (
+            crate::ServiceStatus::Stopped(None),
+            Some(crate::ServiceTransition::Stopping),
+        ),
+        _ => return None,
+    };
+
+    let pid = match status {
+        crate::ServiceStatus::Running => rest
+            .split_once("(pid ")
+            .and_then(|(_, rest)| rest.split_once(')'))
+            .and_then(|(pid, _)| pid.trim().parse().ok()),
+        _ => None,
+    };
+
+    Some(crate::ServiceStatusDetails {
+        status,
+        pid,
+        transition,
+    })
+}
+
+fn make_run_script(ctx: &ServiceInstallCtx) -> String {
+    let mut script = String::from("#!/bin/sh\nexec 2>&1\n");
+
+    if let Some(dir) = &ctx.working_directory {
+        let dir = utils::escape_shell_double_quoted(&dir.to_string_lossy());
+        let _ = writeln!(script, "cd \"{dir}\" || exit 1");
+    }
+
+    if let Some(env_vars) = &ctx.environment {
+        for (var, val) in env_vars {
+            let val = utils::escape_shell_double_quoted(&val.to_string_lossy());
+            let _ = writeln!(script, "export {}=\"{val}\"", var.to_string_lossy());
+        }
+    }
+
+    let chpst = match &ctx.username {
+        Some(username) => format!("chpst -u {username} "),
+        None => String::new(),
+    };
+
+    let cmd = utils::shell_join(ctx.cmd_iter());
+    let _ = write!(script, "exec {chpst}{cmd}");
+
+    script
+}
+
+/// Produces a finish script that stops runsv(8) from restarting the service, as runit otherwise
+/// restarts a service immediately whenever it exits
+fn make_finish_script(restart_policy: Option<RestartPolicy>) -> Option<String> {
+    // The finish script runs from within the service directory and receives the exit code of the
+    // run script as its first argument
+    let body = match restart_policy? {
+        RestartPolicy::Never => String::from("exec sv down \"$PWD\""),
+        RestartPolicy::OnFailure { delay_secs } => {
+            let mut body = String::from("[ \"$1\" = 0 ] && exec sv down \"$PWD\"");
+            if let Some(delay) = delay_secs {
+                let _ = write!(body, "\nsleep {delay}");
+            }
+            body
+        }
+        RestartPolicy::Always {
+            delay_secs: Some(delay),
+        } => format!("sleep {delay}"),
+        RestartPolicy::Always { delay_secs: None } => return None,
+    };
+
+    Some(format!("#!/bin/sh\n{body}"))
+}
+
+fn make_log_script(service: &str) -> String {
+    let log_dir = log_dir_path(service);
+    let log_dir = log_dir.to_string_lossy();
+    format!("#!/bin/sh\nmkdir -p \"{log_dir}\"\nexec svlogd -tt \"{log_dir}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::tests::RecordingRunner;
+    use indoc::indoc;
+    use std::{ffi::OsString, sync::Arc};
+
+    #[test]
+    fn test_signal() {
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = RunitServiceManager::default()
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        manager
+            .signal(crate::ServiceSignalCtx {
+                label: "org.example.my_service".parse().unwrap(),
+                signal: crate::Signal::Usr1,
+            })
+            .unwrap();
+
+        let path = manager.service_dir_path().join("example-my_service");
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [["sv", "1", &path.to_string_lossy()]]
+        );
+    }
+
+    fn install_ctx() -> ServiceInstallCtx {
+        ServiceInstallCtx {
+            args: vec![OsString::from("--port"), OsString::from("8080")],
+            autostart: true,
+            ..crate::utils::tests::install_ctx(PathBuf::from("/usr/local/bin/my_service"))
+        }
+    }
+
+    #[test]
+    fn test_make_run_script() {
+        let ctx = ServiceInstallCtx {
+            username: Some(String::from("nobody")),
+            working_directory: Some(PathBuf::from("/var/lib/my_service")),
+            environment: Some(vec![(OsString::from("RUST_LOG"), OsString::from("info"))]),
+            ..install_ctx()
+        };
+
+        assert_eq!(
+            make_run_script(&ctx),
+            indoc! {r#"
+                #!/bin/sh
+                exec 2>&1
+                cd "/var/lib/my_service" || exit 1
+                export RUST_LOG="info"
+                exec chpst -u nobody /usr/local/bin/my_service --port 8080"#}
+        );
+    }
+
+    #[test]
+    fn test_make_finish_script() {
+        assert_eq!(make_finish_script(None), None,);
+        assert_eq!(
+            make_finish_script(Some(RestartPolicy::Always { delay_secs: None })),
+            None,
+        );
+        assert_eq!(
+            make_finish_script(Some(RestartPolicy::Never)).unwrap(),
+            "#!/bin/sh\nexec sv down \"$PWD\"",
+        );
+        assert_eq!(
+            make_finish_script(Some(RestartPolicy::OnFailure {
+                delay_secs: Some(5)
+            }))
+            .unwrap(),
+            "#!/bin/sh\n[ \"$1\" = 0 ] && exec sv down \"$PWD\"\nsleep 5",
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let details =
+            parse_status("run: /var/service/my_service: (pid 123) 45s; run: log: (pid 122) 45s\n")
+                .unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Running);
+        assert_eq!(details.pid, Some(123));
+
+        let details =
+            parse_status("down: /var/service/my_service: 3s, normally up; run: log: (pid 122) 45s")
+                .unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Stopped(None));
+        assert_eq!(details.pid, None);
+
+        assert_eq!(
+            parse_status("warning: /var/service/my_service: unable to open supervise/ok"),
+            None
+        );
+    }
+}
@@ -0,0 +1,650 @@
+use crate::{manifest::ManifestCommandRunner, ElevationStrategy};
+use once_cell::sync::OnceCell;
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    ops::Deref,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::{mpsc, Arc},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time to wait for the remaining output of a command after it exits when its descendants may
+/// keep its stdout and stderr open
+const LINGERING_OUTPUT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Time between checks of whether a command run with a timeout has exited
+const TIMEOUT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Executes the external commands of service managers, such as `systemctl` or `launchctl`
+///
+/// Service managers use [`ProcessCommandRunner`] by default, which spawns real processes.
+/// Providing another implementation through a manager's `with_runner` lets tests stub the
+/// commands a manager runs and verify the exact programs and arguments it uses, which are
+/// available from [`Command::get_program`] and [`Command::get_args`].
+pub trait CommandRunner: fmt::Debug + Send + Sync {
+    /// Runs `command` to completion, writing `input` to its stdin when provided, and returns its
+    /// exit status alongside its captured stdout and stderr
+    fn run(&self, command: &mut Command, input: Option<&[u8]>) -> io::Result<Output>;
+
+    /// Runs `command`, returning once it exits even if processes it spawned keep its stdout and
+    /// stderr open, as is the case for scripts that start daemons
+    ///
+    /// By default, this is the same as [`CommandRunner::run`] without any input.
+    fn run_until_exit(&self, command: &mut Command) -> io::Result<Output> {
+        self.run(command, None)
+    }
+
+    /// Returns the strategy that this runner elevates commands with
+    ///
+    /// Service managers that write definition files use this to fail early when only their
+    /// commands would be elevated. By default, this is [`ElevationStrategy::None`].
+    fn elevation(&self) -> ElevationStrategy {
+        ElevationStrategy::None
+    }
+}
+
+/// Implementation of [`CommandRunner`] that spawns processes on the current machine
+///
+/// With the `tracing` feature, each command runs within a `command` span naming its program and
+/// arguments, and completes with an event recording its exit code and duration along with the
+/// start of its stderr when it fails.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessCommandRunner;
+
+impl CommandRunner for ProcessCommandRunner {
+    fn run(&self, command: &mut Command, input: Option<&[u8]>) -> io::Result<Output> {
+        trace_command(command, |command| run_with_input(command, input))
+    }
+
+    fn run_until_exit(&self, command: &mut Command) -> io::Result<Output> {
+        trace_command(command, run_until_exit)
+    }
+}
+
+/// Implementation of [`CommandRunner`] that spawns processes on the current machine like
+/// [`ProcessCommandRunner`], but kills any command that does not exit within its timeout and
+/// fails with an error of kind [`io::ErrorKind::TimedOut`]
+///
+/// This keeps a wedged external tool, such as one waiting on a password prompt, from blocking a
+/// service manager forever. Provide it to a manager through its `with_runner`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TimeoutCommandRunner {
+    timeout: Duration,
+}
+
+impl TimeoutCommandRunner {
+    /// Creates a runner that kills commands still running after `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+
+    /// Returns the time commands are given to exit
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl CommandRunner for TimeoutCommandRunner {
+    fn run(&self, command: &mut Command, input: Option<&[u8]>) -> io::Result<Output> {
+        trace_command(command, |command| {
+            run_with_timeout(command, input, self.timeout, false)
+        })
+    }
+
+    fn run_until_exit(&self, command: &mut Command) -> io::Result<Output> {
+        trace_command(command, |command| {
+            run_with_timeout(command, None, self.timeout, true)
+        })
+    }
+}
+
+fn run_with_input(command: &mut Command, input: Option<&[u8]>) -> io::Result<Output> {
+    let input = match input {
+        Some(input) => input,
+        None => return command.output(),
+    };
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Dropping stdin once written closes it so that the command reads to the end
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input)?;
+    }
+
+    child.wait_with_output()
+}
+
+fn run_until_exit(command: &mut Command) -> io::Result<Output> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Rather than waiting for the pipes to close, read them on separate threads and only
+    // wait a short time for the remaining output once the command itself has exited
+    let rx = read_output(&mut child);
+    let status = child.wait()?;
+    Ok(collect_output(status, &rx, LINGERING_OUTPUT_TIMEOUT))
+}
+
+/// Runs `command` like [`run_with_input`], or like [`run_until_exit`] when `until_exit` is true,
+/// killing it if it has not exited by the time `timeout` elapses
+fn run_with_timeout(
+    command: &mut Command,
+    input: Option<&[u8]>,
+    timeout: Duration,
+    until_exit: bool,
+) -> io::Result<Output> {
+    let deadline = Instant::now() + timeout;
+    let mut child = command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Writing on a separate thread keeps a command that never reads its input from blocking us
+    if let (Some(mut stdin), Some(input)) = (child.stdin.take(), input) {
+        let input = input.to_vec();
+        thread::spawn(move || stdin.write_all(&input));
+    }
+
+    let rx = read_output(&mut child);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let output_timeout = if until_exit {
+                LINGERING_OUTPUT_TIMEOUT
+            } else {
+                remaining.max(LINGERING_OUTPUT_TIMEOUT)
+            };
+            return Ok(collect_output(status, &rx, output_timeout));
+        }
+
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "{} did not exit within {timeout:?}",
+                    command.get_program().to_string_lossy()
+                ),
+            ));
+        }
+
+        thread::sleep(TIMEOUT_POLL_INTERVAL);
+    }
+}
+
+/// Reads the stdout and stderr of `child` on separate threads, sending each once it closes
+/// alongside whether it is stdout (`0`) or stderr (`1`)
+fn read_output(child: &mut Child) -> mpsc::Receiver<(usize, Vec<u8>)> {
+    let (tx, rx) = mpsc::channel();
+    let readers: [Option<Box<dyn Read + Send>>; 2] = [
+        child
+            .stdout
+            .take()
+            .map(|x| Box::new(x) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|x| Box::new(x) as Box<dyn Read + Send>),
+    ];
+    for (i, reader) in readers.into_iter().enumerate() {
+        if let Some(mut reader) = reader {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let mut buf = Vec::new();
+                let _ = reader.read_to_end(&mut buf);
+                let _ = tx.send((i, buf));
+            });
+        }
+    }
+
+    rx
+}
+
+/// Collects the output read by [`read_output`], waiting at most `timeout` for each stream
+fn collect_output(
+    status: ExitStatus,
+    rx: &mpsc::Receiver<(usize, Vec<u8>)>,
+    timeout: Duration,
+) -> Output {
+    let mut output = Output {
+        status,
+        stdout: Vec::new(),
+        stderr: Vec::new(),
+    };
+    while let Ok((i, buf)) = rx.recv_timeout(timeout) {
+        match i {
+            0 => output.stdout = buf,
+            _ => output.stderr = buf,
+        }
+    }
+
+    output
+}
+
+/// Runs `command` using `f` within a span naming its program and arguments, recording its exit
+/// code, duration, and the start of its stderr once it completes
+#[cfg(feature = "tracing")]
+fn trace_command(
+    command: &mut Command,
+    f: impl FnOnce(&mut Command) -> io::Result<Output>,
+) -> io::Result<Output> {
+    // Passwords must not end up in the logs of subscribers
+    let mut args = redacted_args(command);
+    let program = args.remove(0);
+    let args = args.join(" ");
+    let span = tracing::debug_span!("command", %program, %args);
+    let _enter = span.enter();
+
+    let start = std::time::Instant::now();
+    let result = f(command);
+    let duration_ms = start.elapsed().as_millis() as u64;
+
+    match &result {
+        Ok(output) if output.status.success() => {
+            tracing::debug!(
+                exit_code = output.status.code(),
+                duration_ms,
+                "command succeeded"
+            );
+        }
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            tracing::warn!(
+                exit_code = output.status.code(),
+                duration_ms,
+                stderr = truncate(stderr.trim(), TRACED_STDERR_MAX_CHARS),
+                "command failed"
+            );
+        }
+        Err(x) => tracing::warn!(duration_ms, error = %x, "command could not be run"),
+    }
+
+    result
+}
+
+#[cfg(not(feature = "tracing"))]
+#[inline]
+fn trace_command(
+    command: &mut Command,
+    f: impl FnOnce(&mut Command) -> io::Result<Output>,
+) -> io::Result<Output> {
+    f(command)
+}
+
+/// Maximum number of characters of stderr recorded when a command fails
+#[cfg(feature = "tracing")]
+const TRACED_STDERR_MAX_CHARS: usize = 1024;
+
+/// Returns at most the first `max_chars` characters of `text`
+#[cfg(feature = "tracing")]
+fn truncate(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((i, _)) => &text[..i],
+        None => text,
+    }
+}
+
+/// Placeholder recorded in place of the secrets passed to commands
+const REDACTED: &str = "<redacted>";
+
+/// Returns the program and arguments of `command` as text, with the secrets passed to it replaced
+/// so that they can be logged or recorded
+///
+/// This covers the value following `password=` as passed to sc.exe, and the password following
+/// the account of an NSSM `ObjectName` parameter.
+pub(crate) fn redacted_args(command: &Command) -> Vec<String> {
+    let mut args = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|x| x.to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+
+    for i in 1..args.len() {
+        let is_secret = args[i - 1].eq_ignore_ascii_case("password=")
+            || (i >= 2 && args[i - 2].eq_ignore_ascii_case("ObjectName"));
+        if is_secret {
+            args[i] = REDACTED.to_string();
+        } else if args[i].len() > "password=".len()
+            && args[i]
+                .get(.."password=".len())
+                .map_or(false, |x| x.eq_ignore_ascii_case("password="))
+        {
+            args[i] = format!("password={REDACTED}");
+        }
+    }
+
+    args
+}
+
+/// Shared handle to the [`CommandRunner`] used by a service manager, defaulting to
+/// [`ProcessCommandRunner`]
+///
+/// Handles are equal when they refer to the same runner. Commands run through a handle during an
+/// install are recorded in the [`InstallManifest`](crate::InstallManifest) of the service.
+#[derive(Clone)]
+pub struct SharedCommandRunner(ManifestCommandRunner);
+
+impl SharedCommandRunner {
+    /// Wraps `runner` in a new handle
+    pub fn new(runner: impl CommandRunner + 'static) -> Self {
+        Self::from(Arc::new(runner) as Arc<dyn CommandRunner>)
+    }
+}
+
+impl Default for SharedCommandRunner {
+    fn default() -> Self {
+        static DEFAULT: OnceCell<SharedCommandRunner> = OnceCell::new();
+        DEFAULT
+            .get_or_init(|| Self::new(ProcessCommandRunner))
+            .clone()
+    }
+}
+
+impl fmt::Debug for SharedCommandRunner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0 .0.fmt(f)
+    }
+}
+
+impl PartialEq for SharedCommandRunner {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0 .0, &other.0 .0)
+    }
+}
+
+impl Eq for SharedCommandRunner {}
+
+impl Deref for SharedCommandRunner {
+    type Target = dyn CommandRunner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<R: CommandRunner + 'static> From<R> for SharedCommandRunner {
+    fn from(runner: R) -> Self {
+        Self::new(runner)
+    }
+}
+
+impl From<Arc<dyn CommandRunner>> for SharedCommandRunner {
+    fn from(runner: Arc<dyn CommandRunner>) -> Self {
+        Self(ManifestCommandRunner(runner))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{ServiceManager, ServiceStartCtx, SystemdServiceManager};
+    use std::sync::Mutex;
+
+    /// Runner that records the commands it receives and reports each as succeeding
+    #[derive(Debug, Default)]
+    pub(crate) struct RecordingRunner {
+        pub(crate) commands: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl CommandRunner for RecordingRunner {
+        fn run(&self, command: &mut Command, _input: Option<&[u8]>) -> io::Result<Output> {
+            let mut args = vec![command.get_program().to_string_lossy().into_owned()];
+            args.extend(command.get_args().map(|x| x.to_string_lossy().into_owned()));
+            self.commands.lock().unwrap().push(args);
+
+            Ok(Output {
+                status: ExitStatus::default(),
+                stdout: Vec::new(),
+                stderr: Vec::new(),
+            })
+        }
+    }
+
+    /// Runner that records the commands it receives and reports each as succeeding, printing the
+    /// next of its outputs to stdout, unless created by [`ScriptedRunner::failing`]
+    #[derive(Debug, Default)]
+    pub(crate) struct ScriptedRunner {
+        pub(crate) commands: Mutex<Vec<Vec<String>>>,
+        pub(crate) outputs: Mutex<std::collections::VecDeque<String>>,
+        pub(crate) failure: Option<String>,
+    }
+
+    impl ScriptedRunner {
+        pub(crate) fn new<'a>(outputs: impl IntoIterator<Item = &'a str>) -> Self {
+            Self {
+                commands: Mutex::default(),
+                outputs: Mutex::new(outputs.into_iter().map(String::from).collect()),
+                failure: None,
+            }
+        }
+
+        /// Creates a runner that reports every command as exiting with 1, printing `stderr`
+        pub(crate) fn failing(stderr: &str) -> Self {
+            Self {
+                failure: Some(stderr.to_string()),
+                ..Default::default()
+            }
+        }
+    }
+
+    impl CommandRunner for ScriptedRunner {
+        fn run(&self, command: &mut Command, _input: Option<&[u8]>) -> io::Result<Output> {
+            let mut args = vec![command.get_program().to_string_lossy().into_owned()];
+            args.extend(command.get_args().map(|x| x.to_string_lossy().into_owned()));
+            self.commands.lock().unwrap().push(args);
+
+            if let Some(stderr) = &self.failure {
+                return Ok(Output {
+                    status: failed_status(),
+                    stdout: Vec::new(),
+                    stderr: stderr.clone().into_bytes(),
+                });
+            }
+
+            Ok(Output {
+                status: ExitStatus::default(),
+                stdout: self
+                    .outputs
+                    .lock()
+                    .unwrap()
+                    .pop_front()
+                    .unwrap_or_default()
+                    .into_bytes(),
+                stderr: Vec::new(),
+            })
+        }
+    }
+
+    /// Status of a process that exited with 1
+    fn failed_status() -> ExitStatus {
+        cfg_if::cfg_if! {
+            if #[cfg(unix)] {
+                std::os::unix::process::ExitStatusExt::from_raw(1 << 8)
+            } else {
+                std::os::windows::process::ExitStatusExt::from_raw(1)
+            }
+        }
+    }
+
+    #[test]
+    fn test_with_runner_routes_commands() {
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = SystemdServiceManager::user()
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        manager
+            .start(ServiceStartCtx {
+                label: "com.example.my-service".parse().unwrap(),
+                wait: crate::WaitMode::None,
+            })
+            .unwrap();
+
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [[
+                "systemctl",
+                "--user",
+                "start",
+                "--no-block",
+                "example-my-service"
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_redacted_args() {
+        let mut command = Command::new("sc.exe");
+        command.args([
+            "create",
+            "my-service",
+            "obj=",
+            r".\me",
+            "password=",
+            "secret",
+        ]);
+        assert_eq!(
+            redacted_args(&command),
+            [
+                "sc.exe",
+                "create",
+                "my-service",
+                "obj=",
+                r".\me",
+                "password=",
+                REDACTED
+            ]
+        );
+
+        let mut command = Command::new("sc.exe");
+        command.args(["config", "my-service", "PASSWORD=secret"]);
+        assert_eq!(
+            redacted_args(&command),
+            ["sc.exe", "config", "my-service", "password=<redacted>"]
+        );
+
+        let mut command = Command::new("nssm.exe");
+        command.args(["set", "my-service", "ObjectName", r".\me", "secret"]);
+        assert_eq!(
+            redacted_args(&command),
+            [
+                "nssm.exe",
+                "set",
+                "my-service",
+                "ObjectName",
+                r".\me",
+                REDACTED
+            ]
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_trace_command_redacts_passwords() {
+        use tracing::{
+            field::{Field, Visit},
+            span, Event, Metadata, Subscriber,
+        };
+
+        /// Subscriber that keeps the fields of each span as `name=value`
+        #[derive(Default)]
+        struct SpanFields(Mutex<Vec<String>>);
+
+        impl Visit for &SpanFields {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                let field = format!("{}={:?}", field.name(), value);
+                self.0.lock().unwrap().push(field);
+            }
+        }
+
+        impl Subscriber for SpanFields {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+                span.record(&mut &*self);
+                span::Id::from_u64(1)
+            }
+
+            fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+            fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+            fn event(&self, _event: &Event<'_>) {}
+            fn enter(&self, _span: &span::Id) {}
+            fn exit(&self, _span: &span::Id) {}
+        }
+
+        let fields = Arc::new(SpanFields::default());
+        tracing::subscriber::with_default(Arc::clone(&fields), || {
+            let mut command = Command::new("sc.exe");
+            command.args(["create", "my-service", "password=", "secret"]);
+            trace_command(&mut command, |_| {
+                Ok(Output {
+                    status: ExitStatus::default(),
+                    stdout: Vec::new(),
+                    stderr: Vec::new(),
+                })
+            })
+            .unwrap();
+        });
+
+        assert_eq!(
+            *fields.0.lock().unwrap(),
+            [
+                "program=sc.exe",
+                "args=create my-service password= <redacted>"
+            ]
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("error", 10), "error");
+        assert_eq!(truncate("errör: failed", 5), "errör");
+        assert_eq!(truncate("", 0), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_timeout_runner() {
+        let runner = TimeoutCommandRunner::new(Duration::from_millis(200));
+
+        let output = runner
+            .run(&mut Command::new("cat"), Some(b"hello".as_slice()))
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello");
+
+        let start = Instant::now();
+        let err = runner
+            .run(Command::new("sleep").arg("10"), None)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_default_runners_are_equal() {
+        assert_eq!(
+            SharedCommandRunner::default(),
+            SharedCommandRunner::default()
+        );
+        assert_ne!(
+            SharedCommandRunner::default(),
+            SharedCommandRunner::new(ProcessCommandRunner)
+        );
+    }
+}
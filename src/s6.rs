@@ -0,0 +1,648 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, utils, CommandRunner, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner,
+};
+use std::{
+    ffi::OsStr,
+    fmt::Write as _,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+static S6_SVC: &str = "s6-svc";
+static S6_SVSCANCTL: &str = "s6-svscanctl";
+static S6_SVSTAT: &str = "s6-svstat";
+static S6_RC: &str = "s6-rc";
+
+// NOTE: s6-supervise(8) requires the run, finish, and log/run scripts to be executable
+const SCRIPT_FILE_PERMISSIONS: u32 = 0o755;
+
+/// Exit code of a finish script that tells s6-supervise(8) not to restart the service
+const FINISH_PERMANENT_FAILURE: u8 = 125;
+
+/// Time in milliseconds that s6-supervise(8) allows a finish script to run before killing it
+const DEFAULT_FINISH_TIMEOUT_MS: u64 = 5000;
+
+/// Configuration settings tied to s6 services
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct S6Config {
+    pub install: S6InstallConfig,
+
+    /// Directory where service directories are created, defaulting to `/etc/s6/sv`
+    ///
+    /// Unused when managing services through s6-rc.
+    pub sv_dir_path: PathBuf,
+
+    /// Directory watched by s6-svscan(8), defaulting to `/run/service`
+    pub scan_dir_path: PathBuf,
+
+    /// When specified, services are defined as s6-rc longruns and controlled via `s6-rc` rather
+    /// than being linked directly into the scan directory
+    pub rc: Option<S6RcConfig>,
+}
+
+impl Default for S6Config {
+    fn default() -> Self {
+        Self {
+            install: S6InstallConfig::default(),
+            sv_dir_path: PathBuf::from("/etc/s6/sv"),
+            scan_dir_path: PathBuf::from("/run/service"),
+            rc: None,
+        }
+    }
+}
+
+/// Configuration settings used when installing s6 services
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct S6InstallConfig {
+    /// Whether to write a `log/run` script that captures the output of the service using
+    /// s6-log(8) within `/var/log/{service}`
+    ///
+    /// Unused when managing services through s6-rc, where loggers are separate services.
+    pub log: bool,
+}
+
+impl Default for S6InstallConfig {
+    fn default() -> Self {
+        Self { log: true }
+    }
+}
+
+/// Configuration settings for managing services through s6-rc
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct S6RcConfig {
+    /// Source directory where service definitions are written, e.g. `/etc/s6-rc/source`
+    ///
+    /// The compiled database must be rebuilt with `s6-rc-compile` and switched to with
+    /// `s6-rc-update` before newly installed services can be started.
+    pub source_dir_path: PathBuf,
+
+    /// Bundle that autostarted services are added to, defaulting to `default`
+    pub bundle: String,
+}
+
+impl Default for S6RcConfig {
+    fn default() -> Self {
+        Self {
+            source_dir_path: PathBuf::from("/etc/s6-rc/source"),
+            bundle: String::from("default"),
+        }
+    }
+}
+
+/// Implementation of [`ServiceManager`] for [s6](https://skarnet.org/software/s6/) and
+/// [s6-rc](https://skarnet.org/software/s6-rc/)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct S6ServiceManager {
+    /// Configuration settings tied to s6 services
+    pub config: S6Config,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl S6ServiceManager {
+    /// Creates a new manager instance working with system services
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: S6Config) -> Self {
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Returns the directory containing the definition of the service
+    fn definition_dir_path(&self, service: &str) -> PathBuf {
+        match &self.config.rc {
+            Some(rc) => rc.source_dir_path.join(service),
+            None => self.config.sv_dir_path.join(service),
+        }
+    }
+}
+
+impl ServiceManager for S6ServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        let program = if self.config.rc.is_some() {
+            S6_RC
+        } else {
+            S6_SVC
+        };
+
+        match which::which(program) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        utils::validate_environment(&ctx, true)?;
+        utils::require_utf8(&ctx, "s6")?;
+        utils::capabilities_unsupported(&ctx, "s6")?;
+        utils::backup_unsupported(&ctx, "s6")?;
+        utils::check_program(&ctx)?;
+        if ctx.kind == crate::ServiceKind::Oneshot {
+            return Err(utils::oneshot_unsupported("s6"));
+        }
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "s6")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::S6, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+
+        let service = ctx.label.to_script_name();
+        let final_dir_path = self.definition_dir_path(&service);
+        let dir_path = utils::staged_path(&ctx, &final_dir_path);
+        std::fs::create_dir_all(&dir_path)?;
+
+        let script = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_run_script(&ctx),
+        };
+        utils::write_definition(
+            &ctx,
+            &dir_path.join("run"),
+            script.as_bytes(),
+            SCRIPT_FILE_PERMISSIONS,
+        )?;
+
+        let finish_path = dir_path.join("finish");
+        let timeout_path = dir_path.join("timeout-finish");
+        match make_finish_script(ctx.restart_policy) {
+            Some((script, timeout_ms)) => {
+                utils::write_file(&finish_path, script.as_bytes(), SCRIPT_FILE_PERMISSIONS)?;
+                utils::write_file(&timeout_path, format!("{timeout_ms}\n").as_bytes(), 0o644)?;
+            }
+            None => {
+                for path in [&finish_path, &timeout_path] {
+                    if path.exists() {
+                        std::fs::remove_file(path)?;
+                    }
+                }
+            }
+        }
+
+        // s6-supervise(8) sends SIGTERM to stop the service unless given another signal
+        let down_signal_path = dir_path.join("down-signal");
+        match ctx.stop_signal {
+            Some(signal) => {
+                utils::write_file(&down_signal_path, format!("{signal}\n").as_bytes(), 0o644)?
+            }
+            None if down_signal_path.exists() => std::fs::remove_file(&down_signal_path)?,
+            None => {}
+        }
+
+        // s6-supervise(8) only sends SIGKILL after a stop times out when given a timeout
+        let timeout_kill_path = dir_path.join("timeout-kill");
+        match ctx.stop_timeout {
+            Some(timeout) => utils::write_file(
+                &timeout_kill_path,
+                format!("{}\n", timeout.as_millis()).as_bytes(),
+                0o644,
+            )?,
+            None if timeout_kill_path.exists() => std::fs::remove_file(&timeout_kill_path)?,
+            None => {}
+        }
+
+        if let Some(rc) = &self.config.rc {
+            utils::write_file(&dir_path.join("type"), b"longrun\n", 0o644)?;
+
+            let bundle_entry = utils::staged_path(&ctx, &bundle_entry_path(rc, &service));
+            if ctx.autostart {
+                let bundle_dir_path =
+                    utils::staged_path(&ctx, &rc.source_dir_path.join(&rc.bundle));
+                std::fs::create_dir_all(bundle_dir_path.join("contents.d"))?;
+                if !bundle_dir_path.join("type").exists() {
+                    utils::write_file(&bundle_dir_path.join("type"), b"bundle\n", 0o644)?;
+                }
+                utils::write_file(&bundle_entry, b"", 0o644)?;
+            } else if bundle_entry.exists() {
+                std::fs::remove_file(&bundle_entry)?;
+            }
+
+            return Ok(());
+        }
+
+        if self.config.install.log {
+            let log_dir_path = dir_path.join("log");
+            std::fs::create_dir_all(&log_dir_path)?;
+            utils::write_file(
+                &log_dir_path.join("run"),
+                make_log_script(&service).as_bytes(),
+                SCRIPT_FILE_PERMISSIONS,
+            )?;
+        }
+
+        // s6-supervise(8) starts a service as soon as it is scanned unless a down file is present,
+        // so services without autostart are still linked to be controllable but are left down
+        let down_path = dir_path.join("down");
+        if ctx.autostart {
+            if down_path.exists() {
+                std::fs::remove_file(&down_path)?;
+            }
+        } else {
+            utils::write_file(&down_path, b"", 0o644)?;
+        }
+
+        // A staged link still points to where the service directory is finally installed
+        let link_path = utils::staged_path(&ctx, &self.config.scan_dir_path.join(&service));
+        if link_path.symlink_metadata().is_err() {
+            if ctx.install_root.is_some() {
+                std::fs::create_dir_all(link_path.parent().unwrap_or(Path::new("/")))?;
+            }
+            utils::symlink(&final_dir_path, &link_path)?;
+        }
+
+        if ctx.install_root.is_none() {
+            wrap_output(s6_svscanctl(
+                &*self.runner,
+                "-a",
+                &self.config.scan_dir_path,
+            )?)?;
+        }
+        Ok(())
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "s6")?;
+
+        let service = ctx.label.to_script_name();
+
+        if let Some(rc) = &self.config.rc {
+            // Bring the service down before removing it, ignoring failures as the service may not
+            // be part of the compiled database
+            let _ = s6_rc(&*self.runner, "-d", &service);
+
+            let bundle_entry = bundle_entry_path(rc, &service);
+            if bundle_entry.exists() {
+                std::fs::remove_file(&bundle_entry)?;
+            }
+        } else {
+            let link_path = self.config.scan_dir_path.join(&service);
+            let _ = s6_svc(&*self.runner, "-d", &link_path);
+
+            if link_path.symlink_metadata().is_ok() {
+                std::fs::remove_file(&link_path)?;
+            }
+
+            // Have s6-svscan(8) stop supervising services whose links were removed
+            wrap_output(s6_svscanctl(
+                &*self.runner,
+                "-an",
+                &self.config.scan_dir_path,
+            )?)?;
+        }
+
+        let dir_path = self.definition_dir_path(&service);
+        if dir_path.exists() {
+            std::fs::remove_dir_all(&dir_path)?;
+        }
+
+        utils::purge(&ctx, [log_dir_path(&service)])?;
+        manifest::remove(ServiceManagerKind::S6, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::S6, self.level(), label)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        let service = ctx.label.to_script_name();
+        let output = match &self.config.rc {
+            Some(_) => s6_rc(&*self.runner, "-u", &service)?,
+            None => s6_svc(
+                &*self.runner,
+                "-u",
+                &self.config.scan_dir_path.join(&service),
+            )?,
+        };
+        wrap_output(output)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        let service = ctx.label.to_script_name();
+        let output = match &self.config.rc {
+            Some(_) => s6_rc(&*self.runner, "-d", &service)?,
+            None => s6_svc(
+                &*self.runner,
+                "-d",
+                &self.config.scan_dir_path.join(&service),
+            )?,
+        };
+        wrap_output(output)?;
+        Ok(())
+    }
+
+    fn level(&self) -> ServiceLevel {
+        ServiceLevel::System
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => Ok(()),
+            ServiceLevel::User => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "s6 does not support user-level services",
+            )),
+        }
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        if self.config.rc.is_some() || !self.config.install.log {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Logging is not enabled for s6 services",
+            ));
+        }
+
+        let text =
+            std::fs::read_to_string(log_dir_path(&ctx.label.to_script_name()).join("current"))?;
+        Ok(utils::tail(&text, ctx.lines))
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let service = ctx.label.to_script_name();
+        if !self.definition_dir_path(&service).exists() {
+            return Ok(crate::ServiceStatus::NotInstalled.into());
+        }
+
+        // Services of s6-rc are only supervised once the compiled database containing them is live,
+        // at which point s6-rc links them into the scan directory
+        let link_path = self.config.scan_dir_path.join(&service);
+        if link_path.symlink_metadata().is_err() {
+            return Ok(crate::ServiceStatus::Stopped(None).into());
+        }
+
+        let output = wrap_output(s6_svstat(&*self.runner, &link_path)?)?;
+        parse_svstat(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "Failed to get status of service {service}: {}",
+                    String::from_utf8_lossy(&output.stdout)
+                ),
+            )
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        let cmd = match ctx.signal {
+            crate::Signal::Hup => "-h",
+            crate::Signal::Int => "-i",
+            crate::Signal::Quit => "-q",
+            crate::Signal::Kill => "-k",
+            crate::Signal::Usr1 => "-1",
+            crate::Signal::Usr2 => "-2",
+            crate::Signal::Term => "-t",
+        };
+        let path = self.config.scan_dir_path.join(ctx.label.to_script_name());
+        wrap_output(s6_svc(&*self.runner, cmd, &path)?)?;
+        Ok(())
+    }
+}
+
+fn s6_svc(runner: &dyn CommandRunner, cmd: &str, service: &Path) -> io::Result<Output> {
+    let mut command = Command::new(S6_SVC);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg(cmd)
+        .arg(service);
+
+    runner.run(&mut command, None)
+}
+
+fn s6_svscanctl(runner: &dyn CommandRunner, cmd: &str, scan_dir: &Path) -> io::Result<Output> {
+    let mut command = Command::new(S6_SVSCANCTL);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg(cmd)
+        .arg(scan_dir);
+
+    runner.run(&mut command, None)
+}
+
+fn s6_svstat(runner: &dyn CommandRunner, service: &Path) -> io::Result<Output> {
+    let mut command = Command::new(S6_SVSTAT);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .args(["-o", "up,pid"])
+        .arg(service);
+
+    runner.run(&mut command, None)
+}
+
+fn s6_rc(runner: &dyn CommandRunner, direction: &str, service: &str) -> io::Result<Output> {
+    let mut command = Command::new(S6_RC);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg(direction)
+        .arg("change")
+        .arg(OsStr::new(service));
+
+    runner.run(&mut command, None)
+}
+
+/// Path of the file that adds a service to the autostart bundle of s6-rc
+#[inline]
+fn bundle_entry_path(rc: &S6RcConfig, service: &str) -> PathBuf {
+    rc.source_dir_path
+        .join(&rc.bundle)
+        .join("contents.d")
+        .join(service)
+}
+
+#[inline]
+fn log_dir_path(service: &str) -> PathBuf {
+    PathBuf::from("/var/log").join(service)
+}
+
+/// Parses the output of `s6-svstat -o up,pid`, which looks like `true 123` or `false -1`
+fn parse_svstat(output: &str) -> Option<crate::ServiceStatusDetails> {
+    let mut fields = output.split_whitespace();
+    let up = fields.next()?;
+    let pid = fields.next().and_then(|pid| pid.parse::<u32>().ok());
+
+    let (status, pid) = match up {
+        "true" => (crate::ServiceStatus::Running, pid),
+        "false" => (crate::ServiceStatus::Stopped(None), None),
+        _ => return None,
+    };
+
+    Some(crate::ServiceStatusDetails {
+        status,
+        pid,
+        transition: None,
+    })
+}
+
+fn make_run_script(ctx: &ServiceInstallCtx) -> String {
+    let mut script = String::from("#!/bin/sh\nexec 2>&1\n");
+
+    if let Some(dir) = &ctx.working_directory {
+        let dir = utils::escape_shell_double_quoted(&dir.to_string_lossy());
+        let _ = writeln!(script, "cd \"{dir}\" || exit 1");
+    }
+
+    if let Some(env_vars) = &ctx.environment {
+        for (var, val) in env_vars {
+            let val = utils::escape_shell_double_quoted(&val.to_string_lossy());
+            let _ = writeln!(script, "export {}=\"{val}\"", var.to_string_lossy());
+        }
+    }
+
+    let setuidgid = match &ctx.username {
+        Some(username) => format!("s6-setuidgid {username} "),
+        None => String::new(),
+    };
+
+    let cmd = utils::shell_join(ctx.cmd_iter());
+    let _ = write!(script, "exec {setuidgid}{cmd}");
+
+    script
+}
+
+/// Produces a finish script alongside its timeout in milliseconds that stops s6-supervise(8) from
+/// restarting the service, as s6 otherwise restarts a service whenever it exits
+fn make_finish_script(restart_policy: Option<RestartPolicy>) -> Option<(String, u64)> {
+    // The finish script receives the exit code of the run script as its first argument
+    let (body, delay_secs) = match restart_policy? {
+        RestartPolicy::Never => (format!("exit {FINISH_PERMANENT_FAILURE}"), None),
+        RestartPolicy::OnFailure { delay_secs } => (
+            format!("[ \"$1\" = 0 ] && exit {FINISH_PERMANENT_FAILURE}"),
+            delay_secs,
+        ),
+        RestartPolicy::Always {
+            delay_secs: Some(delay),
+        } => (String::new(), Some(delay)),
+        RestartPolicy::Always { delay_secs: None } => return None,
+    };
+
+    let mut script = String::from("#!/bin/sh");
+    if !body.is_empty() {
+        let _ = write!(script, "\n{body}");
+    }
+    if let Some(delay) = delay_secs {
+        let _ = write!(script, "\nsleep {delay}");
+    }
+
+    // Extend the timeout of the finish script so that it is not killed while delaying the restart
+    let timeout_ms = DEFAULT_FINISH_TIMEOUT_MS + u64::from(delay_secs.unwrap_or(0)) * 1000;
+    Some((script, timeout_ms))
+}
+
+fn make_log_script(service: &str) -> String {
+    let log_dir = log_dir_path(service);
+    let log_dir = log_dir.to_string_lossy();
+    format!("#!/bin/sh\nmkdir -p \"{log_dir}\"\nexec s6-log -b T \"{log_dir}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::ffi::OsString;
+
+    fn install_ctx() -> ServiceInstallCtx {
+        ServiceInstallCtx {
+            args: vec![OsString::from("--port"), OsString::from("8080")],
+            autostart: true,
+            ..crate::utils::tests::install_ctx(PathBuf::from("/usr/local/bin/my_service"))
+        }
+    }
+
+    #[test]
+    fn test_make_run_script() {
+        let ctx = ServiceInstallCtx {
+            username: Some(String::from("nobody")),
+            environment: Some(vec![(OsString::from("RUST_LOG"), OsString::from("info"))]),
+            ..install_ctx()
+        };
+
+        assert_eq!(
+            make_run_script(&ctx),
+            indoc! {r#"
+                #!/bin/sh
+                exec 2>&1
+                export RUST_LOG="info"
+                exec s6-setuidgid nobody /usr/local/bin/my_service --port 8080"#}
+        );
+    }
+
+    #[test]
+    fn test_make_finish_script() {
+        assert_eq!(make_finish_script(None), None);
+        assert_eq!(
+            make_finish_script(Some(RestartPolicy::Never)),
+            Some((String::from("#!/bin/sh\nexit 125"), 5000)),
+        );
+        assert_eq!(
+            make_finish_script(Some(RestartPolicy::OnFailure {
+                delay_secs: Some(10)
+            })),
+            Some((
+                String::from("#!/bin/sh\n[ \"$1\" = 0 ] && exit 125\nsleep 10"),
+                15000
+            )),
+        );
+        assert_eq!(
+            make_finish_script(Some(RestartPolicy::Always {
+                delay_secs: Some(3)
+            })),
+            Some((String::from("#!/bin/sh\nsleep 3"), 8000)),
+        );
+    }
+
+    #[test]
+    fn test_parse_svstat() {
+        let details = parse_svstat("true 1234\n").unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Running);
+        assert_eq!(details.pid, Some(1234));
+
+        let details = parse_svstat("false -1\n").unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Stopped(None));
+        assert_eq!(details.pid, None);
+
+        assert_eq!(
+            parse_svstat("s6-svstat: fatal: unable to read status"),
+            None
+        );
+    }
+}
@@ -1,14 +1,19 @@
-use crate::utils::wrap_output;
+use crate::utils::{self, wrap_output};
 
 use super::{
-    ServiceInstallCtx, ServiceLevel, ServiceManager, ServiceStartCtx, ServiceStopCtx,
-    ServiceUninstallCtx,
+    manifest, AvailabilityDetails, RestartPolicy, ServiceInstallCtx, ServiceLabel, ServiceLevel,
+    ServiceLogsCtx, ServiceManager, ServiceManagerKind, ServicePauseCtx, ServiceResumeCtx,
+    ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx, SharedCommandRunner, StartLimit,
+    StartLimitAction,
 };
 use std::{
     borrow::Cow,
     ffi::{OsStr, OsString},
     fmt, io,
+    path::PathBuf,
     process::{Command, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
 };
 
 #[cfg(windows)]
@@ -25,11 +30,69 @@ mod shell_escape {
 }
 
 static SC_EXE: &str = "sc.exe";
+static REG_EXE: &str = "reg.exe";
+static TASKKILL_EXE: &str = "taskkill.exe";
+static WEVTUTIL_EXE: &str = "wevtutil.exe";
+
+/// Event log that services write to
+const EVENT_LOG_NAME: &str = "Application";
+
+/// Interval between queries of a service's state while waiting for it to stop
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Seconds to wait for each dependent of a service to stop when no stop timeout is configured, as
+/// the service itself cannot be stopped until they are
+const DEPENDENT_STOP_TIMEOUT_SECS: u32 = 30;
+
+/// Size in bytes of the buffer that `sc enumdepend` uses to list dependent services, which is
+/// otherwise too small for more than a few dependents
+const ENUMDEPEND_BUFFER_SIZE: u32 = 64 * 1024;
+
+/// Registry key under which the service control manager stores the configuration of services
+const SERVICES_REGISTRY_KEY: &str = r"HKLM\SYSTEM\CurrentControlSet\Services";
+
+/// Registry key under which sources of the Application event log are registered
+const EVENT_LOG_REGISTRY_KEY: &str = r"HKLM\SYSTEM\CurrentControlSet\Services\EventLog\Application";
+
+/// Default period of one day after which the failure count of a service is reset
+const DEFAULT_FAILURE_RESET_PERIOD_SECS: u32 = 60 * 60 * 24;
+
+/// Number of times the service control manager restarts a failed service before giving up
+/// until the failure count is reset
+const FAILURE_RESTART_ATTEMPTS: usize = 3;
 
 /// Configuration settings tied to sc.exe services
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct ScConfig {
     pub install: ScInstallConfig,
+
+    /// If provided, stopping a service waits up to this many seconds for the service to reach
+    /// the stopped state, failing with an error of kind [`io::ErrorKind::TimedOut`] otherwise
+    ///
+    /// If not provided, stopping a service returns as soon as the stop request is sent.
+    pub stop_timeout_secs: Option<u32>,
+
+    /// If true, a service that has not stopped within `stop_timeout_secs` has its process
+    /// forcefully terminated via `taskkill` rather than failing
+    pub kill_on_stop_timeout: bool,
+
+    /// If true, running services that depend on a service are stopped ahead of stopping the
+    /// service itself, similar to `net stop /y`
+    ///
+    /// Each dependent is waited on until it has stopped, for up to `stop_timeout_secs` or 30
+    /// seconds if not provided.
+    ///
+    /// Otherwise, stopping a service with running dependents fails.
+    pub stop_dependents: bool,
+
+    /// Path of the `sc.exe` program, which is searched for on the `PATH` if not provided
+    pub sc_exe_path: Option<PathBuf>,
+}
+
+impl ScConfig {
+    fn sc_exe(&self) -> &OsStr {
+        utils::program_or(self.sc_exe_path.as_deref(), SC_EXE)
+    }
 }
 
 /// Configuration settings tied to sc.exe services during installation
@@ -43,6 +106,208 @@ pub struct ScInstallConfig {
 
     /// Severity of the error if the windows service fails when the computer is started
     pub error_severity: WindowsErrorSeverity,
+
+    /// Length of the period without failures, in seconds, after which the failure count used for
+    /// recovery actions is reset to zero
+    ///
+    /// Only used when a [`RestartPolicy`] is provided during installation. If not specified,
+    /// defaults to one day.
+    pub failure_reset_period_secs: Option<u32>,
+
+    /// Actions the service control manager takes upon each consecutive failure of the service,
+    /// where the last action is repeated for any subsequent failures
+    ///
+    /// If not empty, these replace the actions derived from the [`RestartPolicy`] provided during
+    /// installation.
+    pub recovery_actions: Vec<ScRecoveryAction>,
+
+    /// Command line run by [`ScRecoveryAction::RunCommand`]
+    pub recovery_command: Option<String>,
+
+    /// Message broadcast to users of the computer ahead of [`ScRecoveryAction::Reboot`]
+    pub reboot_message: Option<String>,
+
+    /// Optional description of the windows service shown within the Services console
+    pub description: Option<String>,
+
+    /// If true, a service that starts automatically will wait until shortly after the other
+    /// automatic services have started
+    pub delayed_autostart: bool,
+
+    /// Events that start or stop the windows service, configured via `sc triggerinfo`
+    pub triggers: Vec<ScTrigger>,
+
+    /// Optional time in milliseconds that the service control manager waits for the windows
+    /// service to handle the preshutdown notification, written to the service's
+    /// `PreshutdownTimeout` registry value
+    pub preshutdown_timeout_ms: Option<u32>,
+
+    /// If true, the windows service is registered as a source of the Application event log so
+    /// that the events it reports are displayed properly within Event Viewer
+    ///
+    /// The registration is removed on uninstall.
+    pub event_log_source: bool,
+
+    /// Optional file containing the message resources of the event log source
+    ///
+    /// If not specified, the program of the service is used.
+    pub event_message_file: Option<PathBuf>,
+
+    /// Optional account the windows service runs as, passed to `sc create` as `obj=` and
+    /// `password=`
+    ///
+    /// If not specified, the `username` of the install context is used without a password.
+    /// If neither is specified, the service runs as `LocalSystem`.
+    pub account: Option<WindowsAccount>,
+
+    /// Optional flag used to pass the working directory to the program, such as `--cwd`
+    ///
+    /// The service control manager has no notion of a working directory, always starting
+    /// services within `C:\Windows\System32`. When both this flag and the `working_directory` of
+    /// the install context are provided, the flag and directory are appended to the arguments of
+    /// the program so that it can change its own working directory. Otherwise, the working
+    /// directory is ignored.
+    pub working_directory_arg: Option<String>,
+
+    /// Names of services or load ordering groups that must start before the windows service,
+    /// passed to `sc create` as `depend=` separated by forward slashes
+    ///
+    /// Groups are distinguished from services by being prefixed with `+`.
+    pub dependencies: Vec<String>,
+}
+
+/// Account used to run a windows service
+#[derive(Clone, PartialEq, Eq)]
+pub struct WindowsAccount {
+    /// Name of the account such as `NT AUTHORITY\LocalService` or `DOMAIN\user`
+    pub name: String,
+
+    /// Password of the account, which is not needed for built-in and virtual accounts
+    pub password: Option<String>,
+}
+
+impl WindowsAccount {
+    /// Creates an account with the given name and no password
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            password: None,
+        }
+    }
+
+    /// Built-in `LocalSystem` account with full privileges on the computer, which services run
+    /// as by default
+    pub fn local_system() -> Self {
+        Self::new("LocalSystem")
+    }
+
+    /// Built-in `NT AUTHORITY\LocalService` account with minimal privileges on the computer
+    pub fn local_service() -> Self {
+        Self::new(r"NT AUTHORITY\LocalService")
+    }
+
+    /// Built-in `NT AUTHORITY\NetworkService` account that authenticates as the computer on
+    /// the network
+    pub fn network_service() -> Self {
+        Self::new(r"NT AUTHORITY\NetworkService")
+    }
+
+    /// Virtual `NT SERVICE\{service_name}` account created by Windows for the named service
+    pub fn virtual_account(service_name: &str) -> Self {
+        Self::new(format!(r"NT SERVICE\{service_name}"))
+    }
+
+    /// Update the account to use the specified password
+    pub fn with_password(self, password: impl Into<String>) -> Self {
+        Self {
+            name: self.name,
+            password: Some(password.into()),
+        }
+    }
+
+    /// Returns true if Windows manages the credentials of the account, meaning it is
+    /// `LocalSystem`, a built-in `NT AUTHORITY\` account, a virtual `NT SERVICE\` account, or a
+    /// group managed service account whose name ends in `$`
+    ///
+    /// Passwords are never passed to sc.exe for these accounts.
+    pub fn is_managed(&self) -> bool {
+        let name = self.name.to_ascii_uppercase();
+        name == "LOCALSYSTEM"
+            || name.starts_with(r"NT AUTHORITY\")
+            || name.starts_with(r"NT SERVICE\")
+            || name.ends_with('$')
+    }
+}
+
+impl fmt::Debug for WindowsAccount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WindowsAccount")
+            .field("name", &self.name)
+            .field("password", &self.password.as_ref().map(|_| "********"))
+            .finish()
+    }
+}
+
+/// Action taken by the service control manager when a windows service fails
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ScRecoveryAction {
+    /// Take no action
+    None { delay_secs: u32 },
+
+    /// Restart the service
+    Restart { delay_secs: u32 },
+
+    /// Run the configured recovery command
+    RunCommand { delay_secs: u32 },
+
+    /// Reboot the computer
+    Reboot { delay_secs: u32 },
+}
+
+impl fmt::Display for ScRecoveryAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (action, delay_secs) = match self {
+            // An empty action, which is written as `""` when invoking sc.exe from a shell
+            Self::None { delay_secs } => ("", delay_secs),
+            Self::Restart { delay_secs } => ("restart", delay_secs),
+            Self::RunCommand { delay_secs } => ("run", delay_secs),
+            Self::Reboot { delay_secs } => ("reboot", delay_secs),
+        };
+
+        // sc.exe expects the delay in milliseconds
+        write!(f, "{action}/{}", u64::from(*delay_secs) * 1000)
+    }
+}
+
+/// Event that triggers the service control manager to start or stop a windows service
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ScTrigger {
+    /// Start the service when the first IP address becomes available
+    StartOnNetworkAvailable,
+
+    /// Stop the service when the last IP address becomes unavailable
+    StopOnNetworkLost,
+
+    /// Start the service when the computer joins a domain
+    StartOnDomainJoin,
+
+    /// Start the service when a device of the interface class with the given GUID arrives
+    StartOnDeviceArrival(String),
+
+    /// Trigger in the format of `sc triggerinfo` such as `start/portopen/5000;TCP;my.exe;svc`
+    Custom(String),
+}
+
+impl fmt::Display for ScTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StartOnNetworkAvailable => write!(f, "start/networkon"),
+            Self::StopOnNetworkLost => write!(f, "stop/networkoff"),
+            Self::StartOnDomainJoin => write!(f, "start/domainjoin"),
+            Self::StartOnDeviceArrival(guid) => write!(f, "start/deviceinterfacearrival/{guid}"),
+            Self::Custom(trigger) => write!(f, "{trigger}"),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -162,6 +427,12 @@ impl fmt::Display for WindowsErrorSeverity {
 pub struct ScServiceManager {
     /// Configuration settings tied to rc.d services
     pub config: ScConfig,
+
+    /// If provided, services are managed on this remote computer rather than the local one
+    pub host: Option<String>,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
 }
 
 impl ScServiceManager {
@@ -170,23 +441,260 @@ impl ScServiceManager {
         Self::default()
     }
 
+    /// Creates a new manager instance working with system services on the remote computer with
+    /// the given name, which requires permission to manage services on that computer
+    pub fn remote(host: &str) -> Self {
+        Self {
+            config: ScConfig::default(),
+            host: Some(host.to_string()),
+            runner: SharedCommandRunner::default(),
+        }
+    }
+
     /// Update manager to use the specified config
     pub fn with_config(self, config: ScConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            host: self.host,
+            runner: self.runner,
+        }
     }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Queries the configuration of an installed service using `sc qc` and `sc qdescription`
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if the service is not installed.
+    pub fn installed_config(&self, label: &ServiceLabel) -> io::Result<ScInstalledConfig> {
+        let service_name = label.to_qualified_name();
+
+        let output = self.sc_exe("qc", &service_name, [])?;
+        if matches!(output.status.code(), Some(1060)) {
+            // 1060 = The specified service does not exist as an installed service.
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Service {service_name} is not installed"),
+            ));
+        }
+        let output = wrap_output(output)?;
+        let mut config = parse_qc(&String::from_utf8_lossy(&output.stdout));
+
+        let output = wrap_output(self.sc_exe("qdescription", &service_name, [])?)?;
+        config.description = parse_qdescription(&String::from_utf8_lossy(&output.stdout));
+
+        Ok(config)
+    }
+
+    /// Stops the service, waiting for it to stop if configured to do so or if `must_wait` is set
+    fn stop_and_wait(&self, service_name: &str, must_wait: bool) -> io::Result<()> {
+        wrap_output(self.sc_exe("stop", service_name, [])?)?;
+
+        let timeout_secs = match self.config.stop_timeout_secs {
+            Some(timeout_secs) => Some(timeout_secs),
+            None if must_wait => Some(DEPENDENT_STOP_TIMEOUT_SECS),
+            None => None,
+        };
+        if let Some(timeout_secs) = timeout_secs {
+            self.wait_for_stop(service_name, Duration::from_secs(timeout_secs.into()))?;
+        }
+
+        Ok(())
+    }
+
+    /// Polls `sc queryex` until the service is stopped or the timeout is reached, killing the
+    /// service's process upon timeout if configured to do so
+    fn wait_for_stop(&self, service_name: &str, timeout: Duration) -> io::Result<()> {
+        let start = Instant::now();
+
+        loop {
+            let output = wrap_output(self.sc_exe("queryex", service_name, [])?)?;
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            // Only SERVICE_STOPPED counts, as parse_status also reports STOP_PENDING as stopped
+            if parse_state_code(&stdout) == Some(1) {
+                return Ok(());
+            }
+
+            if start.elapsed() >= timeout {
+                return match parse_queryex_pid(&stdout) {
+                    Some(pid) if self.config.kill_on_stop_timeout => {
+                        wrap_output(self.taskkill_exe(pid)?)?;
+                        Ok(())
+                    }
+                    _ => Err(io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        format!("Service {service_name} did not stop within {timeout:?}"),
+                    )),
+                };
+            }
+
+            thread::sleep(STOP_POLL_INTERVAL);
+        }
+    }
+
+    /// Returns the name of the remote computer in the form of `\\{host}`, if any
+    fn unc_host(&self) -> Option<String> {
+        self.host
+            .as_deref()
+            .map(|host| format!(r"\\{}", host.trim_start_matches('\\')))
+    }
+
+    fn sc_exe<'a>(
+        &self,
+        cmd: &str,
+        service_name: &str,
+        args: impl IntoIterator<Item = &'a OsStr>,
+    ) -> io::Result<Output> {
+        let mut command = Command::new(self.config.sc_exe());
+
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if let Some(host) = self.unc_host() {
+            command.arg(host);
+        }
+
+        command.arg(cmd).arg(service_name);
+
+        for arg in args {
+            command.arg(arg);
+        }
+
+        self.runner.run(&mut command, None)
+    }
+
+    fn reg_exe(
+        &self,
+        cmd: &str,
+        key: &str,
+        args: impl IntoIterator<Item = impl AsRef<OsStr>>,
+    ) -> io::Result<Output> {
+        let mut command = Command::new(REG_EXE);
+
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let key = match self.unc_host() {
+            Some(host) => format!(r"{host}\{key}"),
+            None => key.to_string(),
+        };
+        if cmd == "add" {
+            manifest::record(crate::InstalledArtifact::RegistryKey(key.clone()));
+        }
+        command.arg(cmd).arg(key);
+
+        for arg in args {
+            command.arg(arg);
+        }
+
+        self.runner.run(&mut command, None)
+    }
+
+    fn taskkill_exe(&self, pid: u32) -> io::Result<Output> {
+        let mut command = Command::new(TASKKILL_EXE);
+
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if let Some(host) = &self.host {
+            command.arg("/S").arg(host);
+        }
+
+        command.args(["/F", "/T", "/PID"]).arg(pid.to_string());
+        self.runner.run(&mut command, None)
+    }
+}
+
+/// Configuration of an installed sc.exe service as reported by `sc qc` and `sc qdescription`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScInstalledConfig {
+    /// Command line used to launch the service, reported as `BINARY_PATH_NAME`
+    pub binary_path: String,
+
+    /// Start type of the service, or `None` if not recognized
+    pub start_type: Option<WindowsStartType>,
+
+    /// Whether an automatically-started service has its start delayed
+    pub delayed_autostart: bool,
+
+    /// Name displayed for the service, reported as `DISPLAY_NAME`
+    pub display_name: Option<String>,
+
+    /// Account the service runs as, reported as `SERVICE_START_NAME`
+    pub account_name: Option<String>,
+
+    /// Services and load ordering groups the service depends on
+    pub dependencies: Vec<String>,
+
+    /// Description of the service, reported by `sc qdescription`
+    pub description: Option<String>,
 }
 
 impl ServiceManager for ScServiceManager {
     fn available(&self) -> io::Result<bool> {
-        match which::which(SC_EXE) {
-            Ok(_) => Ok(true),
-            Err(which::Error::CannotFindBinaryPath) => Ok(false),
-            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        Ok(self.availability_details()?.available)
+    }
+
+    fn availability_details(&self) -> io::Result<AvailabilityDetails> {
+        match which::which(self.config.sc_exe()) {
+            Ok(_) => {}
+            Err(which::Error::CannotFindBinaryPath) => {
+                return Ok(AvailabilityDetails::unavailable("sc.exe was not found"))
+            }
+            Err(x) => return Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+
+        // Listing services opens the service control manager, which fails when it cannot be
+        // reached, such as on a remote host that refuses the connection
+        let mut command = Command::new(self.config.sc_exe());
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if let Some(host) = self.unc_host() {
+            command.arg(host);
         }
+        command.arg("query");
+
+        let output = self.runner.run(&mut command, None)?;
+        if !output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            return Ok(AvailabilityDetails::unavailable(format!(
+                "Unable to open the service control manager: {stdout}"
+            )));
+        }
+
+        Ok(AvailabilityDetails::available())
     }
 
     fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        crate::utils::validate_environment(&ctx, false)?;
+        crate::utils::capabilities_unsupported(&ctx, "sc.exe")?;
+        crate::utils::backup_unsupported(&ctx, "sc.exe")?;
+        crate::utils::staging_unsupported(&ctx, "sc.exe")?;
+        if self.host.is_none() {
+            utils::check_program(&ctx)?;
+        }
+        if ctx.kind == crate::ServiceKind::Oneshot {
+            return Err(utils::oneshot_unsupported("sc.exe"));
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Sc, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+
         let service_name = ctx.label.to_qualified_name();
+        let service_key = format!(r"{SERVICES_REGISTRY_KEY}\{service_name}");
 
         let service_type = OsString::from(self.config.install.service_type.to_string());
         let error_severity = OsString::from(self.config.install.error_severity.to_string());
@@ -199,56 +707,302 @@ impl ServiceManager for ScServiceManager {
             OsString::from(self.config.install.start_type.to_string())
         };
 
-        // Build our binary including arguments, following similar approach as windows-service-rs
-        let mut binpath = OsString::new();
-        binpath.push(shell_escape::escape(Cow::Borrowed(ctx.program.as_ref())));
-        for arg in ctx.args_iter() {
-            binpath.push(" ");
-            binpath.push(shell_escape::escape(Cow::Borrowed(arg)));
+        let binpath = make_binpath(&self.config.install, &ctx);
+
+        let display_name = OsStr::new(ctx.display_name.as_deref().unwrap_or(&service_name));
+
+        let account = match (&self.config.install.account, &ctx.username) {
+            (Some(account), _) => Some(account.clone()),
+            (None, Some(username)) => Some(WindowsAccount::new(username)),
+            (None, None) => None,
+        };
+
+        let mut extra_args = Vec::new();
+        if let Some(account) = account {
+            let is_managed = account.is_managed();
+
+            // obj= {account_name}
+            extra_args.push(OsString::from("obj="));
+            extra_args.push(OsString::from(account.name));
+
+            // password= {password}
+            if let Some(password) = account.password.filter(|_| !is_managed) {
+                extra_args.push(OsString::from("password="));
+                extra_args.push(OsString::from(password));
+            }
         }
 
-        let display_name = OsStr::new(&service_name);
+        if !self.config.install.dependencies.is_empty() {
+            // depend= {dependency}/{dependency}/...
+            extra_args.push(OsString::from("depend="));
+            extra_args.push(OsString::from(self.config.install.dependencies.join("/")));
+        }
+
+        wrap_output(
+            self.sc_exe(
+                "create",
+                &service_name,
+                [
+                    // type= {service_type}
+                    OsStr::new("type="),
+                    service_type.as_os_str(),
+                    // start= {start_type}
+                    OsStr::new("start="),
+                    start_type.as_os_str(),
+                    // error= {error_severity}
+                    OsStr::new("error="),
+                    error_severity.as_os_str(),
+                    // binpath= "{program} {args}"
+                    OsStr::new("binpath="),
+                    binpath.as_os_str(),
+                    // displayname= {display_name}
+                    OsStr::new("displayname="),
+                    display_name,
+                ]
+                .into_iter()
+                .chain(extra_args.iter().map(OsString::as_os_str)),
+            )?,
+        )?;
+
+        // The service control manager keeps the configuration of the service under its own key
+        manifest::record(crate::InstalledArtifact::RegistryKey(service_key.clone()));
+
+        let description = self.config.install.description.as_ref();
+        if let Some(description) = description.or(ctx.description.as_ref()) {
+            wrap_output(self.sc_exe("description", &service_name, [OsStr::new(description)])?)?;
+        }
+
+        let is_auto_start =
+            ctx.autostart || self.config.install.start_type == WindowsStartType::Auto;
+        if self.config.install.delayed_autostart && is_auto_start {
+            wrap_output(self.sc_exe(
+                "config",
+                &service_name,
+                [OsStr::new("start="), OsStr::new("delayed-auto")],
+            )?)?;
+        }
+
+        if !self.config.install.triggers.is_empty() {
+            let triggers = self
+                .config
+                .install
+                .triggers
+                .iter()
+                .map(|trigger| OsString::from(trigger.to_string()))
+                .collect::<Vec<_>>();
+            wrap_output(self.sc_exe(
+                "triggerinfo",
+                &service_name,
+                triggers.iter().map(OsString::as_os_str),
+            )?)?;
+        }
+
+        if let Some(timeout_ms) = self.config.install.preshutdown_timeout_ms {
+            wrap_output(self.reg_exe(
+                "add",
+                &service_key,
+                [
+                    "/v",
+                    "PreshutdownTimeout",
+                    "/t",
+                    "REG_DWORD",
+                    "/d",
+                    &timeout_ms.to_string(),
+                    "/f",
+                ],
+            )?)?;
+        }
+
+        let install = &self.config.install;
+        let actions = if install.recovery_actions.is_empty() {
+            ctx.restart_policy
+                .and_then(|policy| failure_actions(policy, ctx.start_limit))
+        } else {
+            Some(
+                install
+                    .recovery_actions
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("/"),
+            )
+        };
+
+        if let Some(actions) = actions {
+            // The start limit of the context takes precedence over the config
+            let reset_period = match ctx.start_limit {
+                Some(limit) => limit.interval.as_secs(),
+                None => install
+                    .failure_reset_period_secs
+                    .unwrap_or(DEFAULT_FAILURE_RESET_PERIOD_SECS)
+                    .into(),
+            }
+            .to_string();
+
+            // reset= {reset_period} actions= {action}/{delay}/...
+            let mut args = vec![
+                OsString::from("reset="),
+                OsString::from(reset_period),
+                OsString::from("actions="),
+                OsString::from(actions),
+            ];
+
+            // command= {recovery_command}
+            if let Some(command) = &install.recovery_command {
+                args.push(OsString::from("command="));
+                args.push(OsString::from(command));
+            }
+
+            // reboot= {reboot_message}
+            if let Some(message) = &install.reboot_message {
+                args.push(OsString::from("reboot="));
+                args.push(OsString::from(message));
+            }
+
+            wrap_output(self.sc_exe(
+                "failure",
+                &service_name,
+                args.iter().map(OsString::as_os_str),
+            )?)?;
+
+            // Also apply the recovery actions when the service stops with a non-zero exit code
+            // rather than only when its process crashes
+            wrap_output(self.sc_exe("failureflag", &service_name, [OsStr::new("1")])?)?;
+        }
+
+        if self.config.install.event_log_source || ctx.log_to_syslog.is_some() {
+            let event_log_key = format!(r"{EVENT_LOG_REGISTRY_KEY}\{service_name}");
+            let message_file = self
+                .config
+                .install
+                .event_message_file
+                .as_deref()
+                .unwrap_or(ctx.program.as_path());
+            wrap_output(self.reg_exe(
+                "add",
+                &event_log_key,
+                [
+                    OsStr::new("/v"),
+                    OsStr::new("EventMessageFile"),
+                    OsStr::new("/t"),
+                    OsStr::new("REG_EXPAND_SZ"),
+                    OsStr::new("/d"),
+                    message_file.as_os_str(),
+                    OsStr::new("/f"),
+                ],
+            )?)?;
+            wrap_output(self.reg_exe(
+                "add",
+                &event_log_key,
+                ["/v", "TypesSupported", "/t", "REG_DWORD", "/d", "7", "/f"],
+            )?)?;
+        }
+
+        // sc.exe has no option for environment variables, so they are written to the service's
+        // Environment registry value, which the service control manager passes to the process
+        if let Some(environment) = ctx.environment.as_deref().filter(|env| !env.is_empty()) {
+            wrap_output(self.reg_exe(
+                "add",
+                &service_key,
+                [
+                    OsStr::new("/v"),
+                    OsStr::new("Environment"),
+                    OsStr::new("/t"),
+                    OsStr::new("REG_MULTI_SZ"),
+                    OsStr::new("/d"),
+                    environment_value(environment).as_os_str(),
+                    OsStr::new("/f"),
+                ],
+            )?)?;
+        }
 
-        wrap_output(sc_exe(
-            "create",
-            &service_name,
-            [
-                // type= {service_type}
-                OsStr::new("type="),
-                service_type.as_os_str(),
-                // start= {start_type}
-                OsStr::new("start="),
-                start_type.as_os_str(),
-                // error= {error_severity}
-                OsStr::new("error="),
-                error_severity.as_os_str(),
-                // binpath= "{program} {args}"
-                OsStr::new("binpath="),
-                binpath.as_os_str(),
-                // displayname= {display_name}
-                OsStr::new("displayname="),
-                display_name,
-            ],
-        )?)?;
         Ok(())
     }
 
     fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        crate::utils::ensure_installed(self, &ctx.label)?;
+
         let service_name = ctx.label.to_qualified_name();
-        wrap_output(sc_exe("delete", &service_name, [])?)?;
-        Ok(())
+
+        // Remove any environment variables ahead of deleting the service, ignoring failures as
+        // the value only exists when the service was installed with environment variables
+        let service_key = format!(r"{SERVICES_REGISTRY_KEY}\{service_name}");
+        let _ = self.reg_exe("delete", &service_key, ["/v", "Environment", "/f"]);
+
+        // The service may have been registered as an event log source by the install context
+        // rather than the config, so the registration is removed whenever it exists
+        let event_log_key = format!(r"{EVENT_LOG_REGISTRY_KEY}\{service_name}");
+        let _ = self.reg_exe("delete", &event_log_key, ["/f"]);
+
+        wrap_output(self.sc_exe("delete", &service_name, [])?)?;
+        manifest::remove(ServiceManagerKind::Sc, self.level(), &ctx)?;
+        crate::utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Sc, self.level(), label)
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let service_name = ctx.label.to_qualified_name();
+        let query = format!("*[System[Provider[@Name='{service_name}']]]");
+
+        let mut command = Command::new(WEVTUTIL_EXE);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .args(["qe", EVENT_LOG_NAME])
+            .arg(format!("/q:{query}"))
+            .arg("/f:text");
+
+        if let Some(host) = &self.host {
+            command.arg(format!("/r:{host}"));
+        }
+
+        // Each event spans at least one line, so querying the newest events up to the number of
+        // lines is enough to fill them
+        if let Some(lines) = ctx.lines {
+            command.arg(format!("/c:{lines}")).arg("/rd:true");
+        }
+
+        let output = wrap_output(self.runner.run(&mut command, None)?)?;
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        if ctx.lines.is_some() {
+            text = reverse_events(&text);
+        }
+
+        Ok(utils::tail(&text, ctx.lines))
     }
 
     fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
         let service_name = ctx.label.to_qualified_name();
-        wrap_output(sc_exe("start", &service_name, [])?)?;
-        Ok(())
+        wrap_output(self.sc_exe("start", &service_name, [])?)?;
+        utils::wait_until_active(self, &ctx)
     }
 
     fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
         let service_name = ctx.label.to_qualified_name();
-        wrap_output(sc_exe("stop", &service_name, [])?)?;
-        Ok(())
+
+        if self.config.stop_dependents {
+            let buffer_size = ENUMDEPEND_BUFFER_SIZE.to_string();
+            let output = wrap_output(self.sc_exe(
+                "enumdepend",
+                &service_name,
+                [OsStr::new(&buffer_size)],
+            )?)?;
+
+            // Dependents are listed in the order that they should be stopped, and each must have
+            // stopped before the service itself can be, as sc.exe fails with 1051 otherwise
+            for dependent in parse_running_dependents(&String::from_utf8_lossy(&output.stdout)) {
+                self.stop_and_wait(&dependent, true)?;
+            }
+        }
+
+        self.stop_and_wait(&service_name, false)
     }
 
     fn level(&self) -> ServiceLevel {
@@ -260,18 +1014,25 @@ impl ServiceManager for ScServiceManager {
             ServiceLevel::System => Ok(()),
             ServiceLevel::User => Err(io::Error::new(
                 io::ErrorKind::Unsupported,
-                "sc.exe does not support user-level services",
+                "sc.exe does not support user-level services, which are managed by SchtasksServiceManager instead",
             )),
         }
     }
 
     fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
         let service_name = ctx.label.to_qualified_name();
-        let output = sc_exe("query", &service_name, [])?;
+        let output = self.sc_exe("queryex", &service_name, [])?;
         if !output.status.success() {
             if matches!(output.status.code(), Some(1060)) {
                 // 1060 = The specified service does not exist as an installed service.
-                return Ok(crate::ServiceStatus::NotInstalled);
+                return Ok(crate::ServiceStatus::NotInstalled.into());
             }
             return Err(io::Error::new(
                 io::ErrorKind::Other,
@@ -284,36 +1045,658 @@ impl ServiceManager for ScServiceManager {
         }
 
         let stdout = String::from_utf8_lossy(&output.stdout);
-        let line = stdout.split('\n').find(|line| {
-            line.trim_matches(&['\r', ' '])
-                .to_lowercase()
-                .starts_with("state")
-        });
-        let status = match line {
-            Some(line) if line.contains("RUNNING") => crate::ServiceStatus::Running,
-            _ => crate::ServiceStatus::Stopped(None), // TODO: more statuses?
+        Ok(crate::ServiceStatusDetails {
+            status: parse_status(&stdout),
+            pid: parse_queryex_pid(&stdout),
+            transition: parse_transition(&stdout),
+        })
+    }
+
+    fn pause(&self, ctx: ServicePauseCtx) -> io::Result<()> {
+        let service_name = ctx.label.to_qualified_name();
+        wrap_output(self.sc_exe("pause", &service_name, [])?)?;
+        Ok(())
+    }
+
+    fn resume(&self, ctx: ServiceResumeCtx) -> io::Result<()> {
+        let service_name = ctx.label.to_qualified_name();
+        wrap_output(self.sc_exe("continue", &service_name, [])?)?;
+        Ok(())
+    }
+}
+
+/// Builds the command line of the service including its arguments, following a similar approach
+/// as windows-service-rs
+fn make_binpath(config: &ScInstallConfig, ctx: &ServiceInstallCtx) -> OsString {
+    let mut binpath = OsString::new();
+    binpath.push(shell_escape::escape(Cow::Borrowed(ctx.program.as_ref())));
+    for arg in ctx.args_iter() {
+        binpath.push(" ");
+        binpath.push(shell_escape::escape(Cow::Borrowed(arg)));
+    }
+
+    if let (Some(flag), Some(dir)) = (&config.working_directory_arg, &ctx.working_directory) {
+        binpath.push(" ");
+        binpath.push(shell_escape::escape(Cow::Borrowed(OsStr::new(flag))));
+        binpath.push(" ");
+        binpath.push(shell_escape::escape(Cow::Borrowed(dir.as_os_str())));
+    }
+
+    binpath
+}
+
+/// Parses the output of `sc qc` into the installed configuration of a service
+///
+/// Each field is printed as `KEY : value`, where additional dependencies are printed on their own
+/// lines as `: value`. The description is not part of the output.
+fn parse_qc(stdout: &str) -> ScInstalledConfig {
+    let mut config = ScInstalledConfig::default();
+    let mut last_key = "";
+
+    for line in stdout.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(x) => x,
+            None => continue,
+        };
+        let key = match key.trim() {
+            "" => last_key,
+            key => key,
         };
-        Ok(status)
+        let value = value.trim();
+        last_key = key;
+
+        match key {
+            "BINARY_PATH_NAME" => config.binary_path = value.to_string(),
+            "START_TYPE" => {
+                let code = value.split_whitespace().next();
+                config.start_type = match code {
+                    Some("0") => Some(WindowsStartType::Boot),
+                    Some("1") => Some(WindowsStartType::System),
+                    Some("2") => Some(WindowsStartType::Auto),
+                    Some("3") => Some(WindowsStartType::Demand),
+                    Some("4") => Some(WindowsStartType::Disabled),
+                    _ => None,
+                };
+                config.delayed_autostart = value.contains("DELAYED");
+            }
+            "DISPLAY_NAME" if !value.is_empty() => config.display_name = Some(value.to_string()),
+            "SERVICE_START_NAME" if !value.is_empty() => {
+                config.account_name = Some(value.to_string())
+            }
+            "DEPENDENCIES" if !value.is_empty() => config.dependencies.push(value.to_string()),
+            _ => {}
+        }
+    }
+
+    config
+}
+
+/// Parses the output of `sc qdescription` into the description of a service
+fn parse_qdescription(stdout: &str) -> Option<String> {
+    stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("DESCRIPTION:"))
+        .map(str::trim)
+        .filter(|description| !description.is_empty())
+        .map(ToString::to_string)
+}
+
+/// Parses the output of `sc query` into the status of the service
+fn parse_status(stdout: &str) -> crate::ServiceStatus {
+    // ref: https://learn.microsoft.com/en-us/windows/win32/api/winsvc/ns-winsvc-service_status
+    match parse_state_code(stdout) {
+        // SERVICE_RUNNING
+        Some(4) => crate::ServiceStatus::Running,
+        // SERVICE_PAUSED
+        Some(7) => crate::ServiceStatus::Paused,
+        _ => crate::ServiceStatus::Stopped(None),
     }
 }
 
-fn sc_exe<'a>(
-    cmd: &str,
-    service_name: &str,
-    args: impl IntoIterator<Item = &'a OsStr>,
-) -> io::Result<Output> {
-    let mut command = Command::new(SC_EXE);
+/// Parses the output of `sc query` into the transition the service is in the middle of
+fn parse_transition(stdout: &str) -> Option<crate::ServiceTransition> {
+    match parse_state_code(stdout) {
+        // SERVICE_START_PENDING
+        Some(2) => Some(crate::ServiceTransition::Starting),
+        // SERVICE_STOP_PENDING
+        Some(3) => Some(crate::ServiceTransition::Stopping),
+        // SERVICE_CONTINUE_PENDING
+        Some(5) => Some(crate::ServiceTransition::Resuming),
+        // SERVICE_PAUSE_PENDING
+        Some(6) => Some(crate::ServiceTransition::Pausing),
+        _ => None,
+    }
+}
 
-    command
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+/// Parses the numeric code of the service's state from the output of `sc query`
+///
+/// The names of the fields and states are translated on non-English versions of Windows, so the
+/// state is determined by its numeric code. The `STATE` field is the second field of the output,
+/// which is used to find it when the field is not named `STATE`.
+fn parse_state_code(stdout: &str) -> Option<u32> {
+    let fields = stdout
+        .lines()
+        .filter(|line| line.starts_with([' ', '\t']))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .filter(|(key, _)| !key.is_empty())
+        .collect::<Vec<_>>();
 
-    command.arg(cmd).arg(service_name);
+    fields
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case("state"))
+        .or_else(|| fields.get(1))
+        .and_then(|(_, value)| value.split_whitespace().next())
+        .and_then(|code| code.parse::<u32>().ok())
+}
 
-    for arg in args {
-        command.arg(arg);
+/// Parses the names of the dependent services that are not stopped from the output of
+/// `sc enumdepend`, which lists each service as `SERVICE_NAME: {name}` followed by its status
+fn parse_running_dependents(stdout: &str) -> Vec<String> {
+    let mut dependents = Vec::new();
+    let mut current: Option<(&str, String)> = None;
+
+    for line in stdout.lines() {
+        if let Some(name) = line.strip_prefix("SERVICE_NAME:") {
+            dependents.extend(current.take());
+            current = Some((name.trim(), String::new()));
+        } else if let Some((_, status)) = current.as_mut() {
+            status.push_str(line);
+            status.push('\n');
+        }
     }
+    dependents.extend(current);
 
-    command.output()
+    dependents
+        .into_iter()
+        // SERVICE_STOPPED
+        .filter(|(_, status)| parse_state_code(status) != Some(1))
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+/// Parses the id of the service's process from the output of `sc queryex`, returning `None` if
+/// the service has no running process
+fn parse_queryex_pid(stdout: &str) -> Option<u32> {
+    stdout
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim() == "PID")
+        .and_then(|(_, value)| value.trim().parse::<u32>().ok())
+        .filter(|pid| *pid != 0)
+}
+
+/// Converts a restart policy into the value of `actions=` for `sc failure`, returning `None` if
+/// the service should not be restarted
+///
+/// The service is restarted as many times as the burst of its start limit, if any.
+///
+/// The service control manager only performs recovery actions when a service fails, so
+/// [`RestartPolicy::Always`] restarts the service on failure just like
+/// [`RestartPolicy::OnFailure`].
+fn failure_actions(policy: RestartPolicy, start_limit: Option<StartLimit>) -> Option<String> {
+    let delay_secs = match policy {
+        RestartPolicy::Never => return None,
+        RestartPolicy::Always { delay_secs } | RestartPolicy::OnFailure { delay_secs } => {
+            delay_secs.unwrap_or(0)
+        }
+    };
+
+    let restart = ScRecoveryAction::Restart { delay_secs };
+    let mut actions = match start_limit {
+        Some(limit) => vec![restart; limit.burst as usize],
+        None => vec![restart; FAILURE_RESTART_ATTEMPTS],
+    };
+
+    // The last action is repeated for any further failures, so a start limit ends with its action
+    match start_limit.map(|limit| limit.action) {
+        Some(StartLimitAction::None) => actions.push(ScRecoveryAction::None { delay_secs: 0 }),
+        Some(StartLimitAction::Reboot) => actions.push(ScRecoveryAction::Reboot { delay_secs: 0 }),
+        None => {}
+    }
+
+    Some(
+        actions
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+/// Reverses the order of the events within the text output of `wevtutil qe`, where each event
+/// begins with a line of `Event[{index}]:`
+fn reverse_events(text: &str) -> String {
+    let mut events: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match events.last_mut() {
+            Some(event) if !line.starts_with("Event[") => {
+                event.push_str(line);
+                event.push('\n');
+            }
+            _ => events.push(format!("{line}\n")),
+        }
+    }
+
+    events.into_iter().rev().collect()
+}
+
+/// Converts environment variables into the data of a `REG_MULTI_SZ` value for `reg add`, where
+/// each string is separated by the default separator of `\0`
+fn environment_value(environment: &[(OsString, OsString)]) -> OsString {
+    let mut value = OsString::new();
+    for (i, (var, val)) in environment.iter().enumerate() {
+        if i > 0 {
+            value.push("\\0");
+        }
+        value.push(var);
+        value.push("=");
+        value.push(val);
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::tests::ScriptedRunner;
+    use indoc::indoc;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_make_binpath_with_working_directory_arg() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse().unwrap(),
+            program: PathBuf::from(r"C:\org.example\my_service.exe"),
+            args: vec![OsString::from("--arg"), OsString::from("value")],
+            contents: None,
+            username: None,
+            working_directory: Some(PathBuf::from(r"C:\org.example\data")),
+            environment: None,
+            autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+
+        assert_eq!(
+            make_binpath(&ScInstallConfig::default(), &ctx),
+            OsString::from(r"C:\org.example\my_service.exe --arg value")
+        );
+
+        let config = ScInstallConfig {
+            working_directory_arg: Some(String::from("--cwd")),
+            ..Default::default()
+        };
+        assert_eq!(
+            make_binpath(&config, &ctx),
+            OsString::from(r"C:\org.example\my_service.exe --arg value --cwd C:\org.example\data")
+        );
+    }
+
+    #[test]
+    fn test_windows_account_is_managed() {
+        assert!(WindowsAccount::local_system().is_managed());
+        assert!(WindowsAccount::local_service().is_managed());
+        assert!(WindowsAccount::network_service().is_managed());
+        assert!(WindowsAccount::virtual_account("my_application").is_managed());
+        assert!(WindowsAccount::new(r"EXAMPLE\my-gmsa$").is_managed());
+        assert!(!WindowsAccount::new(r"EXAMPLE\user")
+            .with_password("secret")
+            .is_managed());
+    }
+
+    #[test]
+    fn test_trigger_display() {
+        assert_eq!(
+            ScTrigger::StartOnNetworkAvailable.to_string(),
+            "start/networkon"
+        );
+        assert_eq!(ScTrigger::StopOnNetworkLost.to_string(), "stop/networkoff");
+        assert_eq!(
+            ScTrigger::StartOnDeviceArrival(String::from("{53f56307-b6bf-11d0-94f2-00a0c91efb8b}"))
+                .to_string(),
+            "start/deviceinterfacearrival/{53f56307-b6bf-11d0-94f2-00a0c91efb8b}"
+        );
+    }
+
+    #[test]
+    fn test_recovery_action_display() {
+        let actions = [
+            ScRecoveryAction::Restart { delay_secs: 5 },
+            ScRecoveryAction::RunCommand { delay_secs: 0 },
+            ScRecoveryAction::Reboot { delay_secs: 60 },
+            ScRecoveryAction::None { delay_secs: 0 },
+        ];
+        assert_eq!(
+            actions.map(|action| action.to_string()).join("/"),
+            "restart/5000/run/0/reboot/60000//0"
+        );
+    }
+
+    #[test]
+    fn test_unc_host() {
+        assert_eq!(ScServiceManager::system().unc_host(), None);
+        assert_eq!(
+            ScServiceManager::remote("server01").unc_host(),
+            Some(String::from(r"\\server01"))
+        );
+        assert_eq!(
+            ScServiceManager::remote(r"\\server01").unc_host(),
+            Some(String::from(r"\\server01"))
+        );
+    }
+
+    #[test]
+    fn test_reverse_events() {
+        let text = indoc! {r#"
+            Event[0]:
+              Source: org.example.my_application
+              Description: second
+            Event[1]:
+              Source: org.example.my_application
+              Description: first
+        "#};
+        assert_eq!(
+            reverse_events(text),
+            indoc! {r#"
+                Event[1]:
+                  Source: org.example.my_application
+                  Description: first
+                Event[0]:
+                  Source: org.example.my_application
+                  Description: second
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_environment_value() {
+        let environment = vec![
+            (OsString::from("RUST_LOG"), OsString::from("debug")),
+            (OsString::from("PORT"), OsString::from("8080")),
+        ];
+        assert_eq!(
+            environment_value(&environment),
+            r"RUST_LOG=debug\0PORT=8080"
+        );
+    }
+
+    #[test]
+    fn test_parse_qc() {
+        let output = indoc! {r#"
+            [SC] QueryServiceConfig SUCCESS
+
+            SERVICE_NAME: org.example.my_application
+                    TYPE               : 10  WIN32_OWN_PROCESS
+                    START_TYPE         : 2   AUTO_START  (DELAYED)
+                    ERROR_CONTROL      : 1   NORMAL
+                    BINARY_PATH_NAME   : "C:\Program Files\app.exe" --flag
+                    LOAD_ORDER_GROUP   :
+                    TAG                : 0
+                    DISPLAY_NAME       : org.example.my_application
+                    DEPENDENCIES       : Tcpip
+                                       : my-database
+                    SERVICE_START_NAME : NT AUTHORITY\LocalService
+        "#};
+
+        assert_eq!(
+            parse_qc(output),
+            ScInstalledConfig {
+                binary_path: String::from(r#""C:\Program Files\app.exe" --flag"#),
+                start_type: Some(WindowsStartType::Auto),
+                delayed_autostart: true,
+                display_name: Some(String::from("org.example.my_application")),
+                account_name: Some(String::from(r"NT AUTHORITY\LocalService")),
+                dependencies: vec![String::from("Tcpip"), String::from("my-database")],
+                description: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_qdescription() {
+        let output = indoc! {r#"
+            [SC] QueryServiceConfig2 SUCCESS
+
+            SERVICE_NAME: org.example.my_application
+            DESCRIPTION:  My application
+        "#};
+        assert_eq!(
+            parse_qdescription(output),
+            Some(String::from("My application"))
+        );
+        assert_eq!(
+            parse_qdescription(&output.replace("  My application", "")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let output = indoc! {r#"
+
+            SERVICE_NAME: org.example.my_application
+                    TYPE               : 10  WIN32_OWN_PROCESS
+                    STATE              : 7  PAUSED
+                                            (STOPPABLE, PAUSABLE, ACCEPTS_SHUTDOWN)
+                    WIN32_EXIT_CODE    : 0  (0x0)
+                    SERVICE_EXIT_CODE  : 0  (0x0)
+                    CHECKPOINT         : 0x0
+                    WAIT_HINT          : 0x0
+        "#};
+        assert_eq!(parse_status(output), crate::ServiceStatus::Paused);
+        assert_eq!(
+            parse_status(&output.replace("7  PAUSED", "4  RUNNING")),
+            crate::ServiceStatus::Running
+        );
+        assert_eq!(
+            parse_status(&output.replace("7  PAUSED", "1  STOPPED")),
+            crate::ServiceStatus::Stopped(None)
+        );
+    }
+
+    #[test]
+    fn test_wait_for_stop_polls_while_stop_pending() {
+        let stop_pending = indoc! {r#"
+
+            SERVICE_NAME: org.example.my_application
+                    TYPE               : 10  WIN32_OWN_PROCESS
+                    STATE              : 3  STOP_PENDING
+                                            (STOPPABLE, NOT_PAUSABLE, ACCEPTS_SHUTDOWN)
+                    WIN32_EXIT_CODE    : 0  (0x0)
+                    SERVICE_EXIT_CODE  : 0  (0x0)
+                    CHECKPOINT         : 0x2
+                    WAIT_HINT          : 0x7530
+                    PID                : 4321
+                    FLAGS              :
+        "#};
+        let stopped = stop_pending
+            .replace("3  STOP_PENDING", "1  STOPPED")
+            .replace("4321", "0");
+        let runner = Arc::new(ScriptedRunner::new([stop_pending, &stopped]));
+        let manager = ScServiceManager::system()
+            .with_runner(Arc::clone(&runner) as Arc<dyn crate::CommandRunner>);
+
+        manager
+            .wait_for_stop("org.example.my_application", Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(runner.commands.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_stop_waits_for_dependents() {
+        let dependents = indoc! {r#"
+            Enum: entriesRead = 1
+
+            SERVICE_NAME: org.example.web
+            DISPLAY_NAME: org.example.web
+                    TYPE               : 10  WIN32_OWN_PROCESS
+                    STATE              : 4  RUNNING
+                                            (STOPPABLE, NOT_PAUSABLE, ACCEPTS_SHUTDOWN)
+                    WIN32_EXIT_CODE    : 0  (0x0)
+                    SERVICE_EXIT_CODE  : 0  (0x0)
+                    CHECKPOINT         : 0x0
+                    WAIT_HINT          : 0x0
+        "#};
+        let stopped = indoc! {r#"
+
+            SERVICE_NAME: org.example.web
+                    TYPE               : 10  WIN32_OWN_PROCESS
+                    STATE              : 1  STOPPED
+                    WIN32_EXIT_CODE    : 0  (0x0)
+                    SERVICE_EXIT_CODE  : 0  (0x0)
+                    CHECKPOINT         : 0x0
+                    WAIT_HINT          : 0x0
+                    PID                : 0
+                    FLAGS              :
+        "#};
+        let runner = Arc::new(ScriptedRunner::new([dependents, "", stopped, ""]));
+        let manager = ScServiceManager::system()
+            .with_config(ScConfig {
+                stop_dependents: true,
+                ..Default::default()
+            })
+            .with_runner(Arc::clone(&runner) as Arc<dyn crate::CommandRunner>);
+
+        manager
+            .stop(ServiceStopCtx {
+                label: "org.example.db".parse().unwrap(),
+            })
+            .unwrap();
+
+        let commands = runner.commands.lock().unwrap();
+        let commands = commands
+            .iter()
+            .map(|x| (x[1].as_str(), x[2].as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            commands,
+            [
+                ("enumdepend", "org.example.db"),
+                ("stop", "org.example.web"),
+                ("queryex", "org.example.web"),
+                ("stop", "org.example.db"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_running_dependents() {
+        let output = indoc! {r#"
+            Enum: entriesRead = 2
+
+            SERVICE_NAME: org.example.web
+            DISPLAY_NAME: org.example.web
+                    TYPE               : 10  WIN32_OWN_PROCESS
+                    STATE              : 4  RUNNING
+                                            (STOPPABLE, NOT_PAUSABLE, ACCEPTS_SHUTDOWN)
+                    WIN32_EXIT_CODE    : 0  (0x0)
+                    SERVICE_EXIT_CODE  : 0  (0x0)
+                    CHECKPOINT         : 0x0
+                    WAIT_HINT          : 0x0
+
+            SERVICE_NAME: org.example.worker
+            DISPLAY_NAME: org.example.worker
+                    TYPE               : 10  WIN32_OWN_PROCESS
+                    STATE              : 1  STOPPED
+                    WIN32_EXIT_CODE    : 0  (0x0)
+                    SERVICE_EXIT_CODE  : 0  (0x0)
+                    CHECKPOINT         : 0x0
+                    WAIT_HINT          : 0x0
+        "#};
+        assert_eq!(
+            parse_running_dependents(output),
+            vec![String::from("org.example.web")]
+        );
+    }
+
+    #[test]
+    fn test_parse_queryex_pid() {
+        let output = indoc! {r#"
+
+            SERVICE_NAME: org.example.my_application
+                    TYPE               : 10  WIN32_OWN_PROCESS
+                    STATE              : 3  STOP_PENDING
+                                            (STOPPABLE, NOT_PAUSABLE, ACCEPTS_SHUTDOWN)
+                    WIN32_EXIT_CODE    : 0  (0x0)
+                    SERVICE_EXIT_CODE  : 0  (0x0)
+                    CHECKPOINT         : 0x2
+                    WAIT_HINT          : 0x7530
+                    PID                : 4321
+                    FLAGS              :
+        "#};
+        assert_eq!(parse_queryex_pid(output), Some(4321));
+        assert_eq!(
+            parse_transition(output),
+            Some(crate::ServiceTransition::Stopping)
+        );
+        assert_eq!(parse_queryex_pid(&output.replace("4321", "0")), None);
+    }
+
+    #[test]
+    fn test_parse_status_localized() {
+        let output = indoc! {r#"
+
+            SERVICE_NAME: org.example.my_application
+                    TYP                : 10  WIN32_OWN_PROCESS
+                    STATUS             : 4  WIRD_AUSGEFÜHRT
+                                            (STOPPABLE, NOT_PAUSABLE, ACCEPTS_SHUTDOWN)
+                    WIN32_EXITCODE     : 0  (0x0)
+                    DIENST_EXITCODE    : 0  (0x0)
+                    CHECKPOINT         : 0x0
+                    WAIT_HINT          : 0x0
+        "#};
+        assert_eq!(parse_status(output), crate::ServiceStatus::Running);
+        assert_eq!(
+            parse_status(&output.replace("4  WIRD_AUSGEFÜHRT", "1  BEENDET")),
+            crate::ServiceStatus::Stopped(None)
+        );
+    }
+
+    #[test]
+    fn test_failure_actions() {
+        assert_eq!(failure_actions(RestartPolicy::Never, None), None);
+        assert_eq!(
+            failure_actions(RestartPolicy::OnFailure { delay_secs: None }, None),
+            Some(String::from("restart/0/restart/0/restart/0"))
+        );
+        assert_eq!(
+            failure_actions(
+                RestartPolicy::Always {
+                    delay_secs: Some(5)
+                },
+                None
+            ),
+            Some(String::from("restart/5000/restart/5000/restart/5000"))
+        );
+        assert_eq!(
+            failure_actions(
+                RestartPolicy::OnFailure { delay_secs: None },
+                Some(StartLimit {
+                    interval: std::time::Duration::from_secs(60),
+                    burst: 2,
+                    action: StartLimitAction::Reboot,
+                })
+            ),
+            Some(String::from("restart/0/restart/0/reboot/0"))
+        );
+    }
 }
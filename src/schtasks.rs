@@ -0,0 +1,529 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, CommandRunner, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceManager,
+    ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx, SharedCommandRunner,
+};
+use std::{
+    ffi::OsStr,
+    io::{self, Write},
+    process::{Command, Output, Stdio},
+};
+use xml::common::XmlVersion;
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+static SCHTASKS_EXE: &str = "schtasks.exe";
+
+/// Namespace of the task definition schema understood by Task Scheduler
+const TASK_NAMESPACE: &str = "http://schemas.microsoft.com/windows/2004/02/mit/task";
+
+/// Well-known SID of the LocalSystem account that system-level tasks run as by default
+const LOCAL_SYSTEM_SID: &str = "S-1-5-18";
+
+/// Maximum number of times Task Scheduler restarts a failed task
+const MAX_RESTART_COUNT: u32 = 999;
+
+/// Configuration settings tied to scheduled tasks
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchtasksConfig {
+    pub install: SchtasksInstallConfig,
+}
+
+/// Configuration settings used when creating scheduled tasks
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchtasksInstallConfig {
+    /// Folder within the Task Scheduler library where tasks are created, such as `MyCompany`,
+    /// defaulting to the root folder
+    pub folder: Option<String>,
+
+    /// If true, tasks run with the highest privileges available to their account rather than
+    /// with least privilege
+    pub highest_privileges: bool,
+
+    /// Description written to the registration info of the task
+    pub description: Option<String>,
+}
+
+/// Implementation of [`ServiceManager`] for the Windows
+/// [Task Scheduler](https://learn.microsoft.com/en-us/windows/win32/taskschd/task-scheduler-start-page)
+/// via `schtasks.exe`, which serves as the substitute for user-level services on Windows
+///
+/// System-level tasks are triggered at startup and run as `LocalSystem` unless a username is
+/// provided, while user-level tasks are triggered when the current user logs on and run within
+/// their session. Task Scheduler has no notion of environment variables for a task, so the
+/// `environment` of [`ServiceInstallCtx`] is not applied.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchtasksServiceManager {
+    /// Whether or not this manager is operating at the user-level
+    pub user: bool,
+
+    /// Configuration settings tied to scheduled tasks
+    pub config: SchtasksConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl SchtasksServiceManager {
+    /// Creates a new manager instance working with system-level tasks
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new manager instance working with user-level tasks
+    pub fn user() -> Self {
+        Self::default().into_user()
+    }
+
+    /// Change manager to work with system-level tasks
+    pub fn into_system(self) -> Self {
+        Self {
+            config: self.config,
+            user: false,
+            runner: self.runner,
+        }
+    }
+
+    /// Change manager to work with user-level tasks
+    pub fn into_user(self) -> Self {
+        Self {
+            config: self.config,
+            user: true,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: SchtasksConfig) -> Self {
+        Self {
+            config,
+            user: self.user,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Returns the path of the task within the Task Scheduler library
+    fn task_name(&self, label: &crate::ServiceLabel) -> String {
+        match &self.config.install.folder {
+            Some(folder) => format!(
+                "\\{}\\{}",
+                folder.trim_matches('\\'),
+                label.to_qualified_name()
+            ),
+            None => format!("\\{}", label.to_qualified_name()),
+        }
+    }
+}
+
+impl ServiceManager for SchtasksServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        match which::which(SCHTASKS_EXE) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        crate::utils::capabilities_unsupported(&ctx, "Task Scheduler")?;
+        crate::utils::require_utf8(&ctx, "Task Scheduler")?;
+        crate::utils::backup_unsupported(&ctx, "Task Scheduler")?;
+        crate::utils::staging_unsupported(&ctx, "Task Scheduler")?;
+        crate::utils::check_program(&ctx)?;
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Schtasks, self.level(), &ctx);
+        let ctx = crate::utils::install_binary(ctx)?;
+        let task_name = self.task_name(&ctx.label);
+        let xml = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            None => {
+                let user = if self.user {
+                    Some(current_user()?)
+                } else {
+                    None
+                };
+                make_task_xml(&self.config.install, user.as_deref(), &ctx)?
+            }
+        };
+
+        // schtasks.exe only reliably reads task definitions encoded as UTF-16 with a byte order mark
+        let path = std::env::temp_dir().join(format!("{}.xml", ctx.label.to_qualified_name()));
+        let mut file = std::fs::File::create(&path)?;
+        file.write_all(&[0xFF, 0xFE])?;
+        for unit in xml.encode_utf16() {
+            file.write_all(&unit.to_le_bytes())?;
+        }
+        drop(file);
+
+        let result = schtasks(
+            &*self.runner,
+            "/Create",
+            &task_name,
+            [OsStr::new("/XML"), path.as_os_str(), OsStr::new("/F")],
+        );
+        let _ = std::fs::remove_file(&path);
+        wrap_output(result?)?;
+        Ok(())
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        crate::utils::ensure_installed(self, &ctx.label)?;
+
+        let task_name = self.task_name(&ctx.label);
+
+        // Ending a task that is not running fails, so any failure is ignored before deleting it
+        let _ = schtasks(&*self.runner, "/End", &task_name, []);
+        wrap_output(schtasks(
+            &*self.runner,
+            "/Delete",
+            &task_name,
+            [OsStr::new("/F")],
+        )?)?;
+        manifest::remove(ServiceManagerKind::Schtasks, self.level(), &ctx)?;
+        crate::utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Schtasks, self.level(), label)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        wrap_output(schtasks(
+            &*self.runner,
+            "/Run",
+            &self.task_name(&ctx.label),
+            [],
+        )?)?;
+        crate::utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        wrap_output(schtasks(
+            &*self.runner,
+            "/End",
+            &self.task_name(&ctx.label),
+            [],
+        )?)?;
+        Ok(())
+    }
+
+    fn level(&self) -> ServiceLevel {
+        if self.user {
+            ServiceLevel::User
+        } else {
+            ServiceLevel::System
+        }
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => self.user = false,
+            ServiceLevel::User => self.user = true,
+        }
+
+        Ok(())
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        let task_name = self.task_name(&ctx.label);
+        let output = schtasks(
+            &*self.runner,
+            "/Query",
+            &task_name,
+            [OsStr::new("/FO"), OsStr::new("CSV"), OsStr::new("/NH")],
+        )?;
+
+        // Querying a task only fails when the task does not exist
+        if !output.status.success() {
+            return Ok(crate::ServiceStatus::NotInstalled);
+        }
+
+        Ok(parse_query_status(&String::from_utf8_lossy(&output.stdout)))
+    }
+}
+
+fn schtasks<'a>(
+    runner: &dyn CommandRunner,
+    cmd: &str,
+    task_name: &str,
+    args: impl IntoIterator<Item = &'a OsStr>,
+) -> io::Result<Output> {
+    let mut command = Command::new(SCHTASKS_EXE);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg(cmd)
+        .arg("/TN")
+        .arg(task_name);
+
+    for arg in args {
+        command.arg(arg);
+    }
+
+    runner.run(&mut command, None)
+}
+
+/// Returns the account of the current user as `DOMAIN\user`
+fn current_user() -> io::Result<String> {
+    let username = std::env::var("USERNAME").map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "Unable to determine the current user from %USERNAME%",
+        )
+    })?;
+
+    Ok(match std::env::var("USERDOMAIN") {
+        Ok(domain) => format!("{domain}\\{username}"),
+        Err(_) => username,
+    })
+}
+
+/// Parses the status column of `schtasks /Query /FO CSV /NH`, which looks like
+/// `"\name","N/A","Running"`
+///
+/// The status is localized by Windows, so anything other than the English `Running` is reported
+/// as stopped.
+fn parse_query_status(output: &str) -> crate::ServiceStatus {
+    let running = output
+        .lines()
+        .filter_map(|line| line.trim().rsplit(',').next())
+        .any(|status| status.trim_matches('"') == "Running");
+
+    if running {
+        crate::ServiceStatus::Running
+    } else {
+        crate::ServiceStatus::Stopped(None)
+    }
+}
+
+/// Converts seconds into the minimum restart interval of one minute supported by Task Scheduler,
+/// formatted as an XML duration
+fn restart_interval(delay_secs: Option<u32>) -> String {
+    let minutes = (delay_secs.unwrap_or(0).saturating_add(59) / 60).max(1);
+    format!("PT{minutes}M")
+}
+
+fn make_task_xml(
+    config: &SchtasksInstallConfig,
+    user: Option<&str>,
+    ctx: &ServiceInstallCtx,
+) -> io::Result<String> {
+    let mut buffer = Vec::new();
+    let mut writer = EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(&mut buffer);
+
+    write_event(
+        &mut writer,
+        XmlEvent::StartDocument {
+            version: XmlVersion::Version10,
+            encoding: Some("UTF-16"),
+            standalone: None,
+        },
+    )?;
+    write_event(
+        &mut writer,
+        XmlEvent::start_element("Task")
+            .attr("version", "1.2")
+            .default_ns(TASK_NAMESPACE),
+    )?;
+
+    write_event(&mut writer, XmlEvent::start_element("RegistrationInfo"))?;
+    let description = config
+        .description
+        .clone()
+        .or_else(|| ctx.description.clone())
+        .unwrap_or_else(|| format!("Service for {}", ctx.label.to_qualified_name()));
+    write_element(&mut writer, "Description", &description)?;
+    write_event(&mut writer, XmlEvent::end_element())?;
+
+    // User-level tasks start when the user logs on, while system-level tasks start at boot
+    write_event(&mut writer, XmlEvent::start_element("Triggers"))?;
+    match user {
+        Some(user) => {
+            write_event(&mut writer, XmlEvent::start_element("LogonTrigger"))?;
+            write_element(&mut writer, "Enabled", &ctx.autostart.to_string())?;
+            write_element(&mut writer, "UserId", user)?;
+        }
+        None => {
+            write_event(&mut writer, XmlEvent::start_element("BootTrigger"))?;
+            write_element(&mut writer, "Enabled", &ctx.autostart.to_string())?;
+        }
+    }
+    write_event(&mut writer, XmlEvent::end_element())?;
+    write_event(&mut writer, XmlEvent::end_element())?;
+
+    let run_level = if config.highest_privileges {
+        "HighestAvailable"
+    } else {
+        "LeastPrivilege"
+    };
+    write_event(&mut writer, XmlEvent::start_element("Principals"))?;
+    write_event(
+        &mut writer,
+        XmlEvent::start_element("Principal").attr("id", "Author"),
+    )?;
+    match (user, &ctx.username) {
+        (Some(user), _) => {
+            write_element(&mut writer, "UserId", user)?;
+            write_element(&mut writer, "LogonType", "InteractiveToken")?;
+        }
+        // Without a stored password, other accounts can only run the task without access to
+        // network resources
+        (None, Some(username)) => {
+            write_element(&mut writer, "UserId", username)?;
+            write_element(&mut writer, "LogonType", "S4U")?;
+        }
+        (None, None) => {
+            write_element(&mut writer, "UserId", LOCAL_SYSTEM_SID)?;
+        }
+    }
+    write_element(&mut writer, "RunLevel", run_level)?;
+    write_event(&mut writer, XmlEvent::end_element())?;
+    write_event(&mut writer, XmlEvent::end_element())?;
+
+    write_event(&mut writer, XmlEvent::start_element("Settings"))?;
+    write_element(&mut writer, "MultipleInstancesPolicy", "IgnoreNew")?;
+    write_element(&mut writer, "DisallowStartIfOnBatteries", "false")?;
+    write_element(&mut writer, "StopIfGoingOnBatteries", "false")?;
+    write_element(&mut writer, "AllowStartOnDemand", "true")?;
+    write_element(&mut writer, "ExecutionTimeLimit", "PT0S")?;
+
+    // Task Scheduler only restarts tasks that fail, so both policies restart the task on failure
+    match ctx.restart_policy {
+        Some(RestartPolicy::Always { delay_secs })
+        | Some(RestartPolicy::OnFailure { delay_secs }) => {
+            write_event(&mut writer, XmlEvent::start_element("RestartOnFailure"))?;
+            write_element(&mut writer, "Interval", &restart_interval(delay_secs))?;
+            write_element(&mut writer, "Count", &MAX_RESTART_COUNT.to_string())?;
+            write_event(&mut writer, XmlEvent::end_element())?;
+        }
+        None | Some(RestartPolicy::Never) => {}
+    }
+    write_event(&mut writer, XmlEvent::end_element())?;
+
+    write_event(
+        &mut writer,
+        XmlEvent::start_element("Actions").attr("Context", "Author"),
+    )?;
+    write_event(&mut writer, XmlEvent::start_element("Exec"))?;
+    write_element(&mut writer, "Command", &ctx.program.to_string_lossy())?;
+    if !ctx.args.is_empty() {
+        let args = ctx
+            .args_iter()
+            .map(|arg| crate::utils::windows_quote(&arg.to_string_lossy()).into_owned())
+            .collect::<Vec<String>>()
+            .join(" ");
+        write_element(&mut writer, "Arguments", &args)?;
+    }
+    if let Some(dir) = &ctx.working_directory {
+        write_element(&mut writer, "WorkingDirectory", &dir.to_string_lossy())?;
+    }
+    write_event(&mut writer, XmlEvent::end_element())?;
+    write_event(&mut writer, XmlEvent::end_element())?;
+
+    write_event(&mut writer, XmlEvent::end_element())?;
+    drop(writer);
+
+    String::from_utf8(buffer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_event<'a, W: Write>(
+    writer: &mut EventWriter<W>,
+    event: impl Into<XmlEvent<'a>>,
+) -> io::Result<()> {
+    writer.write(event).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("Writing task definition failed: {}", e),
+        )
+    })
+}
+
+fn write_element<W: Write>(writer: &mut EventWriter<W>, name: &str, value: &str) -> io::Result<()> {
+    write_event(writer, XmlEvent::start_element(name))?;
+    write_event(writer, XmlEvent::characters(value))?;
+    write_event(writer, XmlEvent::end_element())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{ffi::OsString, path::PathBuf};
+
+    fn install_ctx() -> ServiceInstallCtx {
+        ServiceInstallCtx {
+            args: vec![OsString::from("--name"), OsString::from("my service")],
+            working_directory: Some(PathBuf::from(r"C:\ProgramData\My Service")),
+            autostart: true,
+            restart_policy: Some(RestartPolicy::OnFailure {
+                delay_secs: Some(90),
+            }),
+            ..crate::utils::tests::install_ctx(PathBuf::from(
+                r"C:\Program Files\My Service\my_service.exe",
+            ))
+        }
+    }
+
+    #[test]
+    fn test_make_task_xml_for_user() {
+        let xml = make_task_xml(
+            &SchtasksInstallConfig::default(),
+            Some(r"DESKTOP\alice"),
+            &install_ctx(),
+        )
+        .unwrap();
+
+        assert!(xml.starts_with(r#"<?xml version="1.0" encoding="UTF-16"?>"#));
+        assert!(xml.contains(
+            r#"<Task xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task" version="1.2">"#
+        ));
+        assert!(xml.contains(
+            "<LogonTrigger>\n      <Enabled>true</Enabled>\n      <UserId>DESKTOP\\alice</UserId>"
+        ));
+        assert!(xml.contains("<LogonType>InteractiveToken</LogonType>"));
+        assert!(xml.contains("<RunLevel>LeastPrivilege</RunLevel>"));
+        assert!(xml.contains("<Interval>PT2M</Interval>"));
+        assert!(xml.contains(r"<Command>C:\Program Files\My Service\my_service.exe</Command>"));
+        assert!(xml.contains(r#"<Arguments>--name "my service"</Arguments>"#));
+        assert!(xml.contains(r"<WorkingDirectory>C:\ProgramData\My Service</WorkingDirectory>"));
+    }
+
+    #[test]
+    fn test_make_task_xml_for_system() {
+        let ctx = ServiceInstallCtx {
+            autostart: false,
+            restart_policy: None,
+            ..install_ctx()
+        };
+        let xml = make_task_xml(&SchtasksInstallConfig::default(), None, &ctx).unwrap();
+
+        assert!(xml.contains("<BootTrigger>\n      <Enabled>false</Enabled>\n    </BootTrigger>"));
+        assert!(xml.contains("<UserId>S-1-5-18</UserId>"));
+        assert!(!xml.contains("RestartOnFailure"));
+    }
+
+    #[test]
+    fn test_parse_query_status() {
+        assert_eq!(
+            parse_query_status("\"\\org.example.my_service\",\"N/A\",\"Running\"\r\n"),
+            crate::ServiceStatus::Running
+        );
+        assert_eq!(
+            parse_query_status("\"\\org.example.my_service\",\"N/A\",\"Ready\"\r\n"),
+            crate::ServiceStatus::Stopped(None)
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use super::{ServiceLabel, ServiceManagerKind};
+use crate::utils;
+use std::{
+    io,
+    path::{Component, Path},
+};
+
+/// Context provided to the export function of [`ServiceManager`](crate::ServiceManager)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceExportCtx {
+    /// Label associated with the service
+    ///
+    /// E.g. `rocks.distant.manager`
+    pub label: ServiceLabel,
+}
+
+/// Complete definition of an installed service as generated for its service manager, used to back
+/// up a service or move it to another machine of the same platform
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ServiceSnapshot {
+    /// Kind of service manager that the service was exported from, which must match the kind of
+    /// service manager that imports it
+    pub kind: ServiceManagerKind,
+
+    /// Qualified name of the label associated with the service
+    ///
+    /// E.g. `rocks.distant.manager`
+    pub label: String,
+
+    /// Whether the service starts automatically upon OS reboot
+    pub autostart: bool,
+
+    /// Files that define the service, such as a unit file or plist
+    pub files: Vec<ServiceSnapshotFile>,
+}
+
+/// File captured within a [`ServiceSnapshot`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct ServiceSnapshotFile {
+    /// Name of the file relative to the directory of the service manager where it is installed,
+    /// so that a snapshot can be imported on a machine where that directory differs
+    pub name: String,
+
+    /// Contents of the file
+    pub contents: String,
+
+    /// Unix permissions of the file, of which only the read, write, and execute bits are applied
+    pub mode: u32,
+}
+
+impl ServiceSnapshot {
+    /// Parses the label of the service, failing with [`io::ErrorKind::InvalidInput`] if it is not
+    /// a valid label
+    pub(crate) fn service_label(&self) -> io::Result<ServiceLabel> {
+        self.label.parse().map_err(|x: io::Error| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Snapshot label {:?} is invalid: {x}", self.label),
+            )
+        })
+    }
+
+    /// Fails if the snapshot was exported from a different kind of service manager than `kind`
+    pub(crate) fn check_kind(&self, kind: ServiceManagerKind) -> io::Result<()> {
+        if self.kind == kind {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Snapshot of {} was exported from {:?}, not {kind:?}",
+                    self.label, self.kind
+                ),
+            ))
+        }
+    }
+}
+
+/// Reads the file called `name` within `dir`, failing with [`io::ErrorKind::NotFound`] naming the
+/// service if it is not installed
+pub(crate) fn read_file(
+    dir: &Path,
+    name: &str,
+    label: &ServiceLabel,
+    default_mode: u32,
+) -> io::Result<ServiceSnapshotFile> {
+    let path = dir.join(name);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(x) => x,
+        Err(x) if x.kind() == io::ErrorKind::NotFound => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Service {} is not installed", label.to_qualified_name()),
+            ))
+        }
+        Err(x) => return Err(x),
+    };
+
+    #[cfg(unix)]
+    let mode = {
+        use std::os::unix::fs::PermissionsExt;
+        let _ = default_mode;
+        std::fs::metadata(&path)?.permissions().mode() & 0o777
+    };
+
+    #[cfg(not(unix))]
+    let mode = default_mode;
+
+    Ok(ServiceSnapshotFile {
+        name: name.to_string(),
+        contents,
+        mode,
+    })
+}
+
+/// Writes the files of a snapshot into `dir`, refusing any file whose name would place it
+/// outside of `dir` or is not one of `names`, which the service manager derives from the label of
+/// the snapshot so that a snapshot cannot replace the definitions of other services
+///
+/// Only the read, write, and execute bits of the mode of each file are applied, so that a snapshot
+/// cannot install setuid or setgid files.
+pub(crate) fn write_files(
+    dir: &Path,
+    snapshot: &ServiceSnapshot,
+    names: &[String],
+) -> io::Result<()> {
+    for file in &snapshot.files {
+        let mut components = Path::new(&file.name).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Snapshot file name {:?} is not a plain file name",
+                    file.name
+                ),
+            ));
+        }
+
+        if !names.contains(&file.name) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Snapshot file {:?} does not belong to service {}",
+                    file.name, snapshot.label
+                ),
+            ));
+        }
+    }
+
+    std::fs::create_dir_all(dir)?;
+    for file in &snapshot.files {
+        utils::write_file(
+            &dir.join(&file.name),
+            file.contents.as_bytes(),
+            file.mode & 0o777,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(name: &str) -> ServiceSnapshot {
+        ServiceSnapshot {
+            kind: ServiceManagerKind::Systemd,
+            label: String::from("com.example.my-service"),
+            autostart: true,
+            files: vec![ServiceSnapshotFile {
+                name: name.to_string(),
+                contents: String::from("[Unit]\n"),
+                mode: 0o644,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_write_and_read_files() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        let label: ServiceLabel = "com.example.my-service".parse().unwrap();
+        let snapshot = snapshot("example-my-service.service");
+
+        write_files(
+            dir.path(),
+            &snapshot,
+            &[String::from("example-my-service.service")],
+        )
+        .unwrap();
+        assert_eq!(
+            read_file(dir.path(), "example-my-service.service", &label, 0o644).unwrap(),
+            snapshot.files[0]
+        );
+
+        assert_eq!(
+            read_file(dir.path(), "missing.service", &label, 0o644)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn test_write_files_outside_of_dir() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        for name in [
+            "../escape.service",
+            "/etc/escape.service",
+            "a/b.service",
+            "",
+        ] {
+            assert_eq!(
+                write_files(dir.path(), &snapshot(name), &[name.to_string()])
+                    .unwrap_err()
+                    .kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_files_of_other_service() {
+        let dir = assert_fs::TempDir::new().unwrap();
+        assert_eq!(
+            write_files(
+                dir.path(),
+                &snapshot("sshd.service"),
+                &[String::from("example-my-service.service")]
+            )
+            .unwrap_err()
+            .kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert!(!dir.path().join("sshd.service").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_files_masks_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = assert_fs::TempDir::new().unwrap();
+        let mut snapshot = snapshot("example-my-service.service");
+        snapshot.files[0].mode = 0o4755;
+        write_files(
+            dir.path(),
+            &snapshot,
+            &[String::from("example-my-service.service")],
+        )
+        .unwrap();
+
+        let metadata = std::fs::metadata(dir.path().join("example-my-service.service")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o7777, 0o755);
+    }
+
+    #[test]
+    fn test_check_kind() {
+        let snapshot = snapshot("example-my-service.service");
+        assert!(snapshot.check_kind(ServiceManagerKind::Systemd).is_ok());
+        assert_eq!(
+            snapshot
+                .check_kind(ServiceManagerKind::Sc)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+}
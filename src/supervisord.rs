@@ -0,0 +1,721 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, utils, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner,
+};
+use std::{
+    ffi::OsStr,
+    fmt::Write as _,
+    io,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+static SUPERVISORCTL: &str = "supervisorctl";
+
+const CONF_FILE_PERMISSIONS: u32 = 0o644;
+
+/// Configuration settings tied to supervisord programs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SupervisordConfig {
+    pub install: SupervisordInstallConfig,
+
+    /// Directory included by the supervisord configuration where program sections are written,
+    /// defaulting to `/etc/supervisor/conf.d`
+    ///
+    /// Red Hat derivatives typically include `/etc/supervisord.d` instead.
+    pub conf_dir_path: PathBuf,
+
+    /// Extension of the files written to `conf_dir_path`, defaulting to `conf`
+    pub conf_extension: String,
+
+    /// Configuration file of supervisord passed to `supervisorctl -c`, otherwise the default
+    /// search path of supervisorctl is used
+    pub config_file: Option<PathBuf>,
+}
+
+impl Default for SupervisordConfig {
+    fn default() -> Self {
+        Self {
+            install: SupervisordInstallConfig::default(),
+            conf_dir_path: PathBuf::from("/etc/supervisor/conf.d"),
+            conf_extension: String::from("conf"),
+            config_file: None,
+        }
+    }
+}
+
+/// Configuration settings used when installing supervisord programs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SupervisordInstallConfig {
+    /// Number of failed attempts to start the program before it is considered fatal, written as
+    /// `startretries`
+    pub start_retries: Option<u32>,
+
+    /// Seconds the program must stay running after starting to be considered started, written as
+    /// `startsecs`
+    pub start_secs: Option<u32>,
+
+    /// Seconds to wait for the program to exit after being signaled to stop before killing it,
+    /// written as `stopwaitsecs`
+    pub stop_wait_secs: Option<u32>,
+
+    /// Directory where the combined output of the program is logged, defaulting to
+    /// `/var/log/supervisor`
+    pub log_dir_path: PathBuf,
+}
+
+impl Default for SupervisordInstallConfig {
+    fn default() -> Self {
+        Self {
+            start_retries: None,
+            start_secs: None,
+            stop_wait_secs: None,
+            log_dir_path: PathBuf::from("/var/log/supervisor"),
+        }
+    }
+}
+
+/// Implementation of [`ServiceManager`] for [supervisord](http://supervisord.org/)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SupervisordServiceManager {
+    /// Configuration settings tied to supervisord programs
+    pub config: SupervisordConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl SupervisordServiceManager {
+    /// Creates a new manager instance working with system services
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: SupervisordConfig) -> Self {
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    fn conf_path(&self, program: &str) -> PathBuf {
+        self.config
+            .conf_dir_path
+            .join(format!("{program}.{}", self.config.conf_extension))
+    }
+
+    fn supervisorctl<'a>(&self, args: impl IntoIterator<Item = &'a str>) -> io::Result<Output> {
+        let mut command = Command::new(SUPERVISORCTL);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if let Some(config_file) = &self.config.config_file {
+            command.arg("-c").arg(config_file);
+        }
+
+        for arg in args {
+            command.arg(OsStr::new(arg));
+        }
+
+        self.runner.run(&mut command, None)
+    }
+
+    /// Runs `supervisorctl reread` and `supervisorctl update` so that supervisord picks up added,
+    /// changed, and removed program sections
+    fn reload(&self) -> io::Result<()> {
+        wrap_output(self.supervisorctl(["reread"])?)?;
+        wrap_output(self.supervisorctl(["update"])?)?;
+        Ok(())
+    }
+}
+
+impl ServiceManager for SupervisordServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        match which::which(SUPERVISORCTL) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        utils::validate_environment(&ctx, false)?;
+        utils::require_utf8(&ctx, "supervisord")?;
+        utils::capabilities_unsupported(&ctx, "supervisord")?;
+        utils::check_program(&ctx)?;
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "supervisord")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Supervisord, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+        std::fs::create_dir_all(utils::staged_path(&ctx, &self.config.conf_dir_path))?;
+
+        let program = ctx.label.to_script_name();
+        let conf = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_conf(&self.config.install, &program, &ctx),
+        };
+
+        utils::write_definition(
+            &ctx,
+            &utils::staged_path(&ctx, &self.conf_path(&program)),
+            conf.as_bytes(),
+            CONF_FILE_PERMISSIONS,
+        )?;
+
+        match ctx.install_root {
+            Some(_) => Ok(()),
+            None => self.reload(),
+        }
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "supervisord")?;
+
+        let conf_path = self.conf_path(&ctx.label.to_script_name());
+        if conf_path.exists() {
+            std::fs::remove_file(&conf_path)?;
+        }
+        utils::remove_definition_backup(&conf_path)?;
+
+        // Updating after the section is removed stops the program and removes its process group
+        self.reload()?;
+        utils::purge(
+            &ctx,
+            [log_path(&self.config.install, &ctx.label.to_script_name())],
+        )?;
+        manifest::remove(ServiceManagerKind::Supervisord, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn rollback(&self, label: &crate::ServiceLabel) -> io::Result<()> {
+        utils::restore_definition(&self.conf_path(&label.to_script_name()))?;
+        manifest::restore(ServiceManagerKind::Supervisord, self.level(), label)?;
+        self.reload()
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Supervisord, self.level(), label)
+    }
+
+    fn uninstall_matching(
+        &self,
+        prefix: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::ServiceLabel>> {
+        let labels = utils::labels_matching(
+            &self.config.conf_dir_path,
+            &format!(".{}", self.config.conf_extension),
+            prefix,
+            crate::ServiceLabel::to_script_name,
+        )?;
+        utils::uninstall_all(self, labels)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        let program = ctx.label.to_script_name();
+        check_output(self.supervisorctl(["start", &program])?)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        let program = ctx.label.to_script_name();
+        check_output(self.supervisorctl(["stop", &program])?)
+    }
+
+    fn level(&self) -> ServiceLevel {
+        ServiceLevel::System
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => Ok(()),
+            ServiceLevel::User => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "supervisord does not support user-level services",
+            )),
+        }
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let log_path = log_path(&self.config.install, &ctx.label.to_script_name());
+        let text = std::fs::read_to_string(log_path)?;
+        Ok(utils::tail(&text, ctx.lines))
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let program = ctx.label.to_script_name();
+
+        // The exit code of supervisorctl status reflects the state of the program, so only the
+        // output is inspected
+        let output = self.supervisorctl(["status", &program])?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_status(&stdout).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!(
+                    "Failed to get status of program {program}: {}",
+                    if stdout.trim().is_empty() {
+                        String::from_utf8_lossy(&output.stderr)
+                    } else {
+                        stdout.clone()
+                    }
+                ),
+            )
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        let program = ctx.label.to_script_name();
+        check_output(self.supervisorctl(["signal", ctx.signal.name(), &program])?)
+    }
+
+    fn is_up_to_date(&self, ctx: &ServiceInstallCtx) -> io::Result<crate::DriftReport> {
+        utils::require_utf8(ctx, "supervisord")?;
+        let program = ctx.label.to_script_name();
+        let installed = match std::fs::read_to_string(self.conf_path(&program)) {
+            Ok(x) => x,
+            Err(x) if x.kind() == io::ErrorKind::NotFound => {
+                return Ok(crate::DriftReport::not_installed())
+            }
+            Err(x) => return Err(x),
+        };
+
+        let ctx = utils::installed_ctx(ctx)?;
+        let expected = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            None => make_conf(&self.config.install, &program, &ctx),
+        };
+
+        Ok(crate::DriftReport::compare(
+            crate::drift::line_entries(&installed, conf_entries),
+            crate::drift::line_entries(&expected, conf_entries),
+        )
+        .with_missing_files(manifest::missing_files(
+            ServiceManagerKind::Supervisord,
+            self.level(),
+            &ctx.label,
+        )))
+    }
+
+    fn export(&self, ctx: crate::ServiceExportCtx) -> io::Result<crate::ServiceSnapshot> {
+        let program = ctx.label.to_script_name();
+        let file = crate::snapshot::read_file(
+            &self.config.conf_dir_path,
+            &format!("{program}.{}", self.config.conf_extension),
+            &ctx.label,
+            CONF_FILE_PERMISSIONS,
+        )?;
+
+        // Whether the program starts with supervisord is part of its section
+        let autostart = !file
+            .contents
+            .lines()
+            .any(|line| line.replace(' ', "") == "autostart=false");
+
+        Ok(crate::ServiceSnapshot {
+            kind: ServiceManagerKind::Supervisord,
+            label: ctx.label.to_qualified_name(),
+            autostart,
+            files: vec![file],
+        })
+    }
+
+    fn import(&self, snapshot: crate::ServiceSnapshot) -> io::Result<()> {
+        snapshot.check_kind(ServiceManagerKind::Supervisord)?;
+        let label = snapshot.service_label()?;
+        crate::snapshot::write_files(
+            &self.config.conf_dir_path,
+            &snapshot,
+            &[format!(
+                "{}.{}",
+                label.to_script_name(),
+                self.config.conf_extension
+            )],
+        )?;
+        self.reload()
+    }
+}
+
+/// Fails if the command failed or supervisorctl reported an error, as older versions of
+/// supervisorctl exit successfully even when an action fails
+fn check_output(output: Output) -> io::Result<()> {
+    let output = wrap_output(output)?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if stdout.contains("ERROR") {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            stdout.trim().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[inline]
+fn log_path(config: &SupervisordInstallConfig, program: &str) -> PathBuf {
+    config.log_dir_path.join(format!("{program}.log"))
+}
+
+/// Parses the output of `supervisorctl status {name}`, which looks like
+/// `name    RUNNING   pid 123, uptime 0:01:02` or `name: ERROR (no such process)`
+fn parse_status(output: &str) -> Option<crate::ServiceStatusDetails> {
+    let line = output.lines().next()?;
+    if line.contains("no such process") {
+        return Some(crate::ServiceStatus::NotInstalled.into());
+    }
+
+    let mut fields = line.split_whitespace();
+    let _name = fields.next()?;
+    let state = fields.next()?;
+    let description = fields.collect::<Vec<&str>>().join(" ");
+
+    let (status, transition) = match state {
+        "RUNNING" => (crate::ServiceStatus::Running, None),
+        "STARTING" | "BACKOFF" => (
+            crate::ServiceStatus::Stopped(None),
+            Some(crate::ServiceTransition::Starting),
+        ),
+        "STOPPING" => (
+            crate::ServiceStatus::Running,
+            Some(crate::ServiceTransition::Stopping),
+        ),
+        "STOPPED" => (crate::ServiceStatus::Stopped(None), None),
+        "EXITED" | "FATAL" | "UNKNOWN" => (
+            crate::ServiceStatus::Stopped(Some(description.clone())),
+            None,
+        ),
+        _ => return None,
+    };
+
+    let pid = match status {
+        crate::ServiceStatus::Running => description
+            .strip_prefix("pid ")
+            .and_then(|rest| rest.split(',').next())
+            .and_then(|pid| pid.trim().parse().ok()),
+        _ => None,
+    };
+
+    Some(crate::ServiceStatusDetails {
+        status,
+        pid,
+        transition,
+    })
+}
+
+/// Pairs a line of a program's configuration with the setting it belongs to when checking for drift
+fn conf_entries(line: &str) -> Vec<(crate::DriftField, String)> {
+    let field = match line.split_once('=') {
+        Some(("command", cmd)) => return crate::drift::command_entries(cmd),
+        Some(("environment", _)) => crate::DriftField::Environment,
+        Some(("user", _)) => crate::DriftField::Username,
+        Some(("autorestart", _)) => crate::DriftField::RestartPolicy,
+        _ => crate::DriftField::Other,
+    };
+    vec![(field, line.to_string())]
+}
+
+fn make_conf(config: &SupervisordInstallConfig, program: &str, ctx: &ServiceInstallCtx) -> String {
+    // Supervisor splits the command like a shell and expands %(name)s within it
+    let cmd = utils::shell_join(ctx.cmd_iter()).replace('%', "%%");
+
+    let mut conf = format!("[program:{program}]\ncommand={cmd}\n");
+
+    if let Some(dir) = &ctx.working_directory {
+        let _ = writeln!(conf, "directory={}", dir.to_string_lossy());
+    }
+
+    if let Some(username) = &ctx.username {
+        let _ = writeln!(conf, "user={username}");
+    }
+
+    if let Some(env_vars) = &ctx.environment {
+        let env = env_vars
+            .iter()
+            .map(|(var, val)| {
+                // Supervisor expands %(name)s within values, so literal percent signs are doubled
+                let val = val
+                    .to_string_lossy()
+                    .replace('\\', r"\\")
+                    .replace('"', r#"\""#)
+                    .replace('%', "%%");
+                format!("{}=\"{val}\"", var.to_string_lossy())
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        let _ = writeln!(conf, "environment={env}");
+    }
+
+    let _ = writeln!(conf, "autostart={}", ctx.autostart);
+
+    // supervisord has no delay between restarts, so only the kind of policy is carried over
+    let autorestart = match ctx.restart_policy {
+        _ if ctx.kind == crate::ServiceKind::Oneshot => Some("false"),
+        None => None,
+        Some(RestartPolicy::Never) => Some("false"),
+        Some(RestartPolicy::Always { .. }) => Some("true"),
+        Some(RestartPolicy::OnFailure { .. }) => Some("unexpected"),
+    };
+    if let Some(autorestart) = autorestart {
+        let _ = writeln!(conf, "autorestart={autorestart}");
+    }
+
+    // The start limit of the context takes precedence over the config
+    let start_retries = match ctx.start_limit {
+        Some(limit) => Some(limit.burst),
+        None => config.start_retries,
+    };
+    if let Some(start_retries) = start_retries {
+        let _ = writeln!(conf, "startretries={start_retries}");
+    }
+
+    // A oneshot program is expected to exit, so it counts as started as soon as it is spawned
+    let start_secs = match ctx.kind {
+        crate::ServiceKind::Oneshot => config.start_secs.or(Some(0)),
+        crate::ServiceKind::Simple => config.start_secs,
+    };
+    if let Some(start_secs) = start_secs {
+        let _ = writeln!(conf, "startsecs={start_secs}");
+    }
+
+    if let Some(signal) = ctx.stop_signal {
+        let _ = writeln!(conf, "stopsignal={}", signal.name());
+    }
+
+    let stop_wait_secs = config.stop_wait_secs.or(ctx
+        .stop_timeout
+        .map(|timeout| timeout.as_secs().max(1) as u32));
+    if let Some(stop_wait_secs) = stop_wait_secs {
+        let _ = writeln!(conf, "stopwaitsecs={stop_wait_secs}");
+    }
+
+    let _ = writeln!(conf, "redirect_stderr=true");
+    let _ = write!(
+        conf,
+        "stdout_logfile={}",
+        log_path(config, program).to_string_lossy()
+    );
+
+    conf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{runner::tests::RecordingRunner, CommandRunner, ServiceLabel, Signal};
+    use indoc::indoc;
+    use std::{ffi::OsString, sync::Arc};
+
+    #[test]
+    fn test_is_up_to_date() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let manager = SupervisordServiceManager::system().with_config(SupervisordConfig {
+            conf_dir_path: temp.path().to_path_buf(),
+            ..Default::default()
+        });
+        let mut ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse::<ServiceLabel>().unwrap(),
+            program: PathBuf::from("/usr/local/bin/my_service"),
+            args: vec![OsString::from("--port"), OsString::from("8080")],
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: Some(vec![(OsString::from("RUST_LOG"), OsString::from("info"))]),
+            autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+        assert!(!manager.is_up_to_date(&ctx).unwrap().installed);
+
+        let program = ctx.label.to_script_name();
+        let conf = make_conf(&manager.config.install, &program, &ctx);
+        std::fs::write(manager.conf_path(&program), conf).unwrap();
+        assert!(manager.is_up_to_date(&ctx).unwrap().is_up_to_date());
+
+        ctx.args.push(OsString::from("--verbose"));
+        ctx.environment = None;
+        ctx.restart_policy = Some(RestartPolicy::Always { delay_secs: None });
+        assert_eq!(
+            manager.is_up_to_date(&ctx).unwrap().differences,
+            [
+                crate::DriftField::Args,
+                crate::DriftField::Environment,
+                crate::DriftField::RestartPolicy
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = SupervisordServiceManager::system()
+            .with_config(SupervisordConfig {
+                conf_dir_path: temp.path().join("source"),
+                ..Default::default()
+            })
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+        let label = "org.example.my_service".parse::<ServiceLabel>().unwrap();
+        let ctx = crate::ServiceExportCtx {
+            label: label.clone(),
+        };
+        assert_eq!(
+            manager.export(ctx.clone()).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        std::fs::create_dir_all(&manager.config.conf_dir_path).unwrap();
+        let conf_path = manager.conf_path(&label.to_script_name());
+        std::fs::write(
+            &conf_path,
+            "[program:example-my_service]\nautostart=false\n",
+        )
+        .unwrap();
+        let snapshot = manager.export(ctx).unwrap();
+        assert!(!snapshot.autostart);
+        assert_eq!(snapshot.files[0].name, "example-my_service.conf");
+
+        let target = SupervisordServiceManager::system()
+            .with_config(SupervisordConfig {
+                conf_dir_path: temp.path().join("target"),
+                ..Default::default()
+            })
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+        target.import(snapshot).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(target.conf_path(&label.to_script_name())).unwrap(),
+            std::fs::read_to_string(conf_path).unwrap()
+        );
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [["supervisorctl", "reread"], ["supervisorctl", "update"]]
+        );
+    }
+
+    #[test]
+    fn test_make_conf() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse::<ServiceLabel>().unwrap(),
+            program: PathBuf::from("/usr/local/bin/my_service"),
+            args: vec![OsString::from("--port"), OsString::from("8080")],
+            contents: None,
+            username: Some(String::from("nobody")),
+            working_directory: Some(PathBuf::from("/var/lib/my_service")),
+            environment: Some(vec![
+                (OsString::from("RUST_LOG"), OsString::from("info")),
+                (OsString::from("PORT"), OsString::from("8080")),
+                (
+                    OsString::from("GREETING"),
+                    OsString::from(r#"say "hi" 100%"#),
+                ),
+            ]),
+            autostart: true,
+            restart_policy: Some(RestartPolicy::OnFailure { delay_secs: None }),
+            stop_command: None,
+            stop_signal: Some(Signal::Int),
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+        let config = SupervisordInstallConfig {
+            start_retries: Some(5),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            make_conf(&config, "org.example.my_service", &ctx),
+            indoc! {r#"
+                [program:org.example.my_service]
+                command=/usr/local/bin/my_service --port 8080
+                directory=/var/lib/my_service
+                user=nobody
+                environment=RUST_LOG="info",PORT="8080",GREETING="say \"hi\" 100%%"
+                autostart=true
+                autorestart=unexpected
+                startretries=5
+                stopsignal=INT
+                redirect_stderr=true
+                stdout_logfile=/var/log/supervisor/org.example.my_service.log"#}
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let details =
+            parse_status("my_service                       RUNNING   pid 1234, uptime 0:01:02\n")
+                .unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Running);
+        assert_eq!(details.pid, Some(1234));
+
+        let details =
+            parse_status("my_service                       STOPPED   Not started\n").unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Stopped(None));
+
+        let details =
+            parse_status("my_service                       FATAL     Exited too quickly\n")
+                .unwrap();
+        assert_eq!(
+            details.status,
+            crate::ServiceStatus::Stopped(Some(String::from("Exited too quickly")))
+        );
+
+        let details = parse_status("my_service: ERROR (no such process)\n").unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::NotInstalled);
+    }
+}
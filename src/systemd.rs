@@ -1,22 +1,38 @@
 use crate::utils::wrap_output;
 
 use super::{
-    utils, ServiceInstallCtx, ServiceLevel, ServiceManager, ServiceStartCtx, ServiceStopCtx,
-    ServiceUninstallCtx,
+    manifest, utils, AvailabilityDetails, CommandRunner, ServiceInstallCtx, ServiceLevel,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner,
 };
 use std::{
+    ffi::OsStr,
     fmt, io,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, Output, Stdio},
 };
 
 static SYSTEMCTL: &str = "systemctl";
+static JOURNALCTL: &str = "journalctl";
 const SERVICE_FILE_PERMISSIONS: u32 = 0o644;
 
 /// Configuration settings tied to systemd services
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct SystemdConfig {
     pub install: SystemdInstallConfig,
+
+    /// Path of the `systemctl` program, which is searched for on the `PATH` if not provided
+    pub systemctl_path: Option<PathBuf>,
+
+    /// Directory that unit files are written to, overriding [`systemd_global_dir_path`] for
+    /// system services and [`systemd_user_dir_path`] for user services
+    pub unit_dir_path: Option<PathBuf>,
+}
+
+impl SystemdConfig {
+    fn systemctl(&self) -> &OsStr {
+        utils::program_or(self.systemctl_path.as_deref(), SYSTEMCTL)
+    }
 }
 
 /// Configuration settings tied to systemd services during installation
@@ -78,6 +94,9 @@ pub struct SystemdServiceManager {
 
     /// Configuration settings tied to systemd services
     pub config: SystemdConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
 }
 
 impl SystemdServiceManager {
@@ -96,6 +115,7 @@ impl SystemdServiceManager {
         Self {
             config: self.config,
             user: false,
+            runner: self.runner,
         }
     }
 
@@ -104,6 +124,7 @@ impl SystemdServiceManager {
         Self {
             config: self.config,
             user: true,
+            runner: self.runner,
         }
     }
 
@@ -112,49 +133,119 @@ impl SystemdServiceManager {
         Self {
             config,
             user: self.user,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    /// Update manager to write unit files to the specified directory rather than the default one
+    /// of its level, such as a staging directory used to build a package
+    pub fn with_unit_dir(mut self, dir_path: impl Into<PathBuf>) -> Self {
+        self.config.unit_dir_path = Some(dir_path.into());
+        self
+    }
+
+    /// Returns the directory that unit files are written to
+    pub fn unit_dir(&self) -> io::Result<PathBuf> {
+        match &self.config.unit_dir_path {
+            Some(dir_path) => Ok(dir_path.clone()),
+            None if self.user => systemd_user_dir_path(),
+            None => Ok(systemd_global_dir_path()),
         }
     }
 }
 
 impl ServiceManager for SystemdServiceManager {
     fn available(&self) -> io::Result<bool> {
-        match which::which(SYSTEMCTL) {
-            Ok(_) => Ok(true),
-            Err(which::Error::CannotFindBinaryPath) => Ok(false),
-            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        Ok(self.availability_details()?.available)
+    }
+
+    fn availability_details(&self) -> io::Result<AvailabilityDetails> {
+        match which::which(self.config.systemctl()) {
+            Ok(_) => {}
+            Err(which::Error::CannotFindBinaryPath) => {
+                return Ok(AvailabilityDetails::unavailable("systemctl was not found"))
+            }
+            Err(x) => return Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+
+        // systemd creates this directory once booted as the init system, while systemctl is often
+        // installed without it running, such as within containers or WSL
+        if !Path::new("/run/systemd/system").is_dir() {
+            return Ok(AvailabilityDetails::unavailable(if utils::is_wsl() {
+                "systemd is not running within WSL, which requires [boot] systemd=true in /etc/wsl.conf"
+            } else {
+                "systemd is not running as the init system"
+            }));
         }
+
+        let mut command = Command::new(self.config.systemctl());
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if self.user {
+            command.arg("--user");
+        }
+        command.arg("is-system-running");
+
+        let output = self.runner.run(&mut command, None)?;
+        Ok(parse_is_system_running(
+            &String::from_utf8_lossy(&output.stdout),
+            &String::from_utf8_lossy(&output.stderr),
+        ))
     }
 
     fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
-        let dir_path = if self.user {
-            systemd_user_dir_path()?
-        } else {
-            systemd_global_dir_path()
-        };
+        utils::validate_environment(&ctx, true)?;
+        utils::require_utf8(&ctx, "systemd")?;
+        utils::validate_capabilities(&ctx)?;
+        utils::check_program(&ctx)?;
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "systemd")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Systemd, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+        let dir_path = utils::staged_path(&ctx, &self.unit_dir()?);
 
         std::fs::create_dir_all(&dir_path)?;
 
         let script_name = ctx.label.to_script_name();
         let script_path = dir_path.join(format!("{script_name}.service"));
-        let service = match ctx.contents {
-            Some(contents) => contents,
+        let service = match &ctx.contents {
+            Some(contents) => contents.clone(),
             _ => make_service(
                 &self.config.install,
-                &script_name,
+                ctx.description
+                    .as_deref()
+                    .or(ctx.display_name.as_deref())
+                    .unwrap_or(&script_name),
                 &ctx,
                 self.user,
                 ctx.autostart,
             ),
         };
 
-        utils::write_file(
+        utils::write_definition(
+            &ctx,
             script_path.as_path(),
             service.as_bytes(),
             SERVICE_FILE_PERMISSIONS,
         )?;
 
-        if ctx.autostart {
+        // Packages enable their units themselves once installed
+        if ctx.autostart && ctx.install_root.is_none() {
             wrap_output(systemctl(
+                &*self.runner,
+                self.config.systemctl(),
                 "enable",
                 script_path.to_string_lossy().as_ref(),
                 self.user,
@@ -165,29 +256,95 @@ impl ServiceManager for SystemdServiceManager {
     }
 
     fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
-        let dir_path = if self.user {
-            systemd_user_dir_path()?
-        } else {
-            systemd_global_dir_path()
-        };
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "systemd")?;
+
+        let dir_path = self.unit_dir()?;
         let script_name = ctx.label.to_script_name();
         let script_path = dir_path.join(format!("{script_name}.service"));
 
         wrap_output(systemctl(
+            &*self.runner,
+            self.config.systemctl(),
             "disable",
             script_path.to_string_lossy().as_ref(),
             self.user,
         )?)?;
-        std::fs::remove_file(script_path)
+        std::fs::remove_file(&script_path)?;
+        utils::remove_definition_backup(&script_path)?;
+        manifest::remove(ServiceManagerKind::Systemd, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
     }
 
-    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
-        wrap_output(systemctl("start", &ctx.label.to_script_name(), self.user)?)?;
+    fn rollback(&self, label: &crate::ServiceLabel) -> io::Result<()> {
+        let dir_path = self.unit_dir()?;
+        let script_name = label.to_script_name();
+        utils::restore_definition(&dir_path.join(format!("{script_name}.service")))?;
+        manifest::restore(ServiceManagerKind::Systemd, self.level(), label)?;
+
+        let mut command = Command::new(self.config.systemctl());
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if self.user {
+            command.arg("--user");
+        }
+        command.arg("daemon-reload");
+        wrap_output(self.runner.run(&mut command, None)?)?;
         Ok(())
     }
 
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Systemd, self.level(), label)
+    }
+
+    fn uninstall_matching(
+        &self,
+        prefix: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::ServiceLabel>> {
+        let dir_path = self.unit_dir()?;
+        let labels = utils::labels_matching(
+            &dir_path,
+            ".service",
+            prefix,
+            crate::ServiceLabel::to_script_name,
+        )?;
+        utils::uninstall_all(self, labels)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        let mut command = Command::new(self.config.systemctl());
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if self.user {
+            command.arg("--user");
+        }
+
+        // Without --no-block, systemctl waits for the unit to finish activating
+        command.arg("start");
+        if ctx.wait == crate::WaitMode::None {
+            command.arg("--no-block");
+        }
+        command.arg(ctx.label.to_script_name());
+        wrap_output(self.runner.run(&mut command, None)?)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
     fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
-        wrap_output(systemctl("stop", &ctx.label.to_script_name(), self.user)?)?;
+        wrap_output(systemctl(
+            &*self.runner,
+            self.config.systemctl(),
+            "stop",
+            &ctx.label.to_script_name(),
+            self.user,
+        )?)?;
         Ok(())
     }
 
@@ -209,7 +366,17 @@ impl ServiceManager for SystemdServiceManager {
     }
 
     fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
-        let output = systemctl("status", &ctx.label.to_script_name(), self.user)?;
+        let user = match ctx.level {
+            Some(level) => level == ServiceLevel::User,
+            None => self.user,
+        };
+        let output = systemctl(
+            &*self.runner,
+            self.config.systemctl(),
+            "status",
+            &ctx.label.to_script_name(),
+            user,
+        )?;
         // ref: https://www.freedesktop.org/software/systemd/man/latest/systemctl.html#Exit%20status
         match output.status.code() {
             Some(4) => Ok(crate::ServiceStatus::NotInstalled),
@@ -225,10 +392,197 @@ impl ServiceManager for SystemdServiceManager {
             )),
         }
     }
+
+    fn logs(&self, ctx: crate::ServiceLogsCtx) -> io::Result<String> {
+        let mut command = Command::new(JOURNALCTL);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let unit = format!("{}.service", ctx.label.to_script_name());
+        if self.user {
+            command.arg(format!("--user-unit={unit}"));
+        } else {
+            command.arg(format!("--unit={unit}"));
+        }
+
+        command.arg("--no-pager").arg("--output=cat");
+        if let Some(lines) = ctx.lines {
+            command.arg(format!("--lines={lines}"));
+        }
+
+        let output = wrap_output(self.runner.run(&mut command, None)?)?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    }
+
+    fn diagnose(&self, ctx: crate::ServiceDiagnoseCtx) -> io::Result<crate::StartFailureReport> {
+        let status = self.status_details(crate::ServiceStatusCtx {
+            label: ctx.label.clone(),
+            level: None,
+        })?;
+
+        let mut command = Command::new(self.config.systemctl());
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if self.user {
+            command.arg("--user");
+        }
+
+        // Status of the main process of the unit's most recent run, which is 0 if it never ran
+        command
+            .arg("show")
+            .arg("--property=ExecMainStatus")
+            .arg("--value")
+            .arg(ctx.label.to_script_name());
+        let output = wrap_output(self.runner.run(&mut command, None)?)?;
+        let exit_code = String::from_utf8_lossy(&output.stdout).trim().parse().ok();
+
+        let logs = self
+            .logs(crate::ServiceLogsCtx {
+                label: ctx.label.clone(),
+                lines: Some(ctx.lines.unwrap_or(crate::DEFAULT_DIAGNOSE_LOG_LINES)),
+            })
+            .ok();
+
+        Ok(crate::StartFailureReport {
+            label: ctx.label.to_qualified_name(),
+            status,
+            exit_code,
+            logs,
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        let mut command = Command::new(self.config.systemctl());
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if self.user {
+            command.arg("--user");
+        }
+
+        command
+            .arg("kill")
+            .arg("--kill-whom=main")
+            .arg(format!("--signal={}", ctx.signal))
+            .arg(ctx.label.to_script_name());
+        wrap_output(self.runner.run(&mut command, None)?)?;
+        Ok(())
+    }
+
+    fn is_up_to_date(&self, ctx: &ServiceInstallCtx) -> io::Result<crate::DriftReport> {
+        utils::require_utf8(ctx, "systemd")?;
+        let dir_path = self.unit_dir()?;
+
+        let script_name = ctx.label.to_script_name();
+        let installed =
+            match std::fs::read_to_string(dir_path.join(format!("{script_name}.service"))) {
+                Ok(x) => x,
+                Err(x) if x.kind() == io::ErrorKind::NotFound => {
+                    return Ok(crate::DriftReport::not_installed())
+                }
+                Err(x) => return Err(x),
+            };
+
+        let ctx = utils::installed_ctx(ctx)?;
+        let expected = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            None => make_service(
+                &self.config.install,
+                ctx.description
+                    .as_deref()
+                    .or(ctx.display_name.as_deref())
+                    .unwrap_or(&script_name),
+                &ctx,
+                self.user,
+                ctx.autostart,
+            ),
+        };
+
+        Ok(crate::DriftReport::compare(
+            crate::drift::line_entries(&installed, unit_entries),
+            crate::drift::line_entries(&expected, unit_entries),
+        )
+        .with_missing_files(manifest::missing_files(
+            ServiceManagerKind::Systemd,
+            self.level(),
+            &ctx.label,
+        )))
+    }
+
+    fn export(&self, ctx: crate::ServiceExportCtx) -> io::Result<crate::ServiceSnapshot> {
+        let dir_path = self.unit_dir()?;
+
+        let script_name = ctx.label.to_script_name();
+        let file = crate::snapshot::read_file(
+            &dir_path,
+            &format!("{script_name}.service"),
+            &ctx.label,
+            SERVICE_FILE_PERMISSIONS,
+        )?;
+
+        // Services that are not enabled, or cannot be, fail with a non-zero exit code
+        let autostart = systemctl(
+            &*self.runner,
+            self.config.systemctl(),
+            "is-enabled",
+            &script_name,
+            self.user,
+        )?
+        .status
+        .success();
+
+        Ok(crate::ServiceSnapshot {
+            kind: ServiceManagerKind::Systemd,
+            label: ctx.label.to_qualified_name(),
+            autostart,
+            files: vec![file],
+        })
+    }
+
+    fn import(&self, snapshot: crate::ServiceSnapshot) -> io::Result<()> {
+        snapshot.check_kind(ServiceManagerKind::Systemd)?;
+        let dir_path = self.unit_dir()?;
+
+        let label = snapshot.service_label()?;
+        crate::snapshot::write_files(
+            &dir_path,
+            &snapshot,
+            &[format!("{}.service", label.to_script_name())],
+        )?;
+
+        if snapshot.autostart {
+            for file in &snapshot.files {
+                wrap_output(systemctl(
+                    &*self.runner,
+                    self.config.systemctl(),
+                    "enable",
+                    dir_path.join(&file.name).to_string_lossy().as_ref(),
+                    self.user,
+                )?)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
-fn systemctl(cmd: &str, label: &str, user: bool) -> io::Result<Output> {
-    let mut command = Command::new(SYSTEMCTL);
+fn systemctl(
+    runner: &dyn CommandRunner,
+    program: &OsStr,
+    cmd: &str,
+    label: &str,
+    user: bool,
+) -> io::Result<Output> {
+    let mut command = Command::new(program);
 
     command
         .stdin(Stdio::null())
@@ -239,7 +593,25 @@ fn systemctl(cmd: &str, label: &str, user: bool) -> io::Result<Output> {
         command.arg("--user");
     }
 
-    command.arg(cmd).arg(label).output()
+    command.arg(cmd).arg(label);
+    runner.run(&mut command, None)
+}
+
+/// Parses the output of `systemctl is-system-running`, where a degraded system still has a working
+/// systemd, so only the lack of a connection to systemd, such as a missing user bus, makes it
+/// unusable
+fn parse_is_system_running(stdout: &str, stderr: &str) -> AvailabilityDetails {
+    let state = stdout.trim();
+    if !state.is_empty() && state != "offline" {
+        return AvailabilityDetails::available();
+    }
+
+    let stderr = stderr.trim();
+    AvailabilityDetails::unavailable(if stderr.is_empty() {
+        String::from("Unable to reach systemd")
+    } else {
+        format!("Unable to reach systemd: {stderr}")
+    })
 }
 
 #[inline]
@@ -273,6 +645,12 @@ fn make_service(
     let _ = writeln!(service, "[Unit]");
     let _ = writeln!(service, "Description={description}");
 
+    // The start limit of the context takes precedence over the config
+    let (start_limit_interval_sec, start_limit_burst) = match ctx.start_limit {
+        Some(limit) => (Some(limit.interval.as_secs()), Some(limit.burst)),
+        None => (start_limit_interval_sec.map(u64::from), *start_limit_burst),
+    };
+
     if let Some(x) = start_limit_interval_sec {
         let _ = writeln!(service, "StartLimitIntervalSec={x}");
     }
@@ -281,7 +659,22 @@ fn make_service(
         let _ = writeln!(service, "StartLimitBurst={x}");
     }
 
+    if let Some(crate::StartLimit {
+        action: crate::StartLimitAction::Reboot,
+        ..
+    }) = ctx.start_limit
+    {
+        let _ = writeln!(service, "StartLimitAction=reboot");
+    }
+
     let _ = writeln!(service, "[Service]");
+    let oneshot = ctx.kind == crate::ServiceKind::Oneshot;
+    if oneshot {
+        // Keep the unit active after the program exits so that it only runs once per boot
+        let _ = writeln!(service, "Type=oneshot");
+        let _ = writeln!(service, "RemainAfterExit=yes");
+    }
+
     if let Some(working_directory) = &ctx.working_directory {
         let _ = writeln!(
             service,
@@ -292,26 +685,83 @@ fn make_service(
 
     if let Some(env_vars) = &ctx.environment {
         for (var, val) in env_vars {
-            let _ = writeln!(service, "Environment=\"{var}={val}\"");
+            let _ = writeln!(
+                service,
+                "Environment=\"{}={}\"",
+                var.to_string_lossy(),
+                escape_environment_value(&val.to_string_lossy())
+            );
         }
     }
 
-    let program = ctx.program.to_string_lossy();
-    let args = ctx
-        .args
-        .clone()
-        .into_iter()
-        .map(|a| a.to_string_lossy().to_string())
-        .collect::<Vec<String>>()
-        .join(" ");
-    let _ = writeln!(service, "ExecStart={program} {args}");
+    let _ = writeln!(service, "ExecStart={}", make_command_line(ctx.cmd_iter()));
+
+    if ctx.stop_command.is_some() {
+        let stop_cmd = make_command_line(ctx.stop_cmd_iter());
+        let _ = writeln!(service, "ExecStop={stop_cmd}");
+    }
+
+    if let Some(signal) = ctx.stop_signal {
+        let _ = writeln!(service, "KillSignal={signal}");
+    }
 
-    if *restart != SystemdServiceRestartType::No {
-        let _ = writeln!(service, "Restart={restart}");
+    if let Some(timeout) = ctx.start_timeout {
+        let _ = writeln!(service, "TimeoutStartSec={}ms", timeout.as_millis());
     }
 
-    if let Some(x) = restart_sec {
-        let _ = writeln!(service, "RestartSec={x}");
+    if let Some(timeout) = ctx.stop_timeout {
+        let _ = writeln!(service, "TimeoutStopSec={}ms", timeout.as_millis());
+    }
+
+    if let Some(priority) = &ctx.priority {
+        if let Some(nice) = priority.nice {
+            let _ = writeln!(service, "Nice={nice}");
+        }
+
+        if let Some(class) = priority.io_class {
+            let class = match class {
+                crate::IoSchedulingClass::Realtime => "realtime",
+                crate::IoSchedulingClass::BestEffort => "best-effort",
+                crate::IoSchedulingClass::Idle => "idle",
+            };
+            let _ = writeln!(service, "IOSchedulingClass={class}");
+        }
+    }
+
+    if !ctx.capabilities.is_empty() {
+        let caps = ctx.capabilities.join(" ");
+        let _ = writeln!(service, "AmbientCapabilities={caps}");
+        let _ = writeln!(service, "CapabilityBoundingSet={caps}");
+    }
+
+    if let Some(tag) = &ctx.log_to_syslog {
+        let _ = writeln!(service, "SyslogIdentifier={tag}");
+    }
+
+    if let Some(adjust) = ctx.oom_score_adjust {
+        let _ = writeln!(service, "OOMScoreAdjust={adjust}");
+    }
+
+    if !oneshot {
+        // The restart policy of the context takes precedence over the config
+        let (restart, restart_sec) = match ctx.restart_policy {
+            Some(crate::RestartPolicy::Never) => (SystemdServiceRestartType::No, None),
+            Some(crate::RestartPolicy::Always { delay_secs }) => {
+                (SystemdServiceRestartType::Always, delay_secs)
+            }
+            Some(crate::RestartPolicy::OnFailure { delay_secs }) => {
+                (SystemdServiceRestartType::OnFailure, delay_secs)
+            }
+            None => (*restart, *restart_sec),
+        };
+
+        if restart != SystemdServiceRestartType::No {
+            let _ = writeln!(service, "Restart={restart}");
+        }
+
+        if let Some(x) = restart_sec {
+            let _ = writeln!(service, "RestartSec={x}");
+        }
     }
 
     // For Systemd, a user-mode service definition should *not* specify the username, since it runs
@@ -334,3 +784,313 @@ fn make_service(
 
     service.trim().to_string()
 }
+
+/// Pairs a line of a unit file with the setting it belongs to when checking for drift
+fn unit_entries(line: &str) -> Vec<(crate::DriftField, String)> {
+    let field = match line.split_once('=') {
+        Some(("ExecStart", cmd)) => return crate::drift::command_entries(cmd),
+        Some(("Environment", _)) => crate::DriftField::Environment,
+        Some(("User", _)) => crate::DriftField::Username,
+        Some(("Restart" | "RestartSec", _)) => crate::DriftField::RestartPolicy,
+        _ => crate::DriftField::Other,
+    };
+    vec![(field, line.to_string())]
+}
+
+/// Produces the command line of an `ExecStart=` or similar setting, escaping the `%` specifiers and
+/// `$` variables that systemd would otherwise expand and quoting arguments as needed
+fn make_command_line<'a>(args: impl Iterator<Item = &'a OsStr>) -> String {
+    args.map(|arg| {
+        let arg = arg.to_string_lossy().replace('%', "%%").replace('$', "$$");
+        utils::double_quote(&arg).into_owned()
+    })
+    .collect::<Vec<String>>()
+    .join(" ")
+}
+
+/// Escapes the value of an environment variable for a quoted `Environment=` assignment, where
+/// systemd would otherwise expand `%` specifiers and interpret `\\` and `"`
+fn escape_environment_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '"' => escaped.push_str(r#"\""#),
+            '%' => escaped.push_str("%%"),
+            '\n' => escaped.push_str(r"\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::tests::RecordingRunner;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_systemctl_path() {
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = SystemdServiceManager::system()
+            .with_config(SystemdConfig {
+                systemctl_path: Some(PathBuf::from("/opt/shims/systemctl")),
+                ..Default::default()
+            })
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        manager
+            .stop(ServiceStopCtx {
+                label: "com.example.my-service".parse().unwrap(),
+            })
+            .unwrap();
+
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [["/opt/shims/systemctl", "stop", "example-my-service"]]
+        );
+    }
+
+    #[test]
+    fn test_unit_dir() {
+        assert_eq!(
+            SystemdServiceManager::system().unit_dir().unwrap(),
+            systemd_global_dir_path()
+        );
+        assert_eq!(
+            SystemdServiceManager::user()
+                .with_unit_dir("/tmp/staging/etc/systemd/system")
+                .unit_dir()
+                .unwrap(),
+            PathBuf::from("/tmp/staging/etc/systemd/system")
+        );
+    }
+
+    #[test]
+    fn test_install_beneath_root() {
+        let root = assert_fs::TempDir::new().unwrap();
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = SystemdServiceManager::system()
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        manager
+            .install(ServiceInstallCtx {
+                autostart: true,
+                install_root: Some(root.path().to_path_buf()),
+                ..crate::utils::tests::install_ctx(PathBuf::from("/usr/bin/my_service"))
+            })
+            .unwrap();
+
+        let unit = std::fs::read_to_string(
+            root.path()
+                .join("etc/systemd/system/example-my_service.service"),
+        )
+        .unwrap();
+        assert!(unit.contains("ExecStart=/usr/bin/my_service"));
+        assert!(runner.commands.lock().unwrap().is_empty());
+
+        // The manifest is shipped beneath the root while referring to the final paths
+        let manifest = crate::InstallManifest::read(
+            &root
+                .path()
+                .join("var/lib/service-manager/manifests/systemd/org.example.my_service.manifest"),
+        )
+        .unwrap();
+        assert_eq!(
+            manifest.files(),
+            [PathBuf::from(
+                "/etc/systemd/system/example-my_service.service"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_status_of_other_level() {
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = SystemdServiceManager::user()
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        manager
+            .status(crate::ServiceStatusCtx {
+                label: "com.example.my-service".parse().unwrap(),
+                level: Some(ServiceLevel::System),
+            })
+            .unwrap();
+        manager
+            .status(crate::ServiceStatusCtx {
+                label: "com.example.my-service".parse().unwrap(),
+                level: None,
+            })
+            .unwrap();
+
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [
+                vec!["systemctl", "status", "example-my-service"],
+                vec!["systemctl", "--user", "status", "example-my-service"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_signal() {
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = SystemdServiceManager::user()
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        manager
+            .signal(crate::ServiceSignalCtx {
+                label: "com.example.my-service".parse().unwrap(),
+                signal: crate::Signal::Usr1,
+            })
+            .unwrap();
+
+        assert_eq!(
+            *runner.commands.lock().unwrap(),
+            [[
+                "systemctl",
+                "--user",
+                "kill",
+                "--kill-whom=main",
+                "--signal=SIGUSR1",
+                "example-my-service"
+            ]]
+        );
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = SystemdServiceManager::user()
+            .with_runner(Arc::clone(&runner) as Arc<dyn CommandRunner>);
+
+        let report = manager
+            .diagnose(crate::ServiceDiagnoseCtx {
+                label: "com.example.my-service".parse().unwrap(),
+                lines: Some(5),
+            })
+            .unwrap();
+        assert_eq!(report.label, "com.example.my-service");
+        assert_eq!(report.status.status, crate::ServiceStatus::Running);
+
+        let commands = runner.commands.lock().unwrap();
+        assert_eq!(
+            commands[1],
+            [
+                "systemctl",
+                "--user",
+                "show",
+                "--property=ExecMainStatus",
+                "--value",
+                "example-my-service"
+            ]
+        );
+        assert_eq!(
+            commands[2],
+            [
+                "journalctl",
+                "--user-unit=example-my-service.service",
+                "--no-pager",
+                "--output=cat",
+                "--lines=5"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_make_service_with_restart_policy_and_start_limit() {
+        let ctx = ServiceInstallCtx {
+            label: "com.example.my-service".parse().unwrap(),
+            program: PathBuf::from("/usr/local/bin/my-service"),
+            args: Vec::new(),
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: None,
+            autostart: true,
+            restart_policy: Some(crate::RestartPolicy::Always {
+                delay_secs: Some(30),
+            }),
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: Some(crate::StartLimit {
+                interval: std::time::Duration::from_secs(60),
+                burst: 5,
+                action: crate::StartLimitAction::Reboot,
+            }),
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+
+        let service = make_service(
+            &SystemdInstallConfig::default(),
+            "example-my-service",
+            &ctx,
+            false,
+            true,
+        );
+        assert!(service.contains("\nRestart=always\nRestartSec=30\n"));
+        assert!(!service.contains("Restart=on-failure"));
+        assert!(service
+            .contains("\nStartLimitIntervalSec=60\nStartLimitBurst=5\nStartLimitAction=reboot\n"));
+    }
+
+    #[test]
+    fn test_make_command_line() {
+        let args = [
+            "/opt/My App/bin/my_service",
+            "--path",
+            "/opt/My App",
+            r#"--name="quoted""#,
+            "100%",
+            "$HOME",
+        ];
+        assert_eq!(
+            make_command_line(args.iter().map(OsStr::new)),
+            r#""/opt/My App/bin/my_service" --path "/opt/My App" "--name=\"quoted\"" 100%% "$$HOME""#
+        );
+    }
+
+    #[test]
+    fn test_escape_environment_value() {
+        assert_eq!(escape_environment_value("info"), "info");
+        assert_eq!(
+            escape_environment_value(r#"say "hi" to %u\n"#),
+            r#"say \"hi\" to %%u\\n"#
+        );
+        assert_eq!(escape_environment_value("a\nb"), r"a\nb");
+    }
+
+    #[test]
+    fn test_parse_is_system_running() {
+        assert_eq!(
+            parse_is_system_running("degraded\n", ""),
+            AvailabilityDetails::available()
+        );
+        assert_eq!(
+            parse_is_system_running("offline\n", ""),
+            AvailabilityDetails::unavailable("Unable to reach systemd")
+        );
+        assert_eq!(
+            parse_is_system_running("", "Failed to connect to bus: No medium found\n"),
+            AvailabilityDetails::unavailable(
+                "Unable to reach systemd: Failed to connect to bus: No medium found"
+            )
+        );
+    }
+}
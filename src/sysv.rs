@@ -0,0 +1,571 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, utils, CommandRunner, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner,
+};
+use std::{
+    ffi::OsStr,
+    fmt::Write as _,
+    io,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+static SERVICE: &str = "service";
+static UPDATE_RC_D: &str = "update-rc.d";
+static CHKCONFIG: &str = "chkconfig";
+
+// NOTE: On Debian, /etc/init.d/{script} has permissions of rwxr-xr-x (755)
+const SCRIPT_FILE_PERMISSIONS: u32 = 0o755;
+
+/// Configuration settings tied to SysV init services
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SysVConfig {
+    pub install: SysVInstallConfig,
+
+    /// Directory where init scripts are written, defaulting to `/etc/init.d`
+    pub script_dir_path: PathBuf,
+
+    /// Path of the `service` program, which is searched for on the `PATH` if not provided
+    pub service_path: Option<PathBuf>,
+}
+
+impl SysVConfig {
+    fn service(&self) -> &OsStr {
+        utils::program_or(self.service_path.as_deref(), SERVICE)
+    }
+}
+
+impl Default for SysVConfig {
+    fn default() -> Self {
+        Self {
+            install: SysVInstallConfig::default(),
+            script_dir_path: PathBuf::from("/etc/init.d"),
+            service_path: None,
+        }
+    }
+}
+
+/// Configuration settings used when installing SysV init services
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SysVInstallConfig {
+    /// Facilities listed in the `Required-Start` header of the LSB init script
+    pub required_start: Vec<String>,
+
+    /// Facilities listed in the `Required-Stop` header of the LSB init script
+    pub required_stop: Vec<String>,
+
+    /// Runlevels listed in the `Default-Start` header of the LSB init script
+    pub default_start: Vec<u8>,
+
+    /// Runlevels listed in the `Default-Stop` header of the LSB init script
+    pub default_stop: Vec<u8>,
+
+    /// File that the output of the service is appended to, defaulting to `/var/log/{name}.log`
+    pub log_path: Option<PathBuf>,
+}
+
+impl Default for SysVInstallConfig {
+    fn default() -> Self {
+        Self {
+            required_start: vec![String::from("$remote_fs"), String::from("$syslog")],
+            required_stop: vec![String::from("$remote_fs"), String::from("$syslog")],
+            default_start: vec![2, 3, 4, 5],
+            default_stop: vec![0, 1, 6],
+            log_path: None,
+        }
+    }
+}
+
+impl SysVInstallConfig {
+    /// Returns the file that the output of the service is appended to
+    fn log_path(&self, script_name: &str) -> PathBuf {
+        self.log_path
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("/var/log").join(format!("{script_name}.log")))
+    }
+}
+
+/// Implementation of [`ServiceManager`] for classic
+/// [SysV init](https://wiki.debian.org/LSBInitScripts) scripts, registered via `update-rc.d` on
+/// Debian derivatives or `chkconfig` on Red Hat derivatives
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SysVServiceManager {
+    /// Configuration settings tied to SysV init services
+    pub config: SysVConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl SysVServiceManager {
+    /// Creates a new manager instance working with system services
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: SysVConfig) -> Self {
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+}
+
+impl ServiceManager for SysVServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        if !self.config.script_dir_path.is_dir() {
+            return Ok(false);
+        }
+
+        for program in [UPDATE_RC_D, CHKCONFIG] {
+            match which::which(program) {
+                Ok(_) => return Ok(true),
+                Err(which::Error::CannotFindBinaryPath) => continue,
+                Err(x) => return Err(io::Error::new(io::ErrorKind::Other, x)),
+            }
+        }
+
+        Ok(false)
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        utils::validate_environment(&ctx, true)?;
+        utils::require_utf8(&ctx, "SysV init")?;
+        utils::capabilities_unsupported(&ctx, "SysV init")?;
+        utils::backup_unsupported(&ctx, "SysV init")?;
+        utils::check_program(&ctx)?;
+        if ctx.kind == crate::ServiceKind::Oneshot {
+            return Err(utils::oneshot_unsupported("SysV init"));
+        }
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "SysV init")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::SysV, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+
+        let dir_path = utils::staged_path(&ctx, &self.config.script_dir_path);
+        std::fs::create_dir_all(&dir_path)?;
+
+        let script_name = ctx.label.to_script_name();
+        let script_path = dir_path.join(&script_name);
+
+        let script = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_script(&self.config.install, &script_name, &ctx),
+        };
+
+        utils::write_definition(
+            &ctx,
+            &script_path,
+            script.as_bytes(),
+            SCRIPT_FILE_PERMISSIONS,
+        )?;
+
+        if ctx.autostart && ctx.install_root.is_none() {
+            register(&*self.runner, &script_name)?;
+        }
+
+        Ok(())
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "SysV init")?;
+
+        let script_name = ctx.label.to_script_name();
+        let script_path = self.config.script_dir_path.join(&script_name);
+
+        // The script is only registered when autostarted, so failing to deregister it is ignored
+        let _ = deregister(&*self.runner, &script_name);
+
+        if script_path.exists() {
+            std::fs::remove_file(&script_path)?;
+        }
+
+        utils::purge(
+            &ctx,
+            [
+                self.config.install.log_path(&script_name),
+                pidfile_path(&script_name),
+            ],
+        )?;
+        manifest::remove(ServiceManagerKind::SysV, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::SysV, self.level(), label)
+    }
+
+    fn uninstall_matching(
+        &self,
+        prefix: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::ServiceLabel>> {
+        let labels = utils::labels_matching(
+            &self.config.script_dir_path,
+            "",
+            prefix,
+            crate::ServiceLabel::to_script_name,
+        )?;
+        utils::uninstall_all(self, labels)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        wrap_output(service(
+            &*self.runner,
+            self.config.service(),
+            "start",
+            &ctx.label.to_script_name(),
+        )?)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        wrap_output(service(
+            &*self.runner,
+            self.config.service(),
+            "stop",
+            &ctx.label.to_script_name(),
+        )?)?;
+        Ok(())
+    }
+
+    fn level(&self) -> ServiceLevel {
+        ServiceLevel::System
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => Ok(()),
+            ServiceLevel::User => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "SysV init does not support user-level services",
+            )),
+        }
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let log_path = self.config.install.log_path(&ctx.label.to_script_name());
+        let text = std::fs::read_to_string(log_path)?;
+        Ok(utils::tail(&text, ctx.lines))
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let script_name = ctx.label.to_script_name();
+        if !self.config.script_dir_path.join(&script_name).exists() {
+            return Ok(crate::ServiceStatus::NotInstalled.into());
+        }
+
+        // Exit codes of the status action are defined by the LSB specification
+        let output = service(&*self.runner, self.config.service(), "status", &script_name)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let status = match output.status.code() {
+            Some(0) => crate::ServiceStatus::Running,
+            Some(1) | Some(2) => crate::ServiceStatus::Stopped(Some(stdout.trim().to_string())),
+            Some(3) => crate::ServiceStatus::Stopped(None),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!(
+                        "Failed to get status of service {script_name}: {}",
+                        String::from_utf8_lossy(&output.stderr)
+                    ),
+                ))
+            }
+        };
+
+        let pid = match status {
+            crate::ServiceStatus::Running => parse_pid(&stdout),
+            _ => None,
+        };
+
+        Ok(crate::ServiceStatusDetails {
+            status,
+            pid,
+            transition: None,
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        utils::kill_main_process(&*self.runner, self, &ctx.label, ctx.signal)
+    }
+}
+
+fn service(
+    runner: &dyn CommandRunner,
+    program: &OsStr,
+    cmd: &str,
+    script_name: &str,
+) -> io::Result<Output> {
+    let mut command = Command::new(program);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .arg(script_name)
+        .arg(cmd);
+
+    runner.run(&mut command, None)
+}
+
+/// Enables the script for its default runlevels using whichever tool the distribution provides
+fn register(runner: &dyn CommandRunner, script_name: &str) -> io::Result<()> {
+    let output = if which::which(UPDATE_RC_D).is_ok() {
+        run(runner, UPDATE_RC_D, &[script_name, "defaults"])?
+    } else {
+        wrap_output(run(runner, CHKCONFIG, &["--add", script_name])?)?;
+        run(runner, CHKCONFIG, &[script_name, "on"])?
+    };
+    wrap_output(output)?;
+    Ok(())
+}
+
+fn deregister(runner: &dyn CommandRunner, script_name: &str) -> io::Result<()> {
+    let output = if which::which(UPDATE_RC_D).is_ok() {
+        run(runner, UPDATE_RC_D, &["-f", script_name, "remove"])?
+    } else {
+        run(runner, CHKCONFIG, &["--del", script_name])?
+    };
+    wrap_output(output)?;
+    Ok(())
+}
+
+fn run(runner: &dyn CommandRunner, program: &str, args: &[&str]) -> io::Result<Output> {
+    let mut command = Command::new(program);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .args(args);
+
+    runner.run(&mut command, None)
+}
+
+/// Extracts the pid from status output such as `my_service is running with pid 123`
+fn parse_pid(output: &str) -> Option<u32> {
+    let (_, rest) = output.split_once("pid")?;
+    rest.trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Path to the pidfile used by scripts generated by [`make_script`]
+#[inline]
+fn pidfile_path(script_name: &str) -> PathBuf {
+    PathBuf::from("/var/run").join(format!("{script_name}.pid"))
+}
+
+fn make_script(config: &SysVInstallConfig, script_name: &str, ctx: &ServiceInstallCtx) -> String {
+    let join_levels = |levels: &[u8]| {
+        levels
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    let required_start = config.required_start.join(" ");
+    let required_stop = config.required_stop.join(" ");
+    let default_start = join_levels(&config.default_start);
+    let default_stop = join_levels(&config.default_stop);
+
+    // The command is run through `sh -c`, so it is quoted for the shell before being stored
+    let cmd = utils::escape_shell_double_quoted(&utils::shell_join(ctx.cmd_iter()));
+    let pidfile = pidfile_path(script_name);
+    let pidfile = pidfile.to_string_lossy();
+    let log_path = config.log_path(script_name);
+    let log_path = log_path.to_string_lossy();
+
+    let mut setup = String::new();
+    if let Some(dir) = &ctx.working_directory {
+        let dir = utils::escape_shell_double_quoted(&dir.to_string_lossy());
+        let _ = write!(setup, "\n    cd \"{dir}\" || return 1");
+    }
+    if let Some(env_vars) = &ctx.environment {
+        for (var, val) in env_vars {
+            let val = utils::escape_shell_double_quoted(&val.to_string_lossy());
+            let _ = write!(setup, "\n    export {}=\"{val}\"", var.to_string_lossy());
+        }
+    }
+
+    // The background process is launched from a nested shell so that its pid can be recorded when
+    // privileges are dropped via su
+    let launch = match &ctx.username {
+        Some(username) => format!(
+            r#"su -s /bin/sh -c "$COMMAND >> \"$LOGFILE\" 2>&1 & echo \$!" {username} > "$PIDFILE""#
+        ),
+        None => {
+            String::from(r#"/bin/sh -c "$COMMAND >> \"$LOGFILE\" 2>&1 & echo \$!" > "$PIDFILE""#)
+        }
+    };
+
+    format!(
+        r#"
+#!/bin/sh
+### BEGIN INIT INFO
+# Provides:          {script_name}
+# Required-Start:    {required_start}
+# Required-Stop:     {required_stop}
+# Default-Start:     {default_start}
+# Default-Stop:      {default_stop}
+# Short-Description: {script_name}
+### END INIT INFO
+
+NAME="{script_name}"
+COMMAND="{cmd}"
+PIDFILE="{pidfile}"
+LOGFILE="{log_path}"
+
+is_running() {{
+    [ -f "$PIDFILE" ] && kill -0 "$(cat "$PIDFILE")" 2>/dev/null
+}}
+
+start() {{
+    if is_running; then
+        echo "$NAME is already running"
+        return 0
+    fi
+    echo "Starting $NAME"{setup}
+    {launch}
+}}
+
+stop() {{
+    if ! is_running; then
+        echo "$NAME is not running"
+        rm -f "$PIDFILE"
+        return 0
+    fi
+    echo "Stopping $NAME"
+    kill "$(cat "$PIDFILE")"
+    for _ in 1 2 3 4 5 6 7 8 9 10; do
+        is_running || break
+        sleep 1
+    done
+    if is_running; then
+        kill -9 "$(cat "$PIDFILE")"
+    fi
+    rm -f "$PIDFILE"
+}}
+
+status() {{
+    if is_running; then
+        echo "$NAME is running with pid $(cat "$PIDFILE")"
+        return 0
+    elif [ -f "$PIDFILE" ]; then
+        echo "$NAME is dead but pidfile exists"
+        return 1
+    fi
+    echo "$NAME is not running"
+    return 3
+}}
+
+case "$1" in
+    start) start ;;
+    stop) stop ;;
+    status) status ;;
+    restart) stop; start ;;
+    *)
+        echo "Usage: $0 {{start|stop|status|restart}}"
+        exit 2
+        ;;
+esac
+    "#
+    )
+    .trim()
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServiceLabel;
+    use std::ffi::OsString;
+
+    #[test]
+    fn test_make_script() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse::<ServiceLabel>().unwrap(),
+            program: PathBuf::from("/usr/local/bin/my_service"),
+            args: vec![OsString::from("--port"), OsString::from("8080")],
+            contents: None,
+            username: Some(String::from("nobody")),
+            working_directory: Some(PathBuf::from("/var/lib/my_service")),
+            environment: Some(vec![(OsString::from("RUST_LOG"), OsString::from("info"))]),
+            autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+
+        let script = make_script(
+            &SysVInstallConfig::default(),
+            "org.example.my_service",
+            &ctx,
+        );
+        assert!(script.starts_with("#!/bin/sh\n### BEGIN INIT INFO"));
+        assert!(script.contains("# Provides:          org.example.my_service\n"));
+        assert!(script.contains("# Required-Start:    $remote_fs $syslog\n"));
+        assert!(script.contains("# Default-Start:     2 3 4 5\n"));
+        assert!(script.contains("# Default-Stop:      0 1 6\n"));
+        assert!(script.contains("COMMAND=\"/usr/local/bin/my_service --port 8080\"\n"));
+        assert!(script.contains("PIDFILE=\"/var/run/org.example.my_service.pid\"\n"));
+        assert!(script.contains("LOGFILE=\"/var/log/org.example.my_service.log\"\n"));
+        assert!(script.contains(concat!(
+            "    echo \"Starting $NAME\"\n",
+            "    cd \"/var/lib/my_service\" || return 1\n",
+            "    export RUST_LOG=\"info\"\n",
+            "    su -s /bin/sh -c \"$COMMAND >> \\\"$LOGFILE\\\" 2>&1 & echo \\$!\" nobody > \"$PIDFILE\"\n",
+        )));
+    }
+
+    #[test]
+    fn test_parse_pid() {
+        assert_eq!(parse_pid("my_service is running with pid 123\n"), Some(123));
+        assert_eq!(
+            parse_pid("my_service (pid  4567) is running...\n"),
+            Some(4567)
+        );
+        assert_eq!(parse_pid("my_service is not running\n"), None);
+    }
+}
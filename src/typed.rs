@@ -1,30 +1,132 @@
 use super::{
-    LaunchdServiceManager, OpenRcServiceManager, RcdServiceManager, ScServiceManager,
-    ServiceInstallCtx, ServiceLevel, ServiceManager, ServiceManagerKind, ServiceStartCtx,
-    ServiceStopCtx, ServiceUninstallCtx, SystemdServiceManager, WinSwServiceManager,
+    ContainerConfig, ContainerServiceManager, CronConfig, CronServiceManager, DinitConfig,
+    DinitServiceManager, NssmConfig, NssmServiceManager, OpenRcConfig, OpenRcServiceManager,
+    ProcdConfig, ProcdServiceManager, RcdConfig, RcdServiceManager, RunitConfig,
+    RunitServiceManager, S6Config, S6ServiceManager, ScConfig, ScServiceManager, ServiceInstallCtx,
+    ServiceLevel, ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx,
+    ServiceUninstallCtx, SupervisordConfig, SupervisordServiceManager, SysVConfig,
+    SysVServiceManager, SystemdConfig, SystemdServiceManager, UpstartConfig, UpstartServiceManager,
 };
+#[cfg(feature = "launchd")]
+use super::{LaunchdConfig, LaunchdServiceManager};
+#[cfg(feature = "schtasks")]
+use super::{SchtasksConfig, SchtasksServiceManager};
+#[cfg(feature = "winsw")]
+use super::{WinSwConfig, WinSwServiceManager};
 use std::io;
 
+/// Configuration of every kind of service manager, used to target a kind chosen at runtime while
+/// still tuning the settings of whichever kind it turns out to be
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ServiceManagerConfig {
+    /// Configuration used when targeting cron
+    pub cron: CronConfig,
+
+    /// Configuration used when targeting dinit
+    pub dinit: DinitConfig,
+
+    /// Configuration used when targeting docker
+    pub docker: ContainerConfig,
+
+    /// Configuration used when targeting launchd
+    #[cfg(feature = "launchd")]
+    pub launchd: LaunchdConfig,
+
+    /// Configuration used when targeting NSSM
+    pub nssm: NssmConfig,
+
+    /// Configuration used when targeting OpenRC
+    pub openrc: OpenRcConfig,
+
+    /// Configuration used when targeting procd
+    pub procd: ProcdConfig,
+
+    /// Configuration used when targeting rc.d
+    pub rcd: RcdConfig,
+
+    /// Configuration used when targeting runit
+    pub runit: RunitConfig,
+
+    /// Configuration used when targeting s6
+    pub s6: S6Config,
+
+    /// Configuration used when targeting Task Scheduler
+    #[cfg(feature = "schtasks")]
+    pub schtasks: SchtasksConfig,
+
+    /// Configuration used when targeting sc.exe
+    pub sc: ScConfig,
+
+    /// Configuration used when targeting supervisord
+    pub supervisord: SupervisordConfig,
+
+    /// Configuration used when targeting systemd
+    pub systemd: SystemdConfig,
+
+    /// Configuration used when targeting SysV init
+    pub sysv: SysVConfig,
+
+    /// Configuration used when targeting Upstart
+    pub upstart: UpstartConfig,
+
+    /// Configuration used when targeting WinSW
+    #[cfg(feature = "winsw")]
+    pub winsw: WinSwConfig,
+}
+
 /// Represents an implementation of a known [`ServiceManager`]
+// The managers only hold configuration and are rarely moved, so boxing the larger ones is not worth
+// the change to how each variant is constructed and matched
+#[allow(clippy::large_enum_variant)]
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum TypedServiceManager {
+    Cron(CronServiceManager),
+    Dinit(DinitServiceManager),
+    Docker(ContainerServiceManager),
+    #[cfg(feature = "launchd")]
     Launchd(LaunchdServiceManager),
+    Nssm(NssmServiceManager),
     OpenRc(OpenRcServiceManager),
+    Procd(ProcdServiceManager),
     Rcd(RcdServiceManager),
+    Runit(RunitServiceManager),
+    S6(S6ServiceManager),
+    #[cfg(feature = "schtasks")]
+    Schtasks(SchtasksServiceManager),
     Sc(ScServiceManager),
+    Supervisord(SupervisordServiceManager),
     Systemd(SystemdServiceManager),
+    SysV(SysVServiceManager),
+    Upstart(UpstartServiceManager),
+    #[cfg(feature = "winsw")]
     WinSw(WinSwServiceManager),
+    Disabled(DisabledServiceManager),
 }
 
 macro_rules! using {
     ($self:ident, $this:ident -> $expr:expr) => {{
         match $self {
+            TypedServiceManager::Cron($this) => $expr,
+            TypedServiceManager::Dinit($this) => $expr,
+            TypedServiceManager::Docker($this) => $expr,
+            #[cfg(feature = "launchd")]
             TypedServiceManager::Launchd($this) => $expr,
+            TypedServiceManager::Nssm($this) => $expr,
             TypedServiceManager::OpenRc($this) => $expr,
+            TypedServiceManager::Procd($this) => $expr,
             TypedServiceManager::Rcd($this) => $expr,
+            TypedServiceManager::Runit($this) => $expr,
+            TypedServiceManager::S6($this) => $expr,
+            #[cfg(feature = "schtasks")]
+            TypedServiceManager::Schtasks($this) => $expr,
             TypedServiceManager::Sc($this) => $expr,
+            TypedServiceManager::Supervisord($this) => $expr,
             TypedServiceManager::Systemd($this) => $expr,
+            TypedServiceManager::SysV($this) => $expr,
+            TypedServiceManager::Upstart($this) => $expr,
+            #[cfg(feature = "winsw")]
             TypedServiceManager::WinSw($this) => $expr,
+            TypedServiceManager::Disabled($this) => $expr,
         }
     }};
 }
@@ -34,6 +136,10 @@ impl ServiceManager for TypedServiceManager {
         using!(self, x -> x.available())
     }
 
+    fn availability_details(&self) -> io::Result<crate::AvailabilityDetails> {
+        using!(self, x -> x.availability_details())
+    }
+
     fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
         using!(self, x -> x.install(ctx))
     }
@@ -42,6 +148,13 @@ impl ServiceManager for TypedServiceManager {
         using!(self, x -> x.uninstall(ctx))
     }
 
+    fn uninstall_matching(
+        &self,
+        prefix: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::ServiceLabel>> {
+        using!(self, x -> x.uninstall_matching(prefix))
+    }
+
     fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
         using!(self, x -> x.start(ctx))
     }
@@ -61,6 +174,60 @@ impl ServiceManager for TypedServiceManager {
     fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
         using!(self, x -> x.status(ctx))
     }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        using!(self, x -> x.status_details(ctx))
+    }
+
+    fn logs(&self, ctx: crate::ServiceLogsCtx) -> io::Result<String> {
+        using!(self, x -> x.logs(ctx))
+    }
+
+    fn diagnose(&self, ctx: crate::ServiceDiagnoseCtx) -> io::Result<crate::StartFailureReport> {
+        using!(self, x -> x.diagnose(ctx))
+    }
+
+    fn pause(&self, ctx: crate::ServicePauseCtx) -> io::Result<()> {
+        using!(self, x -> x.pause(ctx))
+    }
+
+    fn resume(&self, ctx: crate::ServiceResumeCtx) -> io::Result<()> {
+        using!(self, x -> x.resume(ctx))
+    }
+
+    fn is_up_to_date(&self, ctx: &crate::ServiceInstallCtx) -> io::Result<crate::DriftReport> {
+        using!(self, x -> x.is_up_to_date(ctx))
+    }
+
+    fn health(&self, ctx: crate::ServiceHealthCtx) -> io::Result<crate::ServiceHealth> {
+        using!(self, x -> x.health(ctx))
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        using!(self, x -> x.signal(ctx))
+    }
+
+    fn export(&self, ctx: crate::ServiceExportCtx) -> io::Result<crate::ServiceSnapshot> {
+        using!(self, x -> x.export(ctx))
+    }
+
+    fn import(&self, snapshot: crate::ServiceSnapshot) -> io::Result<()> {
+        using!(self, x -> x.import(snapshot))
+    }
+
+    fn rollback(&self, label: &crate::ServiceLabel) -> io::Result<()> {
+        using!(self, x -> x.rollback(label))
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        using!(self, x -> x.installed_artifacts(label))
+    }
 }
 
 impl TypedServiceManager {
@@ -77,12 +244,97 @@ impl TypedServiceManager {
     /// default service manager instance
     pub fn target(kind: ServiceManagerKind) -> Self {
         match kind {
+            ServiceManagerKind::Cron => Self::Cron(CronServiceManager::default()),
+            ServiceManagerKind::Dinit => Self::Dinit(DinitServiceManager::default()),
+            ServiceManagerKind::Docker => Self::Docker(ContainerServiceManager::default()),
+            #[cfg(feature = "launchd")]
             ServiceManagerKind::Launchd => Self::Launchd(LaunchdServiceManager::default()),
+            #[cfg(not(feature = "launchd"))]
+            ServiceManagerKind::Launchd => Self::Disabled(DisabledServiceManager::new(kind)),
+            ServiceManagerKind::Nssm => Self::Nssm(NssmServiceManager::default()),
             ServiceManagerKind::OpenRc => Self::OpenRc(OpenRcServiceManager::default()),
+            ServiceManagerKind::Procd => Self::Procd(ProcdServiceManager::default()),
             ServiceManagerKind::Rcd => Self::Rcd(RcdServiceManager::default()),
+            ServiceManagerKind::Runit => Self::Runit(RunitServiceManager::default()),
+            ServiceManagerKind::S6 => Self::S6(S6ServiceManager::default()),
+            #[cfg(feature = "schtasks")]
+            ServiceManagerKind::Schtasks => Self::Schtasks(SchtasksServiceManager::default()),
+            #[cfg(not(feature = "schtasks"))]
+            ServiceManagerKind::Schtasks => Self::Disabled(DisabledServiceManager::new(kind)),
             ServiceManagerKind::Sc => Self::Sc(ScServiceManager::default()),
+            ServiceManagerKind::Supervisord => {
+                Self::Supervisord(SupervisordServiceManager::default())
+            }
             ServiceManagerKind::Systemd => Self::Systemd(SystemdServiceManager::default()),
+            ServiceManagerKind::SysV => Self::SysV(SysVServiceManager::default()),
+            ServiceManagerKind::Upstart => Self::Upstart(UpstartServiceManager::default()),
+            #[cfg(feature = "winsw")]
             ServiceManagerKind::WinSw => Self::WinSw(WinSwServiceManager::default()),
+            #[cfg(not(feature = "winsw"))]
+            ServiceManagerKind::WinSw => Self::Disabled(DisabledServiceManager::new(kind)),
+        }
+    }
+
+    /// Creates a new service manager targeting the specific service manager kind using the
+    /// configuration of that kind from `config`
+    pub fn target_with(kind: ServiceManagerKind, config: ServiceManagerConfig) -> Self {
+        match kind {
+            ServiceManagerKind::Cron => {
+                Self::Cron(CronServiceManager::default().with_config(config.cron))
+            }
+            ServiceManagerKind::Dinit => {
+                Self::Dinit(DinitServiceManager::default().with_config(config.dinit))
+            }
+            ServiceManagerKind::Docker => {
+                Self::Docker(ContainerServiceManager::default().with_config(config.docker))
+            }
+            #[cfg(feature = "launchd")]
+            ServiceManagerKind::Launchd => {
+                Self::Launchd(LaunchdServiceManager::default().with_config(config.launchd))
+            }
+            #[cfg(not(feature = "launchd"))]
+            ServiceManagerKind::Launchd => Self::Disabled(DisabledServiceManager::new(kind)),
+            ServiceManagerKind::Nssm => {
+                Self::Nssm(NssmServiceManager::default().with_config(config.nssm))
+            }
+            ServiceManagerKind::OpenRc => {
+                Self::OpenRc(OpenRcServiceManager::default().with_config(config.openrc))
+            }
+            ServiceManagerKind::Procd => {
+                Self::Procd(ProcdServiceManager::default().with_config(config.procd))
+            }
+            ServiceManagerKind::Rcd => {
+                Self::Rcd(RcdServiceManager::default().with_config(config.rcd))
+            }
+            ServiceManagerKind::Runit => {
+                Self::Runit(RunitServiceManager::default().with_config(config.runit))
+            }
+            ServiceManagerKind::S6 => Self::S6(S6ServiceManager::default().with_config(config.s6)),
+            #[cfg(feature = "schtasks")]
+            ServiceManagerKind::Schtasks => {
+                Self::Schtasks(SchtasksServiceManager::default().with_config(config.schtasks))
+            }
+            #[cfg(not(feature = "schtasks"))]
+            ServiceManagerKind::Schtasks => Self::Disabled(DisabledServiceManager::new(kind)),
+            ServiceManagerKind::Sc => Self::Sc(ScServiceManager::default().with_config(config.sc)),
+            ServiceManagerKind::Supervisord => Self::Supervisord(
+                SupervisordServiceManager::default().with_config(config.supervisord),
+            ),
+            ServiceManagerKind::Systemd => {
+                Self::Systemd(SystemdServiceManager::default().with_config(config.systemd))
+            }
+            ServiceManagerKind::SysV => {
+                Self::SysV(SysVServiceManager::default().with_config(config.sysv))
+            }
+            ServiceManagerKind::Upstart => {
+                Self::Upstart(UpstartServiceManager::default().with_config(config.upstart))
+            }
+            #[cfg(feature = "winsw")]
+            ServiceManagerKind::WinSw => {
+                Self::WinSw(WinSwServiceManager::default().with_config(config.winsw))
+            }
+            #[cfg(not(feature = "winsw"))]
+            ServiceManagerKind::WinSw => Self::Disabled(DisabledServiceManager::new(kind)),
         }
     }
 
@@ -91,79 +343,333 @@ impl TypedServiceManager {
     /// * For MacOS, this will use [`LaunchdServiceManager`]
     /// * For Windows, this will use [`ScServiceManager`]
     /// * For BSD variants, this will use [`RcdServiceManager`]
-    /// * For Linux variants, this will use either [`SystemdServiceManager`] or [`OpenRcServiceManager`]
+    /// * For Linux variants, this will use [`SystemdServiceManager`], [`OpenRcServiceManager`],
+    ///   [`RunitServiceManager`], [`S6ServiceManager`], [`DinitServiceManager`],
+    ///   [`ProcdServiceManager`], [`UpstartServiceManager`], or [`SysVServiceManager`]
     pub fn native() -> io::Result<Self> {
         Ok(Self::target(ServiceManagerKind::native()?))
     }
 
+    /// Attempts to select the native service manager for the current operating system that
+    /// supports services at `level`, working with services at that level
+    ///
+    /// See [`ServiceManagerKind::native_for_level`] for how the service manager is selected.
+    pub fn native_for_level(level: ServiceLevel) -> io::Result<Self> {
+        let mut manager = Self::target(ServiceManagerKind::native_for_level(level)?);
+        manager.set_level(level)?;
+        Ok(manager)
+    }
+
     /// Consumes underlying [`ServiceManager`] and moves it onto the heap
     pub fn into_box(self) -> Box<dyn ServiceManager> {
         using!(self, x -> Box::new(x))
     }
 
+    /// Returns true if [`ServiceManager`] instance is for `cron`
+    pub fn is_cron(&self) -> bool {
+        matches!(self, Self::Cron(_))
+    }
+
+    /// Returns true if [`ServiceManager`] instance is for `dinit`
+    pub fn is_dinit(&self) -> bool {
+        matches!(self, Self::Dinit(_))
+    }
+
+    /// Returns true if [`ServiceManager`] instance is for `docker`
+    pub fn is_docker(&self) -> bool {
+        matches!(self, Self::Docker(_))
+    }
+
     /// Returns true if [`ServiceManager`] instance is for `launchd`
+    #[cfg(feature = "launchd")]
     pub fn is_launchd(&self) -> bool {
         matches!(self, Self::Launchd(_))
     }
 
+    /// Returns true if [`ServiceManager`] instance is for `NSSM`
+    pub fn is_nssm(&self) -> bool {
+        matches!(self, Self::Nssm(_))
+    }
+
     /// Returns true if [`ServiceManager`] instance is for `OpenRC`
     pub fn is_openrc(&self) -> bool {
         matches!(self, Self::OpenRc(_))
     }
 
+    /// Returns true if [`ServiceManager`] instance is for `procd`
+    pub fn is_procd(&self) -> bool {
+        matches!(self, Self::Procd(_))
+    }
+
     /// Returns true if [`ServiceManager`] instance is for `rc.d`
     pub fn is_rc_d(&self) -> bool {
         matches!(self, Self::Rcd(_))
     }
 
+    /// Returns true if [`ServiceManager`] instance is for `runit`
+    pub fn is_runit(&self) -> bool {
+        matches!(self, Self::Runit(_))
+    }
+
+    /// Returns true if [`ServiceManager`] instance is for `s6`
+    pub fn is_s6(&self) -> bool {
+        matches!(self, Self::S6(_))
+    }
+
+    /// Returns true if [`ServiceManager`] instance is for `Windows Task Scheduler`
+    #[cfg(feature = "schtasks")]
+    pub fn is_schtasks(&self) -> bool {
+        matches!(self, Self::Schtasks(_))
+    }
+
     /// Returns true if [`ServiceManager`] instance is for `sc`
     pub fn is_sc(&self) -> bool {
         matches!(self, Self::Sc(_))
     }
 
+    /// Returns true if [`ServiceManager`] instance is for `supervisord`
+    pub fn is_supervisord(&self) -> bool {
+        matches!(self, Self::Supervisord(_))
+    }
+
     /// Returns true if [`ServiceManager`] instance is for `systemd`
     pub fn is_systemd(&self) -> bool {
         matches!(self, Self::Systemd(_))
     }
 
+    /// Returns true if [`ServiceManager`] instance is for `SysV init`
+    pub fn is_sysv(&self) -> bool {
+        matches!(self, Self::SysV(_))
+    }
+
+    /// Returns true if [`ServiceManager`] instance is for `upstart`
+    pub fn is_upstart(&self) -> bool {
+        matches!(self, Self::Upstart(_))
+    }
+
     /// Returns true if [`ServiceManager`] instance is for `winsw`
+    #[cfg(feature = "winsw")]
     pub fn is_winsw(&self) -> bool {
         matches!(self, Self::WinSw(_))
     }
+
+    /// Returns true if [`ServiceManager`] instance stands in for a kind whose feature is disabled
+    pub fn is_disabled(&self) -> bool {
+        matches!(self, Self::Disabled(_))
+    }
 }
 
+/// Stands in for a kind of service manager whose feature was disabled when compiling this crate,
+/// failing every operation with [`io::ErrorKind::Unsupported`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisabledServiceManager {
+    kind: ServiceManagerKind,
+    level: ServiceLevel,
+}
+
+impl DisabledServiceManager {
+    /// Creates a stand-in for `kind`, whose feature shares its name, e.g. `launchd`
+    pub fn new(kind: ServiceManagerKind) -> Self {
+        Self {
+            kind,
+            level: ServiceLevel::System,
+        }
+    }
+
+    /// Returns the kind of service manager this stands in for
+    pub fn kind(&self) -> ServiceManagerKind {
+        self.kind
+    }
+
+    fn reason(&self) -> String {
+        format!(
+            "{} support requires the `{}` feature of service-manager",
+            self.kind, self.kind
+        )
+    }
+
+    fn unsupported<T>(&self) -> io::Result<T> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, self.reason()))
+    }
+}
+
+impl ServiceManager for DisabledServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        Ok(false)
+    }
+
+    fn availability_details(&self) -> io::Result<crate::AvailabilityDetails> {
+        Ok(crate::AvailabilityDetails::unavailable(self.reason()))
+    }
+
+    fn install(&self, _ctx: ServiceInstallCtx) -> io::Result<()> {
+        self.unsupported()
+    }
+
+    fn uninstall(&self, _ctx: ServiceUninstallCtx) -> io::Result<()> {
+        self.unsupported()
+    }
+
+    fn start(&self, _ctx: ServiceStartCtx) -> io::Result<()> {
+        self.unsupported()
+    }
+
+    fn stop(&self, _ctx: ServiceStopCtx) -> io::Result<()> {
+        self.unsupported()
+    }
+
+    fn level(&self) -> ServiceLevel {
+        self.level
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        self.level = level;
+        Ok(())
+    }
+
+    fn status(&self, _ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        self.unsupported()
+    }
+}
+
+impl From<super::CronServiceManager> for TypedServiceManager {
+    fn from(manager: super::CronServiceManager) -> Self {
+        Self::Cron(manager)
+    }
+}
+
+impl From<super::DinitServiceManager> for TypedServiceManager {
+    fn from(manager: super::DinitServiceManager) -> Self {
+        Self::Dinit(manager)
+    }
+}
+
+impl From<super::ContainerServiceManager> for TypedServiceManager {
+    fn from(manager: super::ContainerServiceManager) -> Self {
+        Self::Docker(manager)
+    }
+}
+
+#[cfg(feature = "launchd")]
 impl From<super::LaunchdServiceManager> for TypedServiceManager {
     fn from(manager: super::LaunchdServiceManager) -> Self {
         Self::Launchd(manager)
     }
 }
 
+impl From<super::NssmServiceManager> for TypedServiceManager {
+    fn from(manager: super::NssmServiceManager) -> Self {
+        Self::Nssm(manager)
+    }
+}
+
 impl From<super::OpenRcServiceManager> for TypedServiceManager {
     fn from(manager: super::OpenRcServiceManager) -> Self {
         Self::OpenRc(manager)
     }
 }
 
+impl From<super::ProcdServiceManager> for TypedServiceManager {
+    fn from(manager: super::ProcdServiceManager) -> Self {
+        Self::Procd(manager)
+    }
+}
+
 impl From<super::RcdServiceManager> for TypedServiceManager {
     fn from(manager: super::RcdServiceManager) -> Self {
         Self::Rcd(manager)
     }
 }
 
+impl From<super::RunitServiceManager> for TypedServiceManager {
+    fn from(manager: super::RunitServiceManager) -> Self {
+        Self::Runit(manager)
+    }
+}
+
+impl From<super::S6ServiceManager> for TypedServiceManager {
+    fn from(manager: super::S6ServiceManager) -> Self {
+        Self::S6(manager)
+    }
+}
+
+#[cfg(feature = "schtasks")]
+impl From<super::SchtasksServiceManager> for TypedServiceManager {
+    fn from(manager: super::SchtasksServiceManager) -> Self {
+        Self::Schtasks(manager)
+    }
+}
+
 impl From<super::ScServiceManager> for TypedServiceManager {
     fn from(manager: super::ScServiceManager) -> Self {
         Self::Sc(manager)
     }
 }
 
+impl From<super::SupervisordServiceManager> for TypedServiceManager {
+    fn from(manager: super::SupervisordServiceManager) -> Self {
+        Self::Supervisord(manager)
+    }
+}
+
 impl From<super::SystemdServiceManager> for TypedServiceManager {
     fn from(manager: super::SystemdServiceManager) -> Self {
         Self::Systemd(manager)
     }
 }
 
+impl From<super::SysVServiceManager> for TypedServiceManager {
+    fn from(manager: super::SysVServiceManager) -> Self {
+        Self::SysV(manager)
+    }
+}
+
+impl From<super::UpstartServiceManager> for TypedServiceManager {
+    fn from(manager: super::UpstartServiceManager) -> Self {
+        Self::Upstart(manager)
+    }
+}
+
+#[cfg(feature = "winsw")]
 impl From<super::WinSwServiceManager> for TypedServiceManager {
     fn from(manager: super::WinSwServiceManager) -> Self {
         Self::WinSw(manager)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_with() {
+        let systemd = crate::SystemdConfig {
+            systemctl_path: Some(std::path::PathBuf::from("/opt/shims/systemctl")),
+            ..Default::default()
+        };
+        let config = ServiceManagerConfig {
+            systemd: systemd.clone(),
+            ..Default::default()
+        };
+
+        match TypedServiceManager::target_with(ServiceManagerKind::Systemd, config) {
+            TypedServiceManager::Systemd(manager) => assert_eq!(manager.config, systemd),
+            manager => panic!("Unexpected manager: {manager:?}"),
+        }
+    }
+
+    #[test]
+    fn test_disabled_manager_is_unsupported() {
+        let manager = DisabledServiceManager::new(ServiceManagerKind::Launchd);
+
+        assert!(!manager.available().unwrap());
+        let err = manager
+            .status(crate::ServiceStatusCtx {
+                label: "com.example.my-service".parse().unwrap(),
+                level: None,
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(err.to_string().contains("`launchd` feature"));
+    }
+}
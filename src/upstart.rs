@@ -0,0 +1,530 @@
+use crate::utils::wrap_output;
+
+use super::{
+    manifest, utils, CommandRunner, RestartPolicy, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner,
+};
+use std::{
+    ffi::OsStr,
+    fmt::Write as _,
+    io,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+static INITCTL: &str = "initctl";
+
+const JOB_FILE_PERMISSIONS: u32 = 0o644;
+
+/// Configuration settings tied to Upstart jobs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpstartConfig {
+    pub install: UpstartInstallConfig,
+
+    /// Directory where job configuration files are written, defaulting to `/etc/init`
+    pub job_dir_path: PathBuf,
+
+    /// Directory where Upstart logs the output of jobs, defaulting to `/var/log/upstart`
+    pub log_dir_path: PathBuf,
+}
+
+impl Default for UpstartConfig {
+    fn default() -> Self {
+        Self {
+            install: UpstartInstallConfig::default(),
+            job_dir_path: PathBuf::from("/etc/init"),
+            log_dir_path: PathBuf::from("/var/log/upstart"),
+        }
+    }
+}
+
+/// Configuration settings used when installing Upstart jobs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpstartInstallConfig {
+    /// Runlevels in which the job is started when autostart is enabled, defaulting to `2345`
+    pub runlevels: String,
+
+    /// Limit on respawns as `(count, interval_secs)`, written as `respawn limit`, beyond which
+    /// Upstart stops respawning the job
+    pub respawn_limit: Option<(u32, u32)>,
+}
+
+impl Default for UpstartInstallConfig {
+    fn default() -> Self {
+        Self {
+            runlevels: String::from("2345"),
+            respawn_limit: None,
+        }
+    }
+}
+
+/// Implementation of [`ServiceManager`] for [Upstart](https://upstart.ubuntu.com/)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UpstartServiceManager {
+    /// Configuration settings tied to Upstart jobs
+    pub config: UpstartConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
+}
+
+impl UpstartServiceManager {
+    /// Creates a new manager instance working with system services
+    pub fn system() -> Self {
+        Self::default()
+    }
+
+    /// Update manager to use the specified config
+    pub fn with_config(self, config: UpstartConfig) -> Self {
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
+    }
+
+    fn job_path(&self, job: &str) -> PathBuf {
+        self.config.job_dir_path.join(format!("{job}.conf"))
+    }
+}
+
+impl ServiceManager for UpstartServiceManager {
+    fn available(&self) -> io::Result<bool> {
+        match which::which(INITCTL) {
+            Ok(_) => Ok(true),
+            Err(which::Error::CannotFindBinaryPath) => Ok(false),
+            Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
+        }
+    }
+
+    fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        utils::validate_environment(&ctx, false)?;
+        utils::require_utf8(&ctx, "Upstart")?;
+        utils::capabilities_unsupported(&ctx, "Upstart")?;
+        utils::check_program(&ctx)?;
+        if ctx.install_root.is_none() {
+            utils::check_file_access(&*self.runner, self.level(), "Upstart")?;
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::Upstart, self.level(), &ctx);
+        let ctx = utils::install_binary(ctx)?;
+        std::fs::create_dir_all(utils::staged_path(&ctx, &self.config.job_dir_path))?;
+
+        let job = ctx.label.to_script_name();
+        let conf = match &ctx.contents {
+            Some(contents) => contents.clone(),
+            _ => make_job(&self.config.install, &ctx),
+        };
+
+        utils::write_definition(
+            &ctx,
+            &utils::staged_path(&ctx, &self.job_path(&job)),
+            conf.as_bytes(),
+            JOB_FILE_PERMISSIONS,
+        )?;
+
+        // Upstart watches its job directory, but reloading makes the job known immediately
+        if ctx.install_root.is_none() {
+            wrap_output(initctl(&*self.runner, ["reload-configuration"])?)?;
+        }
+        Ok(())
+    }
+
+    fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        utils::ensure_installed(self, &ctx.label)?;
+        utils::check_file_access(&*self.runner, self.level(), "Upstart")?;
+
+        let job = ctx.label.to_script_name();
+        let job_path = self.job_path(&job);
+        if job_path.exists() {
+            // The job cannot be stopped once its configuration is gone, so stop it beforehand
+            // and ignore the failure of stopping a job that is not running
+            let _ = initctl(&*self.runner, ["stop", &job])?;
+            std::fs::remove_file(&job_path)?;
+        }
+        utils::remove_definition_backup(&job_path)?;
+
+        wrap_output(initctl(&*self.runner, ["reload-configuration"])?)?;
+        utils::purge(&ctx, [self.config.log_dir_path.join(format!("{job}.log"))])?;
+        manifest::remove(ServiceManagerKind::Upstart, self.level(), &ctx)?;
+        utils::remove_installed_binary(&ctx)
+    }
+
+    fn rollback(&self, label: &crate::ServiceLabel) -> io::Result<()> {
+        utils::restore_definition(&self.job_path(&label.to_script_name()))?;
+        manifest::restore(ServiceManagerKind::Upstart, self.level(), label)?;
+        wrap_output(initctl(&*self.runner, ["reload-configuration"])?)?;
+        Ok(())
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::Upstart, self.level(), label)
+    }
+
+    fn uninstall_matching(
+        &self,
+        prefix: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::ServiceLabel>> {
+        let labels = utils::labels_matching(
+            &self.config.job_dir_path,
+            ".conf",
+            prefix,
+            crate::ServiceLabel::to_script_name,
+        )?;
+        utils::uninstall_all(self, labels)
+    }
+
+    fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
+        wrap_output(initctl(
+            &*self.runner,
+            ["start", &ctx.label.to_script_name()],
+        )?)?;
+        utils::wait_until_active(self, &ctx)
+    }
+
+    fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
+        wrap_output(initctl(
+            &*self.runner,
+            ["stop", &ctx.label.to_script_name()],
+        )?)?;
+        Ok(())
+    }
+
+    fn level(&self) -> ServiceLevel {
+        ServiceLevel::System
+    }
+
+    fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
+        match level {
+            ServiceLevel::System => Ok(()),
+            ServiceLevel::User => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Upstart does not support user-level services",
+            )),
+        }
+    }
+
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let log_path = self
+            .config
+            .log_dir_path
+            .join(format!("{}.log", ctx.label.to_script_name()));
+        let text = std::fs::read_to_string(log_path)?;
+        Ok(utils::tail(&text, ctx.lines))
+    }
+
+    fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<crate::ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
+        let job = ctx.label.to_script_name();
+        let output = initctl(&*self.runner, ["status", &job])?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if stderr.contains("Unknown job") {
+                return Ok(crate::ServiceStatus::NotInstalled.into());
+            }
+        }
+
+        let output = wrap_output(output)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        parse_status(&stdout).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to get status of job {job}: {}", stdout.trim()),
+            )
+        })
+    }
+
+    fn signal(&self, ctx: crate::ServiceSignalCtx) -> io::Result<()> {
+        utils::kill_main_process(&*self.runner, self, &ctx.label, ctx.signal)
+    }
+}
+
+fn initctl<'a>(
+    runner: &dyn CommandRunner,
+    args: impl IntoIterator<Item = &'a str>,
+) -> io::Result<Output> {
+    let mut command = Command::new(INITCTL);
+
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    for arg in args {
+        command.arg(OsStr::new(arg));
+    }
+
+    runner.run(&mut command, None)
+}
+
+/// Parses the output of `initctl status {job}`, which looks like `job start/running, process 123`
+/// where the goal of the job precedes its current state
+fn parse_status(output: &str) -> Option<crate::ServiceStatusDetails> {
+    let line = output.lines().next()?;
+    let (_job, rest) = line.split_once(' ')?;
+    let (goal_state, process) = match rest.split_once(',') {
+        Some((goal_state, process)) => (goal_state, Some(process)),
+        None => (rest, None),
+    };
+    let (goal, state) = goal_state.trim().split_once('/')?;
+
+    let (status, transition) = match (goal, state) {
+        ("start", "running") => (crate::ServiceStatus::Running, None),
+        ("stop", "waiting") => (crate::ServiceStatus::Stopped(None), None),
+        ("start", _) => (
+            crate::ServiceStatus::Stopped(None),
+            Some(crate::ServiceTransition::Starting),
+        ),
+        ("stop", _) => (
+            crate::ServiceStatus::Running,
+            Some(crate::ServiceTransition::Stopping),
+        ),
+        _ => return None,
+    };
+
+    let pid = process
+        .and_then(|process| process.trim().strip_prefix("process "))
+        .and_then(|pid| pid.trim().parse().ok());
+
+    Some(crate::ServiceStatusDetails {
+        status,
+        pid,
+        transition,
+    })
+}
+
+fn make_job(config: &UpstartInstallConfig, ctx: &ServiceInstallCtx) -> String {
+    let cmd = utils::shell_join(ctx.cmd_iter());
+
+    let description = match &ctx.description {
+        Some(description) => utils::escape_shell_double_quoted(description),
+        None => ctx.label.to_qualified_name(),
+    };
+    let mut job = format!("description \"{description}\"\n\n");
+
+    if ctx.autostart {
+        let _ = writeln!(job, "start on runlevel [{}]", config.runlevels);
+    } else {
+        let _ = writeln!(job, "manual");
+    }
+    let _ = writeln!(job, "stop on runlevel [!{}]", config.runlevels);
+    job.push('\n');
+
+    let oneshot = ctx.kind == crate::ServiceKind::Oneshot;
+    let delay_secs = match ctx.restart_policy {
+        // A task is never respawned and its start only completes once the program exits
+        _ if oneshot => {
+            let _ = writeln!(job, "task");
+            None
+        }
+        Some(RestartPolicy::Never) => None,
+        None => {
+            let _ = writeln!(job, "respawn");
+            None
+        }
+        Some(RestartPolicy::Always { delay_secs }) => {
+            let _ = writeln!(job, "respawn");
+            delay_secs
+        }
+        Some(RestartPolicy::OnFailure { delay_secs }) => {
+            // Exiting with a normal exit code does not cause Upstart to respawn the job
+            let _ = writeln!(job, "respawn");
+            let _ = writeln!(job, "normal exit 0");
+            delay_secs
+        }
+    };
+
+    if !oneshot && ctx.restart_policy != Some(RestartPolicy::Never) {
+        if let Some((count, interval_secs)) = config.respawn_limit {
+            let _ = writeln!(job, "respawn limit {count} {interval_secs}");
+        }
+    }
+
+    let _ = writeln!(job, "console log");
+
+    if let Some(username) = &ctx.username {
+        let _ = writeln!(job, "setuid {username}");
+    }
+
+    if let Some(dir) = &ctx.working_directory {
+        let _ = writeln!(job, "chdir {}", dir.to_string_lossy());
+    }
+
+    if let Some(env_vars) = &ctx.environment {
+        for (var, val) in env_vars {
+            let val = val
+                .to_string_lossy()
+                .replace('\\', r"\\")
+                .replace('"', r#"\""#);
+            let _ = writeln!(job, "env {}=\"{val}\"", var.to_string_lossy());
+        }
+    }
+
+    job.push('\n');
+    let _ = writeln!(job, "exec {cmd}");
+
+    // Upstart has no delay between respawns, so one is added after the job stops
+    if let Some(delay_secs) = delay_secs {
+        let _ = writeln!(job, "post-stop exec sleep {delay_secs}");
+    }
+
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ServiceLabel;
+    use indoc::indoc;
+    use std::ffi::OsString;
+
+    #[test]
+    fn test_make_job() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse::<ServiceLabel>().unwrap(),
+            program: PathBuf::from("/usr/local/bin/my_service"),
+            args: vec![OsString::from("--port"), OsString::from("8080")],
+            contents: None,
+            username: Some(String::from("nobody")),
+            working_directory: Some(PathBuf::from("/var/lib/my_service")),
+            environment: Some(vec![(OsString::from("RUST_LOG"), OsString::from("info"))]),
+            autostart: true,
+            restart_policy: Some(RestartPolicy::OnFailure {
+                delay_secs: Some(5),
+            }),
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+        let config = UpstartInstallConfig {
+            respawn_limit: Some((10, 60)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            make_job(&config, &ctx),
+            indoc! {r#"
+                description "org.example.my_service"
+
+                start on runlevel [2345]
+                stop on runlevel [!2345]
+
+                respawn
+                normal exit 0
+                respawn limit 10 60
+                console log
+                setuid nobody
+                chdir /var/lib/my_service
+                env RUST_LOG="info"
+
+                exec /usr/local/bin/my_service --port 8080
+                post-stop exec sleep 5
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_make_job_for_oneshot() {
+        let ctx = ServiceInstallCtx {
+            label: "org.example.provision".parse::<ServiceLabel>().unwrap(),
+            program: PathBuf::from("/usr/local/bin/provision"),
+            args: Vec::new(),
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: None,
+            autostart: true,
+            restart_policy: Some(RestartPolicy::Always { delay_secs: None }),
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Oneshot,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+        let config = UpstartInstallConfig {
+            respawn_limit: Some((10, 60)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            make_job(&config, &ctx),
+            indoc! {r#"
+                description "org.example.provision"
+
+                start on runlevel [2345]
+                stop on runlevel [!2345]
+
+                task
+                console log
+
+                exec /usr/local/bin/provision
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_parse_status() {
+        let details = parse_status("example-my_service start/running, process 1234\n").unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Running);
+        assert_eq!(details.pid, Some(1234));
+        assert_eq!(details.transition, None);
+
+        let details = parse_status("example-my_service stop/waiting\n").unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Stopped(None));
+        assert_eq!(details.pid, None);
+
+        let details = parse_status("example-my_service start/pre-start, process 99\n").unwrap();
+        assert_eq!(details.status, crate::ServiceStatus::Stopped(None));
+        assert_eq!(details.transition, Some(crate::ServiceTransition::Starting));
+
+        assert_eq!(parse_status(""), None);
+    }
+}
@@ -1,27 +1,749 @@
+use crate::{
+    CommandRunner, ServiceInstallCtx, ServiceLabel, ServiceLevel, ServiceManager, ServiceStartCtx,
+    ServiceStatus, ServiceStatusCtx, ServiceUninstallCtx, Signal, WaitMode,
+};
 use std::{
     borrow::Cow,
+    ffi::{OsStr, OsString},
     fs::OpenOptions,
     io::{self, Write},
-    path::Path,
+    path::{Component, Path, PathBuf},
     process::Output,
+    thread,
+    time::{Duration, Instant},
 };
 
+/// Time between checks of whether a service waited on by [`wait_until_active`] is running
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
 /// Writes/overwrites a file, assigning the permissions of `mode` if on a unix system
-pub fn write_file(path: &Path, data: &[u8], _mode: u32) -> io::Result<()> {
-    let mut opts = OpenOptions::new();
-    opts.create(true).write(true).truncate(true);
+///
+/// The data is written to a temporary file in the same directory, which is synced and then renamed
+/// over `path`, so a crash never leaves a partially written file behind. Files written during an
+/// install are recorded in the [`InstallManifest`](crate::InstallManifest) of the service.
+pub fn write_file(path: &Path, data: &[u8], mode: u32) -> io::Result<()> {
+    write_file_with(path, data, mode, |_| Ok(()))
+}
+
+/// Writes/overwrites a file like [`write_file`], calling `prepare` with the path of the temporary
+/// file before any data is written to it
+fn write_file_with(
+    path: &Path,
+    data: &[u8],
+    _mode: u32,
+    prepare: impl FnOnce(&Path) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    // A temporary file left behind by a crash may have other permissions, so it is never reused
+    let _ = std::fs::remove_file(&tmp_path);
+
+    let result = (|| {
+        let mut opts = OpenOptions::new();
+        opts.create_new(true).write(true);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            opts.mode(_mode);
+        }
+
+        let mut file = opts.open(&tmp_path)?;
+        prepare(&tmp_path)?;
+        file.write_all(data)?;
+
+        // Ensure that the data/metadata is synced and catch errors before replacing the file
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+        return result;
+    }
+
+    // Syncing the directory persists the rename, which is best effort as not every platform
+    // supports opening directories
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().and_then(|dir| std::fs::File::open(dir).ok()) {
+        let _ = dir.sync_all();
+    }
+
+    crate::manifest::record(crate::InstalledArtifact::File(path.to_path_buf()));
+    Ok(())
+}
+
+/// Writes/overwrites a file defining the service of `ctx`, assigning the permissions and owner
+/// requested by `ctx` in place of the default permissions of `mode`
+///
+/// The requested permissions and owner are applied to the temporary file before the definition
+/// is written, so that its contents are never readable by others. If `ctx` asks to back up the
+/// definition, the file being replaced is first copied to the path from [`backup_path`].
+pub fn write_definition(
+    ctx: &ServiceInstallCtx,
+    path: &Path,
+    data: &[u8],
+    mode: u32,
+) -> io::Result<()> {
+    if ctx.backup_definition && path.exists() {
+        std::fs::copy(path, backup_path(path))?;
+    }
+
+    let mode = ctx.definition_permissions.unwrap_or(mode);
+    write_file_with(path, data, mode, |_tmp_path| {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if ctx.definition_permissions.is_some() {
+                std::fs::set_permissions(_tmp_path, std::fs::Permissions::from_mode(mode))?;
+            }
+
+            if let Some(owner) = &ctx.definition_owner {
+                let output = std::process::Command::new("chown")
+                    .arg(owner)
+                    .arg(_tmp_path)
+                    .output()?;
+                wrap_output(output)?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Returns the path of the backup of the definition at `path`, which has `.bak` appended
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+/// Replaces the definition at `path` with the backup kept by [`write_definition`], failing with
+/// [`io::ErrorKind::NotFound`] if there is none
+pub fn restore_definition(path: &Path) -> io::Result<()> {
+    let backup = backup_path(path);
+    if !backup.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No previous definition of {} was kept", path.display()),
+        ));
+    }
+
+    std::fs::rename(backup, path)
+}
+
+/// Removes the backup kept by [`write_definition`] of the definition at `path`, if any
+pub fn remove_definition_backup(path: &Path) -> io::Result<()> {
+    match std::fs::remove_file(backup_path(path)) {
+        Err(x) if x.kind() == io::ErrorKind::NotFound => Ok(()),
+        x => x,
+    }
+}
+
+/// Creates a symbolic link at `link` pointing to `original`, which is only supported on unix systems
+#[cfg(unix)]
+pub fn symlink(original: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+/// Creates a symbolic link at `link` pointing to `original`, which is only supported on unix systems
+#[cfg(not(unix))]
+pub fn symlink(_original: &Path, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Symbolic links can only be created on unix systems",
+    ))
+}
+
+/// Error returned when installing a [`ServiceKind::Oneshot`](crate::ServiceKind::Oneshot) service
+/// with a service manager that only supervises long-running processes
+pub fn oneshot_unsupported(manager: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{manager} does not support oneshot services"),
+    )
+}
+
+/// Fails with [`io::ErrorKind::NotFound`] unless the service is installed, so that uninstalling a
+/// missing service fails the same way with every service manager
+pub fn ensure_installed(manager: &impl ServiceManager, label: &ServiceLabel) -> io::Result<()> {
+    let status = manager.status(ServiceStatusCtx {
+        label: label.clone(),
+        level: None,
+    })?;
+
+    match status {
+        ServiceStatus::NotInstalled => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Service {} is not installed", label.to_qualified_name()),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Returns `path` if provided, otherwise the name of the program `default` to be searched for on
+/// the `PATH`
+pub fn program_or<'a>(path: Option<&'a Path>, default: &'a str) -> &'a OsStr {
+    path.map_or(OsStr::new(default), Path::as_os_str)
+}
+
+/// Fails if any capability of the context is not named like `CAP_NET_BIND_SERVICE`
+pub fn validate_capabilities(ctx: &ServiceInstallCtx) -> io::Result<()> {
+    for cap in &ctx.capabilities {
+        let valid = cap.strip_prefix("CAP_").map_or(false, |x| {
+            !x.is_empty()
+                && x.bytes()
+                    .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+        });
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid capability {cap:?}, expected a name like CAP_NET_BIND_SERVICE"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Fails if the context asks to back up the definition of a service for a service manager that
+/// cannot roll it back
+pub fn backup_unsupported(ctx: &ServiceInstallCtx, manager: &str) -> io::Result<()> {
+    if !ctx.backup_definition {
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{manager} does not support backing up and rolling back service definitions"),
+    ))
+}
+
+/// Fails with [`io::ErrorKind::PermissionDenied`] if `runner` elevates the commands of a service
+/// manager at `level` while the current process, which writes its definition files, is not
+/// elevated itself
+pub fn check_file_access(
+    runner: &dyn CommandRunner,
+    level: ServiceLevel,
+    manager: &str,
+) -> io::Result<()> {
+    let strategy = runner.elevation();
+    if strategy.program().is_none() || !crate::needs_elevation(level)? {
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!(
+            "{manager} definitions are written by this process rather than through {strategy}, \
+            so system services can only be installed and uninstalled by running this program as \
+            root"
+        ),
+    ))
+}
+
+/// Fails if the context asks to write the files of a service beneath an install root for a
+/// service manager that registers services through commands rather than files
+pub fn staging_unsupported(ctx: &ServiceInstallCtx, manager: &str) -> io::Result<()> {
+    if ctx.install_root.is_none() {
+        return Ok(());
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{manager} does not support installing services beneath an install root"),
+    ))
+}
+
+/// Returns where `path` is written when installing the service of `ctx`, which is `path` itself
+/// unless the context has an install root to write files beneath
+pub fn staged_path(ctx: &ServiceInstallCtx, path: &Path) -> PathBuf {
+    match ctx.install_root.as_deref() {
+        Some(root) => root.join(
+            path.components()
+                .filter(|x| !matches!(x, Component::Prefix(_) | Component::RootDir))
+                .collect::<PathBuf>(),
+        ),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Fails if the context has capabilities for a service manager that cannot grant them, pointing
+/// to `setcap` as a way to grant them to the program itself
+pub fn capabilities_unsupported(ctx: &ServiceInstallCtx, manager: &str) -> io::Result<()> {
+    if ctx.capabilities.is_empty() {
+        return Ok(());
+    }
+
+    let caps = ctx
+        .capabilities
+        .iter()
+        .map(|x| x.to_lowercase())
+        .collect::<Vec<_>>()
+        .join(",");
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{manager} cannot grant capabilities to services, but they can be granted to the \
+            program itself using `setcap {caps}+ep {}`",
+            ctx.program.display()
+        ),
+    ))
+}
+
+/// Waits for a service started with [`WaitMode::UntilActive`] to be reported as running by
+/// `manager`, returning immediately for [`WaitMode::None`]
+pub fn wait_until_active(manager: &impl ServiceManager, ctx: &ServiceStartCtx) -> io::Result<()> {
+    let timeout = match ctx.wait {
+        WaitMode::None => return Ok(()),
+        WaitMode::UntilActive(timeout) => timeout,
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        let status = manager.status(ServiceStatusCtx {
+            label: ctx.label.clone(),
+            level: None,
+        })?;
+
+        match status {
+            ServiceStatus::Running => return Ok(()),
+            ServiceStatus::NotInstalled => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Service {} is not installed", ctx.label.to_qualified_name()),
+                ))
+            }
+            status if Instant::now() >= deadline => {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "Service {} was not running within {timeout:?}, last {status:?}",
+                        ctx.label.to_qualified_name()
+                    ),
+                ))
+            }
+            _ => thread::sleep(WAIT_POLL_INTERVAL),
+        }
+    }
+}
+
+/// Sends `signal` to the main process of a service using `kill`, for service managers that can
+/// report the process of a service but have no command to signal it
+pub fn kill_main_process(
+    runner: &dyn CommandRunner,
+    manager: &impl ServiceManager,
+    label: &ServiceLabel,
+    signal: Signal,
+) -> io::Result<()> {
+    let details = manager.status_details(ServiceStatusCtx {
+        label: label.clone(),
+        level: None,
+    })?;
+
+    let pid = match (details.status, details.pid) {
+        (ServiceStatus::NotInstalled, _) => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Service {} is not installed", label.to_qualified_name()),
+            ))
+        }
+        (_, Some(pid)) => pid,
+        (_, None) => {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("Service {} is not running", label.to_qualified_name()),
+            ))
+        }
+    };
+
+    let mut command = std::process::Command::new("kill");
+    command.arg("-s").arg(signal.name()).arg(pid.to_string());
+    wrap_output(runner.run(&mut command, None)?)?;
+    Ok(())
+}
+
+/// Returns true if running within the Windows Subsystem for Linux, whose kernel identifies itself
+/// as a Microsoft build in `/proc/version`
+pub fn is_wsl() -> bool {
+    std::fs::read_to_string("/proc/version").map_or(false, |version| is_wsl_version(&version))
+}
+
+fn is_wsl_version(version: &str) -> bool {
+    let version = version.to_lowercase();
+    version.contains("microsoft") || version.contains("wsl")
+}
+
+/// Checks that the environment variables of a service can be written by a service manager
+///
+/// Names cannot be empty or contain `=`, whitespace, or NUL characters, and values cannot contain
+/// NUL characters. Newlines within values are only accepted when `multiline` is true, as many
+/// service definitions are line-based and have no way of escaping them.
+pub fn validate_environment(ctx: &ServiceInstallCtx, multiline: bool) -> io::Result<()> {
+    for (var, val) in ctx.environment.iter().flatten() {
+        // Characters that cannot be converted are replaced rather than dropped, so checking the
+        // converted text never accepts a name or value that it would otherwise reject
+        let (var, val) = (var.to_string_lossy(), val.to_string_lossy());
+        if var.is_empty() || var.contains(|c: char| c == '=' || c == '\0' || c.is_whitespace()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid environment variable name {var:?}"),
+            ));
+        }
+
+        if val.contains('\0') || (!multiline && val.contains(['\n', '\r'])) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unsupported value of environment variable {var}: {val:?}"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Fails unless the program, arguments, working directory, environment variables, and stop command
+/// of a service are valid UTF-8, for service managers whose definitions can only hold text
+///
+/// Definitions generated once this succeeds convert these settings to text without any loss, rather
+/// than silently replacing the characters that cannot be converted.
+pub fn require_utf8(ctx: &ServiceInstallCtx, manager: &str) -> io::Result<()> {
+    let settings = ctx
+        .cmd_iter()
+        .map(|x| ("program and arguments", x))
+        .chain(
+            ctx.working_directory
+                .iter()
+                .map(|x| ("working directory", x.as_os_str())),
+        )
+        .chain(
+            ctx.environment
+                .iter()
+                .flatten()
+                .flat_map(|(var, val)| [var, val])
+                .map(|x| ("environment variables", x.as_os_str())),
+        )
+        .chain(ctx.stop_cmd_iter().map(|x| ("stop command", x)));
+
+    for (setting, value) in settings {
+        if value.to_str().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{manager} requires the {setting} of a service to be valid UTF-8: {value:?}"
+                ),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Fails unless the program of a service exists and is executable, so that a mistyped or missing
+/// path is reported when installing rather than when the service first starts
+///
+/// Programs given by name alone are looked up within the `PATH`.
+pub fn check_program(ctx: &ServiceInstallCtx) -> io::Result<()> {
+    if ctx.skip_program_check || ctx.install_root.is_some() {
+        return Ok(());
+    }
+
+    let path = resolve_program(&ctx.program)?;
+    let metadata = std::fs::metadata(&path).map_err(|x| {
+        io::Error::new(
+            x.kind(),
+            format!("Program {} cannot be accessed: {x}", path.display()),
+        )
+    })?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Program {} is not a file", path.display()),
+        ));
+    }
 
     #[cfg(unix)]
     {
-        use std::os::unix::fs::OpenOptionsExt;
-        opts.mode(_mode);
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("Program {} is not executable", path.display()),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the path of `program`, looking it up within the `PATH` when given by name alone
+fn resolve_program(program: &Path) -> io::Result<PathBuf> {
+    if program.is_absolute() || program.components().count() != 1 {
+        return Ok(program.to_path_buf());
+    }
+
+    which::which(program).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Program {} was not found in PATH", program.display()),
+        )
+    })
+}
+
+/// Directory within `dir` holding the copy of the program of the service with `label`
+fn installed_binary_dir(dir: &Path, label: &ServiceLabel) -> PathBuf {
+    dir.join(label.to_qualified_name())
+}
+
+/// Returns the program of a service alongside the path of its copy within
+/// [`install_binary_to`](ServiceInstallCtx::install_binary_to), if specified
+fn binary_destination(ctx: &ServiceInstallCtx) -> io::Result<Option<(PathBuf, PathBuf)>> {
+    let dir = match &ctx.install_binary_to {
+        Some(dir) => installed_binary_dir(dir, &ctx.label),
+        None => return Ok(None),
+    };
+
+    let source = resolve_program(&ctx.program)?;
+    let file_name = source.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Program {} has no file name", source.display()),
+        )
+    })?;
+    let destination = dir.join(file_name);
+    Ok(Some((source, destination)))
+}
+
+/// Returns the context of a service as it is installed, with its program pointing at the copy
+/// made by [`install_binary`] if there is one, without copying anything
+pub fn installed_ctx(ctx: &ServiceInstallCtx) -> io::Result<Cow<'_, ServiceInstallCtx>> {
+    Ok(match binary_destination(ctx)? {
+        Some((_, destination)) => Cow::Owned(ServiceInstallCtx {
+            program: destination,
+            ..ctx.clone()
+        }),
+        None => Cow::Borrowed(ctx),
+    })
+}
+
+/// Copies the program of a service into its own directory within
+/// [`install_binary_to`](ServiceInstallCtx::install_binary_to), if specified, returning the
+/// context with its program pointing at the copy
+pub fn install_binary(mut ctx: ServiceInstallCtx) -> io::Result<ServiceInstallCtx> {
+    let (source, destination) = match binary_destination(&ctx)? {
+        Some(x) => x,
+        None => return Ok(ctx),
+    };
+    // The copy is made beneath the install root while the service still runs the final path
+    let staged = staged_path(&ctx, &destination);
+    let dir = staged.parent().unwrap_or(Path::new("/")).to_path_buf();
+
+    // Reinstalling from the copy itself would otherwise truncate it before it is read
+    let same_file = match (source.canonicalize(), staged.canonicalize()) {
+        (Ok(source), Ok(staged)) => source == staged,
+        _ => false,
+    };
+    if !same_file {
+        std::fs::create_dir_all(&dir)?;
+        std::fs::copy(&source, &staged)?;
+        crate::manifest::record(crate::InstalledArtifact::File(destination.clone()));
+    }
+
+    ctx.program = destination;
+    Ok(ctx)
+}
+
+/// Removes each of `paths` that exists, recursively for directories, if `ctx` asks to purge what
+/// was created for the service
+pub fn purge(
+    ctx: &ServiceUninstallCtx,
+    paths: impl IntoIterator<Item = PathBuf>,
+) -> io::Result<()> {
+    if !ctx.purge {
+        return Ok(());
+    }
+
+    for path in paths {
+        let result = match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&path),
+            Ok(_) => std::fs::remove_file(&path),
+            Err(x) => Err(x),
+        };
+
+        match result {
+            Err(x) if x.kind() == io::ErrorKind::NotFound => {}
+            x => x?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the copy of the program of a service made by [`install_binary`], if any
+pub fn remove_installed_binary(ctx: &ServiceUninstallCtx) -> io::Result<()> {
+    let dir = match &ctx.install_binary_to {
+        Some(dir) => installed_binary_dir(dir, &ctx.label),
+        None => return Ok(()),
+    };
+
+    match std::fs::remove_dir_all(dir) {
+        Err(x) if x.kind() == io::ErrorKind::NotFound => Ok(()),
+        x => x,
+    }
+}
+
+/// Finds the labels of services under `prefix` whose definitions are files in `dir` named by
+/// `name_of` their label followed by `suffix`, where a service is under `prefix` when it has the
+/// same label or its application continues that of `prefix` after a `.`
+pub fn labels_matching(
+    dir: &Path,
+    suffix: &str,
+    prefix: &ServiceLabel,
+    name_of: impl Fn(&ServiceLabel) -> String,
+) -> io::Result<Vec<ServiceLabel>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(x) => x,
+        Err(x) if x.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(x) => return Err(x),
+    };
+
+    let prefix_name = name_of(prefix);
+    let mut labels = Vec::new();
+    for entry in entries {
+        let file_name = entry?.file_name();
+        let rest = file_name
+            .to_str()
+            .and_then(|x| x.strip_suffix(suffix))
+            .and_then(|x| x.strip_prefix(prefix_name.as_str()));
+
+        match rest {
+            Some(rest) if rest.is_empty() || (rest.starts_with('.') && rest.len() > 1) => labels
+                .push(ServiceLabel {
+                    application: format!("{}{rest}", prefix.application),
+                    ..prefix.clone()
+                }),
+            _ => {}
+        }
+    }
+
+    labels.sort_by_key(ServiceLabel::to_qualified_name);
+    Ok(labels)
+}
+
+/// Uninstalls each service of `labels` using `manager`, stopping at the first that fails
+pub fn uninstall_all(
+    manager: &impl ServiceManager,
+    labels: Vec<ServiceLabel>,
+) -> io::Result<Vec<ServiceLabel>> {
+    for label in &labels {
+        manager.uninstall(ServiceUninstallCtx {
+            label: label.clone(),
+            install_binary_to: None,
+            purge: false,
+        })?;
+    }
+
+    Ok(labels)
+}
+
+/// Escapes `value` to be placed within double quotes in a POSIX shell script, where `\`, `"`,
+/// `$`, and `` ` `` would otherwise be interpreted
+pub fn escape_shell_double_quoted(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | '"' | '$' | '`') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Returns true if `arg` is non-empty and made up only of characters that no command-line syntax
+/// treats specially, meaning it can be written as-is
+fn is_plain_arg(arg: &str) -> bool {
+    !arg.is_empty()
+        && arg.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '-' | '_' | '.' | '/' | '=' | ':' | ',' | '+' | '@' | '%')
+        })
+}
+
+/// Quotes `arg` for a POSIX shell, wrapping it in single quotes when it contains any characters
+/// the shell would interpret, such as whitespace or quotes
+pub fn shell_quote(arg: &str) -> Cow<'_, str> {
+    if is_plain_arg(arg) {
+        return Cow::Borrowed(arg);
+    }
+
+    Cow::Owned(format!("'{}'", arg.replace('\'', r"'\''")))
+}
+
+/// Joins `args` into a single POSIX shell command line, quoting each argument as needed
+pub fn shell_join<'a>(args: impl IntoIterator<Item = &'a OsStr>) -> String {
+    args.into_iter()
+        .map(|arg| shell_quote(&arg.to_string_lossy()).into_owned())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Quotes `arg` by wrapping it in double quotes, within which `\\` and `"` are escaped by a
+/// backslash, when it contains any characters that would otherwise split it apart
+pub fn double_quote(arg: &str) -> Cow<'_, str> {
+    if is_plain_arg(arg) {
+        return Cow::Borrowed(arg);
+    }
+
+    Cow::Owned(format!(
+        "\"{}\"",
+        arg.replace('\\', r"\\").replace('"', r#"\""#)
+    ))
+}
+
+/// Quotes `arg` for a Windows command line so that it is parsed back into a single argument by
+/// programs following the conventions of `CommandLineToArgvW`
+#[cfg(any(feature = "schtasks", feature = "winsw"))]
+pub fn windows_quote(arg: &str) -> Cow<'_, str> {
+    if !arg.is_empty() && !arg.contains(|c: char| c.is_whitespace() || c == '"') {
+        return Cow::Borrowed(arg);
     }
 
-    let mut file = opts.open(path)?;
-    file.write_all(data)?;
+    // Backslashes are only special when they precede a quote, in which case they are doubled
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat('\\').take(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat('\\').take(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    quoted.extend(std::iter::repeat('\\').take(backslashes * 2));
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
 
-    // Ensure that the data/metadata is synced and catch errors before dropping
-    file.sync_all()
+/// Returns the last `lines` lines of `text`, or all of `text` if `lines` is not specified
+pub fn tail(text: &str, lines: Option<usize>) -> String {
+    match lines {
+        Some(n) => {
+            let all = text.lines().collect::<Vec<&str>>();
+            all[all.len().saturating_sub(n)..].join("\n")
+        }
+        None => text.trim_end().to_string(),
+    }
 }
 
 /// Warp the output of a command in a `std::io::Result` if the command failed
@@ -99,3 +821,433 @@ pub mod encoding {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    pub(crate) fn install_ctx(program: PathBuf) -> ServiceInstallCtx {
+        ServiceInstallCtx {
+            label: "org.example.my_service".parse().unwrap(),
+            program,
+            args: Vec::new(),
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: None,
+            autostart: false,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        }
+    }
+
+    #[test]
+    fn test_is_wsl_version() {
+        assert!(is_wsl_version(
+            "Linux version 5.15.153.1-microsoft-standard-WSL2 (root@941d701f84f1) (gcc (GCC) 11.2.0)"
+        ));
+        assert!(is_wsl_version(
+            "Linux version 4.4.0-19041-Microsoft (Microsoft@Microsoft.com) (gcc version 5.4.0)"
+        ));
+        assert!(!is_wsl_version(
+            "Linux version 6.8.0-45-generic (buildd@lcy02-amd64-115) (x86_64-linux-gnu-gcc-13)"
+        ));
+    }
+
+    #[test]
+    fn test_validate_environment() {
+        let mut ctx = ServiceInstallCtx {
+            environment: Some(vec![(OsString::from("GREETING"), OsString::from("a\nb"))]),
+            ..install_ctx("/usr/local/bin/my_service".into())
+        };
+        assert!(validate_environment(&ctx, true).is_ok());
+        assert_eq!(
+            validate_environment(&ctx, false).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        for var in ["", "A=B", "A B", "A\0"] {
+            ctx.environment = Some(vec![(OsString::from(var), OsString::from("value"))]);
+            assert!(validate_environment(&ctx, true).is_err(), "{var:?}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_require_utf8() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let mut ctx = install_ctx("/usr/local/bin/my_service".into());
+        ctx.environment = Some(vec![(OsString::from("GREETING"), OsString::from("hello"))]);
+        assert!(require_utf8(&ctx, "systemd").is_ok());
+
+        ctx.environment = Some(vec![(
+            OsString::from("GREETING"),
+            OsString::from_vec(vec![0x68, 0xff]),
+        )]);
+        let err = require_utf8(&ctx, "systemd").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("environment variables"), "{err}");
+
+        ctx.environment = None;
+        ctx.args = vec![OsString::from_vec(vec![0xc3, 0x28])];
+        assert!(require_utf8(&ctx, "systemd").is_err());
+    }
+
+    #[test]
+    fn test_purge() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let log_path = temp.path().join("my_service.log");
+        let log_dir_path = temp.path().join("my_service");
+        std::fs::write(&log_path, "log").unwrap();
+        std::fs::create_dir_all(log_dir_path.join("archive")).unwrap();
+
+        let mut ctx = ServiceUninstallCtx {
+            label: "org.example.my_service".parse().unwrap(),
+            install_binary_to: None,
+            purge: false,
+        };
+        let paths = || {
+            [
+                log_path.clone(),
+                log_dir_path.clone(),
+                temp.path().join("missing"),
+            ]
+        };
+        purge(&ctx, paths()).unwrap();
+        assert!(log_path.exists());
+
+        ctx.purge = true;
+        purge(&ctx, paths()).unwrap();
+        assert!(!log_path.exists());
+        assert!(!log_dir_path.exists());
+    }
+
+    #[test]
+    fn test_restore_definition() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("my_service.service");
+        let mut ctx = install_ctx("/usr/local/bin/my_service".into());
+        ctx.backup_definition = true;
+
+        write_definition(&ctx, &path, b"first", 0o644).unwrap();
+        assert!(!backup_path(&path).exists());
+        write_definition(&ctx, &path, b"second", 0o644).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(backup_path(&path)).unwrap(),
+            "first"
+        );
+
+        restore_definition(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first");
+        assert_eq!(
+            restore_definition(&path).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        // Only the definition itself is left behind by writing it
+        let names = std::fs::read_dir(temp.path()).unwrap().count();
+        assert_eq!(names, 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_definition() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let path = temp.path().join("my_service.service");
+        let mut ctx = install_ctx("/usr/local/bin/my_service".into());
+
+        write_definition(&ctx, &path, b"[Unit]\n", 0o644).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o644);
+
+        // Replacing a definition restricts the permissions of the existing file
+        ctx.definition_permissions = Some(0o600);
+        write_definition(&ctx, &path, b"[Service]\n", 0o644).unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[Service]\n");
+    }
+
+    #[test]
+    fn test_check_program() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let mut ctx = install_ctx(temp.path().join("missing"));
+        assert_eq!(
+            check_program(&ctx).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        ctx.program = temp.path().to_path_buf();
+        assert_eq!(
+            check_program(&ctx).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+
+        ctx.skip_program_check = true;
+        assert!(check_program(&ctx).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_program_is_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = assert_fs::TempDir::new().unwrap();
+        let program = temp.path().join("my_service");
+        std::fs::write(&program, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut ctx = install_ctx(program.clone());
+        assert_eq!(
+            check_program(&ctx).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+
+        std::fs::set_permissions(&program, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(check_program(&ctx).is_ok());
+
+        ctx.program = PathBuf::from("sh");
+        assert!(check_program(&ctx).is_ok());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[test]
+    fn test_wait_until_active() {
+        use crate::MockServiceManager;
+
+        let mock = MockServiceManager::new();
+        let ctx = ServiceStartCtx {
+            label: "com.example.my-service".parse().unwrap(),
+            wait: WaitMode::UntilActive(Duration::from_secs(5)),
+        };
+        assert_eq!(
+            wait_until_active(&mock, &ctx).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        mock.set_status(&ctx.label, ServiceStatus::Stopped(None));
+        mock.push_status(&ctx.label, ServiceStatus::Stopped(None));
+        mock.push_status(&ctx.label, ServiceStatus::Running);
+        wait_until_active(&mock, &ctx).unwrap();
+
+        let ctx = ServiceStartCtx {
+            wait: WaitMode::UntilActive(Duration::ZERO),
+            ..ctx
+        };
+        assert_eq!(
+            wait_until_active(&mock, &ctx).unwrap_err().kind(),
+            io::ErrorKind::TimedOut
+        );
+    }
+
+    #[test]
+    fn test_validate_capabilities() {
+        let mut ctx = install_ctx("/usr/local/bin/my_service".into());
+        ctx.capabilities = vec![String::from("CAP_NET_BIND_SERVICE")];
+        assert!(validate_capabilities(&ctx).is_ok());
+        assert_eq!(
+            capabilities_unsupported(&ctx, "OpenRC").unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+
+        for cap in [
+            "NET_BIND_SERVICE",
+            "CAP_",
+            "cap_net_bind_service",
+            "CAP_NET BIND",
+        ] {
+            ctx.capabilities = vec![cap.to_string()];
+            assert_eq!(
+                validate_capabilities(&ctx).unwrap_err().kind(),
+                io::ErrorKind::InvalidInput
+            );
+        }
+
+        ctx.capabilities.clear();
+        assert!(capabilities_unsupported(&ctx, "OpenRC").is_ok());
+    }
+
+    #[test]
+    fn test_labels_matching() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        for name in [
+            "example-app.service",
+            "example-app.api.service",
+            "example-app.worker.service",
+            "example-application.service",
+            "example-app.api.conf",
+            "other-app.api.service",
+        ] {
+            std::fs::write(temp.path().join(name), "").unwrap();
+        }
+
+        let prefix: ServiceLabel = "com.example.app".parse().unwrap();
+        let labels = labels_matching(
+            temp.path(),
+            ".service",
+            &prefix,
+            ServiceLabel::to_script_name,
+        )
+        .unwrap()
+        .iter()
+        .map(ServiceLabel::to_qualified_name)
+        .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            [
+                "com.example.app",
+                "com.example.app.api",
+                "com.example.app.worker"
+            ]
+        );
+
+        assert!(labels_matching(
+            &temp.path().join("missing"),
+            ".service",
+            &prefix,
+            ServiceLabel::to_script_name
+        )
+        .unwrap()
+        .is_empty());
+    }
+
+    #[test]
+    fn test_install_binary() {
+        let temp = assert_fs::TempDir::new().unwrap();
+        let program = temp.path().join("build").join("my_service");
+        std::fs::create_dir_all(program.parent().unwrap()).unwrap();
+        std::fs::write(&program, "binary").unwrap();
+
+        let libexec = temp.path().join("libexec");
+        let ctx = ServiceInstallCtx {
+            install_binary_to: Some(libexec.clone()),
+            ..install_ctx(program.clone())
+        };
+        let ctx = install_binary(ctx).unwrap();
+        let copy = libexec.join("org.example.my_service").join("my_service");
+        assert_eq!(ctx.program, copy);
+        assert_eq!(std::fs::read_to_string(&copy).unwrap(), "binary");
+
+        // Installing again from the copy leaves it intact
+        let ctx = install_binary(ctx).unwrap();
+        assert_eq!(std::fs::read_to_string(&ctx.program).unwrap(), "binary");
+
+        let uninstall_ctx = ServiceUninstallCtx {
+            label: ctx.label.clone(),
+            install_binary_to: Some(libexec.clone()),
+            purge: false,
+        };
+        remove_installed_binary(&uninstall_ctx).unwrap();
+        assert!(!copy.exists());
+        assert!(program.exists());
+        remove_installed_binary(&uninstall_ctx).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_staged_path() {
+        let path = Path::new("/etc/systemd/system/my_service.service");
+        let ctx = install_ctx(PathBuf::from("/usr/bin/my_service"));
+        assert_eq!(staged_path(&ctx, path), path);
+
+        let ctx = ServiceInstallCtx {
+            install_root: Some(PathBuf::from("/tmp/pkg")),
+            ..ctx
+        };
+        assert_eq!(
+            staged_path(&ctx, path),
+            Path::new("/tmp/pkg/etc/systemd/system/my_service.service")
+        );
+        assert!(check_program(&ctx).is_ok());
+        assert_eq!(
+            staging_unsupported(&ctx, "sc.exe").unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_join() {
+        let args = [
+            "--path",
+            "/opt/My App",
+            r#"quote " and ' quote"#,
+            "$HOME;`id`",
+            r"back\slash",
+            "",
+        ];
+        let script = format!(
+            r#"for arg in {}; do printf '%s\n' "$arg"; done"#,
+            shell_join(args.iter().map(OsStr::new))
+        );
+        let output = std::process::Command::new("sh")
+            .args(["-c", &script])
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            args.map(|arg| format!("{arg}\n")).concat()
+        );
+    }
+
+    #[test]
+    fn test_double_quote() {
+        assert_eq!(double_quote("--port"), "--port");
+        assert_eq!(double_quote("/opt/My App"), r#""/opt/My App""#);
+        assert_eq!(double_quote(r#"a "b" \c"#), r#""a \"b\" \\c""#);
+        assert_eq!(double_quote(""), r#""""#);
+    }
+
+    #[test]
+    #[cfg(any(feature = "schtasks", feature = "winsw"))]
+    fn test_windows_quote() {
+        assert_eq!(windows_quote("--port"), "--port");
+        assert_eq!(windows_quote(r"C:\My App\"), r#""C:\My App\\""#);
+        assert_eq!(windows_quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(windows_quote(r#"a\"b"#), r#""a\\\"b""#);
+        assert_eq!(windows_quote(""), r#""""#);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_escape_shell_double_quoted() {
+        for value in [
+            "plain",
+            "with spaces",
+            r#"quote " and ' quote"#,
+            "$HOME and `id` and $(id)",
+            r"back\slash\",
+            "100%",
+            "multi\nline",
+        ] {
+            let script = format!("printf %s \"{}\"", escape_shell_double_quoted(value));
+            let output = std::process::Command::new("sh")
+                .args(["-c", &script])
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), value);
+        }
+    }
+}
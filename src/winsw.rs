@@ -1,11 +1,13 @@
-use crate::utils::wrap_output;
+use crate::utils::{self, wrap_output};
 use crate::ServiceStatus;
 
 use super::{
-    ServiceInstallCtx, ServiceLevel, ServiceManager, ServiceStartCtx, ServiceStopCtx,
-    ServiceUninstallCtx,
+    manifest, RestartPolicy, ScServiceManager, ServiceInstallCtx, ServiceLevel, ServiceLogsCtx,
+    ServiceManager, ServiceManagerKind, ServiceStartCtx, ServiceStopCtx, ServiceUninstallCtx,
+    SharedCommandRunner, WindowsAccount,
 };
 use std::ffi::OsString;
+use std::fmt;
 use std::fs::File;
 use std::io::{self, BufWriter, Cursor, Write};
 use std::path::{Path, PathBuf};
@@ -16,15 +18,36 @@ use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
 
 static WINSW_EXE: &str = "winsw.exe";
 
-///
-/// Service configuration
-///
+/// Name of the WinSW release asset that is downloaded when [`WinSwConfig::auto_download`] is set
+const WINSW_RELEASE_ASSET: &str = "WinSW-x64.exe";
+
+//
+// Service configuration
+//
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct WinSwConfig {
     pub install: WinSwInstallConfig,
     pub options: WinSwOptionsConfig,
     pub service_definition_dir_path: PathBuf,
+
+    /// Optional directory holding the definition and logs of the service, overriding the
+    /// default of `{service_definition_dir_path}\{service_name}`
+    ///
+    /// Environment variables written as `%NAME%` are expanded in both directories. As this
+    /// directory may be shared with other services or data, uninstalling a service only removes
+    /// the files named after the service from it, and the directory itself once it is empty.
+    pub service_dir_path: Option<PathBuf>,
+
+    /// If true, uninstalling a service only removes its definition and leaves its directory in
+    /// place along with any log files, rather than removing the entire directory
+    pub preserve_logs_on_uninstall: bool,
+
+    /// If provided, a pinned release of WinSW is downloaded into the service definition
+    /// directory when `winsw.exe` cannot be found
+    ///
+    /// Requires the `winsw-download` feature.
+    pub auto_download: Option<WinSwAutoDownload>,
 }
 
 impl Default for WinSwConfig {
@@ -33,15 +56,56 @@ impl Default for WinSwConfig {
             install: WinSwInstallConfig::default(),
             options: WinSwOptionsConfig::default(),
             service_definition_dir_path: PathBuf::from("C:\\ProgramData\\service-manager"),
+            service_dir_path: None,
+            preserve_logs_on_uninstall: false,
+            auto_download: None,
         }
     }
 }
 
+/// Pinned release of WinSW to download when `winsw.exe` cannot be found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WinSwAutoDownload {
+    /// Version of the release such as `2.12.0`
+    pub version: String,
+
+    /// Expected SHA-256 checksum of the executable as a hex string, which is verified every time
+    /// before the executable is used, downloading it again if a previous copy does not match
+    pub sha256: String,
+
+    /// Optional url to download the executable from instead of the GitHub release
+    pub url_override: Option<String>,
+}
+
+impl WinSwAutoDownload {
+    /// Returns the url that the executable is downloaded from
+    pub fn url(&self) -> String {
+        match &self.url_override {
+            Some(url) => url.clone(),
+            None => format!(
+                "https://github.com/winsw/winsw/releases/download/v{}/{WINSW_RELEASE_ASSET}",
+                self.version
+            ),
+        }
+    }
+
+    /// Returns the path of the downloaded executable within the given directory
+    pub fn path_within(&self, dir_path: &Path) -> PathBuf {
+        dir_path.join(format!("winsw-{}.exe", self.version))
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct WinSwInstallConfig {
     pub failure_action: WinSwOnFailureAction,
     pub reset_failure_time: Option<String>,
     pub security_descriptor: Option<String>,
+
+    /// Optional account the service runs as, written as the `<serviceaccount>` element
+    ///
+    /// If not specified, the `username` of the install context is used without a password.
+    /// If neither is specified, the service runs as `LocalSystem`.
+    pub service_account: Option<WindowsAccount>,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -55,6 +119,90 @@ pub struct WinSwOptionsConfig {
     pub dependent_services: Option<Vec<String>>,
     pub interactive: Option<bool>,
     pub beep_on_shutdown: Option<bool>,
+    pub log: Option<WinSwLogConfig>,
+    pub downloads: Option<Vec<WinSwDownload>>,
+    pub prestart: Option<WinSwHook>,
+    pub poststop: Option<WinSwHook>,
+}
+
+/// File that WinSW downloads each time before the service starts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WinSwDownload {
+    /// Url to download the file from
+    pub from: String,
+
+    /// Path to write the downloaded file to, where `%BASE%` refers to the service definition
+    /// directory
+    pub to: PathBuf,
+
+    /// If true, the service fails to start when the download fails
+    pub fail_on_error: bool,
+}
+
+/// Executable that WinSW runs around the lifecycle of the service
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WinSwHook {
+    /// Path to the executable to run
+    pub executable: PathBuf,
+
+    /// Optional arguments to pass to the executable
+    pub arguments: Option<String>,
+
+    /// Optional path to redirect the standard output of the executable to
+    pub stdout_path: Option<PathBuf>,
+
+    /// Optional path to redirect the standard error of the executable to
+    pub stderr_path: Option<PathBuf>,
+}
+
+/// Configuration of the logs that WinSW writes for the output of a service
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WinSwLogConfig {
+    /// How log files are written and rotated
+    pub mode: WinSwLogMode,
+
+    /// Size in kilobytes at which a log file is rolled when using [`WinSwLogMode::RollBySize`]
+    pub size_threshold_kb: Option<u32>,
+
+    /// Number of rolled log files to keep when using [`WinSwLogMode::RollBySize`]
+    pub keep_files: Option<u32>,
+
+    /// Directory to write log files into, which defaults to the service definition directory
+    ///
+    /// `%BASE%` refers to the service definition directory.
+    pub path: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WinSwLogMode {
+    /// Append to the same log files forever
+    Append,
+    /// Truncate the log files each time the service starts
+    Reset,
+    /// Discard all output
+    None,
+    /// Roll the log files each time the service starts
+    Roll,
+    /// Roll the log files once they reach a size threshold
+    RollBySize,
+}
+
+impl Default for WinSwLogMode {
+    fn default() -> Self {
+        Self::Append
+    }
+}
+
+impl fmt::Display for WinSwLogMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Append => write!(f, "append"),
+            Self::Reset => write!(f, "reset"),
+            Self::None => write!(f, "none"),
+            Self::Roll => write!(f, "roll"),
+            Self::RollBySize => write!(f, "roll-by-size"),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -88,15 +236,32 @@ pub enum WinSwPriority {
     AboveNormal,
 }
 
-///
-/// Service manager implementation
-///
+impl WinSwPriority {
+    /// Priority class closest to the unix niceness `nice`, where only an explicit
+    /// [`WinSwPriority::RealTime`] is ever used due to its ability to starve the system
+    pub fn from_nice(nice: i8) -> Self {
+        match nice {
+            ..=-10 => Self::High,
+            -9..=-1 => Self::AboveNormal,
+            0 => Self::Normal,
+            1..=9 => Self::BelowNormal,
+            10.. => Self::Idle,
+        }
+    }
+}
+
+//
+// Service manager implementation
+//
 
 /// Implementation of [`ServiceManager`] for [Window Service](https://en.wikipedia.org/wiki/Windows_service)
 /// leveraging [`winsw.exe`](https://github.com/winsw/winsw)
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct WinSwServiceManager {
     pub config: WinSwConfig,
+
+    /// Runner used to execute external commands
+    pub runner: SharedCommandRunner,
 }
 
 impl WinSwServiceManager {
@@ -105,12 +270,29 @@ impl WinSwServiceManager {
             install: WinSwInstallConfig::default(),
             options: WinSwOptionsConfig::default(),
             service_definition_dir_path: PathBuf::from("C:\\ProgramData\\service-manager"),
+            service_dir_path: None,
+            preserve_logs_on_uninstall: false,
+            auto_download: None,
         };
-        Self { config }
+        Self {
+            config,
+            runner: SharedCommandRunner::default(),
+        }
     }
 
     pub fn with_config(self, config: WinSwConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            runner: self.runner,
+        }
+    }
+
+    /// Update manager to execute external commands using the specified runner
+    pub fn with_runner(self, runner: impl Into<SharedCommandRunner>) -> Self {
+        Self {
+            runner: runner.into(),
+            ..self
+        }
     }
 
     pub fn write_service_configuration(
@@ -159,21 +341,26 @@ impl WinSwServiceManager {
 
         // Mandatory values
         Self::write_element(&mut writer, "id", &ctx.label.to_qualified_name())?;
-        Self::write_element(&mut writer, "name", &ctx.label.to_qualified_name())?;
+        Self::write_element(
+            &mut writer,
+            "name",
+            &ctx.display_name
+                .clone()
+                .unwrap_or_else(|| ctx.label.to_qualified_name()),
+        )?;
         Self::write_element(&mut writer, "executable", &ctx.program.to_string_lossy())?;
         Self::write_element(
             &mut writer,
             "description",
-            &format!("Service for {}", ctx.label.to_qualified_name()),
+            &ctx.description
+                .clone()
+                .unwrap_or_else(|| format!("Service for {}", ctx.label.to_qualified_name())),
         )?;
-        let args = ctx
-            .args
-            .clone()
-            .into_iter()
-            .map(|s| s.into_string().unwrap_or_default())
-            .collect::<Vec<String>>()
-            .join(" ");
-        Self::write_element(&mut writer, "arguments", &args)?;
+        // Each argument is written as its own element so that WinSW quotes arguments containing
+        // spaces or quotes rather than splitting them apart
+        for arg in ctx.args_iter() {
+            Self::write_element(&mut writer, "argument", &arg.to_string_lossy())?;
+        }
 
         if let Some(working_directory) = &ctx.working_directory {
             Self::write_element(
@@ -183,18 +370,30 @@ impl WinSwServiceManager {
             )?;
         }
         if let Some(env_vars) = &ctx.environment {
-            for var in env_vars.iter() {
+            for (var, val) in env_vars.iter() {
                 Self::write_element_with_attributes(
                     &mut writer,
                     "env",
-                    &[("name", &var.0), ("value", &var.1)],
+                    &[
+                        ("name", &var.to_string_lossy()),
+                        ("value", &val.to_string_lossy()),
+                    ],
                     None,
                 )?;
             }
         }
 
         // Optional install elements
-        let (action, delay) = match &config.install.failure_action {
+        // The restart policy of the context takes precedence over the config, where WinSW only
+        // restarts services that fail just like sc.exe
+        let failure_action = match ctx.restart_policy {
+            Some(RestartPolicy::Never) => WinSwOnFailureAction::None,
+            Some(
+                RestartPolicy::Always { delay_secs } | RestartPolicy::OnFailure { delay_secs },
+            ) => WinSwOnFailureAction::Restart(delay_secs.map(|x| format!("{x} sec"))),
+            None => config.install.failure_action.clone(),
+        };
+        let (action, delay) = match &failure_action {
             WinSwOnFailureAction::Restart(delay) => ("restart", delay.as_deref()),
             WinSwOnFailureAction::Reboot => ("reboot", None),
             WinSwOnFailureAction::None => ("none", None),
@@ -203,33 +402,114 @@ impl WinSwServiceManager {
             || vec![("action", action)],
             |d| vec![("action", action), ("delay", d)],
         );
-        Self::write_element_with_attributes(&mut writer, "onfailure", &attributes, None)?;
 
-        if let Some(reset_time) = &config.install.reset_failure_time {
+        // WinSW repeats the last action for any further failures, so a start limit restarts the
+        // service up to its burst before ending with the action of the limit
+        match (ctx.start_limit, action) {
+            (Some(limit), "restart") => {
+                for _ in 0..limit.burst {
+                    Self::write_element_with_attributes(
+                        &mut writer,
+                        "onfailure",
+                        &attributes,
+                        None,
+                    )?;
+                }
+                let action = match limit.action {
+                    crate::StartLimitAction::None => "none",
+                    crate::StartLimitAction::Reboot => "reboot",
+                };
+                Self::write_element_with_attributes(
+                    &mut writer,
+                    "onfailure",
+                    &[("action", action)],
+                    None,
+                )?;
+            }
+            _ => Self::write_element_with_attributes(&mut writer, "onfailure", &attributes, None)?,
+        }
+
+        let reset_time = match ctx.start_limit {
+            Some(limit) => Some(format!("{} sec", limit.interval.as_secs())),
+            None => config.install.reset_failure_time.clone(),
+        };
+        if let Some(reset_time) = &reset_time {
             Self::write_element(&mut writer, "resetfailure", reset_time)?;
         }
         if let Some(security_descriptor) = &config.install.security_descriptor {
             Self::write_element(&mut writer, "securityDescriptor", security_descriptor)?;
         }
 
+        let service_account = match (&config.install.service_account, &ctx.username) {
+            (Some(account), _) => Some(account.clone()),
+            (None, Some(username)) => Some(WindowsAccount::new(username)),
+            (None, None) => None,
+        };
+        if let Some(account) =
+            service_account.filter(|a| !a.name.eq_ignore_ascii_case("LocalSystem"))
+        {
+            writer
+                .write(XmlEvent::start_element("serviceaccount"))
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("Failed to write element 'serviceaccount': {}", e),
+                    )
+                })?;
+            Self::write_element(&mut writer, "username", &account.name)?;
+            // Built-in, virtual, and group managed service accounts have no password and already
+            // hold the right to log on as a service
+            if !account.is_managed() {
+                if let Some(password) = &account.password {
+                    Self::write_element(&mut writer, "password", password)?;
+                }
+                Self::write_element(&mut writer, "allowservicelogon", "true")?;
+            }
+            writer.write(XmlEvent::end_element()).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Failed to end element 'serviceaccount': {}", e),
+                )
+            })?;
+        }
+
         // Other optional elements
-        if let Some(priority) = &config.options.priority {
+        let priority = match (&config.options.priority, ctx.priority.and_then(|x| x.nice)) {
+            (Some(priority), _) => Some(*priority),
+            (None, Some(nice)) => Some(WinSwPriority::from_nice(nice)),
+            (None, None) => None,
+        };
+        if let Some(priority) = priority {
             Self::write_element(&mut writer, "priority", &format!("{:?}", priority))?;
         }
-        if let Some(stop_timeout) = &config.options.stop_timeout {
+        let stop_timeout = match (&config.options.stop_timeout, ctx.stop_timeout) {
+            (Some(stop_timeout), _) => Some(stop_timeout.clone()),
+            (None, Some(timeout)) => Some(format!("{} ms", timeout.as_millis())),
+            (None, None) => None,
+        };
+        if let Some(stop_timeout) = &stop_timeout {
             Self::write_element(&mut writer, "stoptimeout", stop_timeout)?;
         }
-        if let Some(stop_executable) = &config.options.stop_executable {
+
+        // A stop executable configured for WinSW takes precedence over the one of the service
+        let (stop_executable, stop_args) =
+            match (&config.options.stop_executable, &ctx.stop_command) {
+                (None, Some((program, args))) => (Some(program), Some(args)),
+                (stop_executable, _) => {
+                    (stop_executable.as_ref(), config.options.stop_args.as_ref())
+                }
+            };
+        if let Some(stop_executable) = stop_executable {
             Self::write_element(
                 &mut writer,
                 "stopexecutable",
                 &stop_executable.to_string_lossy(),
             )?;
         }
-        if let Some(stop_args) = &config.options.stop_args {
+        if let Some(stop_args) = stop_args {
             let stop_args = stop_args
                 .iter()
-                .map(|s| s.to_string_lossy().into_owned())
+                .map(|s| utils::windows_quote(&s.to_string_lossy()).into_owned())
                 .collect::<Vec<String>>()
                 .join(" ");
             Self::write_element(&mut writer, "stoparguments", &stop_args)?;
@@ -261,6 +541,53 @@ impl WinSwServiceManager {
         if let Some(beep_on_shutdown) = config.options.beep_on_shutdown {
             Self::write_element(&mut writer, "beeponshutdown", &beep_on_shutdown.to_string())?;
         }
+        if let Some(downloads) = &config.options.downloads {
+            for download in downloads {
+                Self::write_element_with_attributes(
+                    &mut writer,
+                    "download",
+                    &[
+                        ("from", &download.from),
+                        ("to", &download.to.to_string_lossy()),
+                        ("failOnError", &download.fail_on_error.to_string()),
+                    ],
+                    None,
+                )?;
+            }
+        }
+        if let Some(prestart) = &config.options.prestart {
+            Self::write_hook(&mut writer, "prestart", prestart)?;
+        }
+        if let Some(poststop) = &config.options.poststop {
+            Self::write_hook(&mut writer, "poststop", poststop)?;
+        }
+        if let Some(log) = &config.options.log {
+            if let Some(path) = &log.path {
+                Self::write_element(&mut writer, "logpath", &path.to_string_lossy())?;
+            }
+
+            let mode = log.mode.to_string();
+            writer
+                .write(XmlEvent::start_element("log").attr("mode", &mode))
+                .map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        format!("Failed to write element 'log': {}", e),
+                    )
+                })?;
+            if let Some(size_threshold) = log.size_threshold_kb {
+                Self::write_element(&mut writer, "sizeThreshold", &size_threshold.to_string())?;
+            }
+            if let Some(keep_files) = log.keep_files {
+                Self::write_element(&mut writer, "keepFiles", &keep_files.to_string())?;
+            }
+            writer.write(XmlEvent::end_element()).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("Failed to end element 'log': {}", e),
+                )
+            })?;
+        }
 
         // </service>
         writer.write(XmlEvent::end_element()).map_err(|e| {
@@ -299,6 +626,36 @@ impl WinSwServiceManager {
         Ok(())
     }
 
+    fn write_hook<W: Write>(
+        writer: &mut EventWriter<W>,
+        name: &str,
+        hook: &WinSwHook,
+    ) -> io::Result<()> {
+        writer.write(XmlEvent::start_element(name)).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to write element '{}': {}", name, e),
+            )
+        })?;
+        Self::write_element(writer, "executable", &hook.executable.to_string_lossy())?;
+        if let Some(arguments) = &hook.arguments {
+            Self::write_element(writer, "arguments", arguments)?;
+        }
+        if let Some(stdout_path) = &hook.stdout_path {
+            Self::write_element(writer, "stdoutPath", &stdout_path.to_string_lossy())?;
+        }
+        if let Some(stderr_path) = &hook.stderr_path {
+            Self::write_element(writer, "stderrPath", &stderr_path.to_string_lossy())?;
+        }
+        writer.write(XmlEvent::end_element()).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to end element '{}': {}", name, e),
+            )
+        })?;
+        Ok(())
+    }
+
     fn write_element_with_attributes<W: Write>(
         writer: &mut EventWriter<W>,
         name: &str,
@@ -335,6 +692,57 @@ impl WinSwServiceManager {
         Ok(())
     }
 
+    /// Returns the directory holding the definition and logs of the named service
+    fn service_instance_path(&self, service_name: &str) -> PathBuf {
+        match &self.config.service_dir_path {
+            Some(path) => expand_env_vars(path, None),
+            None => {
+                expand_env_vars(&self.config.service_definition_dir_path, None).join(service_name)
+            }
+        }
+    }
+
+    /// Returns the path of the WinSW executable, downloading it if it cannot be found and
+    /// automatic downloads are configured
+    fn winsw_path(&self) -> io::Result<PathBuf> {
+        if let Ok(val) = std::env::var("WINSW_PATH") {
+            let path = PathBuf::from(val);
+            if path.exists() {
+                return Ok(path);
+            }
+        }
+
+        if let Some(download) = &self.config.auto_download {
+            if which::which(WINSW_EXE).is_err() {
+                let path = download.path_within(&expand_env_vars(
+                    &self.config.service_definition_dir_path,
+                    None,
+                ));
+                fetch_winsw(download, &path)?;
+                return Ok(path);
+            }
+        }
+
+        Ok(PathBuf::from(WINSW_EXE))
+    }
+
+    fn winsw_exe(
+        &self,
+        cmd: &str,
+        service_name: &str,
+        working_dir_path: &Path,
+    ) -> io::Result<Output> {
+        let mut command = Command::new(self.winsw_path()?);
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        command.current_dir(working_dir_path);
+        command.arg(cmd).arg(format!("{}.xml", service_name));
+
+        self.runner.run(&mut command, None)
+    }
+
     fn is_valid_xml(xml_string: &str) -> bool {
         let cursor = Cursor::new(xml_string);
         let parser = EventReader::new(cursor);
@@ -356,64 +764,115 @@ impl ServiceManager for WinSwServiceManager {
                     let path = PathBuf::from(val);
                     Ok(path.exists())
                 }
-                Err(_) => Ok(false),
+                Err(_) => {
+                    Ok(cfg!(feature = "winsw-download") && self.config.auto_download.is_some())
+                }
             },
             Err(x) => Err(io::Error::new(io::ErrorKind::Other, x)),
         }
     }
 
     fn install(&self, ctx: ServiceInstallCtx) -> io::Result<()> {
+        crate::utils::validate_environment(&ctx, true)?;
+        crate::utils::require_utf8(&ctx, "WinSW")?;
+        crate::utils::capabilities_unsupported(&ctx, "WinSW")?;
+        crate::utils::backup_unsupported(&ctx, "WinSW")?;
+        crate::utils::staging_unsupported(&ctx, "WinSW")?;
+        crate::utils::check_program(&ctx)?;
+        if ctx.kind == crate::ServiceKind::Oneshot {
+            return Err(utils::oneshot_unsupported("WinSW"));
+        }
+        // WinSW joins the arguments with spaces and only quotes those containing spaces, so an
+        // empty argument would be lost
+        if ctx.args_iter().any(|arg| arg.is_empty()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "WinSW does not support empty arguments",
+            ));
+        }
+        let _manifest =
+            manifest::ManifestRecorder::start(ServiceManagerKind::WinSw, self.level(), &ctx);
+        let ctx = crate::utils::install_binary(ctx)?;
+
         let service_name = ctx.label.to_qualified_name();
-        let service_instance_path = self
-            .config
-            .service_definition_dir_path
-            .join(service_name.clone());
+        let service_instance_path = self.service_instance_path(&service_name);
+
+        // When the service is already installed, its definition is rewritten in place and applied
+        // with `winsw refresh` rather than failing, which leaves the logs within the directory
+        let status = self.status(crate::ServiceStatusCtx {
+            label: ctx.label.clone(),
+            level: None,
+        })?;
+
         std::fs::create_dir_all(&service_instance_path)?;
 
         let service_config_path = service_instance_path.join(format!("{service_name}.xml"));
         Self::write_service_configuration(&service_config_path, &ctx, &self.config)?;
 
-        wrap_output(winsw_exe("install", &service_name, &service_instance_path)?)?;
+        match status {
+            ServiceStatus::NotInstalled => {
+                wrap_output(self.winsw_exe("install", &service_name, &service_instance_path)?)?;
+            }
+            status => {
+                wrap_output(self.winsw_exe("refresh", &service_name, &service_instance_path)?)?;
+
+                // Changes to the program, arguments, and environment only apply once the wrapped
+                // process is started again
+                if status == ServiceStatus::Running {
+                    wrap_output(self.winsw_exe(
+                        "restart",
+                        &service_name,
+                        &service_instance_path,
+                    )?)?;
+                }
+            }
+        }
+
         Ok(())
     }
 
     fn uninstall(&self, ctx: ServiceUninstallCtx) -> io::Result<()> {
+        crate::utils::ensure_installed(self, &ctx.label)?;
+
         let service_name = ctx.label.to_qualified_name();
-        let service_instance_path = self
-            .config
-            .service_definition_dir_path
-            .join(service_name.clone());
-        wrap_output(winsw_exe(
-            "uninstall",
-            &service_name,
-            &service_instance_path,
-        )?)?;
+        let service_instance_path = self.service_instance_path(&service_name);
+        wrap_output(self.winsw_exe("uninstall", &service_name, &service_instance_path)?)?;
 
         // The service directory is populated with the service definition, and other log files that
         // get generated by WinSW. It can be problematic if a service is later created with the
         // same name. Things are easier to manage if the directory is deleted.
-        std::fs::remove_dir_all(service_instance_path)?;
+        if self.config.preserve_logs_on_uninstall && !ctx.purge {
+            std::fs::remove_file(service_instance_path.join(format!("{service_name}.xml")))?;
+        } else if self.config.service_dir_path.is_some() {
+            // A directory chosen by the caller may be shared with other services or hold other
+            // data, so only the files named after the service are removed from it
+            remove_service_files(&service_instance_path, &service_name)?;
+        } else {
+            std::fs::remove_dir_all(service_instance_path)?;
+        }
 
-        Ok(())
+        manifest::remove(ServiceManagerKind::WinSw, self.level(), &ctx)?;
+        crate::utils::remove_installed_binary(&ctx)
+    }
+
+    fn installed_artifacts(
+        &self,
+        label: &crate::ServiceLabel,
+    ) -> io::Result<Vec<crate::InstalledArtifact>> {
+        manifest::installed_artifacts(ServiceManagerKind::WinSw, self.level(), label)
     }
 
     fn start(&self, ctx: ServiceStartCtx) -> io::Result<()> {
         let service_name = ctx.label.to_qualified_name();
-        let service_instance_path = self
-            .config
-            .service_definition_dir_path
-            .join(service_name.clone());
-        wrap_output(winsw_exe("start", &service_name, &service_instance_path)?)?;
-        Ok(())
+        let service_instance_path = self.service_instance_path(&service_name);
+        wrap_output(self.winsw_exe("start", &service_name, &service_instance_path)?)?;
+        utils::wait_until_active(self, &ctx)
     }
 
     fn stop(&self, ctx: ServiceStopCtx) -> io::Result<()> {
         let service_name = ctx.label.to_qualified_name();
-        let service_instance_path = self
-            .config
-            .service_definition_dir_path
-            .join(service_name.clone());
-        wrap_output(winsw_exe("stop", &service_name, &service_instance_path)?)?;
+        let service_instance_path = self.service_instance_path(&service_name);
+        wrap_output(self.winsw_exe("stop", &service_name, &service_instance_path)?)?;
         Ok(())
     }
 
@@ -421,79 +880,206 @@ impl ServiceManager for WinSwServiceManager {
         ServiceLevel::System
     }
 
+    fn logs(&self, ctx: ServiceLogsCtx) -> io::Result<String> {
+        let service_name = ctx.label.to_qualified_name();
+        let service_instance_path = self.service_instance_path(&service_name);
+        let log_dir_path = match self
+            .config
+            .options
+            .log
+            .as_ref()
+            .and_then(|l| l.path.as_ref())
+        {
+            Some(path) => expand_env_vars(path, Some(&service_instance_path)),
+            None => service_instance_path,
+        };
+
+        // WinSW names log files after the service definition file, writing the output of the
+        // service into separate files alongside its own log file. These are combined in the same
+        // manner as `tail` does for multiple files.
+        let mut logs = String::new();
+        for kind in ["out", "err", "wrapper"] {
+            let path = log_dir_path.join(format!("{service_name}.{kind}.log"));
+            if !path.exists() {
+                continue;
+            }
+
+            let text = std::fs::read_to_string(&path)?;
+            if !logs.is_empty() {
+                logs.push_str("\n\n");
+            }
+            logs.push_str(&format!("==> {} <==\n", path.display()));
+            logs.push_str(&utils::tail(&text, ctx.lines));
+        }
+
+        if logs.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No log files found in {}", log_dir_path.display()),
+            ));
+        }
+
+        Ok(logs)
+    }
+
     fn set_level(&mut self, level: ServiceLevel) -> io::Result<()> {
         match level {
             ServiceLevel::System => Ok(()),
             ServiceLevel::User => Err(io::Error::new(
                 io::ErrorKind::Unsupported,
-                "Windows does not support user-level services",
+                "WinSW does not support user-level services, which are managed by SchtasksServiceManager instead",
             )),
         }
     }
 
     fn status(&self, ctx: crate::ServiceStatusCtx) -> io::Result<ServiceStatus> {
+        Ok(self.status_details(ctx)?.status)
+    }
+
+    fn status_details(
+        &self,
+        ctx: crate::ServiceStatusCtx,
+    ) -> io::Result<crate::ServiceStatusDetails> {
         let service_name = ctx.label.to_qualified_name();
-        let service_instance_path = self
-            .config
-            .service_definition_dir_path
-            .join(service_name.clone());
+        let service_instance_path = self.service_instance_path(&service_name);
         if !service_instance_path.exists() {
-            return Ok(ServiceStatus::NotInstalled);
-        }
-        let output = winsw_exe("status", &service_name, &service_instance_path)?;
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            // It seems the error message is thrown by WinSW v2.x because only WinSW.[xml|yml] is supported
-            if stderr.contains("System.IO.FileNotFoundException: Unable to locate WinSW.[xml|yml] file within executable directory") {
-                return Ok(ServiceStatus::NotInstalled);
-            }
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to get service status: {}", stderr),
-            ));
+            return Ok(ServiceStatus::NotInstalled.into());
         }
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        if stdout.contains("NonExistent") {
-            Ok(ServiceStatus::NotInstalled)
-        } else if stdout.contains("running") {
-            Ok(ServiceStatus::Running)
-        } else {
-            Ok(ServiceStatus::Stopped(None))
+
+        // WinSW registers the service using its id, so the service control manager is queried
+        // directly. Unlike the output of `winsw status`, this does not differ between versions of
+        // WinSW and reports numeric states that do not depend on the language of Windows.
+        ScServiceManager::system()
+            .with_runner(self.runner.clone())
+            .status_details(ctx)
+    }
+}
+
+/// Expands environment variables written as `%NAME%` within a path in the same manner as WinSW,
+/// where `%BASE%` refers to the directory of the service definition when provided
+///
+/// Variables that are not defined are left as-is.
+fn expand_env_vars(path: &Path, base: Option<&Path>) -> PathBuf {
+    let path = path.to_string_lossy();
+    let mut expanded = String::new();
+    let mut rest = path.as_ref();
+
+    while let Some(start) = rest.find('%') {
+        let len = match rest[start + 1..].find('%') {
+            Some(len) => len,
+            None => break,
+        };
+        let name = &rest[start + 1..start + 1 + len];
+        expanded.push_str(&rest[..start]);
+
+        match (name, base) {
+            ("BASE", Some(base)) => expanded.push_str(&base.to_string_lossy()),
+            _ => match std::env::var(name) {
+                Ok(value) if !name.is_empty() => expanded.push_str(&value),
+                _ => expanded.push_str(&rest[start..start + len + 2]),
+            },
         }
+        rest = &rest[start + len + 2..];
     }
+
+    expanded.push_str(rest);
+    PathBuf::from(expanded)
 }
 
-fn winsw_exe(cmd: &str, service_name: &str, working_dir_path: &Path) -> io::Result<Output> {
-    let winsw_path = match std::env::var("WINSW_PATH") {
-        Ok(val) => {
-            let path = PathBuf::from(val);
-            if path.exists() {
-                path
-            } else {
-                PathBuf::from(WINSW_EXE)
-            }
+/// Removes the files that WinSW names after the service from `dir_path`, such as its definition
+/// and logs, and then the directory itself if nothing else is left in it
+fn remove_service_files(dir_path: &Path, service_name: &str) -> io::Result<()> {
+    let prefix = format!("{service_name}.");
+    for entry in std::fs::read_dir(dir_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && entry.file_name().to_string_lossy().starts_with(&prefix)
+        {
+            std::fs::remove_file(entry.path())?;
         }
-        Err(_) => PathBuf::from(WINSW_EXE),
-    };
+    }
+
+    // Fails when the directory is shared, which is expected
+    let _ = std::fs::remove_dir(dir_path);
+    Ok(())
+}
+
+/// Ensures that `path` holds the executable of the pinned release, verifying the checksum of an
+/// existing copy every time as it is run as a service host, and downloading it again when it is
+/// missing or does not match
+#[cfg(feature = "winsw-download")]
+fn fetch_winsw(download: &WinSwAutoDownload, path: &Path) -> io::Result<()> {
+    use std::io::Read;
+
+    match std::fs::read(path) {
+        Ok(bytes) if sha256_hex(&bytes).eq_ignore_ascii_case(download.sha256.trim()) => {
+            return Ok(())
+        }
+        Ok(_) => {}
+        Err(x) if x.kind() == io::ErrorKind::NotFound => {}
+        Err(x) => return Err(x),
+    }
 
-    let mut command = Command::new(winsw_path);
-    command
-        .stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-    command.current_dir(working_dir_path);
-    command.arg(cmd).arg(format!("{}.xml", service_name));
+    let url = download.url();
+    let mut bytes = Vec::new();
+    ureq::get(&url)
+        .call()
+        .map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("Failed to download {url}: {e}"),
+            )
+        })?
+        .into_reader()
+        .read_to_end(&mut bytes)?;
+
+    let checksum = sha256_hex(&bytes);
+    if !checksum.eq_ignore_ascii_case(download.sha256.trim()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Checksum of {WINSW_RELEASE_ASSET} from {url} was {checksum}, expected {}",
+                download.sha256
+            ),
+        ));
+    }
+
+    // Write to a temporary file first so that a partially-written executable is never used
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp_path = path.with_extension("exe.part");
+    std::fs::write(&tmp_path, &bytes)?;
+    std::fs::rename(tmp_path, path)
+}
+
+/// Returns the SHA-256 checksum of `bytes` as a lowercase hex string
+#[cfg(feature = "winsw-download")]
+fn sha256_hex(bytes: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
 
-    command.output()
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(not(feature = "winsw-download"))]
+fn fetch_winsw(_download: &WinSwAutoDownload, _path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Downloading and verifying WinSW requires the winsw-download feature",
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::runner::tests::ScriptedRunner;
     use assert_fs::prelude::*;
     use indoc::indoc;
     use std::ffi::OsString;
     use std::io::Cursor;
+    use std::sync::Arc;
     use xml::reader::{EventReader, XmlEvent};
 
     fn get_element_value(xml: &str, element_name: &str) -> String {
@@ -614,6 +1200,26 @@ mod tests {
             working_directory: None,
             environment: None,
             autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
         };
 
         WinSwServiceManager::write_service_configuration(
@@ -637,8 +1243,8 @@ mod tests {
             get_element_value(&xml, "description")
         );
         assert_eq!(
-            "--arg value --another-arg",
-            get_element_value(&xml, "arguments")
+            vec!["--arg", "value", "--another-arg"],
+            get_element_values(&xml, "argument")
         );
         assert_eq!("Automatic", get_element_value(&xml, "startmode"));
     }
@@ -661,6 +1267,26 @@ mod tests {
             working_directory: None,
             environment: None,
             autostart: false,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
         };
 
         WinSwServiceManager::write_service_configuration(
@@ -684,8 +1310,8 @@ mod tests {
             get_element_value(&xml, "description")
         );
         assert_eq!(
-            "--arg value --another-arg",
-            get_element_value(&xml, "arguments")
+            vec!["--arg", "value", "--another-arg"],
+            get_element_values(&xml, "argument")
         );
         assert_eq!("Manual", get_element_value(&xml, "startmode"));
     }
@@ -708,6 +1334,26 @@ mod tests {
             working_directory: None,
             environment: None,
             autostart: false,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
         };
 
         let mut config = WinSwConfig::default();
@@ -733,8 +1379,8 @@ mod tests {
             get_element_value(&xml, "description")
         );
         assert_eq!(
-            "--arg value --another-arg",
-            get_element_value(&xml, "arguments")
+            vec!["--arg", "value", "--another-arg"],
+            get_element_values(&xml, "argument")
         );
         assert_eq!("Boot", get_element_value(&xml, "startmode"));
     }
@@ -756,10 +1402,30 @@ mod tests {
             username: None,
             working_directory: Some(PathBuf::from("C:\\Program Files\\org.example")),
             environment: Some(vec![
-                ("ENV1".to_string(), "val1".to_string()),
-                ("ENV2".to_string(), "val2".to_string()),
+                (OsString::from("ENV1"), OsString::from("val1")),
+                (OsString::from("ENV2"), OsString::from("val2")),
             ]),
             autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: Some(String::from("My Service")),
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
         };
 
         let config = WinSwConfig {
@@ -769,6 +1435,7 @@ mod tests {
                 security_descriptor: Some(
                     "O:AOG:DAD:(A;;RPWPCCDCLCSWRCWDWOGA;;;S-1-0-0)".to_string(),
                 ),
+                service_account: None,
             },
             options: WinSwOptionsConfig {
                 priority: Some(WinSwPriority::High),
@@ -784,8 +1451,34 @@ mod tests {
                 dependent_services: Some(vec!["service1".to_string(), "service2".to_string()]),
                 interactive: Some(true),
                 beep_on_shutdown: Some(true),
+                log: Some(WinSwLogConfig {
+                    mode: WinSwLogMode::RollBySize,
+                    size_threshold_kb: Some(10240),
+                    keep_files: Some(8),
+                    path: Some(PathBuf::from("%BASE%\\logs")),
+                }),
+                downloads: Some(vec![WinSwDownload {
+                    from: "https://example.com/config.zip".to_string(),
+                    to: PathBuf::from("%BASE%\\config.zip"),
+                    fail_on_error: true,
+                }]),
+                prestart: Some(WinSwHook {
+                    executable: PathBuf::from("C:\\Temp\\prestart.exe"),
+                    arguments: Some("--unpack config.zip".to_string()),
+                    stdout_path: None,
+                    stderr_path: None,
+                }),
+                poststop: Some(WinSwHook {
+                    executable: PathBuf::from("C:\\Temp\\poststop.exe"),
+                    arguments: None,
+                    stdout_path: Some(PathBuf::from("%BASE%\\poststop.out.log")),
+                    stderr_path: None,
+                }),
             },
             service_definition_dir_path: PathBuf::from("C:\\Temp\\service-definitions"),
+            service_dir_path: None,
+            preserve_logs_on_uninstall: false,
+            auto_download: None,
         };
 
         WinSwServiceManager::write_service_configuration(
@@ -800,7 +1493,7 @@ mod tests {
 
         service_config_file.assert(predicates::path::is_file());
         assert_eq!("org.example.my_service", get_element_value(&xml, "id"));
-        assert_eq!("org.example.my_service", get_element_value(&xml, "name"));
+        assert_eq!("My Service", get_element_value(&xml, "name"));
         assert_eq!(
             "C:\\Program Files\\org.example\\my_service.exe",
             get_element_value(&xml, "executable")
@@ -810,8 +1503,8 @@ mod tests {
             get_element_value(&xml, "description")
         );
         assert_eq!(
-            "--arg value --another-arg",
-            get_element_value(&xml, "arguments")
+            vec!["--arg", "value", "--another-arg"],
+            get_element_values(&xml, "argument")
         );
         assert_eq!(
             "C:\\Program Files\\org.example",
@@ -859,6 +1552,36 @@ mod tests {
 
         assert_eq!("true", get_element_value(&xml, "interactive"));
         assert_eq!("true", get_element_value(&xml, "beeponshutdown"));
+
+        assert_eq!("%BASE%\\logs", get_element_value(&xml, "logpath"));
+        assert_eq!(
+            "roll-by-size",
+            get_element_attribute_value(&xml, "log", "mode")
+        );
+        assert_eq!("10240", get_element_value(&xml, "sizeThreshold"));
+        assert_eq!("8", get_element_value(&xml, "keepFiles"));
+
+        assert_eq!(
+            "https://example.com/config.zip",
+            get_element_attribute_value(&xml, "download", "from")
+        );
+        assert_eq!(
+            "%BASE%\\config.zip",
+            get_element_attribute_value(&xml, "download", "to")
+        );
+        assert_eq!(
+            "true",
+            get_element_attribute_value(&xml, "download", "failOnError")
+        );
+        assert_eq!(
+            vec!["C:\\Temp\\prestart.exe", "C:\\Temp\\poststop.exe"],
+            get_element_values(&xml, "executable")[1..]
+        );
+        assert_eq!("--unpack config.zip", get_element_value(&xml, "arguments"));
+        assert_eq!(
+            "%BASE%\\poststop.out.log",
+            get_element_value(&xml, "stdoutPath")
+        );
     }
 
     #[test]
@@ -889,6 +1612,26 @@ mod tests {
             working_directory: None,
             environment: None,
             autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
         };
 
         WinSwServiceManager::write_service_configuration(
@@ -932,6 +1675,26 @@ mod tests {
             working_directory: None,
             environment: None,
             autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
         };
 
         let result = WinSwServiceManager::write_service_configuration(
@@ -948,4 +1711,339 @@ mod tests {
             ),
         }
     }
+
+    #[test]
+    fn test_priority_from_nice() {
+        assert_eq!(WinSwPriority::from_nice(-20), WinSwPriority::High);
+        assert_eq!(WinSwPriority::from_nice(-5), WinSwPriority::AboveNormal);
+        assert_eq!(WinSwPriority::from_nice(0), WinSwPriority::Normal);
+        assert_eq!(WinSwPriority::from_nice(5), WinSwPriority::BelowNormal);
+        assert_eq!(WinSwPriority::from_nice(19), WinSwPriority::Idle);
+    }
+
+    #[test]
+    fn test_auto_download_url_and_path() {
+        let download = WinSwAutoDownload {
+            version: String::from("2.12.0"),
+            sha256: String::from("00"),
+            url_override: None,
+        };
+        assert_eq!(
+            download.url(),
+            "https://github.com/winsw/winsw/releases/download/v2.12.0/WinSW-x64.exe"
+        );
+        assert_eq!(
+            download.path_within(Path::new("service-definitions")),
+            Path::new("service-definitions").join("winsw-2.12.0.exe")
+        );
+
+        let download = WinSwAutoDownload {
+            url_override: Some(String::from("https://example.com/winsw.exe")),
+            ..download
+        };
+        assert_eq!(download.url(), "https://example.com/winsw.exe");
+    }
+
+    #[test]
+    fn test_service_configuration_with_arguments_containing_spaces_and_quotes() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let service_config_file = temp_dir.child("service_config.xml");
+
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse().unwrap(),
+            program: PathBuf::from("C:\\Program Files\\org.example\\my_service.exe"),
+            args: vec![
+                OsString::from("--config"),
+                OsString::from("C:\\Program Files\\x"),
+                OsString::from(r#"--name="quoted value""#),
+            ],
+            contents: None,
+            username: None,
+            working_directory: None,
+            environment: None,
+            autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+
+        WinSwServiceManager::write_service_configuration(
+            &service_config_file.to_path_buf(),
+            &ctx,
+            &WinSwConfig::default(),
+        )
+        .unwrap();
+
+        let xml = std::fs::read_to_string(service_config_file.path()).unwrap();
+        assert!(get_element_values(&xml, "arguments").is_empty());
+        assert_eq!(
+            vec![
+                "--config",
+                "C:\\Program Files\\x",
+                r#"--name="quoted value""#
+            ],
+            get_element_values(&xml, "argument")
+        );
+    }
+
+    #[test]
+    fn test_install_rejects_empty_arguments() {
+        let ctx = ServiceInstallCtx {
+            args: vec![OsString::from("--name"), OsString::from("")],
+            skip_program_check: true,
+            ..crate::utils::tests::install_ctx(PathBuf::from("C:\\my_service.exe"))
+        };
+
+        let err = WinSwServiceManager::system().install(ctx).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_service_configuration_with_service_account() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let service_config_file = temp_dir.child("service_config.xml");
+
+        let ctx = ServiceInstallCtx {
+            label: "org.example.my_service".parse().unwrap(),
+            program: PathBuf::from("C:\\Program Files\\org.example\\my_service.exe"),
+            args: vec![],
+            contents: None,
+            username: Some(String::from(r".\ignored")),
+            working_directory: None,
+            environment: None,
+            autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: crate::ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
+        };
+
+        let mut config = WinSwConfig::default();
+        config.install.service_account =
+            Some(WindowsAccount::new(r"EXAMPLE\user").with_password("secret"));
+
+        WinSwServiceManager::write_service_configuration(
+            &service_config_file.to_path_buf(),
+            &ctx,
+            &config,
+        )
+        .unwrap();
+
+        let xml = std::fs::read_to_string(service_config_file.path()).unwrap();
+        assert_eq!(r"EXAMPLE\user", get_element_value(&xml, "username"));
+        assert_eq!("secret", get_element_value(&xml, "password"));
+        assert_eq!("true", get_element_value(&xml, "allowservicelogon"));
+
+        config.install.service_account = Some(WindowsAccount::network_service());
+        WinSwServiceManager::write_service_configuration(
+            &service_config_file.to_path_buf(),
+            &ctx,
+            &config,
+        )
+        .unwrap();
+
+        let xml = std::fs::read_to_string(service_config_file.path()).unwrap();
+        assert_eq!(
+            r"NT AUTHORITY\NetworkService",
+            get_element_value(&xml, "username")
+        );
+        assert!(get_element_values(&xml, "password").is_empty());
+        assert!(get_element_values(&xml, "allowservicelogon").is_empty());
+    }
+
+    #[test]
+    fn test_logs_reads_output_files() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let service_dir = temp_dir.child("org.example.my_service");
+        service_dir
+            .child("org.example.my_service.out.log")
+            .write_str("out 1\nout 2\nout 3\n")
+            .unwrap();
+        service_dir
+            .child("org.example.my_service.err.log")
+            .write_str("err 1\n")
+            .unwrap();
+
+        let manager = WinSwServiceManager::system().with_config(WinSwConfig {
+            service_definition_dir_path: temp_dir.to_path_buf(),
+            ..Default::default()
+        });
+        let logs = manager
+            .logs(ServiceLogsCtx {
+                label: "org.example.my_service".parse().unwrap(),
+                lines: Some(2),
+            })
+            .unwrap();
+
+        assert_eq!(
+            logs,
+            format!(
+                "==> {} <==\nout 2\nout 3\n\n==> {} <==\nerr 1",
+                service_dir
+                    .child("org.example.my_service.out.log")
+                    .path()
+                    .display(),
+                service_dir
+                    .child("org.example.my_service.err.log")
+                    .path()
+                    .display(),
+            )
+        );
+    }
+
+    #[test]
+    fn test_status_queries_through_runner() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        temp_dir
+            .child("org.example.my_service")
+            .create_dir_all()
+            .unwrap();
+        let running = indoc! {r#"
+
+            SERVICE_NAME: org.example.my_service
+                    TYPE               : 10  WIN32_OWN_PROCESS
+                    STATE              : 4  RUNNING
+                                            (STOPPABLE, NOT_PAUSABLE, ACCEPTS_SHUTDOWN)
+                    WIN32_EXIT_CODE    : 0  (0x0)
+                    SERVICE_EXIT_CODE  : 0  (0x0)
+                    CHECKPOINT         : 0x0
+                    WAIT_HINT          : 0x0
+                    PID                : 4321
+                    FLAGS              :
+        "#};
+        let runner = Arc::new(ScriptedRunner::new([running]));
+        let manager = WinSwServiceManager::system()
+            .with_config(WinSwConfig {
+                service_definition_dir_path: temp_dir.to_path_buf(),
+                ..Default::default()
+            })
+            .with_runner(Arc::clone(&runner) as Arc<dyn crate::CommandRunner>);
+
+        let status = manager
+            .status(crate::ServiceStatusCtx {
+                label: "org.example.my_service".parse().unwrap(),
+                level: None,
+            })
+            .unwrap();
+        assert_eq!(status, ServiceStatus::Running);
+
+        let commands = runner.commands.lock().unwrap();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0][1..], ["queryex", "org.example.my_service"]);
+    }
+
+    #[test]
+    fn test_expand_env_vars() {
+        std::env::set_var("SERVICE_MANAGER_TEST_DIR", "C:\\Data");
+        assert_eq!(
+            expand_env_vars(
+                Path::new("%SERVICE_MANAGER_TEST_DIR%\\%BASE%\\%UNDEFINED_VAR%\\100%"),
+                Some(Path::new("C:\\Base")),
+            ),
+            PathBuf::from("C:\\Data\\C:\\Base\\%UNDEFINED_VAR%\\100%")
+        );
+        assert_eq!(
+            expand_env_vars(Path::new("%BASE%\\logs"), None),
+            PathBuf::from("%BASE%\\logs")
+        );
+    }
+
+    #[cfg(feature = "winsw-download")]
+    #[test]
+    fn test_fetch_winsw_verifies_existing_copy() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let mut download = WinSwAutoDownload {
+            version: String::from("2.12.0"),
+            sha256: sha256_hex(b"winsw"),
+            url_override: Some(String::from("http://127.0.0.1:9/winsw.exe")),
+        };
+        let path = download.path_within(temp_dir.path());
+        std::fs::write(&path, "winsw").unwrap();
+        fetch_winsw(&download, &path).unwrap();
+
+        // A copy that does not match is downloaded again rather than used
+        download.sha256 = sha256_hex(b"other");
+        assert!(fetch_winsw(&download, &path).is_err());
+    }
+
+    #[test]
+    fn test_remove_service_files() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let dir_path = temp_dir.path().join("services");
+        std::fs::create_dir(&dir_path).unwrap();
+        for name in [
+            "org.example.my_service.xml",
+            "org.example.my_service.out.log",
+            "org.example.my_service.wrapper.log",
+            "org.example.other_service.xml",
+            "data.db",
+        ] {
+            std::fs::write(dir_path.join(name), "").unwrap();
+        }
+
+        remove_service_files(&dir_path, "org.example.my_service").unwrap();
+        let mut names = std::fs::read_dir(&dir_path)
+            .unwrap()
+            .map(|x| x.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["data.db", "org.example.other_service.xml"]);
+
+        // The directory itself is removed once empty
+        remove_service_files(&dir_path, "org.example.other_service").unwrap();
+        std::fs::remove_file(dir_path.join("data.db")).unwrap();
+        remove_service_files(&dir_path, "org.example.other_service").unwrap();
+        assert!(!dir_path.exists());
+    }
+
+    #[test]
+    fn test_service_instance_path() {
+        let manager = WinSwServiceManager::system();
+        assert_eq!(
+            manager.service_instance_path("org.example.my_service"),
+            PathBuf::from("C:\\ProgramData\\service-manager").join("org.example.my_service")
+        );
+
+        let manager = manager.with_config(WinSwConfig {
+            service_dir_path: Some(PathBuf::from("D:\\services\\my_service")),
+            ..Default::default()
+        });
+        assert_eq!(
+            manager.service_instance_path("org.example.my_service"),
+            PathBuf::from("D:\\services\\my_service")
+        );
+    }
 }
@@ -1,4 +1,4 @@
-use assert_cmd::{crate_name, Command};
+use assert_cmd::{pkg_name, Command};
 use service_manager::*;
 use std::{
     ffi::OsString,
@@ -67,7 +67,7 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
     // Copy the service binary to a location where it can be accessed by a different user account
     // if need be.
     let temp_dir = std::env::temp_dir();
-    let bin_path = assert_cmd::cargo::cargo_bin(crate_name!());
+    let bin_path = assert_cmd::cargo::cargo_bin(pkg_name!());
     let temp_bin_path = temp_dir.join(bin_path.file_name().unwrap());
     if temp_bin_path.exists() {
         std::fs::remove_file(temp_bin_path.clone()).unwrap();
@@ -96,6 +96,7 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
             manager
                 .status(ServiceStatusCtx {
                     label: service_label.clone(),
+                    level: None,
                 })
                 .unwrap(),
             ServiceStatus::NotInstalled,
@@ -115,6 +116,26 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
             working_directory: None,
             environment: None,
             autostart: true,
+            restart_policy: None,
+            stop_command: None,
+            stop_signal: None,
+            start_timeout: None,
+            stop_timeout: None,
+            kind: ServiceKind::Simple,
+            health_check: None,
+            skip_program_check: false,
+            install_binary_to: None,
+            priority: None,
+            oom_score_adjust: None,
+            capabilities: Vec::new(),
+            display_name: None,
+            description: None,
+            start_limit: None,
+            log_to_syslog: None,
+            definition_permissions: None,
+            definition_owner: None,
+            backup_definition: false,
+            install_root: None,
         })
         .unwrap();
 
@@ -127,6 +148,7 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
             manager
                 .status(ServiceStatusCtx {
                     label: service_label.clone(),
+                    level: None,
                 })
                 .unwrap(),
             ServiceStatus::Stopped(_)
@@ -142,6 +164,7 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
     manager
         .start(ServiceStartCtx {
             label: service_label.clone(),
+            wait: WaitMode::None,
         })
         .unwrap();
 
@@ -154,6 +177,7 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
             manager
                 .status(ServiceStatusCtx {
                     label: service_label.clone(),
+                    level: None,
                 })
                 .unwrap(),
             ServiceStatus::Running
@@ -163,7 +187,7 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
 
     // Communicate with the service
     eprintln!("Talking to service");
-    Command::cargo_bin(crate_name!())
+    Command::cargo_bin(pkg_name!())
         .unwrap()
         .arg("talk")
         .arg(addr.to_string())
@@ -178,11 +202,8 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
         let res = manager.stop(ServiceStopCtx {
             label: service_label.clone(),
         });
-        if res.is_err() {
-            eprintln!(
-                "OpenRC stop is bugged in CI test, so skipping: {}",
-                res.unwrap_err()
-            );
+        if let Err(x) = res {
+            eprintln!("OpenRC stop is bugged in CI test, so skipping: {x}");
         }
     } else {
         manager
@@ -201,6 +222,7 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
             manager
                 .status(ServiceStatusCtx {
                     label: service_label.clone(),
+                    level: None,
                 })
                 .unwrap(),
             ServiceStatus::Stopped(_)
@@ -213,6 +235,8 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
     manager
         .uninstall(ServiceUninstallCtx {
             label: service_label.clone(),
+            install_binary_to: None,
+            purge: false,
         })
         .unwrap();
     wait();
@@ -223,6 +247,7 @@ pub fn run_test(manager: &TypedServiceManager, username: Option<String>) -> Opti
             manager
                 .status(ServiceStatusCtx {
                     label: service_label,
+                    level: None,
                 })
                 .unwrap(),
             ServiceStatus::NotInstalled